@@ -0,0 +1,64 @@
+//! Stores per-ball calibration in the Application Configuration Block of a
+//! mock robot that remembers what is written to it.
+//!
+//! Needs no hardware: `cargo run --example app_data --features tokio`
+
+use deku::DekuContainerRead;
+use sphero_rs::appdata::AppData;
+use sphero_rs::command::ACB_LEN;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::packet::{MRSPField, SpheroCommandID, SpheroCommandPacketV1};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::sync::{Arc, Mutex};
+
+/// Version of this app's calibration layout
+const VERSION: u8 = 2;
+
+/// Heading offset in degrees and speed scale in percent
+fn calibration(heading_offset: u16, speed_scale: u8) -> AppData {
+    let mut payload = heading_offset.to_be_bytes().to_vec();
+    payload.push(speed_scale);
+    AppData::new(VERSION, payload).unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // A fresh robot's block is all zeros
+    let acb = Arc::new(Mutex::new([0u8; ACB_LEN]));
+    let stored = acb.clone();
+    let mock = MockTransport::with_responder(move |bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        let mut data = vec![];
+        if packet.cid() == SpheroCommandID::SetApplicationConfigurationBlock as u8 {
+            stored.lock().unwrap().copy_from_slice(packet.data());
+        } else if packet.cid() == SpheroCommandID::GetApplicationConfigurationBlock as u8 {
+            data = stored.lock().unwrap().to_vec();
+        }
+        vec![respond(&packet, MRSPField::Ok, data)]
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+
+    // Never written: fall back to defaults
+    match AppData::decode(&device.read_app_data().await?) {
+        Some(stored) => println!("calibration stored: {:?}", stored.payload),
+        None => println!("no calibration stored"),
+    }
+
+    // Round trip
+    let saved = calibration(90, 80);
+    device.write_app_data(&saved.encode()).await?;
+    println!("block written: {:02x?}", *acb.lock().unwrap());
+    if let Some(loaded) = AppData::decode(&device.read_app_data().await?) {
+        println!("calibration read back: {:?}", loaded.payload);
+    }
+
+    // Written by an older version of the app: readable, but needs migrating
+    let old = AppData::new(1, vec![90]).unwrap();
+    device.write_app_data(&old.encode()).await?;
+    if let Some(loaded) = AppData::decode(&device.read_app_data().await?) {
+        if loaded.version < VERSION {
+            println!("found version {} data, migrating", loaded.version);
+        }
+    }
+    Ok(())
+}
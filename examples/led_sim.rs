@@ -0,0 +1,74 @@
+//! Plays LED patterns on a mock robot and checks the colors sent.
+//!
+//! The mock robot reports its LED as dark teal and acknowledges everything
+//! else. Needs no hardware: `cargo run --example led_sim --features tokio`
+
+use deku::DekuContainerRead;
+use sphero_rs::color::RgbColor;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::error::Error;
+use sphero_rs::led::{PatternStrategy, Patterns};
+use sphero_rs::packet::{MRSPField, SpheroCommandID, SpheroCommandPacketV1};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::time::Duration;
+
+const TEAL: RgbColor = RgbColor::new(0, 0x40, 0x40);
+
+/// Colors sent with Set RGB LED Output since the last check
+fn led_writes(mock: &MockTransport) -> Vec<RgbColor> {
+    let colors = mock
+        .written_packets()
+        .iter()
+        .filter(|packet| packet.cid() == SpheroCommandID::SetRGBLEDOutput as u8)
+        .map(|packet| {
+            let data = packet.data();
+            RgbColor::new(data[0], data[1], data[2])
+        })
+        .collect();
+    mock.clear_written();
+    colors
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mock = MockTransport::with_responder(|bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        let data = if packet.cid() == SpheroCommandID::GetRGBLEDOutput as u8 {
+            vec![TEAL.red, TEAL.green, TEAL.blue]
+        } else {
+            vec![]
+        };
+        vec![respond(&packet, MRSPField::Ok, data)]
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+    let patterns = Patterns::new(&device, PatternStrategy::HostDriven);
+
+    // Blink: on, off, on, off, then back to the color read from the robot
+    patterns
+        .blink(RgbColor::RED, 2, Duration::from_millis(40))
+        .await?;
+    let black = RgbColor::BLACK;
+    let red = RgbColor::RED;
+    assert_eq!(led_writes(&mock), vec![red, black, red, black, TEAL]);
+    println!("blink: restored {:?}", device.led_color().unwrap());
+
+    // A long pulse, cancelled part way, still restores the color
+    let pulse = patterns.pulse(RgbColor::WHITE, Duration::from_secs(5));
+    let cancel = async {
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        patterns.cancel();
+    };
+    let (result, ()) = tokio::join!(pulse, cancel);
+    assert!(matches!(result, Err(Error::Cancelled)));
+    let writes = led_writes(&mock);
+    assert!(writes.len() < 16, "pulse ran to the end");
+    assert_eq!(writes.last(), Some(&TEAL));
+    println!("pulse: cancelled after {} steps", writes.len() - 1);
+
+    let compiled = Patterns::new(&device, PatternStrategy::Macro);
+    assert!(matches!(
+        compiled.police().await,
+        Err(Error::NotImplemented)
+    ));
+    Ok(())
+}
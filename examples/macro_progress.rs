@@ -0,0 +1,86 @@
+//! Runs a macro on a mock robot and follows it: the robot emits markers and
+//! answers Get Macro Status from a script, then a second run is aborted
+//! while waiting for a marker that never comes.
+//!
+//! Needs no hardware: `cargo run --example macro_progress --features tokio`
+
+use deku::{DekuContainerRead, DekuContainerWrite};
+use sphero_rs::color::RgbColor;
+use sphero_rs::command::TEMPORARY_MACRO_ID;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::macros::MacroBuilder;
+use sphero_rs::packet::{
+    MRSPField, SpheroAsynchronousPacketV1, SpheroCommandID, SpheroCommandPacketV1,
+};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Macro Marker async message, as the robot sends it
+fn marker(marker: u8, command: u16) -> Vec<u8> {
+    let [hi, lo] = command.to_be_bytes();
+    SpheroAsynchronousPacketV1::new(0x06, vec![marker, TEMPORARY_MACRO_ID, hi, lo])
+        .to_bytes()
+        .unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Running macro and command reported by Get Macro Status
+    let status = Arc::new(Mutex::new((0u8, 0u16)));
+    let script = status.clone();
+    let mock = MockTransport::with_responder(move |bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        let data = if packet.cid() == SpheroCommandID::GetMacroStatus as u8 {
+            let (id, command) = *script.lock().unwrap();
+            let [hi, lo] = command.to_be_bytes();
+            vec![id, hi, lo]
+        } else {
+            vec![]
+        };
+        vec![respond(&packet, MRSPField::Ok, data)]
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+
+    let flash = MacroBuilder::new()
+        .marker(1)
+        .set_rgb(RgbColor::RED)
+        .delay(500)
+        .marker(2)
+        .set_rgb(RgbColor::BLACK)
+        .end()?;
+
+    // Markers: one already received is found without waiting
+    *status.lock().unwrap() = (TEMPORARY_MACRO_ID, 1);
+    let execution = device.run_macro_bytes(&flash, None).await?;
+    mock.inject(marker(1, 1));
+    let first = execution.await_marker(1).await?;
+    println!("marker 1 at command {}", first.command);
+    println!("waiting again: {:?}", execution.await_marker(1).await?);
+
+    // Completion: status polls move the progress on until no macro is running
+    let (completion, ()) = tokio::join!(execution.await_completion(), async {
+        *status.lock().unwrap() = (TEMPORARY_MACRO_ID, 3);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        println!("progress from status: {:?}", execution.progress());
+        mock.inject(marker(2, 4));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        println!("progress from marker: {:?}", execution.progress());
+        *status.lock().unwrap() = (0, 0);
+    });
+    completion?;
+    println!("completed at command {:?}", execution.progress());
+
+    // Abort: the pending wait fails and Abort Macro goes out
+    *status.lock().unwrap() = (TEMPORARY_MACRO_ID, 1);
+    let execution = device.run_macro_bytes(&flash, None).await?;
+    let (waited, aborted) = tokio::join!(execution.await_marker(9), async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        execution.abort().await
+    });
+    aborted?;
+    if let Err(e) = waited {
+        println!("aborted: the wait for marker 9 failed with {e}");
+    }
+    Ok(())
+}
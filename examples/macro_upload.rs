@@ -0,0 +1,88 @@
+//! Uploads macros to a mock robot and prints the packets sent: a small macro
+//! in one packet, the same macro persisted, a large one in chunks, and a
+//! large one whose fourth chunk the robot rejects.
+//!
+//! Needs no hardware: `cargo run --example macro_upload --features tokio`
+
+use deku::DekuContainerRead;
+use sphero_rs::color::RgbColor;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::macros::{MacroBuilder, MacroFlags, MacroTarget};
+use sphero_rs::packet::{DeviceID, MRSPField, SpheroCommandID, SpheroCommandPacketV1};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Print the macro commands sent since the last call
+fn print_macro_writes(mock: &MockTransport) {
+    for packet in mock.written_packets() {
+        if packet.did() == DeviceID::Sphero {
+            println!("  cid {:#04x}: {:02x?}", packet.cid(), packet.data());
+        }
+    }
+    mock.clear_written();
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let reject_chunk = Arc::new(AtomicBool::new(false));
+    let reject = reject_chunk.clone();
+    let mock = MockTransport::with_responder(move |bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        let rejected = reject.load(Ordering::Relaxed)
+            && packet.cid() == SpheroCommandID::AppendMacroChunk as u8
+            && packet.data()[1] == 3;
+        let mrsp = if rejected {
+            MRSPField::ExecuteError
+        } else {
+            MRSPField::Ok
+        };
+        vec![respond(&packet, mrsp, vec![])]
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+    let _ = device.capabilities().await;
+    mock.clear_written();
+
+    // Small: one Save Temporary Macro, then run the temporary macro
+    let blink = MacroBuilder::new()
+        .loop_start(3)
+        .set_rgb(RgbColor::RED)
+        .delay(200)
+        .set_rgb(RgbColor::BLACK)
+        .delay(200)
+        .loop_end()
+        .end()?;
+    device.run_macro_bytes(&blink, None).await?;
+    println!("small macro, {} bytes:", blink.len());
+    print_macro_writes(&mock);
+
+    // Persisted: Save Macro under the ID, then run it
+    let spin = MacroBuilder::new()
+        .target(MacroTarget::Persistent(40))
+        .flags(MacroFlags::EXCLUSIVE_DRIVE | MacroFlags::STOP_MOTORS_ON_END)
+        .rotate_over_time(720, 2000)
+        .end()?;
+    device.run_macro_bytes(&spin, Some(40)).await?;
+    println!("persisted macro 40:");
+    print_macro_writes(&mock);
+
+    // Large: chunks numbered from 0, the last one ending in a stream end
+    let mut builder = MacroBuilder::new().max_len(1024);
+    for i in 0..60 {
+        builder = builder
+            .set_rgb(RgbColor::new(i * 4, 0, 255 - i * 4))
+            .delay(50);
+    }
+    let fade = builder.end()?;
+    device.run_macro_bytes(&fade, None).await?;
+    println!("large macro, {} bytes:", fade.len());
+    print_macro_writes(&mock);
+
+    // Rejected: the error names the chunk and the macro is not run
+    reject_chunk.store(true, Ordering::Relaxed);
+    if let Err(e) = device.run_macro_bytes(&fade, None).await {
+        println!("rejected: {e}");
+    }
+    print_macro_writes(&mock);
+    Ok(())
+}
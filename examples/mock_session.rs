@@ -0,0 +1,141 @@
+//! Sends every implemented command to a mock robot and prints the bytes on the wire.
+//!
+//! Needs no hardware: `cargo run --example mock_session`
+
+use deku::DekuContainerWrite;
+use sphero_rs::client::SpheroClient;
+use sphero_rs::collision::CollisionConfig;
+use sphero_rs::command::*;
+use sphero_rs::heading::Heading;
+use sphero_rs::packet::SOP1Field;
+use sphero_rs::sensor::{Sensor, StreamingConfig};
+use sphero_rs::speed::Speed;
+use sphero_rs::transport::mock::MockTransport;
+use std::error::Error;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let commands: Vec<(&str, Box<dyn ToCommandPacket>)> = vec![
+        ("Ping", Box::new(Ping {})),
+        ("GetVersioning", Box::new(GetVersioning {})),
+        ("GetBluetoothInfo", Box::new(GetBluetoothInfo {})),
+        ("GetPowerState", Box::new(GetPowerState {})),
+        (
+            "Sleep",
+            Box::new(Sleep {
+                wakeup: 60,
+                ..Default::default()
+            }),
+        ),
+        ("EraseUserConfig", Box::new(EraseUserConfig {})),
+        ("AssignTimeValue", Box::new(AssignTimeValue { time: 1000 })),
+        ("PollPacketTimes", Box::new(PollPacketTimes { time: 1000 })),
+        ("SetHeading", Box::new(SetHeading { heading: 90 })),
+        (
+            "SetStabilization",
+            Box::new(SetStabilization { enabled: true }),
+        ),
+        (
+            "SetRGBLEDOutput",
+            Box::new(SetRGBLEDOutput::transient(0xff, 0x80, 0x00)),
+        ),
+        (
+            "SetBackLEDOutput",
+            Box::new(SetBackLEDOutput { brightness: 0xff }),
+        ),
+        (
+            "Roll",
+            Box::new(Roll {
+                speed: Speed::new(0x40),
+                heading: Heading::new(180)?,
+                state: true,
+            }),
+        ),
+        ("ReadLocator", Box::new(ReadLocator {})),
+        ("ConfigureLocator", Box::new(ConfigureLocator::default())),
+        (
+            "SetRawMotorValues",
+            Box::new(SetRawMotorValues {
+                left_mode: MotorMode::Forward,
+                left_power: 0x80,
+                right_mode: MotorMode::Reverse,
+                right_power: 0x80,
+            }),
+        ),
+        (
+            "SetDataStreaming",
+            Box::new(
+                StreamingConfig::new()
+                    .with_sensors(&[Sensor::ImuPitch, Sensor::ImuRoll, Sensor::ImuYaw])
+                    .build(),
+            ),
+        ),
+        (
+            "ConfigureCollisionDetection",
+            Box::new(ConfigureCollisionDetection::from(CollisionConfig::method1())),
+        ),
+        (
+            "SetMacroParameter",
+            Box::new(SetMacroParameter {
+                index: MacroParameterIndex::Speed1,
+                value: 0x80,
+            }),
+        ),
+        (
+            "AppendMacroChunk",
+            Box::new(AppendMacroChunk {
+                macro_id: TEMPORARY_MACRO_ID,
+                index: 0,
+                data: vec![0x00],
+            }),
+        ),
+        (
+            "EraseOrbbasicStorage",
+            Box::new(EraseOrbbasicStorage {
+                area: OrbBasicArea::Area0,
+            }),
+        ),
+        (
+            "AppendOrbbasicFragment",
+            Box::new(AppendOrbbasicFragment {
+                area: OrbBasicArea::Area0,
+                fragment: b"10 RGB 255, 0, 0\0".to_vec(),
+            }),
+        ),
+        (
+            "ExecuteOrbbasicProgram",
+            Box::new(ExecuteOrbbasicProgram {
+                area: OrbBasicArea::Area0,
+                start_line: 10,
+            }),
+        ),
+        ("AbortOrbbasicProgram", Box::new(AbortOrbbasicProgram {})),
+    ];
+
+    let mut client = SpheroClient::new(MockTransport::acknowledging());
+    for (seq, (name, cmd)) in (1..).zip(&commands) {
+        let packet = cmd.to_packet(seq);
+        let bytes = packet.to_bytes()?;
+        assert!(!bytes.is_empty(), "{} serialized to nothing", name);
+        assert_eq!(bytes[0], SOP1Field::All as u8, "{} has a bad SOP1", name);
+        println!("{:<28} {}", name, hex(&bytes));
+
+        let response = client.send_packet(packet).await?;
+        assert_eq!(response.seq(), seq, "{} answered out of order", name);
+    }
+
+    println!(
+        "{} commands sent, {} writes recorded",
+        commands.len(),
+        client.transport().written().len()
+    );
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Drives a simulated robot to a waypoint, plays the recorded path back
+//! mirrored to return home, then cancels a route midway.
+//!
+//! The simulated robot moves at 1 cm/s per unit of Roll speed, in the
+//! commanded heading, and answers Read Locator with its position. Needs no
+//! hardware:
+//! `cargo run --example navigate_sim --features tokio`
+
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::drive::{DriveController, DriveLimits};
+use sphero_rs::error::Error;
+use sphero_rs::nav::{
+    Navigator, NavigatorConfig, Odometry, PathRecorder, PlaybackOptions, Waypoint,
+};
+use sphero_rs::transport::sim::SimulatedSphero;
+use std::time::{Duration, Instant};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let sim = SimulatedSphero::default();
+    let device = SpheroDevice::new(sim.clone()).await?;
+    let limits = DriveLimits {
+        tick: Duration::from_millis(20),
+        max_acceleration: 400.0,
+        max_turn_rate: 720.0,
+    };
+    let drive = DriveController::new(&device, limits, 0)?;
+    let odometry = Odometry::new(&device);
+    let config = NavigatorConfig {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_secs(10),
+        ..NavigatorConfig::default()
+    };
+    let navigator = Navigator::new(&odometry, &drive, config);
+
+    // Background loops run until the navigation under test finishes
+    let background = async {
+        tokio::select! {
+            result = drive.run() => result,
+            result = odometry.poll(Duration::from_millis(20)) => result,
+        }
+    };
+    tokio::pin!(background);
+
+    // A single waypoint, ahead and to the left, recording the way there
+    let recorder = PathRecorder::new(&odometry);
+    let leg = async {
+        let result = navigator.drive_to(100, 50, 5).await;
+        recorder.stop();
+        result
+    };
+    let arrival = tokio::select! {
+        (result, ()) = async { tokio::join!(leg, recorder.record(Duration::from_millis(50))) } => result,
+        result = &mut background => result,
+    };
+    arrival?;
+    let (x, y, heading) = odometry.pose();
+    println!("reached ({}, {}) heading {:.0}", x, y, heading);
+    assert!((x - 100).pow(2) + (y - 50).pow(2) <= 25);
+
+    // The same path turned about and moved to start here leads back home
+    let path = recorder.path().transform(x, y, 180.0);
+    let options = PlaybackOptions {
+        time_scale: 2.0,
+        ..PlaybackOptions::default()
+    };
+    let schedule = path.schedule(options)?;
+    let (_, last) = schedule.last().unwrap();
+    assert!(last.x_cm.abs() <= 10 && last.y_cm.abs() <= 10);
+    let started = Instant::now();
+    let played = tokio::select! {
+        result = navigator.play(&path, options) => result,
+        result = &mut background => result.map(|()| 0),
+    };
+    assert_eq!(played?, schedule.len());
+    assert!(started.elapsed() >= recorder.path().duration() / 2);
+    let (x, y, _) = odometry.pose();
+    println!("played {} waypoints back to ({}, {})", schedule.len(), x, y);
+    assert!(x.abs() <= 15 && y.abs() <= 15);
+
+    // A route, cancelled before it can finish
+    navigator.push_waypoint(Waypoint {
+        x_cm: -100,
+        y_cm: -100,
+        tolerance_cm: 5,
+    });
+    navigator.push_waypoint(Waypoint {
+        x_cm: 0,
+        y_cm: 0,
+        tolerance_cm: 5,
+    });
+    let route = navigator.follow_route();
+    let cancel = async {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        navigator.cancel();
+        // Give the drive controller time to ramp down
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    };
+    let cancelled = tokio::select! {
+        (result, ()) = async { tokio::join!(route, cancel) } => result,
+        result = &mut background => result.map(|()| 0),
+    };
+    assert!(matches!(cancelled, Err(Error::Cancelled)));
+    assert_eq!(navigator.remaining(), 0);
+    assert_eq!(sim.speed(), 0, "robot still moving after cancel");
+    let (x, y, _) = odometry.pose();
+    println!("route cancelled at ({}, {})", x, y);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Runs orbBasic programs on a mock robot that prints and fails on cue,
+//! and prints the output a session reports.
+//!
+//! Needs no hardware: `cargo run --example orbbasic_session --features tokio`
+
+use deku::DekuContainerWrite;
+use futures::StreamExt;
+use sphero_rs::command::OrbBasicArea;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::orbbasic::OrbBasicOutput;
+use sphero_rs::packet::SpheroAsynchronousPacketV1;
+use sphero_rs::transport::mock::MockTransport;
+use std::time::Duration;
+
+/// orbBasic print message, as the robot sends it
+fn print(text: &str) -> Vec<u8> {
+    let data = format!("{text}\r\n").into_bytes();
+    SpheroAsynchronousPacketV1::new(0x08, data)
+        .to_bytes()
+        .unwrap()
+}
+
+/// orbBasic binary error message, as the robot sends it
+fn error(code: u16, line: u16) -> Vec<u8> {
+    let mut data = code.to_be_bytes().to_vec();
+    data.extend(line.to_be_bytes());
+    SpheroAsynchronousPacketV1::new(0x0a, data)
+        .to_bytes()
+        .unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mock = MockTransport::acknowledging();
+    let device = SpheroDevice::new(mock.clone()).await?;
+    let source = "10 FOR I = 1 TO 2\n20 PRINT I\n30 NEXT I\n40 X = 1 / 0\n";
+    device.load_orbbasic(OrbBasicArea::Area0, source).await?;
+
+    // Prints, then an error that ends the session; later output is not seen
+    let session = device.run_orbbasic(OrbBasicArea::Area0, 10).await?;
+    for message in [print("1"), print("2"), error(0x08, 40), print("late")] {
+        mock.inject(message);
+    }
+    let output: Vec<_> = tokio::time::timeout(Duration::from_secs(1), session.collect()).await?;
+    for line in &output {
+        match line {
+            OrbBasicOutput::Error(failure) => println!("program stopped: {failure}"),
+            line => println!("{line:?}"),
+        }
+    }
+    // Let the reader deliver the late line before the next program starts
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Aborted: the stream ends and Abort orbBasic Program goes out
+    let mut session = device.run_orbbasic(OrbBasicArea::Area0, 10).await?;
+    mock.inject(print("1"));
+    println!("{:?}", session.next().await);
+    session.abort().await?;
+    mock.inject(print("2"));
+    println!("aborted, then {:?}", session.next().await);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // An error reported as text ends the session too
+    let session = device.run_orbbasic(OrbBasicArea::Area0, 10).await?;
+    let text = SpheroAsynchronousPacketV1::new(0x09, b"Syntax error in line 10\r\n".to_vec());
+    mock.inject(text.to_bytes()?);
+    let output: Vec<_> = tokio::time::timeout(Duration::from_secs(1), session.collect()).await?;
+    println!("text error: {output:?}");
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Uploads an orbBasic program to a mock robot and prints the packets sent,
+//! then repeats the upload with the robot rejecting the second fragment.
+//!
+//! Needs no hardware: `cargo run --example orbbasic_upload --features tokio`
+
+use deku::DekuContainerRead;
+use sphero_rs::command::OrbBasicArea;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::orbbasic::program_bytes;
+use sphero_rs::packet::{DeviceID, MRSPField, SpheroCommandID, SpheroCommandPacketV1};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Print the orbBasic commands sent since the last call
+fn print_orbbasic_writes(mock: &MockTransport) {
+    for packet in mock.written_packets() {
+        if packet.did() == DeviceID::Sphero {
+            println!(
+                "  cid {:#04x}: {} data bytes",
+                packet.cid(),
+                packet.data().len()
+            );
+        }
+    }
+    mock.clear_written();
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Appends seen so far; the one numbered `reject_at` is refused
+    let appends = Arc::new(AtomicUsize::new(0));
+    let reject_at = Arc::new(AtomicUsize::new(usize::MAX));
+    let (seen, reject) = (appends.clone(), reject_at.clone());
+    let mock = MockTransport::with_responder(move |bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        let mut mrsp = MRSPField::Ok;
+        if packet.cid() == SpheroCommandID::AppendOrbbasicFragment as u8
+            && seen.fetch_add(1, Ordering::Relaxed) == reject.load(Ordering::Relaxed)
+        {
+            mrsp = MRSPField::ExecuteError;
+        }
+        vec![respond(&packet, mrsp, vec![])]
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+    let _ = device.capabilities().await;
+    mock.clear_written();
+
+    let source: String = (1..=60)
+        .map(|n| format!("{} RGB {}, 0, {}\r\n", n * 10, n * 4, 255 - n * 4))
+        .collect();
+    let program = program_bytes(&source);
+
+    // Erase, then fragments that rebuild the program, ending on lines
+    device.load_orbbasic(OrbBasicArea::Area1, &source).await?;
+    println!("uploaded {} bytes:", program.len());
+    print_orbbasic_writes(&mock);
+
+    device.run_orbbasic(OrbBasicArea::Area1, 10).await?;
+    device.abort_orbbasic().await?;
+    println!("ran and aborted:");
+    print_orbbasic_writes(&mock);
+
+    // Rejected: the error names the fragment and where it starts
+    appends.store(0, Ordering::Relaxed);
+    reject_at.store(1, Ordering::Relaxed);
+    if let Err(e) = device.load_orbbasic(OrbBasicArea::Area1, &source).await {
+        println!("rejected: {e}");
+    }
+    print_orbbasic_writes(&mock);
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Walks a power policy through low, recovered and critical battery states.
+//!
+//! The mock robot acknowledges every command; power notifications are
+//! injected as if the robot had sent them. Needs no hardware:
+//! `cargo run --example power_sim --features tokio`
+
+use deku::DekuContainerWrite;
+use sphero_rs::color::RgbColor;
+use sphero_rs::command::SetRGBLEDOutput;
+use sphero_rs::device::{ShutdownOptions, SpheroDevice};
+use sphero_rs::drive::{DriveController, DriveLimits};
+use sphero_rs::packet::{SpheroAsynchronousPacketV1, SpheroCommandID};
+use sphero_rs::power::{PowerPolicy, PowerPolicyConfig, PowerState};
+use sphero_rs::transport::mock::MockTransport;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Power notification with state byte `state`
+fn notify(mock: &MockTransport, state: u8) {
+    let packet = SpheroAsynchronousPacketV1::new(0x01, vec![state]);
+    mock.inject(packet.to_bytes().unwrap());
+}
+
+/// Colors sent with Set RGB LED Output so far
+fn led_writes(mock: &MockTransport) -> Vec<RgbColor> {
+    mock.written_packets()
+        .iter()
+        .filter(|packet| packet.cid() == SpheroCommandID::SetRGBLEDOutput as u8)
+        .map(|packet| {
+            let data = packet.data();
+            RgbColor::new(data[0], data[1], data[2])
+        })
+        .collect()
+}
+
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mock = MockTransport::acknowledging();
+    let device = SpheroDevice::new(mock.clone()).await?;
+    let user_color = RgbColor::BLUE;
+    drop(
+        device
+            .send(&SetRGBLEDOutput::from_color(user_color, false))
+            .await?,
+    );
+
+    let drive = DriveController::new(&device, DriveLimits::default(), 0)?;
+    let config = PowerPolicyConfig::default();
+    let warning = config.warning_color.unwrap();
+    let seen = Mutex::new(vec![]);
+    let policy = PowerPolicy::new(&device, config)
+        .with_drive(&drive)
+        .on_change(|state| seen.lock().unwrap().push(state));
+
+    let script = async {
+        settle().await;
+
+        notify(&mock, 3);
+        settle().await;
+        assert_eq!(policy.state(), Some(PowerState::Low));
+        assert_eq!(drive.speed_limit(), Some(config.low_max_speed));
+        assert_eq!(led_writes(&mock), vec![user_color, warning]);
+        println!("low: speed capped at {}, LED warning", config.low_max_speed);
+
+        // A repeated notification changes nothing
+        notify(&mock, 3);
+        settle().await;
+        assert_eq!(led_writes(&mock).len(), 2);
+
+        // Measurement noise: back to OK restores the user's color
+        notify(&mock, 2);
+        settle().await;
+        assert_eq!(drive.speed_limit(), None);
+        assert_eq!(led_writes(&mock), vec![user_color, warning, user_color]);
+        println!("recovered: limit lifted, LED restored");
+
+        notify(&mock, 3);
+        settle().await;
+        notify(&mock, 4);
+    };
+    let (result, ()) = tokio::join!(policy.run(), script);
+    result?;
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            PowerState::Low,
+            PowerState::Ok,
+            PowerState::Low,
+            PowerState::Critical
+        ]
+    );
+    println!("critical: shutting down");
+    drop(policy);
+    device.shutdown(ShutdownOptions::default()).await?;
+    assert!(mock.is_closed());
+    Ok(())
+}
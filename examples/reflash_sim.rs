@@ -0,0 +1,156 @@
+//! Reflashes a mock robot with a short fake image: one page fails to program
+//! and is retried, then the battery runs low mid-way and the reflash resumes
+//! from the page it stopped at.
+//!
+//! Needs no hardware: `cargo run --example reflash_sim --features tokio`
+
+use deku::DekuContainerRead;
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::error::Error;
+use sphero_rs::firmware::{FirmwareImage, FIRMWARE_PAGE_SIZE};
+use sphero_rs::packet::{
+    BootloaderCommandID, CoreCommandID, DeviceID, MRSPField, SpheroCommandPacketV1,
+};
+use sphero_rs::reflash::{ReflashOptions, ReflashProgress};
+use sphero_rs::transport::mock::{respond, MockTransport};
+use std::sync::{Arc, Mutex};
+
+/// Flash of a robot in its bootloader, and the faults to play back
+#[derive(Default)]
+struct Robot {
+    pages: Vec<Option<Vec<u8>>>,
+    app_version: u8,
+    /// Page that fails to program once
+    flaky_page: Option<u16>,
+    /// Page at which the battery is too low to go on
+    low_voltage_page: Option<u16>,
+}
+
+impl Robot {
+    fn answer(&mut self, packet: &SpheroCommandPacketV1) -> Vec<Vec<u8>> {
+        let (mrsp, data) = match (packet.did(), packet.cid()) {
+            (DeviceID::Core, cid) if cid == CoreCommandID::GetVersioningInformation as u8 => {
+                let version = [
+                    0x02,
+                    0x03,
+                    0x01,
+                    self.app_version,
+                    0x00,
+                    0x41,
+                    0x21,
+                    0x04,
+                    0x01,
+                    0x12,
+                ];
+                (MRSPField::Ok, version.to_vec())
+            }
+            (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::Reflash as u8 => {
+                self.pages.iter_mut().for_each(|page| *page = None);
+                (MRSPField::Ok, vec![])
+            }
+            (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::IsPageBlank as u8 => {
+                let page = u16::from_be_bytes([packet.data()[0], packet.data()[1]]);
+                let blank = self.pages[page as usize].is_none();
+                (MRSPField::Ok, vec![blank as u8])
+            }
+            (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::HereIsPage as u8 => {
+                let page = u16::from_be_bytes([packet.data()[0], packet.data()[1]]);
+                if self.low_voltage_page == Some(page) {
+                    self.low_voltage_page = None;
+                    (MRSPField::LowVoltageError, vec![])
+                } else if self.flaky_page == Some(page) {
+                    self.flaky_page = None;
+                    (MRSPField::FlashFailError, vec![])
+                } else {
+                    self.pages[page as usize] = Some(packet.data()[2..].to_vec());
+                    (MRSPField::Ok, vec![])
+                }
+            }
+            (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::LeaveBootloader as u8 => {
+                // Starts the new application without answering
+                self.app_version += 1;
+                return vec![];
+            }
+            _ => (MRSPField::Ok, vec![]),
+        };
+        vec![respond(packet, mrsp, data)]
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let data: Vec<u8> = (0..FIRMWARE_PAGE_SIZE * 4 - 10).map(|i| i as u8).collect();
+    let image = FirmwareImage::from_binary(&data)?;
+    let robot = Arc::new(Mutex::new(Robot {
+        pages: vec![None; image.page_count() as usize],
+        app_version: 1,
+        flaky_page: Some(1),
+        low_voltage_page: Some(2),
+    }));
+    let flash = robot.clone();
+    let mock = MockTransport::with_responder(move |bytes| {
+        let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+        flash.lock().unwrap().answer(&packet)
+    });
+    let device = SpheroDevice::new(mock.clone()).await?;
+
+    // Page 1 is retried, then the reflash stops at page 2
+    let mut events = vec![];
+    let stopped = device.reflash(&image, |event| events.push(event)).await;
+    let Err(Error::Reflash { page, error }) = stopped else {
+        panic!("expected the reflash to stop, got {stopped:?}");
+    };
+    assert_eq!(page, 2);
+    assert!(matches!(
+        *error,
+        Error::ResponseCode(MRSPField::LowVoltageError)
+    ));
+    assert_eq!(
+        events,
+        vec![
+            ReflashProgress::Started {
+                start_page: 0,
+                pages: 4
+            },
+            ReflashProgress::PageWritten { page: 0, pages: 4 },
+            ReflashProgress::PageRetry {
+                page: 1,
+                attempt: 1
+            },
+            ReflashProgress::PageWritten { page: 1, pages: 4 },
+        ]
+    );
+    println!("stopped at page {page}: {error}");
+
+    // Resume from where it stopped, skipping pages already written
+    let options = ReflashOptions {
+        start_page: page,
+        check_blank: true,
+        ..ReflashOptions::default()
+    };
+    mock.clear_written();
+    let mut events = vec![];
+    let version = device
+        .reflash_with(&image, options, |event| events.push(event))
+        .await?;
+    assert_eq!(
+        events.first(),
+        Some(&ReflashProgress::Started {
+            start_page: 2,
+            pages: 4
+        })
+    );
+    assert_eq!(events.last(), Some(&ReflashProgress::Finished));
+    assert!(!mock
+        .written_packets()
+        .iter()
+        .any(|packet| packet.cid() == BootloaderCommandID::Reflash as u8
+            && packet.did() == DeviceID::Bootloader));
+    assert_eq!(version.msa_ver, 2);
+    for page in 0..image.page_count() {
+        let written = robot.lock().unwrap().pages[page as usize].clone();
+        assert_eq!(written.as_deref(), image.page(page));
+    }
+    println!("reflashed, application version {}", version.msa_ver);
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Replays a recorded session through the device client and checks what it decodes.
+//!
+//! `fixtures/sensor_session.rec` was captured with `transport::Record`: the
+//! firmware probe, a ping, an LED change, then accelerometer streaming, with
+//! one sensor packet split across two chunks. Needs no hardware:
+//! `cargo run --example replay_session --features tokio`
+
+use futures::StreamExt;
+use sphero_rs::color::RgbColor;
+use sphero_rs::command::{Ping, SetRGBLEDOutput};
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::sensor::{Sensor, SensorFrame, StreamingConfig};
+use sphero_rs::transport::{Recording, Replay, WriteCheck};
+use std::time::Duration;
+
+const SESSION: &str = include_str!("fixtures/sensor_session.rec");
+
+/// Frame of the filtered accelerometer axes
+fn accel(x: i16, y: i16, z: i16) -> SensorFrame {
+    SensorFrame {
+        values: vec![
+            (Sensor::AccelX, x),
+            (Sensor::AccelY, y),
+            (Sensor::AccelZ, z),
+        ],
+    }
+}
+
+// One thread, so the background tasks write in the order they did when recorded
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let replay = Replay::new(SESSION.parse::<Recording>()?, WriteCheck::Strict);
+    let device = SpheroDevice::new(replay.clone()).await?;
+    // Let the firmware probe go out first, as it did
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let capabilities = device.capabilities().await?;
+    assert_eq!(capabilities.firmware(), (3, 18));
+    println!("firmware {:?}", capabilities.firmware());
+
+    drop(device.send(&Ping {}).await?);
+    drop(
+        device
+            .send(&SetRGBLEDOutput::from_color(RgbColor::GREEN, false))
+            .await?,
+    );
+
+    let config =
+        StreamingConfig::new().with_sensors(&[Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ]);
+    let mut stream = device.start_streaming(config).await?;
+    let mut frames = vec![];
+    for _ in 0..4 {
+        frames.push(stream.next().await.unwrap()?);
+    }
+    assert_eq!(
+        frames,
+        vec![
+            accel(100, -200, 4096),
+            accel(120, -180, 4000),
+            accel(-50, 10, 4090),
+            accel(0, 0, 4096),
+        ]
+    );
+    println!("decoded {} frames", frames.len());
+    stream.stop().await?;
+
+    assert!(replay.is_finished(), "{} chunks left", replay.remaining());
+    println!("replayed the whole session");
+    Ok(())
+}
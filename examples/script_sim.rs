@@ -0,0 +1,95 @@
+//! Runs a JSON script against a mock robot and checks the commands it sends,
+//! then rejects a script with a bad step and cancels one mid-way.
+//!
+//! Needs no hardware: `cargo run --example script_sim --features tokio,serde`
+
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::error::Error;
+use sphero_rs::packet::{DeviceID, SpheroCommandID};
+use sphero_rs::script::{Script, ScriptStep, MAX_REPEAT_DEPTH};
+use sphero_rs::transport::mock::MockTransport;
+use std::time::{Duration, Instant};
+
+const SCRIPT: &str = r#"{ "steps": [
+    { "step": "set_color", "red": 0, "green": 255, "blue": 0 },
+    { "step": "repeat", "count": 2, "steps": [
+        { "step": "roll", "speed": 80, "heading": 90, "duration_ms": 30 },
+        { "step": "set_heading", "heading": 0 }
+    ] },
+    { "step": "wait", "ms": 20 },
+    { "step": "run_macro", "id": 2 }
+] }"#;
+
+/// Command IDs and data sent to the Sphero device
+fn sphero_commands(mock: &MockTransport) -> Vec<(u8, Vec<u8>)> {
+    mock.written_packets()
+        .iter()
+        .filter(|packet| packet.did() == DeviceID::Sphero)
+        .map(|packet| (packet.cid(), packet.data().to_vec()))
+        .collect()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mock = MockTransport::acknowledging();
+    let device = SpheroDevice::new(mock.clone()).await?;
+
+    let script: Script = serde_json::from_str(SCRIPT)?;
+    let started = Instant::now();
+    device.run_script(&script).await?;
+    // Two rolls and the wait hold for their delays
+    assert!(started.elapsed() >= Duration::from_millis(80));
+    let (led, roll, heading, run_macro) = (
+        SpheroCommandID::SetRGBLEDOutput as u8,
+        SpheroCommandID::Roll as u8,
+        SpheroCommandID::SetHeading as u8,
+        SpheroCommandID::RunMacro as u8,
+    );
+    let lap = [
+        (roll, vec![80, 0, 90, 1]),
+        (roll, vec![0, 0, 90, 0]),
+        (heading, vec![0, 0]),
+    ];
+    let mut expected = vec![(led, vec![0, 255, 0, 0])];
+    expected.extend(lap.iter().cloned().chain(lap.iter().cloned()));
+    expected.push((run_macro, vec![2]));
+    assert_eq!(sphero_commands(&mock), expected);
+    println!("ran {} steps", script.steps.len());
+
+    // A bad step is reported by position and nothing is sent
+    mock.clear_written();
+    let mut nested = ScriptStep::Wait { ms: 0 };
+    for _ in 0..=MAX_REPEAT_DEPTH {
+        nested = ScriptStep::Repeat {
+            count: 1,
+            steps: vec![nested],
+        };
+    }
+    let too_deep = Script::new(vec![ScriptStep::Wait { ms: 0 }, nested]);
+    let rejected = device.run_script(&too_deep).await;
+    let Err(error @ Error::ScriptStep { index: 1, .. }) = rejected else {
+        panic!("expected step 1 to be rejected, got {rejected:?}");
+    };
+    assert!(sphero_commands(&mock).is_empty());
+    println!("rejected: {error}");
+
+    // Cancelling stops the robot and fails the script
+    let long_roll = Script::new(vec![ScriptStep::Roll {
+        speed: 100,
+        heading: 45,
+        duration_ms: Some(10_000),
+    }]);
+    let started = Instant::now();
+    let (ran, ()) = tokio::join!(device.run_script(&long_roll), async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        device.cancel_script();
+    });
+    assert!(matches!(ran, Err(Error::Cancelled)));
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(
+        sphero_commands(&mock),
+        vec![(roll, vec![100, 0, 45, 1]), (roll, vec![0, 0, 45, 0])]
+    );
+    println!("cancelled after {:?}", started.elapsed());
+    Ok(())
+}
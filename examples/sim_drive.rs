@@ -0,0 +1,101 @@
+//! Drives the simulated robot on a script and checks it ends up where the
+//! model says: first by the locator, then by streamed odometry, then by the
+//! collision reported when it runs into a wall.
+//!
+//! Needs no hardware: `cargo run --example sim_drive --features tokio`
+
+use futures::StreamExt;
+use sphero_rs::collision::CollisionConfig;
+use sphero_rs::command::{ConfigureCollisionDetection, ReadLocator, Roll};
+use sphero_rs::device::SpheroDevice;
+use sphero_rs::event::AsyncMessage;
+use sphero_rs::heading::Heading;
+use sphero_rs::sensor::{Sensor, StreamingConfig};
+use sphero_rs::transport::sim::{SimConfig, SimulatedSphero, Walls};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How far the position may stray from the prediction, in cm
+const TOLERANCE: f32 = 6.0;
+
+fn near(actual: (f32, f32), expected: (f32, f32)) -> bool {
+    (actual.0 - expected.0).hypot(actual.1 - expected.1) <= TOLERANCE
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SimConfig {
+        walls: Some(Walls::square(150.0)),
+        ..SimConfig::default()
+    };
+    let sim = SimulatedSphero::new(config);
+    let device = SpheroDevice::new(sim.clone()).await?;
+    let collision = ConfigureCollisionDetection::from(CollisionConfig::method1());
+    drop(device.send(&collision).await?);
+    let mut events = device.events();
+
+    let streaming = StreamingConfig::new().with_sensors(&[Sensor::OdometerX, Sensor::OdometerY]);
+    let mut stream = device.start_streaming(streaming).await?;
+    let odometer = Mutex::new((0.0, 0.0));
+    let watch = async {
+        while let Some(frame) = stream.next().await {
+            let frame = frame?;
+            let x = frame.get(Sensor::OdometerX).unwrap_or_default();
+            let y = frame.get(Sensor::OdometerY).unwrap_or_default();
+            *odometer.lock().unwrap() = (x as f32, y as f32);
+        }
+        Ok::<(), sphero_rs::error::Error>(())
+    };
+
+    let script = async {
+        // 80 cm/s east for a second
+        let east = Roll {
+            speed: 80.into(),
+            heading: Heading::new(90)?,
+            state: true,
+        };
+        drop(device.send(&east).await?);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        drop(device.send(&Roll::stop(90)?).await?);
+
+        let locator = device.query(&ReadLocator {}).await?;
+        let position = (locator.x as f32, locator.y as f32);
+        println!("locator after the first leg: {:?}", position);
+        assert!(near(position, (80.0, 0.0)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let streamed = *odometer.lock().unwrap();
+        println!("streamed odometry: {:?}", streamed);
+        assert!(near(streamed, position));
+
+        // North for two seconds would reach y = 200, but the wall is at 150
+        let north = Roll {
+            speed: 100.into(),
+            heading: Heading::ZERO,
+            state: true,
+        };
+        drop(device.send(&north).await?);
+        let hit = tokio::time::timeout(Duration::from_secs(3), async {
+            while let Some(message) = events.next().await {
+                if let AsyncMessage::Collision(hit) = message {
+                    return Some(hit);
+                }
+            }
+            None
+        })
+        .await?
+        .expect("no collision reported");
+        println!("collision: axis {} at speed {}", hit.axis, hit.speed);
+        assert_eq!((hit.axis, hit.speed), (0b10, 100));
+        assert_eq!(sim.speed(), 0);
+        assert!(near(sim.position(), (80.0, 150.0)));
+        Ok::<(), Box<dyn std::error::Error>>(())
+    };
+
+    tokio::select! {
+        result = script => result?,
+        result = watch => result?,
+    }
+    stream.stop().await?;
+    println!("stopped at {:?}", sim.position());
+    Ok(())
+}
@@ -0,0 +1,177 @@
+/*!
+ * Sphero Aiming
+ *
+ * The tail light marks the back of the robot. Aiming lights it, rotates the
+ * robot in place until the tail faces the user, then makes that orientation
+ * the new 0 degree heading.
+ */
+use crate::command::{Roll, SetBackLEDOutput, SetHeading, SetStabilization};
+use crate::device::SpheroDevice;
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::speed::Speed;
+use crate::transport::Transport;
+
+/// Back LED brightness while aiming
+pub const AIMING_BRIGHTNESS: u8 = 0xff;
+
+impl<T: Transport + 'static> SpheroDevice<T> {
+    /// Light the tail light and start aiming
+    pub async fn start_aiming(&self) -> Result<AimingSession<'_, T>, Error> {
+        let previous_brightness = self.back_led_brightness();
+        drop(
+            self.send(&SetBackLEDOutput {
+                brightness: AIMING_BRIGHTNESS,
+            })
+            .await?,
+        );
+        Ok(AimingSession {
+            device: self,
+            previous_brightness,
+        })
+    }
+}
+
+/// Sphero Aiming Session
+///
+/// Finish with `commit` or `cancel`; dropping the session without either
+/// leaves the tail light on and the robot facing wherever it was rotated to.
+pub struct AimingSession<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    previous_brightness: u8,
+}
+
+impl<T: Transport + 'static> AimingSession<'_, T> {
+    /// Rotate in place to `degrees` (0..359) from the current 0 heading
+    pub async fn rotate_to(&mut self, degrees: u16) -> Result<(), Error> {
+        self.roll_to(degrees % 360).await
+    }
+
+    /// Make the current orientation the new 0 heading and turn the tail light off
+    pub async fn commit(self) -> Result<(), Error> {
+        drop(self.device.send(&SetHeading { heading: 0 }).await?);
+        self.finish(0).await
+    }
+
+    /// Rotate back to the original heading and restore the tail light
+    pub async fn cancel(self) -> Result<(), Error> {
+        self.roll_to(0).await?;
+        let brightness = self.previous_brightness;
+        self.finish(brightness).await
+    }
+
+    async fn roll_to(&self, heading: u16) -> Result<(), Error> {
+        let roll = Roll {
+            speed: Speed::zero(),
+            heading: Heading::new(heading)?,
+            state: true,
+        };
+        self.device.send(&roll).await.map(drop)
+    }
+
+    /// Re-enable stabilization so the robot holds its heading, then set the tail light
+    async fn finish(self, brightness: u8) -> Result<(), Error> {
+        drop(
+            self.device
+                .send(&SetStabilization { enabled: true })
+                .await?,
+        );
+        self.device
+            .send(&SetBackLEDOutput { brightness })
+            .await
+            .map(drop)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::packet::{
+        CoreCommandID, DeviceID, MRSPField, SpheroCommandID, SpheroCommandPacketV1,
+    };
+    use crate::transport::mock::{ack, respond, MockTransport};
+
+    /// Same answer as a Sphero 2.0 gives to Get Versioning
+    const FIRMWARE: [u8; 10] = [0x02, 0x03, 0x01, 0x03, 0x3b, 0x41, 0x21, 0x04, 0x01, 0x14];
+
+    fn is_probe(packet: &SpheroCommandPacketV1) -> bool {
+        packet.did() == DeviceID::Core
+            && packet.cid() == CoreCommandID::GetVersioningInformation as u8
+    }
+
+    fn robot() -> MockTransport {
+        MockTransport::with_responder(|bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            match is_probe(&packet) {
+                true => vec![respond(&packet, MRSPField::Ok, FIRMWARE.to_vec())],
+                false => vec![ack(&packet)],
+            }
+        })
+    }
+
+    /// Command IDs and payloads written to `mock`, apart from the firmware probe
+    fn written(mock: &MockTransport) -> Vec<(u8, Vec<u8>)> {
+        mock.written_packets()
+            .iter()
+            .filter(|packet| !is_probe(packet))
+            .map(|packet| (packet.cid(), packet.data().to_vec()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn commit_makes_the_rotation_the_new_heading() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+
+        let mut aiming = device.start_aiming().await.unwrap();
+        aiming.rotate_to(450).await.unwrap();
+        aiming.commit().await.unwrap();
+
+        assert_eq!(
+            written(&mock),
+            [
+                (
+                    SpheroCommandID::SetBackLEDOutput as u8,
+                    vec![AIMING_BRIGHTNESS]
+                ),
+                (SpheroCommandID::Roll as u8, vec![0x00, 0x00, 0x5a, 0x01]),
+                (SpheroCommandID::SetHeading as u8, vec![0x00, 0x00]),
+                (SpheroCommandID::SetStabilization as u8, vec![0x01]),
+                (SpheroCommandID::SetBackLEDOutput as u8, vec![0x00]),
+            ]
+        );
+        assert_eq!(device.back_led_brightness(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_turns_back_and_restores_the_tail_light() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        drop(
+            device
+                .send(&SetBackLEDOutput { brightness: 0x40 })
+                .await
+                .unwrap(),
+        );
+        mock.clear_written();
+
+        let mut aiming = device.start_aiming().await.unwrap();
+        aiming.rotate_to(90).await.unwrap();
+        aiming.cancel().await.unwrap();
+
+        assert_eq!(
+            written(&mock),
+            [
+                (
+                    SpheroCommandID::SetBackLEDOutput as u8,
+                    vec![AIMING_BRIGHTNESS]
+                ),
+                (SpheroCommandID::Roll as u8, vec![0x00, 0x00, 0x5a, 0x01]),
+                (SpheroCommandID::Roll as u8, vec![0x00, 0x00, 0x00, 0x01]),
+                (SpheroCommandID::SetStabilization as u8, vec![0x01]),
+                (SpheroCommandID::SetBackLEDOutput as u8, vec![0x40]),
+            ]
+        );
+        assert_eq!(device.back_led_brightness(), 0x40);
+    }
+}
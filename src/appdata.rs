@@ -0,0 +1,89 @@
+/*!
+ * Sphero Application Data
+ *
+ * A small framing for the Application Configuration Block, so an app can
+ * tell its own data from a block that was never written (or written by
+ * someone else), and which version of the app wrote it:
+ *
+ * | Offset | Size | Field                         |
+ * |--------|------|-------------------------------|
+ * | 0      | 1    | magic, `APP_DATA_MAGIC`       |
+ * | 1      | 1    | version, chosen by the app    |
+ * | 2      | 1    | payload length                |
+ * | 3      | 28   | payload, zero padded          |
+ * | 31     | 1    | checksum of the bytes before  |
+ */
+use crate::command::ACB_LEN;
+use crate::error::Error;
+use crate::packet::calculate_checksum;
+
+/// First byte of a block written with `AppData::encode`
+pub const APP_DATA_MAGIC: u8 = 0xa5;
+/// Largest payload that fits in the block
+pub const MAX_APP_DATA_LEN: usize = ACB_LEN - 4;
+
+/// Sphero Application Data
+///
+/// ```
+/// use sphero_rs::appdata::AppData;
+///
+/// // Heading offset and speed scale, as version 2 of the app stores them
+/// let data = AppData::new(2, vec![0x00, 0x5a, 0x50]).unwrap();
+/// let block = data.encode();
+/// assert_eq!(block[..5], [0xa5, 2, 3, 0x00, 0x5a]);
+/// assert_eq!(AppData::decode(&block), Some(data));
+///
+/// // Never written, or written by something else
+/// assert_eq!(AppData::decode(&[0; 32]), None);
+/// assert_eq!(AppData::decode(&[0xff; 32]), None);
+///
+/// // A corrupted block is not trusted
+/// let mut corrupt = block;
+/// corrupt[4] ^= 0x01;
+/// assert_eq!(AppData::decode(&corrupt), None);
+///
+/// assert!(AppData::new(1, vec![0; 29]).is_err());
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct AppData {
+    /// Version of the app that wrote the payload
+    pub version: u8,
+    /// App-defined contents
+    pub payload: Vec<u8>,
+}
+
+impl AppData {
+    /// Payload written by version `version` of the app
+    /// Fails with `Error::BadDataLength` if longer than `MAX_APP_DATA_LEN`.
+    pub fn new(version: u8, payload: Vec<u8>) -> Result<Self, Error> {
+        if payload.len() > MAX_APP_DATA_LEN {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self { version, payload })
+    }
+
+    /// Block to write with `SpheroDevice::write_app_data`
+    pub fn encode(&self) -> [u8; ACB_LEN] {
+        let len = self.payload.len().min(MAX_APP_DATA_LEN);
+        let mut block = [0; ACB_LEN];
+        block[0] = APP_DATA_MAGIC;
+        block[1] = self.version;
+        block[2] = len as u8;
+        block[3..3 + len].copy_from_slice(&self.payload[..len]);
+        block[ACB_LEN - 1] = calculate_checksum(&block[..ACB_LEN - 1], &[]);
+        block
+    }
+
+    /// Data in a block read with `SpheroDevice::read_app_data`
+    /// `None` if the block was never written with `encode` or is corrupt.
+    pub fn decode(block: &[u8; ACB_LEN]) -> Option<Self> {
+        let len = block[2] as usize;
+        let valid = block[0] == APP_DATA_MAGIC
+            && len <= MAX_APP_DATA_LEN
+            && block[ACB_LEN - 1] == calculate_checksum(&block[..ACB_LEN - 1], &[]);
+        valid.then(|| Self {
+            version: block[1],
+            payload: block[3..3 + len].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,277 @@
+/*!
+ * Sphero Asynchronous Payloads
+ *
+ * Typed data of each documented asynchronous message, keyed by its ID code.
+ * <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 9)
+ */
+use crate::error::Error;
+use crate::packet::AsynchronousIDCode;
+use crate::power::PowerState;
+use crate::sensor::{SensorFrame, SensorMask};
+
+/// Power Notification (ID code 01h)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct PowerNotificationPayload {
+    /// Power state
+    pub state: PowerState,
+}
+
+/// Level 1 Diagnostic Response (ID code 02h)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Level1DiagnosticPayload {
+    /// Diagnostic report, as ASCII text
+    pub text: String,
+}
+
+/// Sensor Data Streaming (ID code 03h)
+/// Frames can only be split with the streaming mask in effect, see `frames`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SensorDataPayload {
+    /// Raw frames, back to back
+    pub data: Vec<u8>,
+}
+
+impl SensorDataPayload {
+    /// Decode the frames streamed with `mask`
+    pub fn frames(&self, mask: &SensorMask) -> Result<Vec<SensorFrame>, Error> {
+        SensorFrame::decode(mask, &self.data)
+    }
+}
+
+/// Config Block Contents (ID code 04h)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ConfigBlockPayload {
+    /// Raw configuration block
+    pub data: Vec<u8>,
+}
+
+/// Macro Marker (ID code 06h)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct MacroMarkerPayload {
+    /// Marker value from the macro
+    pub marker: u8,
+    /// ID of the macro that emitted it
+    pub macro_id: u8,
+    /// Number of the marker command within the macro
+    pub command: u16,
+}
+
+/// Collision Detected (ID code 07h)
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 30)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct CollisionDetectedPayload {
+    /// Impact acceleration, X axis
+    pub x: i16,
+    /// Impact acceleration, Y axis
+    pub y: i16,
+    /// Impact acceleration, Z axis
+    pub z: i16,
+    /// Axis bitfield: bit 0 = X, bit 1 = Y
+    pub axis: u8,
+    /// Power that crossed the programmed threshold, X axis
+    pub x_magnitude: i16,
+    /// Power that crossed the programmed threshold, Y axis
+    pub y_magnitude: i16,
+    /// Speed at the time of impact
+    pub speed: u8,
+    /// Robot time of the impact, in ms
+    pub timestamp: u32,
+}
+
+impl CollisionDetectedPayload {
+    /// Decode the data of a collision async packet
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 16 {
+            return Err(Error::BadDataLength);
+        }
+        let word = |i: usize| i16::from_be_bytes([data[i], data[i + 1]]);
+        Ok(Self {
+            x: word(0),
+            y: word(2),
+            z: word(4),
+            axis: data[6],
+            x_magnitude: word(7),
+            y_magnitude: word(9),
+            speed: data[11],
+            timestamp: u32::from_be_bytes([data[12], data[13], data[14], data[15]]),
+        })
+    }
+}
+
+/// orbBasic PRINT Message (ID code 08h)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct OrbBasicPrintPayload {
+    /// Printed text
+    pub text: String,
+}
+
+/// orbBasic ASCII Error Message (ID code 09h)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct OrbBasicErrorAsciiPayload {
+    /// Error message, e.g. "Syntax error in line 10"
+    pub text: String,
+}
+
+/// orbBasic Binary Error Message (ID code 0Ah)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct OrbBasicErrorBinaryPayload {
+    /// Error code
+    pub code: u16,
+    /// Line the error occurred on
+    pub line: u16,
+}
+
+/// Self Level Result (ID code 0Bh)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct SelfLevelResultPayload {
+    /// Result code: 0 = unknown, 1 = timed out, 2 = sensors error,
+    /// 3 = self level disabled, 4 = aborted, 5 = charger not found, 6 = success
+    pub result: u8,
+}
+
+/// Gyro Axis Limit Exceeded (ID code 0Ch)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct GyroAxisLimitPayload {
+    /// Axes over the limit: bits 0..5 = X+, Y+, Z+, X-, Y-, Z-
+    pub axes: u8,
+}
+
+/// Sphero Asynchronous Payload
+#[derive(Debug, PartialEq, Clone)]
+pub enum AsyncPayload {
+    /// Power Notification
+    PowerNotification(PowerNotificationPayload),
+    /// Level 1 Diagnostic Response
+    Level1Diagnostic(Level1DiagnosticPayload),
+    /// Sensor Data Streaming
+    SensorData(SensorDataPayload),
+    /// Config Block Contents
+    ConfigBlock(ConfigBlockPayload),
+    /// Pre-sleep Warning: the robot goes to sleep in 10 seconds
+    PreSleepWarning,
+    /// Macro Marker
+    MacroMarker(MacroMarkerPayload),
+    /// Collision Detected
+    CollisionDetected(CollisionDetectedPayload),
+    /// orbBasic PRINT Message
+    OrbBasicPrint(OrbBasicPrintPayload),
+    /// orbBasic ASCII Error Message
+    OrbBasicErrorAscii(OrbBasicErrorAsciiPayload),
+    /// orbBasic Binary Error Message
+    OrbBasicErrorBinary(OrbBasicErrorBinaryPayload),
+    /// Self Level Result
+    SelfLevelResult(SelfLevelResultPayload),
+    /// Gyro Axis Limit Exceeded
+    GyroAxisLimitExceeded(GyroAxisLimitPayload),
+    /// Game notifications (soul data, level up, shield damage, XP and boost updates), undecoded
+    Game(AsynchronousIDCode, Vec<u8>),
+}
+
+/// ASCII payload as text, without trailing NULs or line breaks
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches(['\0', '\r', '\n'])
+        .to_string()
+}
+
+/// Decode the data of an async packet with ID code `idcode`
+///
+/// ```
+/// use sphero_rs::async_payload::{parse, AsyncPayload, OrbBasicPrintPayload};
+/// use sphero_rs::error::Error;
+/// use sphero_rs::packet::AsynchronousIDCode;
+///
+/// let payload = parse(AsynchronousIDCode::OrbBasicPrint, b"hello\r\n").unwrap();
+/// assert_eq!(
+///     payload,
+///     AsyncPayload::OrbBasicPrint(OrbBasicPrintPayload { text: "hello".to_string() })
+/// );
+///
+/// let code = AsynchronousIDCode::try_from(0x01).unwrap();
+/// assert!(matches!(parse(code, &[0x02]), Ok(AsyncPayload::PowerNotification(_))));
+/// assert!(parse(AsynchronousIDCode::CollisionDetected, &[0; 4]).is_err());
+/// assert!(matches!(
+///     AsynchronousIDCode::try_from(0x42),
+///     Err(Error::UnknownAsyncIdCode(0x42))
+/// ));
+/// ```
+pub fn parse(idcode: AsynchronousIDCode, data: &[u8]) -> Result<AsyncPayload, Error> {
+    let at_least = |len: usize| {
+        if data.len() < len {
+            Err(Error::BadDataLength)
+        } else {
+            Ok(())
+        }
+    };
+    Ok(match idcode {
+        AsynchronousIDCode::PowerNotification => {
+            at_least(1)?;
+            AsyncPayload::PowerNotification(PowerNotificationPayload {
+                state: PowerState::from(data[0]),
+            })
+        }
+        AsynchronousIDCode::Level1Diagnostic => {
+            AsyncPayload::Level1Diagnostic(Level1DiagnosticPayload { text: text(data) })
+        }
+        AsynchronousIDCode::SensorData => AsyncPayload::SensorData(SensorDataPayload {
+            data: data.to_vec(),
+        }),
+        AsynchronousIDCode::ConfigBlock => AsyncPayload::ConfigBlock(ConfigBlockPayload {
+            data: data.to_vec(),
+        }),
+        AsynchronousIDCode::PreSleepWarning => AsyncPayload::PreSleepWarning,
+        AsynchronousIDCode::MacroMarker => {
+            at_least(4)?;
+            AsyncPayload::MacroMarker(MacroMarkerPayload {
+                marker: data[0],
+                macro_id: data[1],
+                command: u16::from_be_bytes([data[2], data[3]]),
+            })
+        }
+        AsynchronousIDCode::CollisionDetected => {
+            AsyncPayload::CollisionDetected(CollisionDetectedPayload::decode(data)?)
+        }
+        AsynchronousIDCode::OrbBasicPrint => {
+            AsyncPayload::OrbBasicPrint(OrbBasicPrintPayload { text: text(data) })
+        }
+        AsynchronousIDCode::OrbBasicErrorAscii => {
+            AsyncPayload::OrbBasicErrorAscii(OrbBasicErrorAsciiPayload { text: text(data) })
+        }
+        AsynchronousIDCode::OrbBasicErrorBinary => {
+            at_least(4)?;
+            AsyncPayload::OrbBasicErrorBinary(OrbBasicErrorBinaryPayload {
+                code: u16::from_be_bytes([data[0], data[1]]),
+                line: u16::from_be_bytes([data[2], data[3]]),
+            })
+        }
+        AsynchronousIDCode::SelfLevelResult => {
+            at_least(1)?;
+            AsyncPayload::SelfLevelResult(SelfLevelResultPayload { result: data[0] })
+        }
+        AsynchronousIDCode::GyroAxisLimitExceeded => {
+            at_least(1)?;
+            AsyncPayload::GyroAxisLimitExceeded(GyroAxisLimitPayload { axes: data[0] })
+        }
+        AsynchronousIDCode::SoulData
+        | AsynchronousIDCode::LevelUp
+        | AsynchronousIDCode::ShieldDamage
+        | AsynchronousIDCode::XpUpdate
+        | AsynchronousIDCode::BoostUpdate => AsyncPayload::Game(idcode, data.to_vec()),
+    })
+}
+
+/// Decode an async packet by its raw ID code byte
+///
+/// Fails with `Error::UnknownAsyncIdCode` if the firmware sent a message this
+/// crate doesn't know.
+///
+/// ```
+/// use sphero_rs::async_payload::{parse_raw, AsyncPayload};
+/// use sphero_rs::error::Error;
+///
+/// assert_eq!(parse_raw(0x05, &[]).unwrap(), AsyncPayload::PreSleepWarning);
+/// assert!(matches!(parse_raw(0x42, &[]), Err(Error::UnknownAsyncIdCode(0x42))));
+/// ```
+pub fn parse_raw(idcode: u8, data: &[u8]) -> Result<AsyncPayload, Error> {
+    parse(AsynchronousIDCode::try_from(idcode)?, data)
+}
@@ -0,0 +1,170 @@
+//! sphero-ctl: talk to a Sphero over BLE for scripting and diagnostics
+//!
+//! Build with `cargo build --features cli`; run without arguments for usage.
+//! Parsing and output live in `sphero_rs::cli`.
+
+use btleplug::api::Manager as _;
+use btleplug::platform::Manager;
+use futures::StreamExt;
+use sphero_rs::cli::{
+    csv_header, csv_row, format_power, format_response, format_scan_line, format_version, Args,
+    Command, USAGE,
+};
+use sphero_rs::client::SpheroClient;
+use sphero_rs::command::{Ping, Roll, SetRGBLEDOutput, Sleep, ToCommandPacket};
+use sphero_rs::discover::scan_for_spheros;
+use sphero_rs::error::Error;
+use sphero_rs::event::SpheroEvent;
+use sphero_rs::packet::{DeviceID, SpheroCommandPacketV1};
+use sphero_rs::sensor::{SensorFrame, SensorMask, StreamingConfig};
+use sphero_rs::transport::ble::BleTransport;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// Command given by raw IDs on the command line
+struct RawCommand {
+    did: DeviceID,
+    cid: u8,
+    data: Vec<u8>,
+}
+
+impl ToCommandPacket for RawCommand {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        SpheroCommandPacketV1::new(self.did, self.cid, seq, self.data.clone())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match Args::parse(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("sphero-ctl: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("sphero-ctl: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run(args)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("sphero-ctl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let manager = Manager::new().await?;
+    let adapter = manager
+        .adapters()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(Error::TargetUnavailable)?;
+    let found = scan_for_spheros(&adapter, args.scan_timeout).await?;
+    if args.command == Command::Scan {
+        for sphero in &found {
+            let address = sphero.address.to_string();
+            println!("{}", format_scan_line(&sphero.name, &address, sphero.rssi));
+        }
+        return Ok(());
+    }
+
+    let sphero = found
+        .into_iter()
+        .find(|sphero| {
+            let address = sphero.address.to_string();
+            args.target
+                .as_ref()
+                .is_none_or(|target| target.matches(&sphero.name, &address))
+        })
+        .ok_or(Error::TargetUnavailable)?;
+    let mut client = SpheroClient::new(BleTransport::connect(sphero.peripheral).await?);
+
+    match args.command {
+        Command::Scan => {}
+        Command::Ping => {
+            let sent = Instant::now();
+            drop(client.send(&Ping {}).await?);
+            println!("pong in {} ms", sent.elapsed().as_millis());
+        }
+        Command::Color(color) => {
+            drop(
+                client
+                    .send(&SetRGBLEDOutput::from_color(color, false))
+                    .await?,
+            );
+        }
+        Command::Roll { speed, heading } => {
+            let roll = Roll {
+                speed,
+                heading,
+                state: true,
+            };
+            drop(client.send(&roll).await?);
+        }
+        Command::Power => println!("{}", format_power(&client.get_power_state().await?)),
+        Command::Version => println!("{}", format_version(&client.get_version().await?)),
+        Command::Stream {
+            sensors,
+            rate_hz,
+            count,
+            csv,
+        } => {
+            let mut out: Box<dyn Write> = match csv {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout()),
+            };
+            writeln!(out, "{}", csv_header(&sensors))?;
+            let mask = SensorMask::from_sensors(&sensors);
+            let config = StreamingConfig::new()
+                .sample_rate_hz(rate_hz)?
+                .with_sensors(&sensors);
+            let mut events = client.event_stream().await?;
+            drop(client.send(&config.build()).await?);
+            let mut index = 0;
+            while count.is_none_or(|count| index < count) {
+                let Some(event) = events.next().await else {
+                    break;
+                };
+                let SpheroEvent::Async(packet) = event? else {
+                    continue;
+                };
+                if packet.idcode() != 0x03 {
+                    continue;
+                }
+                for frame in SensorFrame::decode(&mask, packet.data())? {
+                    writeln!(out, "{}", csv_row(index, &frame))?;
+                    index += 1;
+                }
+            }
+            out.flush()?;
+            drop(client.send(&StreamingConfig::new().build()).await?);
+        }
+        Command::Sleep => drop(client.send(&Sleep::default()).await?),
+        Command::Raw { did, cid, data } => {
+            let raw = RawCommand {
+                did: DeviceID::try_from_byte(did)?,
+                cid,
+                data,
+            };
+            println!("{}", format_response(&client.send(&raw).await?));
+        }
+    }
+    Ok(())
+}
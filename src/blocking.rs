@@ -0,0 +1,332 @@
+/*!
+ * Sphero Blocking Client
+ *
+ * A synchronous facade over `SpheroDevice` for scripts and quick experiments.
+ * Every call blocks until the robot answers or the default response timeout
+ * expires, and the robot is shut down when the client is dropped.
+ */
+use crate::color::RgbColor;
+use crate::command::{
+    GetPowerState, GetVersioning, Ping, Roll, SetBackLEDOutput, SetRGBLEDOutput, ToCommandPacket,
+};
+use crate::device::{ShutdownOptions, SpheroDevice};
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::packet::SpheroResponsePacketV1;
+use crate::response::{PowerStateInfo, VersioningInfo};
+use crate::transport::Transport;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::thread;
+
+#[cfg(feature = "ble")]
+pub use self::ble::SCAN_TIMEOUT;
+
+type BoxSpawner = Box<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>;
+
+/// Drives the async client from synchronous code
+enum Executor {
+    /// Calls block the calling thread; each background task gets a thread
+    Threads,
+    /// A tokio runtime run by a dedicated thread, needed by btleplug
+    #[cfg(feature = "ble")]
+    Tokio {
+        handle: tokio::runtime::Handle,
+        // Dropping this ends the runtime thread
+        _stop: futures::channel::oneshot::Sender<()>,
+    },
+}
+
+impl Executor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match self {
+            Executor::Threads => futures::executor::block_on(future),
+            #[cfg(feature = "ble")]
+            Executor::Tokio { handle, .. } => handle.block_on(future),
+        }
+    }
+
+    fn spawner(&self) -> BoxSpawner {
+        match self {
+            Executor::Threads => Box::new(|future| {
+                drop(thread::spawn(move || futures::executor::block_on(future)));
+            }),
+            #[cfg(feature = "ble")]
+            Executor::Tokio { handle, .. } => {
+                let handle = handle.clone();
+                Box::new(move |future| drop(handle.spawn(future)))
+            }
+        }
+    }
+}
+
+/// Sphero Blocking Client
+pub struct BlockingSphero<T: Transport + 'static> {
+    // Only taken when dropped
+    device: Option<SpheroDevice<T>>,
+    executor: Executor,
+}
+
+impl<T: Transport + 'static> BlockingSphero<T> {
+    /// Connect over a transport that doesn't need a particular async runtime,
+    /// such as the mock transport
+    pub fn new(transport: T) -> Result<Self, Error> {
+        Self::with_executor(transport, Executor::Threads)
+    }
+
+    fn with_executor(transport: T, executor: Executor) -> Result<Self, Error> {
+        let device =
+            executor.block_on(SpheroDevice::with_spawner(transport, executor.spawner()))?;
+        Ok(Self {
+            device: Some(device),
+            executor,
+        })
+    }
+
+    /// Underlying async client
+    pub fn device(&self) -> &SpheroDevice<T> {
+        self.device.as_ref().expect("device is only taken on drop")
+    }
+
+    /// Send any command and wait for its response
+    pub fn send(&self, cmd: &impl ToCommandPacket) -> Result<SpheroResponsePacketV1, Error> {
+        self.executor.block_on(self.device().send(cmd))
+    }
+
+    /// Check the robot is responding
+    pub fn ping(&self) -> Result<(), Error> {
+        self.send(&Ping {}).map(drop)
+    }
+
+    /// Set the main LED
+    pub fn set_color(&self, color: RgbColor) -> Result<(), Error> {
+        self.send(&SetRGBLEDOutput::from_color(color, false))
+            .map(drop)
+    }
+
+    /// Set the tail light brightness
+    pub fn set_back_led(&self, brightness: u8) -> Result<(), Error> {
+        self.send(&SetBackLEDOutput { brightness }).map(drop)
+    }
+
+    /// Roll at `speed` towards `heading` (0..359 degrees)
+    pub fn roll(&self, speed: u8, heading: u16) -> Result<(), Error> {
+        let cmd = Roll {
+            speed: speed.into(),
+            heading: Heading::new(heading % 360)?,
+            state: true,
+        };
+        self.send(&cmd).map(drop)
+    }
+
+    /// Stop rolling
+    pub fn stop(&self) -> Result<(), Error> {
+        self.send(&Roll::stop_at_zero()).map(drop)
+    }
+
+    /// Battery and charging state
+    pub fn power_state(&self) -> Result<PowerStateInfo, Error> {
+        self.executor
+            .block_on(self.device().query(&GetPowerState {}))
+    }
+
+    /// Firmware and hardware versions
+    pub fn version(&self) -> Result<VersioningInfo, Error> {
+        self.executor
+            .block_on(self.device().query(&GetVersioning {}))
+    }
+
+    /// Shut the robot down and close the link, see `SpheroDevice::shutdown`
+    pub fn close(mut self) -> Result<(), Error> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        match self.device.take() {
+            Some(device) => self
+                .executor
+                .block_on(device.shutdown(ShutdownOptions::default())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Transport + 'static> Drop for BlockingSphero<T> {
+    fn drop(&mut self) {
+        drop(self.shutdown());
+    }
+}
+
+#[cfg(feature = "ble")]
+mod ble {
+    use super::{BlockingSphero, Executor};
+    use crate::discover::scan_for_spheros;
+    use crate::error::Error;
+    use crate::transport::ble::BleTransport;
+    use btleplug::api::Manager as _;
+    use btleplug::platform::{Manager, Peripheral};
+    use futures::channel::oneshot;
+    use std::thread;
+    use std::time::Duration;
+
+    /// How long `connect_by_name` scans for
+    pub const SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+
+    impl Executor {
+        fn tokio() -> Result<Self, Error> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| Error::Transport(e.to_string()))?;
+            let handle = runtime.handle().clone();
+            let (stop, stopped) = oneshot::channel::<()>();
+            drop(
+                thread::Builder::new()
+                    .name("sphero-runtime".to_string())
+                    .spawn(move || {
+                        // Runs until the executor is dropped
+                        let _ = runtime.block_on(stopped);
+                    })
+                    .map_err(|e| Error::Transport(e.to_string()))?,
+            );
+            Ok(Executor::Tokio {
+                handle,
+                _stop: stop,
+            })
+        }
+    }
+
+    impl BlockingSphero<BleTransport<Peripheral>> {
+        /// Scan with the first Bluetooth adapter and connect to the robot advertising `name`
+        /// Names are compared ignoring case. Fails with `Error::TargetUnavailable` if no robot matches.
+        pub fn connect_by_name(name: &str) -> Result<Self, Error> {
+            let executor = Executor::tokio()?;
+            let transport = executor.block_on(async {
+                let manager = Manager::new().await?;
+                let adapter = manager
+                    .adapters()
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or(Error::TargetUnavailable)?;
+                let found = scan_for_spheros(&adapter, SCAN_TIMEOUT)
+                    .await?
+                    .into_iter()
+                    .find(|sphero| sphero.name.eq_ignore_ascii_case(name))
+                    .ok_or(Error::TargetUnavailable)?;
+                BleTransport::connect(found.peripheral).await
+            })?;
+            Self::with_executor(transport, executor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{
+        CoreCommandID, DeviceID, MRSPField, SpheroCommandID, SpheroCommandPacketV1,
+    };
+    use crate::power::{PowerState, Voltage};
+    use crate::transport::mock::{ack, respond, MockTransport};
+
+    /// Same answer as a Sphero 2.0 gives to Get Versioning
+    const FIRMWARE: [u8; 10] = [0x02, 0x03, 0x01, 0x03, 0x3b, 0x41, 0x21, 0x04, 0x01, 0x14];
+
+    fn is_probe(packet: &SpheroCommandPacketV1) -> bool {
+        packet.did() == DeviceID::Core
+            && packet.cid() == CoreCommandID::GetVersioningInformation as u8
+    }
+
+    /// Mock robot answering the firmware probe, and every other command
+    /// with `answer`
+    fn robot<F>(answer: F) -> MockTransport
+    where
+        F: Fn(SpheroCommandPacketV1) -> Vec<u8> + Send + 'static,
+    {
+        MockTransport::with_responder(move |bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            match is_probe(&packet) {
+                true => vec![respond(&packet, MRSPField::Ok, FIRMWARE.to_vec())],
+                false => vec![answer(packet)],
+            }
+        })
+    }
+
+    /// Commands written to `mock`, apart from the firmware probe
+    fn written(mock: &MockTransport) -> Vec<SpheroCommandPacketV1> {
+        mock.written_packets()
+            .into_iter()
+            .filter(|packet| !is_probe(packet))
+            .collect()
+    }
+
+    #[test]
+    fn roll_wraps_the_heading() {
+        let mock = robot(|packet| ack(&packet));
+        let sphero = BlockingSphero::new(mock.clone()).unwrap();
+
+        sphero.roll(0x80, 450).unwrap();
+
+        let roll = written(&mock).pop().unwrap();
+        assert_eq!(roll.cid(), SpheroCommandID::Roll as u8);
+        assert_eq!(roll.data(), [0x80, 0x00, 0x5a, 0x01]);
+    }
+
+    #[test]
+    fn power_state_is_decoded() {
+        let mock = robot(|packet| match packet.did() == DeviceID::Core {
+            true => respond(&packet, MRSPField::Ok, vec![1, 2, 0x02, 0xe6, 0, 7, 0, 60]),
+            false => ack(&packet),
+        });
+        let sphero = BlockingSphero::new(mock).unwrap();
+
+        let power = sphero.power_state().unwrap();
+
+        assert_eq!(power.state, PowerState::Ok);
+        assert_eq!(power.voltage, Voltage::from_hundredths(742));
+        assert_eq!((power.num_charges, power.time_since_charge), (7, 60));
+    }
+
+    #[test]
+    fn error_response_is_returned() {
+        let mock = robot(|packet| respond(&packet, MRSPField::GeneralError, vec![]));
+        let sphero = BlockingSphero::new(mock).unwrap();
+
+        let result = sphero.set_back_led(0xff);
+
+        assert!(matches!(
+            result,
+            Err(Error::ResponseCode(MRSPField::GeneralError))
+        ));
+    }
+
+    #[test]
+    fn dropping_shuts_the_robot_down() {
+        let mock = robot(|packet| ack(&packet));
+        drop(BlockingSphero::new(mock.clone()).unwrap());
+
+        let commands: Vec<u8> = written(&mock).iter().map(|p| p.cid()).collect();
+        assert_eq!(
+            commands,
+            [
+                SpheroCommandID::SetDataStreaming as u8,
+                SpheroCommandID::Roll as u8,
+                SpheroCommandID::SetStabilization as u8,
+            ]
+        );
+        assert!(mock.is_closed());
+    }
+
+    #[test]
+    fn close_shuts_down_once() {
+        let mock = robot(|packet| ack(&packet));
+        let sphero = BlockingSphero::new(mock.clone()).unwrap();
+
+        sphero.close().unwrap();
+
+        // Drop after close doesn't shut down a second time
+        assert_eq!(written(&mock).len(), 3);
+        assert!(mock.is_closed());
+    }
+}
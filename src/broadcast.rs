@@ -0,0 +1,174 @@
+/*!
+ * Broadcast channel for device events
+ *
+ * Every subscriber gets its own bounded queue. A subscriber that falls behind
+ * loses its oldest items instead of stalling the sender.
+ */
+use futures::stream::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// Sending half, shared by the reader task
+pub(crate) struct Broadcast<T> {
+    subscribers: Mutex<Vec<Arc<Mutex<Queue<T>>>>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl<T: Clone> Broadcast<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            capacity,
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Queue `item` for every live subscriber
+    pub(crate) fn send(&self, item: T) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // The subscription holds the only other reference; drop queues nobody reads
+        subscribers.retain(|queue| Arc::strong_count(queue) > 1);
+        for queue in subscribers.iter() {
+            let mut queue = queue.lock().unwrap();
+            if queue.items.len() == self.capacity {
+                drop(queue.items.pop_front());
+                let _ = self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            queue.items.push_back(item.clone());
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// End every subscription once its queue is drained
+    pub(crate) fn close(&self) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        self.closed.store(true, Ordering::Relaxed);
+        for queue in subscribers.drain(..) {
+            let mut queue = queue.lock().unwrap();
+            queue.closed = true;
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Subscription<T> {
+        // Hold the lock so a concurrent `close` cannot miss this subscriber
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let queue = Arc::new(Mutex::new(Queue {
+            items: VecDeque::new(),
+            waker: None,
+            closed: self.closed.load(Ordering::Relaxed),
+        }));
+        subscribers.push(queue.clone());
+        Subscription { queue }
+    }
+
+    /// Items discarded because a subscriber fell behind
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Receiving half
+pub(crate) struct Subscription<T> {
+    queue: Arc<Mutex<Queue<T>>>,
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(item) = queue.items.pop_front() {
+            return Poll::Ready(Some(item));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn every_subscriber_gets_every_item() {
+        let broadcast = Broadcast::new(4);
+        let first = broadcast.subscribe();
+        let second = broadcast.subscribe();
+
+        broadcast.send(1);
+        broadcast.send(2);
+        broadcast.close();
+
+        assert_eq!(block_on(first.collect::<Vec<_>>()), [1, 2]);
+        assert_eq!(block_on(second.collect::<Vec<_>>()), [1, 2]);
+        assert_eq!(broadcast.dropped(), 0);
+    }
+
+    #[test]
+    fn lagging_subscriber_loses_its_oldest_items() {
+        let broadcast = Broadcast::new(2);
+        let subscription = broadcast.subscribe();
+
+        for item in 1..=5 {
+            broadcast.send(item);
+        }
+        broadcast.close();
+
+        assert_eq!(block_on(subscription.collect::<Vec<_>>()), [4, 5]);
+        assert_eq!(broadcast.dropped(), 3);
+    }
+
+    #[test]
+    fn dropped_subscription_is_not_queued_for() {
+        let broadcast = Broadcast::new(1);
+        drop(broadcast.subscribe());
+
+        broadcast.send(1);
+        broadcast.send(2);
+
+        assert_eq!(broadcast.dropped(), 0);
+        assert!(broadcast.subscribers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn pending_subscriber_is_woken_by_send() {
+        let broadcast = Arc::new(Broadcast::new(4));
+        let mut subscription = broadcast.subscribe();
+        let sender = broadcast.clone();
+
+        let sending = std::thread::spawn(move || sender.send(7));
+
+        assert_eq!(block_on(subscription.next()), Some(7));
+        sending.join().unwrap();
+    }
+
+    #[test]
+    fn subscribing_after_close_ends_at_once() {
+        let broadcast = Broadcast::<u8>::new(4);
+        broadcast.close();
+
+        assert_eq!(block_on(broadcast.subscribe().next()), None);
+    }
+}
@@ -0,0 +1,124 @@
+/*!
+ * Sphero Capabilities
+ *
+ * Some commands only exist from a given main application firmware version
+ * on; older robots answer them with an unknown command error. Knowing the
+ * firmware up front lets such commands fail locally with a clear message.
+ */
+use crate::error::Error;
+use crate::packet::{DeviceID, SpheroCommandID};
+use crate::response::VersioningInfo;
+
+/// Main application firmware version as (version, revision)
+pub type FirmwareVersion = (u8, u8);
+
+/// Sphero device commands newer than the original firmware, with the first version implementing them
+const SPHERO_REQUIREMENTS: [(u8, FirmwareVersion); 5] = [
+    (SpheroCommandID::ConfigureLocator as u8, LOCATOR),
+    (SpheroCommandID::ReadLocator as u8, LOCATOR),
+    (SpheroCommandID::SetMotionTimeout as u8, MOTION_TIMEOUT),
+    (SpheroCommandID::SetOptionsFlags as u8, OPTIONS_FLAGS),
+    (SpheroCommandID::GetOptionsFlags as u8, OPTIONS_FLAGS),
+];
+
+/// First firmware with Configure Locator and Read Locator
+const LOCATOR: FirmwareVersion = (1, 13);
+/// First firmware with Set Motion Timeout
+const MOTION_TIMEOUT: FirmwareVersion = (1, 17);
+/// First firmware with the persistent option flags
+const OPTIONS_FLAGS: FirmwareVersion = (1, 17);
+
+/// Minimum firmware for command `cid` of device `did`, `None` if every firmware has it
+pub fn minimum_firmware(did: DeviceID, cid: u8) -> Option<FirmwareVersion> {
+    if did != DeviceID::Sphero {
+        return None;
+    }
+    SPHERO_REQUIREMENTS
+        .iter()
+        .find(|(c, _)| *c == cid)
+        .map(|(_, version)| *version)
+}
+
+/// Sphero Capabilities
+/// What the robot's firmware implements, from Get Versioning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    firmware: FirmwareVersion,
+}
+
+impl Capabilities {
+    /// Capabilities of main application firmware `firmware`
+    ///
+    /// ```
+    /// use sphero_rs::capabilities::Capabilities;
+    ///
+    /// let original = Capabilities::new((1, 10));
+    /// assert!(!original.locator() && !original.motion_timeout());
+    ///
+    /// let middle = Capabilities::new((1, 13));
+    /// assert!(middle.locator() && !middle.motion_timeout());
+    ///
+    /// let current = Capabilities::new((3, 59));
+    /// assert!(current.locator() && current.motion_timeout() && current.options_flags());
+    /// ```
+    pub const fn new(firmware: FirmwareVersion) -> Self {
+        Self { firmware }
+    }
+
+    /// Capabilities from a Get Versioning response
+    pub fn from_version(info: &VersioningInfo) -> Self {
+        Self::new((info.msa_ver, info.msa_rev))
+    }
+
+    /// Main application firmware version
+    pub fn firmware(&self) -> FirmwareVersion {
+        self.firmware
+    }
+
+    /// Configure Locator and Read Locator
+    pub fn locator(&self) -> bool {
+        self.firmware >= LOCATOR
+    }
+
+    /// Set Motion Timeout
+    pub fn motion_timeout(&self) -> bool {
+        self.firmware >= MOTION_TIMEOUT
+    }
+
+    /// Set Options Flags and Get Options Flags
+    pub fn options_flags(&self) -> bool {
+        self.firmware >= OPTIONS_FLAGS
+    }
+
+    /// Whether the firmware implements command `cid` of device `did`
+    pub fn supports(&self, did: DeviceID, cid: u8) -> bool {
+        self.check(did, cid).is_ok()
+    }
+
+    /// `Error::RequiresFirmware` naming the minimum version if the command isn't implemented
+    ///
+    /// ```
+    /// use sphero_rs::capabilities::Capabilities;
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::packet::{DeviceID, SpheroCommandID};
+    ///
+    /// let caps = Capabilities::new((1, 10));
+    /// let cid = SpheroCommandID::ReadLocator as u8;
+    /// assert!(matches!(
+    ///     caps.check(DeviceID::Sphero, cid),
+    ///     Err(Error::RequiresFirmware { minimum: (1, 13), .. })
+    /// ));
+    /// assert!(caps.check(DeviceID::Sphero, SpheroCommandID::Roll as u8).is_ok());
+    /// ```
+    pub fn check(&self, did: DeviceID, cid: u8) -> Result<(), Error> {
+        match minimum_firmware(did, cid) {
+            Some(minimum) if self.firmware < minimum => Err(Error::RequiresFirmware {
+                did,
+                cid,
+                minimum,
+                found: self.firmware,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
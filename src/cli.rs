@@ -0,0 +1,454 @@
+/*!
+ * sphero-ctl Command Line
+ *
+ * Argument parsing and output formatting for the `sphero-ctl` binary, which
+ * talks to a robot over BLE for scripting and quick diagnostics. Kept here,
+ * apart from the binary, so it can be tested without a robot.
+ */
+use crate::color::RgbColor;
+use crate::heading::Heading;
+use crate::packet::SpheroResponsePacketV1;
+use crate::response::{PowerStateInfo, VersioningInfo};
+use crate::sensor::{Sensor, SensorFrame, SensorMask};
+use crate::speed::Speed;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long to scan for robots unless `--scan-secs` says otherwise
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Sample rate for `stream` unless `--rate` says otherwise
+pub const DEFAULT_STREAM_RATE_HZ: f32 = 20.0;
+
+/// Help text
+pub const USAGE: &str = "\
+usage: sphero-ctl [--name PREFIX | --address ADDR] [--scan-secs N] COMMAND
+
+commands:
+  scan                         list the robots in range
+  ping                         time a round trip
+  color R G B                  set the LED color
+  roll SPEED HEADING           roll at SPEED (0-255) towards HEADING (0-359)
+  power                        show the battery state
+  version                      show the firmware versions
+  stream [--sensors LIST] [--rate HZ] [--count N] [--csv FILE]
+                               print sensor frames as CSV; LIST is a comma
+                               separated list of accel, gyro, imu, quaternion,
+                               odometer, velocity and emf
+  sleep                        put the robot to sleep
+  raw DID CID [HEXDATA]        send any command and print the response
+
+Without --name or --address, the first robot found is used.
+Exits with status 1 if the robot rejects a command.";
+
+/// Bad command line, with what was wrong
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UsageError(pub String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+fn usage_error(message: impl Into<String>) -> UsageError {
+    UsageError(message.into())
+}
+
+/// Which robot to connect to
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Target {
+    /// The first robot whose advertised name starts with this
+    Name(String),
+    /// The robot with this Bluetooth address
+    Address(String),
+}
+
+impl Target {
+    /// Whether a robot advertising `name` at `address` is the one wanted
+    /// Names and addresses are compared ignoring case.
+    pub fn matches(&self, name: &str, address: &str) -> bool {
+        match self {
+            Target::Name(prefix) => name
+                .get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+            Target::Address(wanted) => address.eq_ignore_ascii_case(wanted),
+        }
+    }
+}
+
+/// What to do
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    /// List the robots in range
+    Scan,
+    /// Time a round trip
+    Ping,
+    /// Set the LED color
+    Color(RgbColor),
+    /// Roll
+    Roll {
+        /// Speed
+        speed: Speed,
+        /// Heading
+        heading: Heading,
+    },
+    /// Show the battery state
+    Power,
+    /// Show the firmware versions
+    Version,
+    /// Print sensor frames as CSV
+    Stream {
+        /// Sensors to stream
+        sensors: Vec<Sensor>,
+        /// Sample rate, in Hz
+        rate_hz: f32,
+        /// Frames to print before stopping, `None` for no limit
+        count: Option<usize>,
+        /// File to write to instead of standard output
+        csv: Option<PathBuf>,
+    },
+    /// Put the robot to sleep
+    Sleep,
+    /// Send any command
+    Raw {
+        /// Device ID
+        did: u8,
+        /// Command ID
+        cid: u8,
+        /// Data
+        data: Vec<u8>,
+    },
+}
+
+/// Parsed command line
+///
+/// ```
+/// use sphero_rs::cli::{Args, Command, Target};
+/// use sphero_rs::color::RgbColor;
+/// use sphero_rs::sensor::Sensor;
+///
+/// let args = Args::parse(["--name", "SK-", "color", "255", "0", "128"])?;
+/// assert_eq!(args.target, Some(Target::Name("SK-".to_string())));
+/// assert_eq!(args.command, Command::Color(RgbColor::new(255, 0, 128)));
+///
+/// let args = Args::parse(["raw", "0x02", "0x20", "ff0000"])?;
+/// assert_eq!(args.target, None);
+/// assert_eq!(args.command, Command::Raw { did: 2, cid: 0x20, data: vec![0xff, 0, 0] });
+///
+/// let args = Args::parse(["stream", "--sensors", "accel,imu", "--rate", "50", "--csv", "out.csv"])?;
+/// let Command::Stream { sensors, rate_hz, count, csv } = args.command else { panic!() };
+/// assert_eq!(sensors[..4], [Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ, Sensor::ImuPitch]);
+/// assert_eq!((rate_hz, count), (50.0, None));
+/// assert_eq!(csv.unwrap().to_str(), Some("out.csv"));
+///
+/// // Mistakes are reported rather than guessed at
+/// assert!(Args::parse(["color", "256", "0", "0"]).is_err());
+/// assert!(Args::parse(["roll", "100"]).is_err());
+/// assert!(Args::parse(["roll", "100", "360"]).is_err());
+/// assert!(Args::parse(["stream", "--sensors", "sonar"]).is_err());
+/// assert!(Args::parse(["raw", "0", "1", "abc"]).is_err());
+/// assert!(Args::parse(["ping", "extra"]).is_err());
+/// assert!(Args::parse(["fly"]).is_err());
+/// assert!(Args::parse::<[&str; 0], &str>([]).is_err());
+/// # Ok::<(), sphero_rs::cli::UsageError>(())
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Args {
+    /// Robot to connect to, `None` for the first found
+    pub target: Option<Target>,
+    /// How long to scan for
+    pub scan_timeout: Duration,
+    /// What to do
+    pub command: Command,
+}
+
+impl Args {
+    /// Parse the arguments after the program name
+    pub fn parse<I, S>(args: I) -> Result<Self, UsageError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args
+            .into_iter()
+            .map(|arg| arg.as_ref().to_string())
+            .collect();
+        let mut args = args.iter().map(String::as_str);
+        let mut target = None;
+        let mut scan_timeout = DEFAULT_SCAN_TIMEOUT;
+        let name = loop {
+            match args.next() {
+                Some("--name") => target = Some(Target::Name(value(&mut args, "--name")?.into())),
+                Some("--address") => {
+                    target = Some(Target::Address(value(&mut args, "--address")?.into()))
+                }
+                Some("--scan-secs") => {
+                    let secs: u64 = number(value(&mut args, "--scan-secs")?, "--scan-secs")?;
+                    scan_timeout = Duration::from_secs(secs);
+                }
+                Some(name) => break name,
+                None => return Err(usage_error("no command given")),
+            }
+        };
+        let rest: Vec<&str> = args.collect();
+        let command = match (name, &rest[..]) {
+            ("scan", []) => Command::Scan,
+            ("ping", []) => Command::Ping,
+            ("color", [r, g, b]) => Command::Color(RgbColor::new(
+                number(r, "red")?,
+                number(g, "green")?,
+                number(b, "blue")?,
+            )),
+            ("roll", [speed, heading]) => Command::Roll {
+                speed: Speed::new(number(speed, "speed")?),
+                heading: Heading::new(number(heading, "heading")?)
+                    .map_err(|_| usage_error(format!("heading must be 0-359, not {heading}")))?,
+            },
+            ("power", []) => Command::Power,
+            ("version", []) => Command::Version,
+            ("stream", options) => parse_stream(options)?,
+            ("sleep", []) => Command::Sleep,
+            ("raw", [did, cid, data @ ..]) if data.len() <= 1 => Command::Raw {
+                did: byte(did, "DID")?,
+                cid: byte(cid, "CID")?,
+                data: data.first().map_or(Ok(vec![]), |hex| parse_hex(hex))?,
+            },
+            ("scan" | "ping" | "color" | "roll" | "power" | "version" | "sleep" | "raw", _) => {
+                return Err(usage_error(format!("wrong arguments for {name}")))
+            }
+            _ => return Err(usage_error(format!("unknown command {name}"))),
+        };
+        Ok(Self {
+            target,
+            scan_timeout,
+            command,
+        })
+    }
+}
+
+/// Options of `stream`
+fn parse_stream(options: &[&str]) -> Result<Command, UsageError> {
+    let mut sensors = parse_sensors("accel,gyro")?;
+    let mut rate_hz = DEFAULT_STREAM_RATE_HZ;
+    let mut count = None;
+    let mut csv = None;
+    let mut options = options.iter().copied();
+    while let Some(option) = options.next() {
+        match option {
+            "--sensors" => sensors = parse_sensors(value(&mut options, option)?)?,
+            "--rate" => rate_hz = number(value(&mut options, option)?, option)?,
+            "--count" => count = Some(number(value(&mut options, option)?, option)?),
+            "--csv" => csv = Some(PathBuf::from(value(&mut options, option)?)),
+            _ => return Err(usage_error(format!("unknown stream option {option}"))),
+        }
+    }
+    if rate_hz.is_nan() || rate_hz <= 0.0 {
+        return Err(usage_error("--rate must be above 0"));
+    }
+    Ok(Command::Stream {
+        sensors,
+        rate_hz,
+        count,
+        csv,
+    })
+}
+
+/// Sensors named by a comma separated list of groups
+///
+/// ```
+/// use sphero_rs::cli::parse_sensors;
+/// use sphero_rs::sensor::Sensor;
+///
+/// let sensors = parse_sensors("gyro, imu")?;
+/// assert_eq!(sensors.len(), 6);
+/// assert_eq!(sensors[3], Sensor::ImuPitch);
+/// assert!(parse_sensors("gyro,").is_err());
+/// # Ok::<(), sphero_rs::cli::UsageError>(())
+/// ```
+pub fn parse_sensors(list: &str) -> Result<Vec<Sensor>, UsageError> {
+    let mut sensors = vec![];
+    for group in list.split(',').map(str::trim) {
+        sensors.extend_from_slice(match group {
+            "accel" => &[Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ],
+            "gyro" => &[Sensor::GyroX, Sensor::GyroY, Sensor::GyroZ],
+            "imu" => &[Sensor::ImuPitch, Sensor::ImuRoll, Sensor::ImuYaw],
+            "quaternion" => &[
+                Sensor::QuaternionQ0,
+                Sensor::QuaternionQ1,
+                Sensor::QuaternionQ2,
+                Sensor::QuaternionQ3,
+            ],
+            "odometer" => &[Sensor::OdometerX, Sensor::OdometerY],
+            "velocity" => &[Sensor::VelocityX, Sensor::VelocityY],
+            "emf" => &[Sensor::RightMotorEmf, Sensor::LeftMotorEmf],
+            _ => return Err(usage_error(format!("unknown sensor group {group:?}"))),
+        });
+    }
+    Ok(sensors)
+}
+
+/// Bytes written as hex digits, optionally after `0x`
+///
+/// ```
+/// use sphero_rs::cli::parse_hex;
+///
+/// assert_eq!(parse_hex("0x01ff")?, vec![0x01, 0xff]);
+/// assert_eq!(parse_hex("")?, vec![]);
+/// assert!(parse_hex("1").is_err());
+/// assert!(parse_hex("zz").is_err());
+/// # Ok::<(), sphero_rs::cli::UsageError>(())
+/// ```
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>, UsageError> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(usage_error(format!("{hex:?} is not whole bytes of hex")));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| usage_error(format!("{hex:?} is not hex")))
+        })
+        .collect()
+}
+
+/// Value following option `option`
+fn value<'a>(
+    args: &mut impl Iterator<Item = &'a str>,
+    option: &str,
+) -> Result<&'a str, UsageError> {
+    args.next()
+        .ok_or_else(|| usage_error(format!("{option} needs a value")))
+}
+
+/// `text` as a number, `what` naming it in the error
+fn number<N: std::str::FromStr>(text: &str, what: &str) -> Result<N, UsageError> {
+    text.parse()
+        .map_err(|_| usage_error(format!("bad {what} {text:?}")))
+}
+
+/// A byte in decimal or `0x` hex
+fn byte(text: &str, what: &str) -> Result<u8, UsageError> {
+    match text.strip_prefix("0x") {
+        Some(hex) => {
+            u8::from_str_radix(hex, 16).map_err(|_| usage_error(format!("bad {what} {text:?}")))
+        }
+        None => number(text, what),
+    }
+}
+
+/// One line of `scan` output
+///
+/// ```
+/// use sphero_rs::cli::format_scan_line;
+///
+/// assert_eq!(format_scan_line("SK-1A2B", "F0:12:34:56:78:9A", Some(-61)), "SK-1A2B\tF0:12:34:56:78:9A\t-61 dBm");
+/// assert_eq!(format_scan_line("BB-0001", "F0:12:34:56:78:9B", None), "BB-0001\tF0:12:34:56:78:9B\t-");
+/// ```
+pub fn format_scan_line(name: &str, address: &str, rssi: Option<i16>) -> String {
+    match rssi {
+        Some(rssi) => format!("{name}\t{address}\t{rssi} dBm"),
+        None => format!("{name}\t{address}\t-"),
+    }
+}
+
+/// `version` output
+///
+/// ```
+/// use sphero_rs::cli::format_version;
+/// use sphero_rs::response::VersioningInfo;
+///
+/// let info = VersioningInfo { mdl: 3, hw: 1, msa_ver: 3, msa_rev: 0x41, bl: 0x21, bas: 0x33, macro_ver: 4, api_maj: 1, api_min: 20, ..Default::default() };
+/// assert_eq!(
+///     format_version(&info),
+///     "model 3, hardware 1\nmain application 3.65\nbootloader 2.1\norbBasic 3.3\nmacros 4\nAPI 1.20.0"
+/// );
+/// ```
+pub fn format_version(info: &VersioningInfo) -> String {
+    let nibbles = |b: u8| format!("{}.{}", b >> 4, b & 0x0f);
+    format!(
+        "model {}, hardware {}\nmain application {}.{}\nbootloader {}\norbBasic {}\nmacros {}\nAPI {}.{}.{}",
+        info.mdl,
+        info.hw,
+        info.msa_ver,
+        info.msa_rev,
+        nibbles(info.bl),
+        nibbles(info.bas),
+        info.macro_ver,
+        info.api_maj,
+        info.api_min,
+        info.patch
+    )
+}
+
+/// `power` output
+///
+/// ```
+/// use sphero_rs::cli::format_power;
+/// use sphero_rs::power::{PowerState, Voltage};
+/// use sphero_rs::response::PowerStateInfo;
+///
+/// let info = PowerStateInfo {
+///     state: PowerState::Low,
+///     voltage: Voltage::from_hundredths(702),
+///     num_charges: 31,
+///     time_since_charge: 1250,
+///     ..Default::default()
+/// };
+/// assert_eq!(format_power(&info), "Low, 7.02 V\n31 charges, awake 1250 s since the last");
+/// ```
+pub fn format_power(info: &PowerStateInfo) -> String {
+    format!(
+        "{:?}, {}\n{} charges, awake {} s since the last",
+        info.state, info.voltage, info.num_charges, info.time_since_charge
+    )
+}
+
+/// `raw` output: the response code and data in hex
+///
+/// ```
+/// use sphero_rs::cli::format_response;
+/// use sphero_rs::packet::{MRSPField, SpheroResponsePacketV1};
+///
+/// let packet = SpheroResponsePacketV1::new(MRSPField::Ok, 4, vec![0x01, 0xab]);
+/// assert_eq!(format_response(&packet), "Ok 01 ab");
+/// let packet = SpheroResponsePacketV1::new(MRSPField::Ok, 5, vec![]);
+/// assert_eq!(format_response(&packet), "Ok");
+/// ```
+pub fn format_response(packet: &SpheroResponsePacketV1) -> String {
+    let mut line = format!("{:?}", packet.mrsp());
+    for b in packet.data() {
+        line.push_str(&format!(" {b:02x}"));
+    }
+    line
+}
+
+/// CSV header for frames streaming `sensors`, in the order frames carry them
+///
+/// ```
+/// use sphero_rs::cli::{csv_header, csv_row};
+/// use sphero_rs::sensor::{Sensor, SensorFrame};
+///
+/// let header = csv_header(&[Sensor::ImuYaw, Sensor::AccelX]);
+/// assert_eq!(header, "frame,ImuYaw,AccelX");
+/// let frame = SensorFrame { values: vec![(Sensor::ImuYaw, 90), (Sensor::AccelX, -12)] };
+/// assert_eq!(csv_row(7, &frame), "7,90,-12");
+/// ```
+pub fn csv_header(sensors: &[Sensor]) -> String {
+    let mut header = "frame".to_string();
+    for sensor in SensorMask::from_sensors(sensors).sensors() {
+        header.push_str(&format!(",{sensor:?}"));
+    }
+    header
+}
+
+/// CSV row for frame number `index`
+pub fn csv_row(index: usize, frame: &SensorFrame) -> String {
+    let mut row = index.to_string();
+    for (_, value) in &frame.values {
+        row.push_str(&format!(",{value}"));
+    }
+    row
+}
@@ -0,0 +1,875 @@
+/*!
+ * Sphero Client
+ *
+ * Sends commands over a transport and waits for the matching response.
+ * Asynchronous messages received while waiting are queued as events.
+ */
+use crate::clock::ClockSample;
+use crate::collision::CollisionConfig;
+use crate::color::RgbColor;
+use crate::command::{
+    chunk_macro_bytes, CommandWithResponse, EraseUserConfig, GetBluetoothInfo, GetPowerState,
+    GetRGBLEDOutput, GetVersioning, Ping, PollPacketTimes, Roll, SetRGBLEDOutput, ToCommandPacket,
+    MACRO_CHUNK_SIZE,
+};
+use crate::error::Error;
+use crate::event::SpheroEvent;
+use crate::heading::Heading;
+use crate::packet::{MRSPField, SpheroCommandPacketV1, SpheroResponsePacketV1};
+use crate::reader::{PacketReader, SpheroEventStream};
+use crate::response::{
+    BluetoothInfo, FromResponsePacket, PacketTimes, PowerStateInfo, VersioningInfo,
+};
+use crate::sensor::StreamingConfig;
+use crate::seq::SeqAllocator;
+use crate::speed::Speed;
+use crate::trace::debug_event;
+use crate::transport::Transport;
+use deku::DekuContainerWrite;
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Host wall clock in ms, truncated to the 32 bits Poll Packet Times carries
+fn now_as_u32_ms() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u32)
+}
+
+/// How long `SpheroClient` waits for a response unless told otherwise
+#[cfg(feature = "async")]
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Roll commands per second sent by `SpheroClient::drive_arc` and `spin`
+pub const STEER_RATE_HZ: u32 = 20;
+/// Ground speed per unit of Roll speed, in cm/s, assumed when planning arcs
+/// The real figure depends on the model, the floor and the battery.
+pub const CM_PER_SPEED_UNIT: f32 = 1.0;
+
+/// Roll commands tracing an arc from heading 0, one per `STEER_RATE_HZ` tick
+///
+/// The arc is `radius_cm` in radius and turns through `angle_deg`, to the
+/// right when `clockwise`. Each command turns the heading by an equal share
+/// of the angle; the last one faces the end of the arc.
+///
+/// ```
+/// use sphero_rs::client::arc_rolls;
+/// use sphero_rs::error::Error;
+/// use sphero_rs::speed::Speed;
+///
+/// // 15.7 cm at 100 cm/s takes 4 ticks
+/// let rolls = arc_rolls(Speed::new(100), 10.0, 90.0, false).unwrap();
+/// let headings: Vec<_> = rolls.iter().map(|roll| roll.heading.degrees()).collect();
+/// assert_eq!(headings, vec![337, 315, 292, 270]);
+/// assert!(rolls.iter().all(|roll| roll.speed.value() == 100));
+///
+/// assert!(matches!(arc_rolls(Speed::new(100), 0.0, 90.0, true), Err(Error::BadParameterValue)));
+/// assert!(matches!(arc_rolls(Speed::new(100), 10.0, -5.0, true), Err(Error::BadParameterValue)));
+/// assert!(matches!(arc_rolls(Speed::zero(), 10.0, 90.0, true), Err(Error::BadParameterValue)));
+/// ```
+pub fn arc_rolls(
+    speed: Speed,
+    radius_cm: f32,
+    angle_deg: f32,
+    clockwise: bool,
+) -> Result<Vec<Roll>, Error> {
+    let valid = |value: f32| value > 0.0 && value.is_finite();
+    if !valid(radius_cm) || !valid(angle_deg) || speed.is_zero() {
+        return Err(Error::BadParameterValue);
+    }
+    let length = radius_cm * angle_deg.to_radians();
+    let seconds = length / (speed.value() as f32 * CM_PER_SPEED_UNIT);
+    let ticks = (seconds * STEER_RATE_HZ as f32).ceil().max(1.0) as u32;
+    let sign = if clockwise { 1.0 } else { -1.0 };
+    Ok((1..=ticks)
+        .map(|tick| Roll {
+            speed,
+            heading: Heading::from_degrees_clamped(sign * angle_deg * tick as f32 / ticks as f32),
+            state: true,
+        })
+        .collect())
+}
+
+/// Roll commands turning on the spot through `full_rotations` turns over
+/// `duration`, one per `STEER_RATE_HZ` tick
+///
+/// ```
+/// use sphero_rs::client::spin_rolls;
+/// use sphero_rs::error::Error;
+/// use std::time::Duration;
+///
+/// let rolls = spin_rolls(1.0, Duration::from_millis(200)).unwrap();
+/// let headings: Vec<_> = rolls.iter().map(|roll| roll.heading.degrees()).collect();
+/// assert_eq!(headings, vec![90, 180, 270, 0]);
+/// assert!(rolls.iter().all(|roll| roll.speed.is_zero() && roll.state));
+///
+/// assert!(matches!(spin_rolls(0.0, Duration::from_secs(1)), Err(Error::BadParameterValue)));
+/// assert!(matches!(spin_rolls(1.0, Duration::ZERO), Err(Error::BadParameterValue)));
+/// ```
+pub fn spin_rolls(full_rotations: f32, duration: Duration) -> Result<Vec<Roll>, Error> {
+    if !(full_rotations > 0.0 && full_rotations.is_finite()) || duration.is_zero() {
+        return Err(Error::BadParameterValue);
+    }
+    let ticks = (duration.as_secs_f32() * STEER_RATE_HZ as f32)
+        .ceil()
+        .max(1.0) as u32;
+    let degrees = full_rotations * 360.0;
+    Ok((1..=ticks)
+        .map(|tick| Roll {
+            speed: Speed::zero(),
+            heading: Heading::from_degrees_clamped(degrees * tick as f32 / ticks as f32),
+            state: true,
+        })
+        .collect())
+}
+
+/// How `SpheroClient::send` retries commands that fail for a transient reason
+///
+/// `Error::InvalidPacket` and `Error::Timeout` are transient; any other error,
+/// such as `Error::CommandRestricted`, is returned at once. Only idempotent
+/// commands are retried (see `SpheroCommandPacketV1::is_idempotent`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffRetryPolicy {
+    /// Attempts in all, the first included
+    pub max_attempts: u8,
+    /// Wait before the first retry
+    pub initial_delay: Duration,
+    /// Growth of the wait after each retry
+    pub backoff_factor: f32,
+}
+
+impl BackoffRetryPolicy {
+    /// A single attempt, no retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay: Duration::ZERO,
+            backoff_factor: 1.0,
+        }
+    }
+
+    /// Up to `max` attempts, waiting 50ms before the first retry and twice as long before each next
+    pub fn exponential(max: u8) -> Self {
+        Self {
+            max_attempts: max,
+            initial_delay: Duration::from_millis(50),
+            backoff_factor: 2.0,
+        }
+    }
+
+    /// Wait before retry number `retry`, counting from 1
+    ///
+    /// ```
+    /// use sphero_rs::client::BackoffRetryPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = BackoffRetryPolicy::exponential(4);
+    /// assert_eq!(policy.delay(1), Duration::from_millis(50));
+    /// assert_eq!(policy.delay(3), Duration::from_millis(200));
+    /// ```
+    pub fn delay(&self, retry: u8) -> Duration {
+        let exponent = i32::from(retry.saturating_sub(1));
+        let scale = f64::from(self.backoff_factor.max(0.0)).powi(exponent);
+        Duration::from_nanos((self.initial_delay.as_nanos() as f64 * scale).round() as u64)
+    }
+
+    /// Whether `error` is worth another attempt
+    pub fn is_transient(error: &Error) -> bool {
+        matches!(error, Error::InvalidPacket | Error::Timeout)
+    }
+}
+
+impl Default for BackoffRetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Sphero Client Send Statistics
+/// Counted since the client was created or `reset_stats` was last called.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SendStats {
+    /// Command packets written
+    pub commands_sent: u64,
+    /// Responses matched to a command, whatever their response code
+    pub responses_received: u64,
+    /// Inbound packets dropped for a bad checksum
+    pub checksum_errors: u64,
+    /// Sends that failed with `Error::Timeout`, from the transport or while
+    /// waiting for the response
+    pub timeouts: u64,
+}
+
+/// Sphero Client
+/// Without the `async` feature there is no timer: a send waits for its
+/// response for as long as the transport does, and the timeout can't be set.
+pub struct SpheroClient<T: Transport> {
+    transport: T,
+    inbound: Option<BoxStream<'static, Vec<u8>>>,
+    reader: PacketReader,
+    seq: SeqAllocator,
+    events: VecDeque<SpheroEvent>,
+    stats: SendStats,
+    retry: BackoffRetryPolicy,
+    #[cfg(feature = "async")]
+    response_timeout: Duration,
+}
+
+impl<T: Transport> SpheroClient<T> {
+    /// Create a new client over `transport`
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            inbound: None,
+            reader: PacketReader::new(),
+            seq: SeqAllocator::new(),
+            events: VecDeque::new(),
+            stats: SendStats::default(),
+            retry: BackoffRetryPolicy::none(),
+            #[cfg(feature = "async")]
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+        }
+    }
+
+    /// Underlying transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Give up waiting for a response after `timeout` (default 2 s)
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::command::Ping;
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::transport::mock::MockTransport;
+    /// use std::time::Duration;
+    ///
+    /// // Never answers
+    /// let mut client = SpheroClient::new(MockTransport::default())
+    ///     .with_response_timeout(Duration::from_millis(50));
+    /// assert_eq!(client.response_timeout(), Duration::from_millis(50));
+    /// assert!(matches!(block_on(client.send(&Ping {})), Err(Error::Timeout)));
+    /// assert_eq!(client.stats().timeouts, 1);
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn with_response_timeout(mut self, timeout: Duration) -> Self {
+        self.set_response_timeout(timeout);
+        self
+    }
+
+    /// Give up waiting for a response after `timeout` (default 2 s)
+    ///
+    /// A send that runs out of time fails with `Error::Timeout`.
+    #[cfg(feature = "async")]
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    /// How long to wait for a response
+    #[cfg(feature = "async")]
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    /// Retry transient failures of `send` as `policy` says (default none)
+    pub fn set_retry_policy(&mut self, policy: BackoffRetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Send a command and wait for its response
+    ///
+    /// Retries as set with `set_retry_policy`, each time with a fresh sequence
+    /// number. Without the `async` feature there is no timer, so retries go
+    /// out without waiting.
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use futures::stream::BoxStream;
+    /// use sphero_rs::client::{BackoffRetryPolicy, SpheroClient};
+    /// use sphero_rs::command::Ping;
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::transport::mock::MockTransport;
+    /// use sphero_rs::transport::Transport;
+    /// use std::sync::atomic::{AtomicU8, Ordering};
+    ///
+    /// // Times out on the first two writes, then works
+    /// struct Flaky(MockTransport, AtomicU8);
+    ///
+    /// impl Transport for Flaky {
+    ///     async fn write(&self, data: &[u8]) -> Result<(), Error> {
+    ///         if self.1.fetch_add(1, Ordering::Relaxed) < 2 {
+    ///             return Err(Error::Timeout);
+    ///         }
+    ///         self.0.write(data).await
+    ///     }
+    ///
+    ///     async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+    ///         self.0.subscribe().await
+    ///     }
+    /// }
+    ///
+    /// let mut client = SpheroClient::new(Flaky(MockTransport::acknowledging(), AtomicU8::new(0)));
+    /// assert!(matches!(block_on(client.send(&Ping {})), Err(Error::Timeout)));
+    ///
+    /// client.set_retry_policy(BackoffRetryPolicy::exponential(3));
+    /// assert!(block_on(client.send(&Ping {})).is_ok());
+    /// assert_eq!(client.stats().timeouts, 2);
+    /// ```
+    pub async fn send(
+        &mut self,
+        cmd: &impl ToCommandPacket,
+    ) -> Result<SpheroResponsePacketV1, Error> {
+        let mut attempt = 1;
+        loop {
+            let seq = self.seq.allocate()?;
+            let packet = cmd.to_packet(seq);
+            let retryable = packet.is_idempotent();
+            let result = self.send_packet(packet).await;
+            self.seq.release(seq);
+            match result {
+                Err(e)
+                    if retryable
+                        && attempt < self.retry.max_attempts
+                        && BackoffRetryPolicy::is_transient(&e) =>
+                {
+                    debug_event!(attempt, error = %e, "retrying");
+                    #[cfg(feature = "async")]
+                    crate::runtime::sleep(self.retry.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a pre-built packet and wait for the response with the same sequence number
+    /// Never retried.
+    pub async fn send_packet(
+        &mut self,
+        packet: SpheroCommandPacketV1,
+    ) -> Result<SpheroResponsePacketV1, Error> {
+        self.subscribe().await?;
+        let bytes = packet.to_bytes()?;
+        debug_event!(seq = packet.seq(), bytes = %crate::trace::hex(&bytes), "sent");
+        let response = match self.write_and_await(&packet, &bytes).await {
+            Err(Error::Timeout) => {
+                self.stats.timeouts += 1;
+                return Err(Error::Timeout);
+            }
+            result => result?,
+        };
+        match response.mrsp() {
+            MRSPField::Ok => Ok(response),
+            mrsp => Err(Error::ResponseCode(mrsp)),
+        }
+    }
+
+    /// Send a command and decode its response
+    pub async fn query<C: CommandWithResponse>(&mut self, cmd: &C) -> Result<C::Response, Error> {
+        let response = self.send(cmd).await?;
+        C::Response::from_response(&response)
+    }
+
+    /// Firmware and hardware versions
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::color::RgbColor;
+    /// use sphero_rs::packet::{MRSPField, SpheroCommandPacketV1};
+    /// use sphero_rs::transport::mock::{respond, MockTransport};
+    ///
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     let data = match packet.cid() {
+    ///         0x02 => vec![0x02, 0x01, 0x00, 0x03, 0x30, 0x01, 0x14, 0x33, 0x01, 0x14],
+    ///         0x22 => vec![0xff, 0x80, 0x00],
+    ///         _ => vec![],
+    ///     };
+    ///     vec![respond(&packet, MRSPField::Ok, data)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// let version = block_on(client.get_version()).unwrap();
+    /// assert_eq!((version.msa_ver, version.msa_rev), (3, 0x30));
+    /// let color = block_on(client.get_rgb_led()).unwrap();
+    /// assert_eq!(color, RgbColor::new(0xff, 0x80, 0x00));
+    /// ```
+    pub async fn get_version(&mut self) -> Result<VersioningInfo, Error> {
+        self.query(&GetVersioning {}).await
+    }
+
+    /// Bluetooth name and address
+    pub async fn get_bluetooth_info(&mut self) -> Result<BluetoothInfo, Error> {
+        self.query(&GetBluetoothInfo {}).await
+    }
+
+    /// Battery state, with the voltage and charge counters reported alongside it
+    ///
+    /// Returns the whole Get Power State record rather than only the
+    /// `power::PowerState` in its `state` field, which would drop the rest.
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::packet::{MRSPField, SpheroCommandPacketV1};
+    /// use sphero_rs::power::PowerState;
+    /// use sphero_rs::transport::mock::{respond, MockTransport};
+    ///
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     let data = vec![0x01, 0x02, 0x02, 0xef, 0x00, 0x2a, 0x01, 0x2c];
+    ///     vec![respond(&packet, MRSPField::Ok, data)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// let power = block_on(client.get_power_state()).unwrap();
+    /// assert_eq!(power.state, PowerState::Ok);
+    /// assert_eq!(power.voltage.hundredths(), 751);
+    /// assert_eq!((power.num_charges, power.time_since_charge), (42, 300));
+    /// ```
+    pub async fn get_power_state(&mut self) -> Result<PowerStateInfo, Error> {
+        self.query(&GetPowerState {}).await
+    }
+
+    /// User LED color, as last set with the persist flag
+    pub async fn get_rgb_led(&mut self) -> Result<RgbColor, Error> {
+        Ok(self.query(&GetRGBLEDOutput {}).await?.color)
+    }
+
+    /// Move the LED `alpha` of the way from its color towards `target`
+    ///
+    /// Starts from the color read back with `get_rgb_led`, which is the last
+    /// persisted one, and sets the blend without persisting it. Returns the
+    /// color set; fails with `Error::BadParameterValue` unless `alpha` is in 0..=1.
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::color::RgbColor;
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::packet::{MRSPField, SpheroCommandPacketV1};
+    /// use sphero_rs::transport::mock::{respond, MockTransport};
+    ///
+    /// // The LED was last persisted as black
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     let data = if packet.cid() == 0x22 { vec![0, 0, 0] } else { vec![] };
+    ///     vec![respond(&packet, MRSPField::Ok, data)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// let color = block_on(client.set_led_blended(RgbColor::new(200, 100, 0), 0.25)).unwrap();
+    /// assert_eq!(color, RgbColor::new(50, 25, 0));
+    /// assert!(matches!(
+    ///     block_on(client.set_led_blended(RgbColor::RED, 1.5)),
+    ///     Err(Error::BadParameterValue)
+    /// ));
+    /// ```
+    pub async fn set_led_blended(
+        &mut self,
+        target: RgbColor,
+        alpha: f32,
+    ) -> Result<RgbColor, Error> {
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(Error::BadParameterValue);
+        }
+        let color = self.get_rgb_led().await?.lerp(target, alpha);
+        drop(
+            self.send(&SetRGBLEDOutput::from_color(color, false))
+                .await?,
+        );
+        Ok(color)
+    }
+
+    /// Ping the robot and time the round trip
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::packet::SpheroCommandPacketV1;
+    /// use sphero_rs::transport::mock::{ack, MockTransport};
+    ///
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     vec![ack(&packet)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// let rtt = block_on(client.ping()).unwrap();
+    /// assert!(rtt.as_secs() < 1);
+    /// ```
+    pub async fn ping(&mut self) -> Result<Duration, Error> {
+        let sent = Instant::now();
+        drop(self.send(&Ping {}).await?);
+        Ok(sent.elapsed())
+    }
+
+    /// Measure the link delay with Poll Packet Times, in ms
+    ///
+    /// Round trip less the robot's processing time, `(T4 - T1) - (T3 - T2)`;
+    /// half of it estimates the one-way latency.
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::packet::{MRSPField, SpheroCommandPacketV1};
+    /// use sphero_rs::transport::mock::{respond, MockTransport};
+    ///
+    /// // The robot echoes T1 and stamps T2 and T3 with its own clock
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     let mut data = packet.data().to_vec();
+    ///     data.extend(5000u32.to_be_bytes());
+    ///     data.extend(5000u32.to_be_bytes());
+    ///     vec![respond(&packet, MRSPField::Ok, data)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// let delay = block_on(client.poll_packet_times()).unwrap();
+    /// assert!(delay < 1000);
+    /// ```
+    pub async fn poll_packet_times(&mut self) -> Result<u32, Error> {
+        let t1 = now_as_u32_ms();
+        let response = self.send(&PollPacketTimes { time: t1 }).await?;
+        let t4 = now_as_u32_ms();
+        let sample = ClockSample::new(PacketTimes::from_response(&response)?, t4);
+        Ok(sample.delay().max(0) as u32)
+    }
+
+    /// Subscribe to everything the robot sends, parsed into events
+    ///
+    /// The stream has its own subscription to the transport, so it sees
+    /// responses and async messages alike, including those `send` waits on.
+    ///
+    /// ```
+    /// use deku::DekuContainerWrite;
+    /// use futures::executor::block_on;
+    /// use futures::StreamExt;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::event::SpheroEvent;
+    /// use sphero_rs::packet::SpheroAsynchronousPacketV1;
+    /// use sphero_rs::transport::mock::MockTransport;
+    ///
+    /// let mock = MockTransport::default();
+    /// let client = SpheroClient::new(mock.clone());
+    /// let mut events = block_on(client.event_stream()).unwrap();
+    ///
+    /// // One power notification split over two chunks
+    /// let bytes = SpheroAsynchronousPacketV1::new(0x01, vec![0x02]).to_bytes().unwrap();
+    /// mock.inject(bytes[..3].to_vec());
+    /// mock.inject(bytes[3..].to_vec());
+    /// let event = block_on(events.next()).unwrap().unwrap();
+    /// assert!(matches!(event, SpheroEvent::Async(packet) if packet.idcode() == 0x01));
+    /// ```
+    pub async fn event_stream(&self) -> Result<SpheroEventStream, Error> {
+        Ok(SpheroEventStream::new(self.transport.subscribe().await?))
+    }
+
+    /// Turn on collision detection and data streaming, and listen for both
+    ///
+    /// Collision detection is configured first, so its answer is not held up
+    /// behind sensor packets. The events are subscribed to before streaming
+    /// starts, so no frame is missed; they also carry the Set Data Streaming
+    /// response. A collision config that fails validation sends nothing.
+    ///
+    /// ```
+    /// use deku::DekuContainerWrite;
+    /// use futures::executor::block_on;
+    /// use futures::StreamExt;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::collision::CollisionConfig;
+    /// use sphero_rs::event::SpheroEvent;
+    /// use sphero_rs::packet::{SpheroAsynchronousPacketV1, SpheroCommandID};
+    /// use sphero_rs::sensor::{Sensor, StreamingConfig};
+    /// use sphero_rs::transport::mock::MockTransport;
+    ///
+    /// let mock = MockTransport::acknowledging();
+    /// let mut client = SpheroClient::new(mock.clone());
+    /// let streaming = StreamingConfig::new().sample_rate_hz(10.0)?.with_sensors(&[Sensor::ImuYaw]);
+    /// let session = client.configure_standard_session(CollisionConfig::method1(), streaming);
+    /// let mut events = block_on(session)?;
+    /// let sent: Vec<_> = mock.written_packets().iter().map(|packet| packet.cid()).collect();
+    /// assert_eq!(
+    ///     sent,
+    ///     vec![
+    ///         SpheroCommandID::ConfigureCollisionDetection as u8,
+    ///         SpheroCommandID::SetDataStreaming as u8,
+    ///     ]
+    /// );
+    ///
+    /// // The streaming answer, then the frames
+    /// let answer = block_on(events.next()).unwrap()?;
+    /// assert!(answer.is_response_for(&mock.written_packets()[1]));
+    /// mock.inject(SpheroAsynchronousPacketV1::new(0x03, vec![0x00, 0x2d]).to_bytes()?);
+    /// let frame = block_on(events.next()).unwrap()?;
+    /// assert!(matches!(frame, SpheroEvent::Async(packet) if packet.idcode() == 0x03));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub async fn configure_standard_session(
+        &mut self,
+        collision: CollisionConfig,
+        streaming: StreamingConfig,
+    ) -> Result<SpheroEventStream, Error> {
+        let collision = collision.build()?;
+        drop(self.send(&collision).await?);
+        let events = self.event_stream().await?;
+        drop(self.send(&streaming.build()).await?);
+        Ok(events)
+    }
+
+    /// Drive along an arc of `radius_cm` through `angle_deg`, then stop
+    ///
+    /// Sends the commands from `arc_rolls` at `STEER_RATE_HZ`, starting from
+    /// heading 0, so set the heading first to choose where the arc starts.
+    /// Without the `async` feature there is no timer and the commands go out
+    /// back to back.
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::SpheroClient;
+    /// use sphero_rs::packet::SpheroCommandID;
+    /// use sphero_rs::speed::Speed;
+    /// use sphero_rs::transport::mock::MockTransport;
+    ///
+    /// let mock = MockTransport::acknowledging();
+    /// let mut client = SpheroClient::new(mock.clone());
+    /// block_on(client.drive_arc(Speed::new(100), 10.0, 90.0, true)).unwrap();
+    /// let headings: Vec<_> = mock
+    ///     .written_packets()
+    ///     .iter()
+    ///     .filter(|packet| packet.cid() == SpheroCommandID::Roll as u8)
+    ///     .map(|packet| (packet.data()[0], u16::from_be_bytes([packet.data()[1], packet.data()[2]])))
+    ///     .collect();
+    /// assert_eq!(headings, vec![(100, 23), (100, 45), (100, 68), (100, 90), (0, 90)]);
+    /// ```
+    pub async fn drive_arc(
+        &mut self,
+        speed: Speed,
+        radius_cm: f32,
+        angle_deg: f32,
+        clockwise: bool,
+    ) -> Result<(), Error> {
+        let rolls = arc_rolls(speed, radius_cm, angle_deg, clockwise)?;
+        let mut last = Heading::ZERO;
+        for roll in rolls {
+            last = roll.heading;
+            drop(self.send(&roll).await?);
+            #[cfg(feature = "async")]
+            crate::runtime::sleep(Duration::from_secs(1) / STEER_RATE_HZ).await;
+        }
+        self.send(&Roll::stop(last.degrees())?).await.map(drop)
+    }
+
+    /// Turn on the spot through `full_rotations` turns over `duration`
+    ///
+    /// Sends the commands from `spin_rolls` at `STEER_RATE_HZ`, starting from
+    /// heading 0. Without the `async` feature there is no timer and the
+    /// commands go out back to back.
+    pub async fn spin(&mut self, full_rotations: f32, duration: Duration) -> Result<(), Error> {
+        for roll in spin_rolls(full_rotations, duration)? {
+            drop(self.send(&roll).await?);
+            #[cfg(feature = "async")]
+            crate::runtime::sleep(Duration::from_secs(1) / STEER_RATE_HZ).await;
+        }
+        Ok(())
+    }
+
+    /// Take the asynchronous messages received so far
+    pub fn drain_events(&mut self) -> Vec<SpheroEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Commands, responses and failures counted so far
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use futures::executor::block_on;
+    /// use sphero_rs::client::{SendStats, SpheroClient};
+    /// use sphero_rs::command::Ping;
+    /// use sphero_rs::packet::SpheroCommandPacketV1;
+    /// use sphero_rs::transport::mock::{ack, MockTransport};
+    ///
+    /// // Each answer is preceded by a copy with a corrupt checksum
+    /// let mock = MockTransport::with_responder(|bytes| {
+    ///     let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+    ///     let mut corrupt = ack(&packet);
+    ///     *corrupt.last_mut().unwrap() ^= 0xff;
+    ///     vec![corrupt, ack(&packet)]
+    /// });
+    /// let mut client = SpheroClient::new(mock);
+    /// block_on(client.send(&Ping {})).unwrap();
+    /// block_on(client.send(&Ping {})).unwrap();
+    /// assert_eq!(
+    ///     *client.stats(),
+    ///     SendStats { commands_sent: 2, responses_received: 2, checksum_errors: 2, timeouts: 0 }
+    /// );
+    /// client.reset_stats();
+    /// assert_eq!(*client.stats(), SendStats::default());
+    /// ```
+    pub fn stats(&self) -> &SendStats {
+        &self.stats
+    }
+
+    /// Start counting from zero again
+    pub fn reset_stats(&mut self) {
+        self.stats = SendStats::default();
+    }
+
+    async fn subscribe(&mut self) -> Result<(), Error> {
+        if self.inbound.is_none() {
+            self.inbound = Some(self.transport.subscribe().await?);
+        }
+        Ok(())
+    }
+
+    async fn write_and_await(
+        &mut self,
+        packet: &SpheroCommandPacketV1,
+        bytes: &[u8],
+    ) -> Result<SpheroResponsePacketV1, Error> {
+        self.transport.write(bytes).await?;
+        self.stats.commands_sent += 1;
+        #[cfg(feature = "async")]
+        let response =
+            crate::runtime::timeout(self.response_timeout, self.await_response(packet)).await??;
+        #[cfg(not(feature = "async"))]
+        let response = self.await_response(packet).await?;
+        self.stats.responses_received += 1;
+        Ok(response)
+    }
+
+    async fn await_response(
+        &mut self,
+        packet: &SpheroCommandPacketV1,
+    ) -> Result<SpheroResponsePacketV1, Error> {
+        loop {
+            // A chunk may end partway through a packet or hold several packets
+            while let Some(event) = self.reader.next_event() {
+                match event {
+                    Ok(event) if event.is_response_for(packet) => {
+                        return event.into_response().ok_or(Error::InvalidPacket)
+                    }
+                    Ok(event) => self.events.push_back(event),
+                    Err(Error::ChecksumMismatch { .. }) => self.stats.checksum_errors += 1,
+                    Err(_) => continue,
+                }
+            }
+            let inbound = self.inbound.as_mut().ok_or(Error::TargetUnavailable)?;
+            let chunk = inbound.next().await.ok_or(Error::TargetUnavailable)?;
+            debug_event!(bytes = %crate::trace::hex(&chunk), "received");
+            self.reader.push(&chunk);
+        }
+    }
+}
+
+/// Erase the user config, but only once `confirm` agrees to it
+/// Returns `Error::CommandRestricted` without sending anything if `confirm` returns false.
+pub async fn erase_user_config_confirmed<T, F>(
+    client: &mut SpheroClient<T>,
+    confirm: F,
+) -> Result<(), Error>
+where
+    T: Transport,
+    F: FnOnce() -> bool,
+{
+    if !confirm() {
+        return Err(Error::CommandRestricted);
+    }
+    client.send(&EraseUserConfig {}).await.map(|_| ())
+}
+
+/// Upload macro bytecode as `macro_id`, one Append Macro Chunk at a time
+/// Stops at the first chunk the robot rejects.
+pub async fn upload_macro<T: Transport>(
+    client: &mut SpheroClient<T>,
+    macro_id: u8,
+    data: &[u8],
+) -> Result<(), Error> {
+    if data.is_empty() || data.len() > MACRO_CHUNK_SIZE * 256 {
+        return Err(Error::BadDataLength);
+    }
+    for mut chunk in chunk_macro_bytes(data) {
+        chunk.macro_id = macro_id;
+        drop(client.send(&chunk).await?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{BootloaderCommandID, DeviceID};
+    use crate::transport::mock::MockTransport;
+    use futures::executor::block_on;
+
+    #[test]
+    fn erase_user_config_is_sent_once_confirmed() {
+        let mock = MockTransport::acknowledging();
+        let mut client = SpheroClient::new(mock.clone());
+        block_on(erase_user_config_confirmed(&mut client, || true)).unwrap();
+        let sent = mock.written_packets();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].did(), DeviceID::Bootloader);
+        assert_eq!(sent[0].cid(), BootloaderCommandID::EraseUserConfig as u8);
+    }
+
+    #[test]
+    fn erase_user_config_is_refused_without_confirmation() {
+        let mock = MockTransport::acknowledging();
+        let mut client = SpheroClient::new(mock.clone());
+        let erased = block_on(erase_user_config_confirmed(&mut client, || false));
+        assert!(matches!(erased, Err(Error::CommandRestricted)));
+        assert!(mock.written().is_empty());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn unanswered_command_times_out() {
+        // Accepts every write and never answers
+        let mock = MockTransport::new();
+        let mut client =
+            SpheroClient::new(mock.clone()).with_response_timeout(Duration::from_millis(20));
+        let sent = block_on(client.send(&Ping {}));
+        assert!(matches!(sent, Err(Error::Timeout)));
+        assert_eq!(mock.written().len(), 1);
+        assert_eq!(
+            *client.stats(),
+            SendStats {
+                commands_sent: 1,
+                responses_received: 0,
+                checksum_errors: 0,
+                timeouts: 1,
+            }
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn late_response_after_a_timeout_is_not_taken_for_the_next() {
+        use crate::transport::mock::{ack, respond};
+        use deku::DekuContainerRead;
+
+        let mock = MockTransport::new();
+        let mut client =
+            SpheroClient::new(mock.clone()).with_response_timeout(Duration::from_millis(20));
+        assert!(matches!(
+            block_on(client.send(&Ping {})),
+            Err(Error::Timeout)
+        ));
+
+        // The first answer turns up late, then the robot answers as it should
+        let (_, first) = SpheroCommandPacketV1::from_bytes((&mock.written()[0], 0)).unwrap();
+        mock.inject(ack(&first));
+        mock.set_responder(|bytes| {
+            let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+            vec![respond(&packet, MRSPField::Ok, vec![0xaa])]
+        });
+        let response = block_on(client.send(&Ping {})).unwrap();
+        assert_eq!(response.data(), [0xaa]);
+        assert_eq!(client.stats().timeouts, 1);
+    }
+}
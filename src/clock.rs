@@ -0,0 +1,131 @@
+/*!
+ * Sphero Clock Synchronization
+ *
+ * Collision and macro messages are stamped with the robot's millisecond
+ * clock. Poll Packet Times yields the four timestamps of an NTP-style
+ * exchange, from which the offset between the host and robot clocks follows
+ * (see the API docs, page 21). Samples with the smallest round-trip delay
+ * give the tightest estimate.
+ */
+use crate::response::PacketTimes;
+use std::time::{Duration, SystemTime};
+
+/// One Poll Packet Times exchange
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ClockSample {
+    /// Timestamps echoed and stamped by the robot
+    pub times: PacketTimes,
+    /// Client clock when the response arrived, in ms
+    pub client_rx: u32,
+}
+
+impl ClockSample {
+    /// Pair a response with the time it arrived
+    pub fn new(times: PacketTimes, client_rx: u32) -> Self {
+        Self { times, client_rx }
+    }
+
+    /// Robot clock minus client clock, in ms
+    /// `((Rx - Tx) + (Tx' - Rx')) / 2`, tolerant of either clock wrapping.
+    pub fn offset(&self) -> i64 {
+        let outbound = diff(self.times.robot_rx, self.times.client_tx);
+        let inbound = diff(self.times.robot_tx, self.client_rx);
+        (outbound + inbound) / 2
+    }
+
+    /// Time spent on the link, excluding the robot's processing, in ms
+    /// `(Rx' - Tx) - (Tx' - Rx)`
+    pub fn delay(&self) -> i64 {
+        let round_trip = diff(self.client_rx, self.times.client_tx);
+        let processing = diff(self.times.robot_tx, self.times.robot_rx);
+        round_trip - processing
+    }
+}
+
+/// Wrapping difference `a - b` of two u32 clock readings
+fn diff(a: u32, b: u32) -> i64 {
+    a.wrapping_sub(b) as i32 as i64
+}
+
+/// Sample with the lowest delay, the one least skewed by link latency
+///
+/// ```
+/// use sphero_rs::clock::{best_sample, ClockSample};
+/// use sphero_rs::response::PacketTimes;
+///
+/// // Robot clock runs 1000 ms ahead; the second exchange took the shortest path
+/// let samples = [
+///     ClockSample::new(PacketTimes { client_tx: 0, robot_rx: 1040, robot_tx: 1041 }, 60),
+///     ClockSample::new(PacketTimes { client_tx: 100, robot_rx: 1110, robot_tx: 1112 }, 122),
+///     ClockSample::new(PacketTimes { client_tx: 200, robot_rx: 1230, robot_tx: 1231 }, 251),
+/// ];
+/// let best = best_sample(&samples).unwrap();
+/// assert_eq!(best.delay(), 20);
+/// assert_eq!(best.offset(), 1000);
+/// assert!(best_sample(&[]).is_none());
+/// ```
+pub fn best_sample(samples: &[ClockSample]) -> Option<&ClockSample> {
+    samples.iter().min_by_key(|sample| sample.delay())
+}
+
+/// Mapping from robot clock readings to host time
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ClockOffset {
+    /// Host time at client clock 0
+    pub epoch: SystemTime,
+    /// Robot clock minus client clock, in ms
+    pub offset_ms: i64,
+    /// Delay of the sample the offset came from, in ms
+    pub delay_ms: i64,
+}
+
+impl ClockOffset {
+    /// Offset estimated from `sample`, with client clock 0 at `epoch`
+    pub fn from_sample(epoch: SystemTime, sample: &ClockSample) -> Self {
+        Self {
+            epoch,
+            offset_ms: sample.offset(),
+            delay_ms: sample.delay(),
+        }
+    }
+
+    /// Host time of the robot clock reading `robot_ms`
+    ///
+    /// ```
+    /// use sphero_rs::clock::{ClockOffset, ClockSample};
+    /// use sphero_rs::response::PacketTimes;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let sample = ClockSample::new(PacketTimes { client_tx: 100, robot_rx: 1110, robot_tx: 1112 }, 122);
+    /// let clock = ClockOffset::from_sample(UNIX_EPOCH, &sample);
+    /// assert_eq!(clock.to_host_time(1500), UNIX_EPOCH + Duration::from_millis(500));
+    /// ```
+    pub fn to_host_time(&self, robot_ms: u32) -> SystemTime {
+        let client_ms = robot_ms as i64 - self.offset_ms;
+        if client_ms >= 0 {
+            self.epoch + Duration::from_millis(client_ms as u64)
+        } else {
+            self.epoch - Duration::from_millis(client_ms.unsigned_abs())
+        }
+    }
+
+    /// Robot clock reading at host time `host`, wrapping like the robot's clock
+    ///
+    /// ```
+    /// use sphero_rs::clock::{ClockOffset, ClockSample};
+    /// use sphero_rs::response::PacketTimes;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let sample = ClockSample::new(PacketTimes { client_tx: 100, robot_rx: 1110, robot_tx: 1112 }, 122);
+    /// let clock = ClockOffset::from_sample(UNIX_EPOCH, &sample);
+    /// assert_eq!(clock.to_robot_time(UNIX_EPOCH + Duration::from_millis(500)), 1500);
+    /// assert_eq!(clock.to_robot_time(clock.to_host_time(7)), 7);
+    /// ```
+    pub fn to_robot_time(&self, host: SystemTime) -> u32 {
+        let client_ms = match host.duration_since(self.epoch) {
+            Ok(after) => after.as_millis() as i64,
+            Err(before) => -(before.duration().as_millis() as i64),
+        };
+        (client_ms + self.offset_ms) as u32
+    }
+}
@@ -0,0 +1,140 @@
+/*!
+ * Sphero Collision Detection
+ *
+ * A collision is reported when the measured impact on an axis exceeds
+ * `threshold + speed * speed_threshold / 255` for that axis.
+ * <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 29)
+ */
+use crate::command::ConfigureCollisionDetection;
+use crate::error::Error;
+
+/// Default threshold for both axes
+pub const DEFAULT_THRESHOLD: u8 = 100;
+/// Default speed dependent threshold for both axes
+pub const DEFAULT_SPEED_THRESHOLD: u8 = 100;
+/// Default dead time (10ms units)
+pub const DEFAULT_DEAD_TIME: u8 = 10;
+
+/// Sphero Collision Detection Configuration
+///
+/// ```
+/// use sphero_rs::collision::CollisionConfig;
+/// use sphero_rs::command::ConfigureCollisionDetection;
+///
+/// let cmd: ConfigureCollisionDetection = CollisionConfig::method1()
+///     .x_threshold(80)
+///     .y_threshold(80)
+///     .dead_time(20)
+///     .build()
+///     .unwrap();
+/// assert_eq!(cmd.meth, 0x01);
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CollisionConfig {
+    method: u8,
+    x_threshold: u8,
+    x_speed: u8,
+    y_threshold: u8,
+    y_speed: u8,
+    dead_time: u8,
+}
+
+impl CollisionConfig {
+    fn with_method(method: u8) -> Self {
+        Self {
+            method,
+            x_threshold: DEFAULT_THRESHOLD,
+            x_speed: DEFAULT_SPEED_THRESHOLD,
+            y_threshold: DEFAULT_THRESHOLD,
+            y_speed: DEFAULT_SPEED_THRESHOLD,
+            dead_time: DEFAULT_DEAD_TIME,
+        }
+    }
+
+    /// Detection method 1, with default thresholds
+    pub fn method1() -> Self {
+        Self::with_method(0x01)
+    }
+
+    /// Detection method 2, with default thresholds
+    pub fn method2() -> Self {
+        Self::with_method(0x02)
+    }
+
+    /// Turn collision detection off
+    pub fn disable() -> Self {
+        Self {
+            method: 0x00,
+            x_threshold: 0,
+            x_speed: 0,
+            y_threshold: 0,
+            y_speed: 0,
+            dead_time: 0,
+        }
+    }
+
+    /// Threshold for the X (left/right) axis
+    pub fn x_threshold(mut self, threshold: u8) -> Self {
+        self.x_threshold = threshold;
+        self
+    }
+
+    /// Threshold for the Y (front/back) axis
+    pub fn y_threshold(mut self, threshold: u8) -> Self {
+        self.y_threshold = threshold;
+        self
+    }
+
+    /// Speed dependent threshold for the X axis
+    pub fn x_speed(mut self, speed: u8) -> Self {
+        self.x_speed = speed;
+        self
+    }
+
+    /// Speed dependent threshold for the Y axis
+    pub fn y_speed(mut self, speed: u8) -> Self {
+        self.y_speed = speed;
+        self
+    }
+
+    /// Time to ignore further collisions after one is reported (10ms units)
+    pub fn dead_time(mut self, dead_time: u8) -> Self {
+        self.dead_time = dead_time;
+        self
+    }
+
+    /// Whether this configuration turns detection on
+    pub fn is_enabled(&self) -> bool {
+        self.method != 0x00
+    }
+
+    /// Check the configuration is usable
+    ///
+    /// An enabled configuration with a zero threshold on either axis fires on
+    /// every bump of the accelerometer, so it is rejected with `BadParameterValue`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.is_enabled() && (self.x_threshold == 0 || self.y_threshold == 0) {
+            return Err(Error::BadParameterValue);
+        }
+        Ok(())
+    }
+
+    /// Validate and convert to the command
+    pub fn build(self) -> Result<ConfigureCollisionDetection, Error> {
+        self.validate()?;
+        Ok(self.into())
+    }
+}
+
+impl From<CollisionConfig> for ConfigureCollisionDetection {
+    fn from(config: CollisionConfig) -> Self {
+        ConfigureCollisionDetection {
+            meth: config.method,
+            xt: config.x_threshold,
+            xspd: config.x_speed,
+            yt: config.y_threshold,
+            yspd: config.y_speed,
+            dead: config.dead_time,
+        }
+    }
+}
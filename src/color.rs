@@ -0,0 +1,230 @@
+/*!
+ * Sphero Colors
+ *
+ * Colors add and subtract channel by channel, saturating at 0 and 255, and
+ * scale by a factor, e.g. to fade:
+ *
+ * ```
+ * use sphero_rs::color::RgbColor;
+ *
+ * let orange = RgbColor::new(255, 128, 0);
+ * assert_eq!(orange + RgbColor::new(10, 200, 30), RgbColor::new(255, 255, 30));
+ * assert_eq!(orange - RgbColor::new(10, 200, 30), RgbColor::new(245, 0, 0));
+ * assert_eq!(orange * 0.5, RgbColor::new(128, 64, 0));
+ * assert_eq!(orange * 3.0, RgbColor::new(255, 255, 0));
+ * assert_eq!(orange * -1.0, RgbColor::BLACK);
+ * ```
+ */
+use std::ops::{Add, Mul, Sub};
+
+/// RGB Color, as shown on the main LED
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct RgbColor {
+    /// Red
+    pub red: u8,
+    /// Green
+    pub green: u8,
+    /// Blue
+    pub blue: u8,
+}
+
+impl RgbColor {
+    /// LED off
+    pub const BLACK: RgbColor = RgbColor::new(0, 0, 0);
+    /// Full white
+    pub const WHITE: RgbColor = RgbColor::new(0xff, 0xff, 0xff);
+    /// Full red
+    pub const RED: RgbColor = RgbColor::new(0xff, 0, 0);
+    /// Full green
+    pub const GREEN: RgbColor = RgbColor::new(0, 0xff, 0);
+    /// Full blue
+    pub const BLUE: RgbColor = RgbColor::new(0, 0, 0xff);
+
+    /// Color from its channels
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Linear blend from `self` (`t` = 0) to `other` (`t` = 1), rounding each channel
+    /// `t` is clamped to 0..1, so the result always lies between the two.
+    ///
+    /// ```
+    /// use sphero_rs::color::RgbColor;
+    ///
+    /// let from = RgbColor::new(0, 100, 255);
+    /// assert_eq!(from.lerp(RgbColor::WHITE, 0.5), RgbColor::new(128, 178, 255));
+    /// assert_eq!(from.lerp(RgbColor::WHITE, 0.0), from);
+    /// assert_eq!(RgbColor::lerp(from, RgbColor::WHITE, 1.0), RgbColor::WHITE);
+    /// assert_eq!(from.lerp(RgbColor::WHITE, 2.0), RgbColor::WHITE);
+    /// ```
+    pub fn lerp(self, other: RgbColor, t: f32) -> RgbColor {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        RgbColor::new(
+            channel(self.red, other.red),
+            channel(self.green, other.green),
+            channel(self.blue, other.blue),
+        )
+    }
+}
+
+impl From<(u8, u8, u8)> for RgbColor {
+    fn from((red, green, blue): (u8, u8, u8)) -> Self {
+        Self::new(red, green, blue)
+    }
+}
+
+impl Add for RgbColor {
+    type Output = RgbColor;
+
+    fn add(self, rhs: RgbColor) -> RgbColor {
+        RgbColor::new(
+            self.red.saturating_add(rhs.red),
+            self.green.saturating_add(rhs.green),
+            self.blue.saturating_add(rhs.blue),
+        )
+    }
+}
+
+impl Sub for RgbColor {
+    type Output = RgbColor;
+
+    fn sub(self, rhs: RgbColor) -> RgbColor {
+        RgbColor::new(
+            self.red.saturating_sub(rhs.red),
+            self.green.saturating_sub(rhs.green),
+            self.blue.saturating_sub(rhs.blue),
+        )
+    }
+}
+
+impl Mul<f32> for RgbColor {
+    type Output = RgbColor;
+
+    /// Scale each channel, rounding and clamping to 0..255
+    fn mul(self, factor: f32) -> RgbColor {
+        let channel = |c: u8| (c as f32 * factor).round().clamp(0.0, 255.0) as u8;
+        RgbColor::new(channel(self.red), channel(self.green), channel(self.blue))
+    }
+}
+
+/// HSV Color, for animating hue and brightness
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct HsvColor {
+    /// Hue, 0..360 degrees from red through green and blue
+    pub hue: f32,
+    /// Saturation, 0..1
+    pub saturation: f32,
+    /// Value (brightness), 0..1
+    pub value: f32,
+}
+
+impl HsvColor {
+    /// Color from its components; `hue` is wrapped into 0..360
+    pub fn new(hue: f32, saturation: f32, value: f32) -> Self {
+        Self {
+            hue: hue.rem_euclid(360.0),
+            saturation,
+            value,
+        }
+    }
+
+    /// Hue turned by `degrees`, wrapped into 0..360
+    ///
+    /// ```
+    /// use sphero_rs::color::HsvColor;
+    ///
+    /// let red = HsvColor::new(0.0, 1.0, 1.0);
+    /// assert_eq!(red.rotate_hue(90.0).hue, 90.0);
+    /// assert_eq!(red.rotate_hue(-30.0).hue, 330.0);
+    /// assert_eq!(red.rotate_hue(360.0).hue, 0.0);
+    /// assert_eq!(red.rotate_hue(750.0).hue, 30.0);
+    /// assert_eq!(HsvColor::new(350.0, 1.0, 1.0).rotate_hue(20.0).hue, 10.0);
+    /// ```
+    pub fn rotate_hue(&self, degrees: f32) -> Self {
+        Self::new(self.hue + degrees, self.saturation, self.value)
+    }
+
+    /// Opposite hue
+    ///
+    /// ```
+    /// use sphero_rs::color::HsvColor;
+    ///
+    /// assert_eq!(HsvColor::new(300.0, 1.0, 0.5).complement(), HsvColor::new(120.0, 1.0, 0.5));
+    /// ```
+    pub fn complement(&self) -> Self {
+        self.rotate_hue(180.0)
+    }
+
+    /// The two hues 150 degrees either side
+    ///
+    /// ```
+    /// use sphero_rs::color::HsvColor;
+    ///
+    /// let (a, b) = HsvColor::new(100.0, 1.0, 1.0).split_complement();
+    /// assert_eq!((a.hue, b.hue), (250.0, 310.0));
+    /// ```
+    pub fn split_complement(&self) -> (Self, Self) {
+        (self.rotate_hue(150.0), self.rotate_hue(-150.0))
+    }
+}
+
+impl From<HsvColor> for RgbColor {
+    /// ```
+    /// use sphero_rs::color::{HsvColor, RgbColor};
+    ///
+    /// assert_eq!(RgbColor::from(HsvColor::new(0.0, 1.0, 1.0)), RgbColor::RED);
+    /// assert_eq!(RgbColor::from(HsvColor::new(120.0, 1.0, 1.0)), RgbColor::GREEN);
+    /// assert_eq!(RgbColor::from(HsvColor::new(240.0, 1.0, 1.0)), RgbColor::BLUE);
+    /// assert_eq!(RgbColor::from(HsvColor::new(60.0, 1.0, 0.5)), RgbColor::new(128, 128, 0));
+    /// assert_eq!(RgbColor::from(HsvColor::new(200.0, 0.0, 1.0)), RgbColor::WHITE);
+    /// ```
+    fn from(hsv: HsvColor) -> Self {
+        let value = hsv.value.clamp(0.0, 1.0);
+        let chroma = value * hsv.saturation.clamp(0.0, 1.0);
+        let sector = hsv.hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+        let (r, g, b) = match sector as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+        RgbColor::new(channel(r), channel(g), channel(b))
+    }
+}
+
+impl From<RgbColor> for HsvColor {
+    /// ```
+    /// use sphero_rs::color::{HsvColor, RgbColor};
+    ///
+    /// assert_eq!(HsvColor::from(RgbColor::BLUE), HsvColor::new(240.0, 1.0, 1.0));
+    /// assert_eq!(HsvColor::from(RgbColor::BLACK), HsvColor::new(0.0, 0.0, 0.0));
+    /// let orange = RgbColor::new(255, 128, 0);
+    /// assert_eq!(RgbColor::from(HsvColor::from(orange)), orange);
+    /// ```
+    fn from(rgb: RgbColor) -> Self {
+        let (r, g, b) = (
+            rgb.red as f32 / 255.0,
+            rgb.green as f32 / 255.0,
+            rgb.blue as f32 / 255.0,
+        );
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        HsvColor::new(hue, saturation, max)
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+ * Sphero Bootloader Commands
+ */
+use super::{CommandWithResponse, ToCommandPacket};
+use crate::packet::{BootloaderCommandID, DeviceID, SpheroCommandPacketV1};
+use crate::response::PageBlankResponse;
+
+/// Sphero Erase User Config Command
+/// Wipes all user settings, see `client::erase_user_config_confirmed`
+#[derive(Debug, Default)]
+pub struct EraseUserConfig {}
+
+/// Sphero Begin Reflash Command
+/// Erases the main application, ready for Here Is Page
+#[derive(Debug, Default)]
+pub struct BeginReflash {}
+
+/// Sphero Here Is Page Command
+/// Writes one page of the main application, see `firmware::FirmwareImage`
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct HereIsPage {
+    /// Page number, from 0
+    pub page: u16,
+    /// Page contents
+    pub data: Vec<u8>,
+}
+
+/// Sphero Leave Bootloader Command
+/// Starts the main application; the robot does not answer
+#[derive(Debug, Default)]
+pub struct LeaveBootloader {}
+
+/// Sphero Is Page Blank Command
+#[derive(Debug, Default)]
+pub struct IsPageBlank {
+    /// Page number, from 0
+    pub page: u16,
+}
+
+impl ToCommandPacket for EraseUserConfig {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Bootloader; // = device id
+        let cid: u8 = BootloaderCommandID::EraseUserConfig as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for BeginReflash {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Bootloader; // = device id
+        let cid: u8 = BootloaderCommandID::Reflash as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for HereIsPage {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Bootloader; // = device id
+        let cid: u8 = BootloaderCommandID::HereIsPage as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let mut data = self.page.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.data);
+        SpheroCommandPacketV1::new(did, cid, seq, data)
+    }
+}
+
+impl ToCommandPacket for LeaveBootloader {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Bootloader; // = device id
+        let cid: u8 = BootloaderCommandID::LeaveBootloader as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for IsPageBlank {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Bootloader; // = device id
+        let cid: u8 = BootloaderCommandID::IsPageBlank as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.page.to_be_bytes().to_vec())
+    }
+}
+
+impl CommandWithResponse for IsPageBlank {
+    type Response = PageBlankResponse;
+}
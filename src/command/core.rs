@@ -0,0 +1,217 @@
+/*!
+ * Sphero Core Commands
+ */
+use super::{CommandWithResponse, ToCommandPacket};
+use crate::error::Error;
+use crate::packet::{
+    CoreCommandID, DeviceID, MRSPField, SpheroCommandPacketV1, SpheroResponsePacketV1,
+};
+use crate::response::{BluetoothInfo, PacketTimes, PowerStateInfo, VersioningInfo};
+
+/// Sphero Ping Command
+#[derive(Debug, Default)]
+pub struct Ping {}
+
+/// Sphero Echo Ping Command
+/// A Ping carrying `data`, to check a link carries payloads intact
+///
+/// Robots answer Ping without data, so the bytes only come back through a
+/// transport or simulator that echoes them; `Ping` covers the empty case.
+///
+/// ```
+/// use deku::DekuContainerRead;
+/// use sphero_rs::command::{EchoPing, ToCommandPacket};
+/// use sphero_rs::error::Error;
+/// use sphero_rs::packet::{MRSPField, SpheroCommandPacketV1};
+/// use sphero_rs::transport::mock::respond;
+///
+/// let ping = EchoPing { data: vec![0xde, 0xad, 0xbe, 0xef] };
+/// let packet = ping.to_packet(7);
+/// assert_eq!(packet.data(), [0xde, 0xad, 0xbe, 0xef]);
+///
+/// // An echoing link hands the data back
+/// let echoed = respond(&packet, MRSPField::Ok, packet.data().to_vec());
+/// assert_eq!(EchoPing::from_bytes(&echoed)?, ping.data);
+///
+/// let refused = respond(&packet, MRSPField::ChecksumError, vec![]);
+/// assert!(matches!(
+///     EchoPing::from_bytes(&refused),
+///     Err(Error::ResponseCode(MRSPField::ChecksumError))
+/// ));
+/// assert!(EchoPing::from_bytes(&echoed[..5]).is_err());
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct EchoPing {
+    /// Bytes to send
+    pub data: Vec<u8>,
+}
+
+impl EchoPing {
+    /// Data echoed in a serialized response packet
+    /// Fails if `bytes` isn't a whole response, or its response code isn't OK.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let packet = SpheroResponsePacketV1::try_from(bytes)?;
+        match packet.mrsp() {
+            MRSPField::Ok => Ok(packet.data().to_vec()),
+            mrsp => Err(Error::ResponseCode(mrsp)),
+        }
+    }
+}
+
+/// Sphero Get Versioning Command
+#[derive(Debug, Default)]
+pub struct GetVersioning {}
+
+/// Sphero Get Bluetooth Info Command
+#[derive(Debug, Default)]
+pub struct GetBluetoothInfo {}
+
+/// Sphero Get Power State Command
+#[derive(Debug, Default)]
+pub struct GetPowerState {}
+
+/// Sphero Sleep Command
+#[derive(Debug, Default)]
+pub struct Sleep {
+    /// Seconds until the robot wakes up again (0 = stay asleep)
+    pub wakeup: u16,
+    /// Macro to run on wakeup (0 = none)
+    pub macro_id: u8,
+    /// orbBasic line to run on wakeup (0 = none)
+    pub orbbasic_line: u16,
+}
+
+/// Sphero Assign Time Value Command
+/// Sets the robot's internal millisecond clock
+#[derive(Debug, Default)]
+pub struct AssignTimeValue {
+    /// New clock value, in ms
+    pub time: u32,
+}
+
+/// Sphero Poll Packet Times Command
+/// The robot stamps when it received and answered the command, see `clock`
+#[derive(Debug, Default)]
+pub struct PollPacketTimes {
+    /// Client clock when sending, in ms; echoed back
+    pub time: u32,
+}
+
+/// Sphero Jump To Bootloader Command
+/// Restarts into the bootloader, which answers the Bootloader device commands
+#[derive(Debug, Default)]
+pub struct JumpToBootloader {}
+
+impl ToCommandPacket for Ping {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::Ping as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for EchoPing {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::Ping as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.data.clone())
+    }
+}
+
+impl ToCommandPacket for GetVersioning {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::GetVersioningInformation as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for GetBluetoothInfo {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::GetBluetoothInfo as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for GetPowerState {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::GetPowerState as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for Sleep {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::Sleep as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let wbs = self.wakeup.to_be_bytes();
+        let obs = self.orbbasic_line.to_be_bytes();
+        SpheroCommandPacketV1::new(
+            did,
+            cid,
+            seq,
+            vec![wbs[0], wbs[1], self.macro_id, obs[0], obs[1]],
+        )
+    }
+}
+
+impl ToCommandPacket for AssignTimeValue {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::AssignTimeValue as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.time.to_be_bytes().to_vec())
+    }
+}
+
+impl ToCommandPacket for PollPacketTimes {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::PollPacketTimes as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.time.to_be_bytes().to_vec())
+    }
+}
+
+impl ToCommandPacket for JumpToBootloader {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Core; // = device id
+        let cid: u8 = CoreCommandID::JumpToBootloader as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl CommandWithResponse for GetVersioning {
+    type Response = VersioningInfo;
+}
+
+impl CommandWithResponse for PollPacketTimes {
+    type Response = PacketTimes;
+}
+
+impl CommandWithResponse for GetBluetoothInfo {
+    type Response = BluetoothInfo;
+}
+
+impl CommandWithResponse for GetPowerState {
+    type Response = PowerStateInfo;
+}
@@ -0,0 +1,59 @@
+/*!
+ * Sphero Commands
+ *
+ * Grouped by the virtual device they address; everything is re-exported
+ * here, so `sphero_rs::command::Roll` and `sphero_rs::command::sphero::Roll`
+ * name the same type:
+ *
+ * ```
+ * use sphero_rs::command::{
+ *     AbortOrbbasicProgram, AppendMacroChunk, AppendOrbbasicFragment, AssignTimeValue,
+ *     ConfigureCollisionDetection, EraseOrbbasicStorage, EraseUserConfig,
+ *     ExecuteOrbbasicProgram, GetBluetoothInfo, GetPowerState, GetVersioning,
+ *     MacroParameterIndex, MotorMode, OrbBasicArea, Ping, PollPacketTimes, ReadLocator, Roll,
+ *     SetBackLEDOutput, SetDataStreaming, SetHeading, SetMacroParameter, SetRGBLEDOutput,
+ *     SetRawMotorValues, SetStabilization, Sleep, ToCommandPacket, MACRO_CHUNK_SIZE,
+ *     TEMPORARY_MACRO_ID,
+ * };
+ *
+ * let roll: sphero_rs::command::sphero::Roll = Roll::stop_at_zero();
+ * let ping: sphero_rs::command::core::Ping = Ping {};
+ * let erase: sphero_rs::command::bootloader::EraseUserConfig = EraseUserConfig {};
+ * assert_eq!(roll.to_packet(1).cid(), 0x30);
+ * assert_eq!(ping.to_packet(1).cid(), 0x01);
+ * assert_eq!(erase.to_packet(1).did(), sphero_rs::packet::DeviceID::Bootloader);
+ * assert_eq!(sphero_rs::command::chunk_macro_bytes(&[0; 20]).len(), 2);
+ * ```
+ */
+use crate::packet::SpheroCommandPacketV1;
+use crate::response::FromResponsePacket;
+use crate::seq::NO_ANSWER_SEQ;
+
+pub mod bootloader;
+pub mod core;
+pub mod sphero;
+
+pub use self::bootloader::*;
+pub use self::core::*;
+pub use self::sphero::*;
+
+/// Sphero Command Conversion (requires seq)
+pub trait ToCommandPacket {
+    /// Convert to a Sphero Command Packet
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1;
+}
+
+/// Sphero Commands with a typed response
+pub trait CommandWithResponse: ToCommandPacket {
+    /// Decoded response data
+    type Response: FromResponsePacket;
+}
+
+/// Sphero Fire-and-Forget Commands
+/// High-frequency commands that are usually sent without waiting for an answer
+pub trait FireAndForget: ToCommandPacket {
+    /// Convert to a packet that asks the robot not to answer
+    fn to_fire_and_forget_packet(&self) -> SpheroCommandPacketV1 {
+        self.to_packet(NO_ANSWER_SEQ).with_response_required(false)
+    }
+}
@@ -0,0 +1,941 @@
+/*!
+ * Sphero Device Commands
+ */
+// deku's DekuRead derive rounds bit counts up by hand
+#![allow(clippy::manual_div_ceil)]
+use super::{CommandWithResponse, FireAndForget, ToCommandPacket};
+use crate::color::RgbColor;
+use crate::config::ConfigBlock;
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::speed::Speed;
+use crate::packet::{DeviceID, SpheroCommandID, SpheroCommandPacketV1};
+use crate::response::{
+    ApplicationConfigurationBlock, LocatorData, MacroStatusResponse, RGBLEDColorResponse,
+};
+use deku::prelude::*;
+
+/// Sphero Set Heading Command
+/// Makes the current orientation the new 0 degree heading
+#[derive(Debug, Default)]
+pub struct SetHeading {
+    /// Heading adjustment - 0..359 degrees
+    pub heading: u16,
+}
+
+/// Sphero Set Stabilization Command
+#[derive(Debug, Default)]
+pub struct SetStabilization {
+    /// Flag - true = stabilization on
+    pub enabled: bool,
+}
+
+/// Sphero Set RGB LED Output Command
+///
+/// Prefer `transient`, `persist` or `from_color` to a struct literal: building
+/// one directly is deprecated, as `flag` doesn't say what it does.
+#[derive(Debug, Default)]
+pub struct SetRGBLEDOutput {
+    /// Red
+    pub red: u8,
+    /// Green
+    pub green: u8,
+    /// Blue
+    pub blue: u8,
+    /// Flag - persists across power cycles
+    pub flag: bool,
+}
+
+impl SetRGBLEDOutput {
+    /// Color that is kept as the default across power cycles
+    ///
+    /// ```
+    /// use sphero_rs::color::RgbColor;
+    /// use sphero_rs::command::SetRGBLEDOutput;
+    ///
+    /// assert!(SetRGBLEDOutput::persist(0xff, 0, 0).flag);
+    /// assert!(!SetRGBLEDOutput::transient(0xff, 0, 0).flag);
+    ///
+    /// let cmd = SetRGBLEDOutput::from_color(RgbColor::BLUE, false);
+    /// assert_eq!((cmd.red, cmd.green, cmd.blue, cmd.flag), (0, 0, 0xff, false));
+    /// ```
+    pub const fn persist(red: u8, green: u8, blue: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            flag: true,
+        }
+    }
+
+    /// Color until the next change or power cycle
+    pub const fn transient(red: u8, green: u8, blue: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            flag: false,
+        }
+    }
+
+    /// Set the LED to `color`, optionally keeping it across power cycles
+    pub const fn from_color(color: RgbColor, persist: bool) -> Self {
+        Self {
+            red: color.red,
+            green: color.green,
+            blue: color.blue,
+            flag: persist,
+        }
+    }
+}
+
+/// Sphero Set Back LED Output Command
+#[derive(Debug, Default)]
+pub struct SetBackLEDOutput {
+    /// Brightness of Fixed Color LED
+    pub brightness: u8,
+}
+
+impl SetBackLEDOutput {
+    /// Turn the back LED off
+    pub const fn off() -> Self {
+        Self { brightness: 0 }
+    }
+
+    /// Full brightness, e.g. while aiming
+    pub const fn full() -> Self {
+        Self { brightness: 255 }
+    }
+
+    /// Brightness as a fraction from 0.0 (off) to 1.0 (full)
+    ///
+    /// ```
+    /// use sphero_rs::command::SetBackLEDOutput;
+    ///
+    /// let half = SetBackLEDOutput::at_percent(0.5).unwrap();
+    /// assert!(half.brightness == 127 || half.brightness == 128);
+    /// assert!(SetBackLEDOutput::at_percent(1.1).is_err());
+    /// ```
+    pub fn at_percent(pct: f32) -> Result<Self, Error> {
+        if !(0.0..=1.0).contains(&pct) {
+            return Err(Error::BadParameterValue);
+        }
+        Ok(Self {
+            brightness: (pct * 255.0).round() as u8,
+        })
+    }
+}
+
+/// Sphero Roll Command
+#[derive(Debug, Default)]
+pub struct Roll {
+    /// Speed
+    pub speed: Speed,
+    /// Heading
+    pub heading: Heading,
+    /// (CES firmware) State - true = roll, false = stop
+    pub state: bool,
+}
+
+impl Roll {
+    /// Stop, holding `last_heading` so the robot doesn't turn as it stops
+    ///
+    /// ```
+    /// use sphero_rs::command::Roll;
+    /// use sphero_rs::heading::Heading;
+    /// use sphero_rs::speed::Speed;
+    ///
+    /// let stop = Roll::stop(90).unwrap();
+    /// assert_eq!((stop.speed.value(), stop.heading.degrees(), stop.state), (0, 90, false));
+    /// assert!(stop.is_stop());
+    /// assert!(Roll::stop(360).is_err());
+    /// assert!(Roll::stop_at_zero().is_stop());
+    /// assert!(!Roll { speed: Speed::zero(), heading: Heading::ZERO, state: true }.is_stop());
+    /// ```
+    pub fn stop(last_heading: u16) -> Result<Roll, Error> {
+        Ok(Roll {
+            speed: Speed::zero(),
+            heading: Heading::new(last_heading)?,
+            state: false,
+        })
+    }
+
+    /// Stop, facing the 0 degree heading
+    pub const fn stop_at_zero() -> Roll {
+        Roll {
+            speed: Speed::zero(),
+            heading: Heading::ZERO,
+            state: false,
+        }
+    }
+
+    /// Whether this stops the robot: zero speed and state false
+    pub fn is_stop(&self) -> bool {
+        self.speed.is_zero() && !self.state
+    }
+}
+
+/// Sphero Raw Motor Mode
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+#[deku(type = "u8", endian = "big")]
+pub enum MotorMode {
+    /// Off (motor is open circuit)
+    #[default]
+    #[deku(id = "0x00")]
+    Off = 0x00,
+    /// Forward
+    #[deku(id = "0x01")]
+    Forward = 0x01,
+    /// Reverse
+    #[deku(id = "0x02")]
+    Reverse = 0x02,
+    /// Brake (motor is shorted)
+    #[deku(id = "0x03")]
+    Brake = 0x03,
+    /// Ignore (motor mode and power is left unchanged)
+    #[deku(id = "0x04")]
+    Ignore = 0x04,
+}
+
+impl TryFrom<u8> for MotorMode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(MotorMode::Off),
+            0x01 => Ok(MotorMode::Forward),
+            0x02 => Ok(MotorMode::Reverse),
+            0x03 => Ok(MotorMode::Brake),
+            0x04 => Ok(MotorMode::Ignore),
+            _ => Err(Error::BadParameterValue),
+        }
+    }
+}
+
+/// Sphero Set Raw Motor Values Command
+/// Takes direct control of the motors, disabling stabilization
+#[derive(Debug, Default)]
+pub struct SetRawMotorValues {
+    /// Left motor mode
+    pub left_mode: MotorMode,
+    /// Left motor power (0..255)
+    pub left_power: u8,
+    /// Right motor mode
+    pub right_mode: MotorMode,
+    /// Right motor power (0..255)
+    pub right_power: u8,
+}
+
+/// Sphero Read Locator Command
+#[derive(Debug, Default)]
+pub struct ReadLocator {}
+
+/// Sphero Configure Locator Command
+/// Moves the locator origin: the robot's current position becomes (`x`, `y`)
+#[derive(Debug, Default)]
+pub struct ConfigureLocator {
+    /// Flag - true = Set Heading also corrects the yaw tare
+    pub auto_yaw_tare: bool,
+    /// New X position, in cm
+    pub x: i16,
+    /// New Y position, in cm
+    pub y: i16,
+    /// Yaw tare - 0..359 degrees between the locator's Y axis and heading 0
+    pub yaw_tare: i16,
+}
+
+/// Sphero Get RGB LED Command
+/// Reads the user LED color, as last set with the persist flag
+#[derive(Debug, Default)]
+pub struct GetRGBLEDOutput {}
+
+/// Sphero Macro Parameter Index
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+#[deku(type = "u8", endian = "big")]
+pub enum MacroParameterIndex {
+    /// System speed 1
+    #[default]
+    #[deku(id = "0x01")]
+    Speed1 = 0x01,
+    /// System speed 2
+    #[deku(id = "0x02")]
+    Speed2 = 0x02,
+    /// System delay 1 (ms)
+    #[deku(id = "0x03")]
+    Delay1 = 0x03,
+    /// System delay 2 (ms)
+    #[deku(id = "0x04")]
+    Delay2 = 0x04,
+    /// Front (RGB) LED brightness
+    #[deku(id = "0x05")]
+    FrontBrightness = 0x05,
+    /// Back LED brightness
+    #[deku(id = "0x06")]
+    BackBrightness = 0x06,
+}
+
+impl TryFrom<u8> for MacroParameterIndex {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(MacroParameterIndex::Speed1),
+            0x02 => Ok(MacroParameterIndex::Speed2),
+            0x03 => Ok(MacroParameterIndex::Delay1),
+            0x04 => Ok(MacroParameterIndex::Delay2),
+            0x05 => Ok(MacroParameterIndex::FrontBrightness),
+            0x06 => Ok(MacroParameterIndex::BackBrightness),
+            _ => Err(Error::BadParameterValue),
+        }
+    }
+}
+
+/// Sphero Set Macro Parameter Command
+/// Sets a system value read by the running macro
+#[derive(Debug, Default)]
+pub struct SetMacroParameter {
+    /// Parameter to set
+    pub index: MacroParameterIndex,
+    /// Value (VAL1 is the high byte, VAL2 the low byte)
+    pub value: u16,
+}
+
+/// Largest macro chunk accepted by Append Macro Chunk
+pub const MACRO_CHUNK_SIZE: usize = 16;
+/// ID of the temporary macro buffer
+pub const TEMPORARY_MACRO_ID: u8 = 0xff;
+
+/// Sphero Append Macro Chunk Command
+/// Appends up to `MACRO_CHUNK_SIZE` bytes of bytecode to a macro
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct AppendMacroChunk {
+    /// Macro being uploaded
+    pub macro_id: u8,
+    /// Position of this chunk in the macro, from 0
+    pub index: u8,
+    /// Bytecode
+    pub data: Vec<u8>,
+}
+
+/// Split macro bytecode into numbered chunks for the temporary macro
+/// The last chunk may be shorter than `MACRO_CHUNK_SIZE`; indices wrap after 256 chunks.
+pub fn chunk_macro_bytes(data: &[u8]) -> Vec<AppendMacroChunk> {
+    data.chunks(MACRO_CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| AppendMacroChunk {
+            macro_id: TEMPORARY_MACRO_ID,
+            index: index as u8,
+            data: chunk.to_vec(),
+        })
+        .collect()
+}
+
+/// Sphero Run Macro Command
+/// Runs a stored macro, or the temporary macro as `TEMPORARY_MACRO_ID`
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RunMacro {
+    /// Macro to run
+    pub macro_id: u8,
+}
+
+/// Sphero Save Temporary Macro Command
+/// Replaces the temporary macro with bytecode that fits in one packet
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SaveTemporaryMacro {
+    /// Macro, header first
+    pub data: Vec<u8>,
+}
+
+/// Sphero Save Macro Command
+/// Stores bytecode that fits in one packet as a persistent macro
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SaveMacro {
+    /// Macro to store
+    pub macro_id: u8,
+    /// Macro after its ID, from the flags byte of the header on
+    pub data: Vec<u8>,
+}
+
+/// Sphero Abort Macro Command
+/// Stops the running macro, if any
+#[derive(Debug, Default)]
+pub struct AbortMacro {}
+
+/// Length of the Application Configuration Block
+pub const ACB_LEN: usize = 32;
+
+/// Sphero Set Application Configuration Block Command
+#[derive(Debug, Default)]
+pub struct SetApplicationConfigurationBlock {
+    /// New block contents
+    pub data: [u8; ACB_LEN],
+}
+
+/// Sphero Get Application Configuration Block Command
+#[derive(Debug, Default)]
+pub struct GetApplicationConfigurationBlock {}
+
+/// Block read by `GetConfigurationBlock` for the user configuration
+pub const USER_CONFIG_BLOCK: u8 = 0x01;
+
+/// Sphero Get Configuration Block Command
+/// The block arrives afterwards as a Config Block Contents async message.
+#[derive(Debug, Default)]
+pub struct GetConfigurationBlock {
+    /// Block to read: 0 for the factory block, `USER_CONFIG_BLOCK` for the user block
+    pub block: u8,
+}
+
+/// Sphero Set Configuration Block Command
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SetConfigurationBlock {
+    /// Block as serialized by `ConfigBlock::to_bytes`
+    pub data: Vec<u8>,
+}
+
+impl From<&ConfigBlock> for SetConfigurationBlock {
+    fn from(block: &ConfigBlock) -> Self {
+        Self {
+            data: block.to_bytes(),
+        }
+    }
+}
+
+/// Sphero Get Macro Status Command
+/// Asks which macro is running and which of its commands it is on
+#[derive(Debug, Default)]
+pub struct GetMacroStatus {}
+
+/// Sphero orbBasic Storage Area
+///
+/// ```
+/// use deku::DekuContainerWrite;
+/// use sphero_rs::command::OrbBasicArea;
+///
+/// assert_eq!(OrbBasicArea::Area0.to_bytes().unwrap(), vec![0x00]);
+/// assert_eq!(OrbBasicArea::Area1.to_bytes().unwrap(), vec![0x01]);
+/// ```
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+#[deku(type = "u8", endian = "big")]
+pub enum OrbBasicArea {
+    /// RAM, lost on power off
+    #[default]
+    #[deku(id = "0x00")]
+    Area0 = 0x00,
+    /// Persistent storage
+    #[deku(id = "0x01")]
+    Area1 = 0x01,
+}
+
+impl TryFrom<u8> for OrbBasicArea {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(OrbBasicArea::Area0),
+            0x01 => Ok(OrbBasicArea::Area1),
+            _ => Err(Error::BadParameterValue),
+        }
+    }
+}
+
+/// Sphero Erase orbBasic Storage Command
+#[derive(Debug, Default)]
+pub struct EraseOrbbasicStorage {
+    /// Area to erase
+    pub area: OrbBasicArea,
+}
+
+/// Sphero Append orbBasic Fragment Command
+#[derive(Debug, Default)]
+pub struct AppendOrbbasicFragment {
+    /// Area to append to
+    pub area: OrbBasicArea,
+    /// Program text
+    pub fragment: Vec<u8>,
+}
+
+/// Sphero Execute orbBasic Program Command
+#[derive(Debug, Default)]
+pub struct ExecuteOrbbasicProgram {
+    /// Area holding the program
+    pub area: OrbBasicArea,
+    /// Line number to start at
+    pub start_line: u16,
+}
+
+/// Sphero Abort orbBasic Program Command
+#[derive(Debug, Default)]
+pub struct AbortOrbbasicProgram {}
+
+/// Sphero Set Streaming Data
+#[derive(Debug, Default)]
+pub struct SetDataStreaming {
+    /// Divisor of the maximum sensor sampling rate
+    /// the control system runs at 400Hz and because it's pretty unlikely
+    /// you will want to see data at that rate, N allows you to divide
+    /// that down. N = 2 yields data samples at 200Hz, N = 10, 40Hz, etc.
+    pub n: u16,
+    /// Number of sample frames emitted per packet
+    /// M value defines how many frames to collect in memory before the
+    /// packet is emitted
+    pub m: u16,
+    /// Bitwise selector of data sources to stream
+    pub mask1: u32,
+    /// Packet count (set to 0 for unlimited streaming)
+    pub pcnt: u8,
+    /// Bitwise selector of more data sources to stream (optional)
+    pub mask2: Option<u32>,
+}
+
+/// Sphero Configure Collision Detection Command
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 29)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ConfigureCollisionDetection {
+    /// Detection method (0x00 disables collision detection)
+    pub meth: u8,
+    /// Threshold for the X (left/right) axis
+    pub xt: u8,
+    /// Speed dependent threshold added to `xt`
+    pub xspd: u8,
+    /// Threshold for the Y (front/back) axis
+    pub yt: u8,
+    /// Speed dependent threshold added to `yt`
+    pub yspd: u8,
+    /// Post-collision dead time, in 10ms increments
+    pub dead: u8,
+}
+
+impl ToCommandPacket for SetHeading {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetHeading as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.heading.to_be_bytes().to_vec())
+    }
+}
+
+impl ToCommandPacket for SetStabilization {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetStabilization as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.enabled as u8])
+    }
+}
+
+impl ToCommandPacket for SetRGBLEDOutput {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetRGBLEDOutput as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(
+            did,
+            cid,
+            seq,
+            vec![self.red, self.green, self.blue, self.flag as u8],
+        )
+    }
+}
+
+impl ToCommandPacket for SetBackLEDOutput {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetBackLEDOutput as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.brightness])
+    }
+}
+
+impl ToCommandPacket for Roll {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::Roll as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(
+            did,
+            cid,
+            seq,
+            vec![
+                self.speed.value(),
+                (self.heading.degrees() >> 8) as u8,
+                self.heading.degrees() as u8,
+                self.state as u8,
+            ],
+        )
+    }
+}
+
+impl ToCommandPacket for ReadLocator {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::ReadLocator as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for ConfigureLocator {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::ConfigureLocator as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let mut data = vec![self.auto_yaw_tare as u8];
+        data.extend(self.x.to_be_bytes());
+        data.extend(self.y.to_be_bytes());
+        data.extend(self.yaw_tare.to_be_bytes());
+        SpheroCommandPacketV1::new(did, cid, seq, data)
+    }
+}
+
+impl CommandWithResponse for ReadLocator {
+    type Response = LocatorData;
+}
+
+impl ToCommandPacket for GetRGBLEDOutput {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::GetRGBLEDOutput as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl CommandWithResponse for GetRGBLEDOutput {
+    type Response = RGBLEDColorResponse;
+}
+
+impl FireAndForget for SetRGBLEDOutput {}
+
+impl FireAndForget for SetBackLEDOutput {}
+
+impl FireAndForget for Roll {}
+
+impl ToCommandPacket for SetRawMotorValues {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetRawMotorValues as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(
+            did,
+            cid,
+            seq,
+            vec![
+                self.left_mode as u8,
+                self.left_power,
+                self.right_mode as u8,
+                self.right_power,
+            ],
+        )
+    }
+}
+
+impl ToCommandPacket for SetDataStreaming {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetDataStreaming as u8;
+        let seq: u8 = seq; // = sequence number
+
+        // if mask2: data = [0xfh [N bid endian] [M big endian] [mask1 big endian] [pcnt] [mask2 big endian]]
+        // else: data = [0xfh [N bid endian] [M big endian] [mask1 big endian] [pcnt]]
+        let nbs = self.n.to_be_bytes();
+        let mbs = self.m.to_be_bytes();
+        let m1bs = self.mask1.to_be_bytes();
+
+        match self.mask2 {
+            Some(mask2) => {
+                let m2bs = mask2.to_be_bytes();
+                SpheroCommandPacketV1::new(
+                    did,
+                    cid,
+                    seq,
+                    vec![
+                        nbs[0], nbs[1], mbs[0], mbs[1], m1bs[0], m1bs[1], m1bs[2], m1bs[3],
+                        self.pcnt, m2bs[0], m2bs[1], m2bs[2], m2bs[3],
+                    ],
+                )
+            }
+            None => SpheroCommandPacketV1::new(
+                did,
+                cid,
+                seq,
+                vec![
+                    nbs[0], nbs[1], mbs[0], mbs[1], m1bs[0], m1bs[1], m1bs[2], m1bs[3], self.pcnt,
+                ],
+            ),
+        }
+    }
+}
+
+impl ToCommandPacket for ConfigureCollisionDetection {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::ConfigureCollisionDetection as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(
+            did,
+            cid,
+            seq,
+            vec![self.meth, self.xt, self.xspd, self.yt, self.yspd, self.dead],
+        )
+    }
+}
+
+impl ToCommandPacket for SetMacroParameter {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetMacroParameter as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let vbs = self.value.to_be_bytes();
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.index as u8, vbs[0], vbs[1]])
+    }
+}
+
+impl ToCommandPacket for AppendMacroChunk {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::AppendMacroChunk as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let mut data = vec![self.macro_id, self.index];
+        data.extend_from_slice(&self.data);
+        SpheroCommandPacketV1::new(did, cid, seq, data)
+    }
+}
+
+impl ToCommandPacket for RunMacro {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::RunMacro as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.macro_id])
+    }
+}
+
+impl ToCommandPacket for SaveTemporaryMacro {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SaveTemporaryMacro as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.data.clone())
+    }
+}
+
+impl ToCommandPacket for SaveMacro {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SaveMacro as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let mut data = vec![self.macro_id];
+        data.extend_from_slice(&self.data);
+        SpheroCommandPacketV1::new(did, cid, seq, data)
+    }
+}
+
+impl ToCommandPacket for AbortMacro {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::AbortMacro as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl ToCommandPacket for SetApplicationConfigurationBlock {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetApplicationConfigurationBlock as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.data.to_vec())
+    }
+}
+
+impl ToCommandPacket for GetApplicationConfigurationBlock {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::GetApplicationConfigurationBlock as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl CommandWithResponse for GetApplicationConfigurationBlock {
+    type Response = ApplicationConfigurationBlock;
+}
+
+impl ToCommandPacket for GetConfigurationBlock {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::GetConfigurationBlock as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.block])
+    }
+}
+
+impl ToCommandPacket for SetConfigurationBlock {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::SetConfigurationBlock as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, self.data.clone())
+    }
+}
+
+impl ToCommandPacket for GetMacroStatus {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::GetMacroStatus as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+impl CommandWithResponse for GetMacroStatus {
+    type Response = MacroStatusResponse;
+}
+
+impl ToCommandPacket for EraseOrbbasicStorage {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::EraseOrbbasicStorage as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.area as u8])
+    }
+}
+
+impl ToCommandPacket for AppendOrbbasicFragment {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::AppendOrbbasicFragment as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let mut data = vec![self.area as u8];
+        data.extend_from_slice(&self.fragment);
+        SpheroCommandPacketV1::new(did, cid, seq, data)
+    }
+}
+
+impl ToCommandPacket for ExecuteOrbbasicProgram {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::ExecuteOrbbasicProgram as u8;
+        let seq: u8 = seq; // = sequence number
+
+        let lbs = self.start_line.to_be_bytes();
+        SpheroCommandPacketV1::new(did, cid, seq, vec![self.area as u8, lbs[0], lbs[1]])
+    }
+}
+
+impl ToCommandPacket for AbortOrbbasicProgram {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        let did = DeviceID::Sphero; // = device id
+        let cid: u8 = SpheroCommandID::AbortOrbbasicProgram as u8;
+        let seq: u8 = seq; // = sequence number
+
+        SpheroCommandPacketV1::new(did, cid, seq, vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn motor_mode_try_from_every_byte() {
+        let modes = [
+            MotorMode::Off,
+            MotorMode::Forward,
+            MotorMode::Reverse,
+            MotorMode::Brake,
+            MotorMode::Ignore,
+        ];
+        for byte in 0..=u8::MAX {
+            match modes.get(byte as usize) {
+                Some(&mode) => {
+                    assert_eq!(MotorMode::try_from(byte).unwrap(), mode);
+                    assert_eq!(mode as u8, byte);
+                    assert_eq!(mode.to_bytes().unwrap(), [byte]);
+                    assert_eq!(MotorMode::from_bytes((&[byte], 0)).unwrap().1, mode);
+                }
+                None => {
+                    assert!(matches!(MotorMode::try_from(byte), Err(Error::BadParameterValue)));
+                    assert!(MotorMode::from_bytes((&[byte], 0)).is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn set_raw_motor_values_sends_modes_and_powers() {
+        let cmd = SetRawMotorValues {
+            left_mode: MotorMode::Forward,
+            left_power: 0x80,
+            right_mode: MotorMode::Brake,
+            right_power: 0xff,
+        };
+        let packet = cmd.to_packet(1);
+        assert_eq!(packet.cid(), SpheroCommandID::SetRawMotorValues as u8);
+        assert_eq!(packet.data(), [0x01, 0x80, 0x03, 0xff]);
+    }
+
+    #[test]
+    fn macro_parameter_index_rejects_0x00_and_0xff() {
+        for byte in [0x00, 0x07, 0xff] {
+            assert!(matches!(
+                MacroParameterIndex::try_from(byte),
+                Err(Error::BadParameterValue)
+            ));
+        }
+    }
+
+    #[test]
+    fn macro_parameter_index_round_trips_every_index() {
+        let indices = [
+            MacroParameterIndex::Speed1,
+            MacroParameterIndex::Speed2,
+            MacroParameterIndex::Delay1,
+            MacroParameterIndex::Delay2,
+            MacroParameterIndex::FrontBrightness,
+            MacroParameterIndex::BackBrightness,
+        ];
+        for (index, byte) in indices.into_iter().zip(0x01..) {
+            assert_eq!(MacroParameterIndex::try_from(byte).unwrap(), index);
+            assert_eq!(index as u8, byte);
+        }
+    }
+
+    #[test]
+    fn set_macro_parameter_sends_the_value_high_byte_first() {
+        let cmd = SetMacroParameter {
+            index: MacroParameterIndex::Delay2,
+            value: 0x01f4,
+        };
+        let packet = cmd.to_packet(1);
+        assert_eq!(packet.cid(), SpheroCommandID::SetMacroParameter as u8);
+        assert_eq!(packet.data(), [0x04, 0x01, 0xf4]);
+    }
+}
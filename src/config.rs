@@ -0,0 +1,128 @@
+/*!
+ * Sphero Configuration Block
+ *
+ * The user configuration block read with Get Configuration Block (it arrives
+ * as a Config Block Contents async message) and written back with Set
+ * Configuration Block. Only its leading, user-adjustable fields are named;
+ * the rest is kept as it was read. All fields are big-endian:
+ *
+ * | Offset | Size | Field                     |
+ * |--------|------|---------------------------|
+ * | 0      | 1    | block ID                  |
+ * | 1      | 2    | sleep timeout (s)         |
+ * | 3      | 4    | option flags              |
+ * | 7      | 3    | initial RGB LED color     |
+ * | 10     | 1    | back LED brightness       |
+ * | 11     | 2    | motion timeout (ms)       |
+ * | 13     | 6    | gyro trim x, y, z         |
+ * | 19     | 6    | accelerometer trim x, y, z|
+ * | 25     | ...  | undocumented              |
+ * | last   | 1    | checksum                  |
+ *
+ * The checksum is computed like a packet's, over every byte before it.
+ */
+use crate::color::RgbColor;
+use crate::error::Error;
+use crate::packet::calculate_checksum;
+
+/// Length of the named fields at the start of the block
+pub const CONFIG_FIELDS_LEN: usize = 25;
+
+/// Sphero Configuration Block
+///
+/// ```
+/// use sphero_rs::color::RgbColor;
+/// use sphero_rs::config::ConfigBlock;
+///
+/// // Captured user block: 600 s sleep timeout, blue, 2 s motion timeout
+/// let captured = [
+///     0x01, 0x02, 0x58, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0xff, 0x00, 0x07, 0xd0, 0x00, 0x03,
+///     0xff, 0xfe, 0x00, 0x00, 0x00, 0x10, 0xff, 0xf0, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00,
+///     0x00, 0x96,
+/// ];
+/// let mut block = ConfigBlock::parse(&captured).unwrap();
+/// assert_eq!(block.sleep_timeout, 600);
+/// assert_eq!(block.initial_color, RgbColor::BLUE);
+/// assert_eq!(block.gyro_trim, [3, -2, 0]);
+/// assert_eq!(block.to_bytes(), captured);
+///
+/// // Only the color and the checksum change
+/// block.initial_color = RgbColor::new(0xff, 0x80, 0x00);
+/// let edited = block.to_bytes();
+/// assert_eq!(edited[7..10], [0xff, 0x80, 0x00]);
+/// assert_eq!(edited[31], 0x16);
+/// assert_eq!(edited[..7], captured[..7]);
+/// assert_eq!(edited[10..31], captured[10..31]);
+/// assert_eq!(ConfigBlock::parse(&edited).unwrap(), block);
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ConfigBlock {
+    /// Which block this is
+    pub block_id: u8,
+    /// Seconds of inactivity before the robot goes to sleep
+    pub sleep_timeout: u16,
+    /// Option flags, as also read with Get Options Flags
+    pub option_flags: u32,
+    /// RGB LED color shown after waking up
+    pub initial_color: RgbColor,
+    /// Back LED brightness after waking up
+    pub back_led_brightness: u8,
+    /// Milliseconds a roll command drives for without a new one
+    pub motion_timeout: u16,
+    /// Gyro trim, x, y and z
+    pub gyro_trim: [i16; 3],
+    /// Accelerometer trim, x, y and z
+    pub accel_trim: [i16; 3],
+    /// Undocumented remainder of the block, before the checksum
+    pub reserved: Vec<u8>,
+}
+
+impl ConfigBlock {
+    /// Parse a block as read from the robot, checksum included
+    ///
+    /// Fails with `Error::BadDataLength` if it is too short for the named
+    /// fields, or `Error::ChecksumMismatch` if its checksum is wrong.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let Some((&actual, body)) = bytes.split_last() else {
+            return Err(Error::BadDataLength);
+        };
+        if body.len() < CONFIG_FIELDS_LEN {
+            return Err(Error::BadDataLength);
+        }
+        let expected = calculate_checksum(body, &[]);
+        if expected != actual {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([body[i], body[i + 1]]);
+        let i16_at = |i: usize| i16::from_be_bytes([body[i], body[i + 1]]);
+        Ok(Self {
+            block_id: body[0],
+            sleep_timeout: u16_at(1),
+            option_flags: u32::from_be_bytes([body[3], body[4], body[5], body[6]]),
+            initial_color: RgbColor::new(body[7], body[8], body[9]),
+            back_led_brightness: body[10],
+            motion_timeout: u16_at(11),
+            gyro_trim: [i16_at(13), i16_at(15), i16_at(17)],
+            accel_trim: [i16_at(19), i16_at(21), i16_at(23)],
+            reserved: body[CONFIG_FIELDS_LEN..].to_vec(),
+        })
+    }
+
+    /// Serialize the block for Set Configuration Block, with a fresh checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CONFIG_FIELDS_LEN + self.reserved.len() + 1);
+        bytes.push(self.block_id);
+        bytes.extend(self.sleep_timeout.to_be_bytes());
+        bytes.extend(self.option_flags.to_be_bytes());
+        let color = self.initial_color;
+        bytes.extend([color.red, color.green, color.blue]);
+        bytes.push(self.back_led_brightness);
+        bytes.extend(self.motion_timeout.to_be_bytes());
+        for trim in self.gyro_trim.iter().chain(&self.accel_trim) {
+            bytes.extend(trim.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.reserved);
+        bytes.push(calculate_checksum(&bytes, &[]));
+        bytes
+    }
+}
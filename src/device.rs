@@ -0,0 +1,3104 @@
+/*!
+ * Sphero Device
+ *
+ * A concurrent client: a background task reads packets from the transport,
+ * matches responses to outstanding commands by their echoed sequence number
+ * and routes asynchronous messages to a separate channel. Any number of
+ * commands may be in flight at once.
+ */
+use crate::async_payload::{self, AsyncPayload, MacroMarkerPayload};
+use crate::broadcast::Broadcast;
+use crate::capabilities::Capabilities;
+use crate::clock::{self, ClockOffset, ClockSample};
+use crate::color::RgbColor;
+use crate::command::{
+    chunk_macro_bytes, AbortMacro, AbortOrbbasicProgram, AppendOrbbasicFragment, AssignTimeValue,
+    BeginReflash, CommandWithResponse, EraseOrbbasicStorage, ExecuteOrbbasicProgram,
+    GetApplicationConfigurationBlock, GetConfigurationBlock, GetMacroStatus, GetVersioning,
+    HereIsPage, IsPageBlank, JumpToBootloader, LeaveBootloader, OrbBasicArea, Ping,
+    PollPacketTimes, Roll, RunMacro, SaveMacro, SaveTemporaryMacro,
+    SetApplicationConfigurationBlock, SetConfigurationBlock, SetDataStreaming, SetStabilization,
+    Sleep, ToCommandPacket, ACB_LEN, TEMPORARY_MACRO_ID, USER_CONFIG_BLOCK,
+};
+#[cfg(feature = "serde")]
+use crate::command::{SetHeading, SetRGBLEDOutput};
+use crate::config::ConfigBlock;
+use crate::error::Error;
+use crate::event::{AsyncMessage, SpheroEvent};
+use crate::firmware::FirmwareImage;
+#[cfg(feature = "serde")]
+use crate::heading::Heading;
+use crate::macros::{self, MAX_MACRO_LEN};
+use crate::orbbasic::{self, OrbBasicOutput};
+use crate::packet::{
+    DeviceID, MRSPField, SOP2Field, SpheroCommandID, SpheroCommandPacketV1, SpheroResponsePacketV1,
+};
+use crate::ratelimit::{Pace, RateLimitStats, RateLimiter, Ticket};
+use crate::reader::PacketReader;
+use crate::reconnect::{Link, LinkState, ReconnectPolicy, Session};
+use crate::reflash::{ReflashOptions, ReflashProgress};
+use crate::response::{FromResponsePacket, PacketTimes, VersioningInfo};
+use crate::runtime::{self, Spawner};
+#[cfg(feature = "serde")]
+use crate::script::{Script, ScriptStep};
+use crate::sensor::{
+    Attitude, MotionEvent, OrientationConfig, OrientationMonitor, PickupConfig, PickupDetector,
+    SensorFrame, SensorMask, StreamWatchdog, StreamingConfig, WatchdogConfig, MAX_SAMPLE_RATE_HZ,
+};
+use crate::seq::{SeqAllocator, NO_ANSWER_SEQ};
+#[cfg(feature = "serde")]
+use crate::speed::Speed;
+use crate::stats::Stats;
+use crate::trace::{debug_event, warn_event};
+use crate::transport::Transport;
+use deku::DekuContainerWrite;
+use futures::channel::oneshot;
+use futures::future::{self, select, AbortHandle, Abortable, BoxFuture, Either, FutureExt};
+use futures::lock::Mutex as AsyncMutex;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(feature = "serde")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Asynchronous messages queued per subscriber before the oldest are dropped
+pub const EVENT_QUEUE_CAPACITY: usize = 64;
+/// How long cancelling a script may take to be noticed
+#[cfg(feature = "serde")]
+const SCRIPT_CANCEL_POLL: Duration = Duration::from_millis(20);
+
+type Pending = Mutex<HashMap<u8, oneshot::Sender<SpheroResponsePacketV1>>>;
+
+/// State shared between the device handle and its reader task
+struct Shared {
+    pending: Pending,
+    seq: Mutex<SeqAllocator>,
+    last_sent: Mutex<Instant>,
+    limiter: Mutex<RateLimiter>,
+    link: Mutex<Link>,
+    session: Mutex<Session>,
+    reconnect: Mutex<Option<ReconnectPolicy>>,
+    events: Broadcast<AsyncMessage>,
+    mask: Mutex<Option<SensorMask>>,
+    /// Sample rate of the configured stream, in Hz
+    stream_rate: Mutex<f32>,
+    pickup: Mutex<Option<PickupDetector>>,
+    orientation: Mutex<Option<OrientationMonitor>>,
+    streaming: AtomicBool,
+    stats: Stats,
+    /// Client clock 0 for clock synchronization, as a monotonic and a wall time
+    epoch: (Instant, SystemTime),
+    clock: Mutex<Option<ClockOffset>>,
+    capabilities: Mutex<Option<Capabilities>>,
+    check_capabilities: AtomicBool,
+}
+
+impl Shared {
+    /// Client clock in ms, as sent in Assign Time Value and Poll Packet Times
+    fn client_ms(&self) -> u32 {
+        self.epoch.0.elapsed().as_millis() as u32
+    }
+}
+
+/// Response timeout used unless overridden
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Poll Packet Times exchanges per clock synchronization
+pub const CLOCK_SYNC_ROUNDS: usize = 5;
+/// Retries given to idempotent commands unless overridden
+pub const DEFAULT_RETRIES: u8 = 1;
+
+/// Per-command send options
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendOptions {
+    /// How long to wait for each response
+    pub timeout: Duration,
+    /// How many times to resend after a timeout
+    pub retries: u8,
+    /// Send without requesting an answer, returning as soon as the packet is written
+    pub no_answer: bool,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            retries: 0,
+            no_answer: false,
+        }
+    }
+}
+
+impl SendOptions {
+    /// Default options for `packet`: retries are only enabled for idempotent commands
+    pub fn for_packet(packet: &SpheroCommandPacketV1) -> Self {
+        Self {
+            retries: if packet.is_idempotent() {
+                DEFAULT_RETRIES
+            } else {
+                0
+            },
+            ..Self::default()
+        }
+    }
+}
+
+/// Steps of `SpheroDevice::shutdown` beyond stopping streaming and the motors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShutdownOptions {
+    /// Turn stabilization back on, e.g. after raw motor control
+    pub restore_stabilization: bool,
+    /// Put the robot to sleep
+    pub sleep: bool,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        Self {
+            restore_stabilization: true,
+            sleep: false,
+        }
+    }
+}
+
+/// Sphero Device
+///
+/// Dropping the device without calling `shutdown` stops streaming and the
+/// motors on a best-effort basis: the commands are written in the background,
+/// without waiting for an answer.
+pub struct SpheroDevice<T: Transport + 'static> {
+    transport: Arc<T>,
+    shared: Arc<Shared>,
+    back_led: AtomicU8,
+    led: Mutex<Option<RgbColor>>,
+    spawner: Arc<dyn Spawner>,
+    keepalive: Mutex<Option<AbortHandle>>,
+    clock_resync: Mutex<Option<AbortHandle>>,
+    reader: AbortHandle,
+    shut_down: bool,
+    /// Bumped by `cancel_script`; scripts started before then stop
+    #[cfg(feature = "serde")]
+    script_generation: AtomicU64,
+}
+
+impl<T: Transport + 'static> SpheroDevice<T> {
+    /// Connect a device over `transport`, running background tasks on tokio
+    #[cfg(feature = "tokio")]
+    pub async fn new(transport: T) -> Result<Self, Error> {
+        Self::with_spawner(transport, runtime::TokioSpawner).await
+    }
+
+    /// Connect a device over `transport` and start the background reader task on `spawner`
+    ///
+    /// No tokio runtime is needed, e.g. with a thread per task:
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use futures::future::BoxFuture;
+    /// use sphero_rs::command::Ping;
+    /// use sphero_rs::device::SpheroDevice;
+    /// use sphero_rs::transport::mock::MockTransport;
+    ///
+    /// let spawner = |task: BoxFuture<'static, ()>| {
+    ///     drop(std::thread::spawn(move || block_on(task)));
+    /// };
+    /// let mock = MockTransport::acknowledging();
+    /// block_on(async {
+    ///     let device = SpheroDevice::with_spawner(mock.clone(), spawner).await?;
+    ///     let response = device.send(&Ping {}).await?;
+    ///     assert!(response.data().is_empty());
+    ///     Ok::<(), sphero_rs::error::Error>(())
+    /// })?;
+    /// assert!(!mock.written().is_empty());
+    /// # Ok::<(), sphero_rs::error::Error>(())
+    /// ```
+    pub async fn with_spawner(transport: T, spawner: impl Spawner) -> Result<Self, Error> {
+        let inbound = transport.subscribe().await?;
+        let shared = Arc::new(Shared {
+            pending: Pending::default(),
+            seq: Mutex::new(SeqAllocator::new()),
+            last_sent: Mutex::new(Instant::now()),
+            limiter: Mutex::new(RateLimiter::new()),
+            link: Mutex::new(Link::new()),
+            session: Mutex::new(Session::default()),
+            reconnect: Mutex::new(None),
+            events: Broadcast::new(EVENT_QUEUE_CAPACITY),
+            mask: Mutex::new(None),
+            stream_rate: Mutex::new(StreamingConfig::default().effective_rate_hz()),
+            pickup: Mutex::new(None),
+            orientation: Mutex::new(None),
+            streaming: AtomicBool::new(false),
+            stats: Stats::new(),
+            epoch: (Instant::now(), SystemTime::now()),
+            clock: Mutex::new(None),
+            capabilities: Mutex::new(None),
+            check_capabilities: AtomicBool::new(true),
+        });
+
+        let transport = Arc::new(transport);
+        let (reader, registration) = AbortHandle::new_pair();
+        let task = connection_loop(transport.clone(), shared.clone(), inbound);
+        spawner.spawn(Abortable::new(task, registration).map(|_| ()).boxed());
+        // Learn the firmware in the background; `capabilities` retries if this fails
+        let probe = fetch_capabilities(transport.clone(), shared.clone());
+        spawner.spawn(probe.map(drop).boxed());
+
+        Ok(Self {
+            transport,
+            shared,
+            back_led: AtomicU8::new(0),
+            led: Mutex::new(None),
+            spawner: Arc::new(spawner),
+            keepalive: Mutex::new(None),
+            clock_resync: Mutex::new(None),
+            reader,
+            shut_down: false,
+            #[cfg(feature = "serde")]
+            script_generation: AtomicU64::new(0),
+        })
+    }
+
+    /// Underlying transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Send a command and wait for its correlated response
+    ///
+    /// Uses `SendOptions::for_packet`, so only idempotent commands are retried.
+    /// Once the firmware version is known, a command it predates fails with
+    /// `Error::RequiresFirmware` without being sent, see `set_capability_check`.
+    pub async fn send(&self, cmd: &impl ToCommandPacket) -> Result<SpheroResponsePacketV1, Error> {
+        let packet = cmd.to_packet(NO_ANSWER_SEQ);
+        if self.shared.check_capabilities.load(Ordering::Relaxed) {
+            if let Some(capabilities) = *self.shared.capabilities.lock().unwrap() {
+                capabilities.check(packet.did(), packet.cid())?;
+            }
+        }
+        let options = SendOptions::for_packet(&packet);
+        self.send_with(cmd, options)
+            .await?
+            .ok_or(Error::InvalidPacket)
+    }
+
+    /// Send a command and decode its response
+    pub async fn query<C: CommandWithResponse>(&self, cmd: &C) -> Result<C::Response, Error> {
+        let response = self.send(cmd).await?;
+        C::Response::from_response(&response)
+    }
+
+    /// Send a command with explicit timeout, retry and answer options
+    ///
+    /// Each retry is sent with a fresh sequence number; a late response to an
+    /// earlier attempt is dropped as unsolicited. Returns `None` for `no_answer` sends.
+    /// Under a rate limit, a Roll or Set RGB LED Output replaced while waiting
+    /// for its slot returns `Error::Superseded` (`None` for `no_answer` sends).
+    pub async fn send_with(
+        &self,
+        cmd: &impl ToCommandPacket,
+        options: SendOptions,
+    ) -> Result<Option<SpheroResponsePacketV1>, Error> {
+        let packet = cmd.to_packet(NO_ANSWER_SEQ);
+        self.observe(&packet);
+        let ticket = self.shared.limiter.lock().unwrap().ticket(&packet);
+        if options.no_answer {
+            connected(&self.shared).await?;
+            match pace(&self.shared, ticket).await {
+                Err(Error::Superseded) => return Ok(None),
+                result => result?,
+            }
+            let bytes = packet.with_response_required(false).to_bytes()?;
+            write(&*self.transport, &self.shared, &bytes).await?;
+            return Ok(None);
+        }
+
+        let mut attempt = 0;
+        loop {
+            match send_once(&*self.transport, &self.shared, cmd, ticket, options.timeout).await {
+                Ok(response) => return Ok(Some(response)),
+                Err(Error::Timeout) if attempt < options.retries => {
+                    self.shared.stats.record_retry();
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send a command by raw device and command ID, e.g. to probe undocumented commands
+    ///
+    /// Goes through the same sequence numbering and response matching as typed
+    /// commands, without retries. Returns `None` unless `want_answer`. Fails with
+    /// `Error::UnknownDeviceId` if `did` isn't a known device.
+    pub async fn send_raw(
+        &self,
+        did: u8,
+        cid: u8,
+        data: Vec<u8>,
+        want_answer: bool,
+    ) -> Result<Option<SpheroResponsePacketV1>, Error> {
+        let raw = RawCommand {
+            did: DeviceID::try_from_byte(did)?,
+            cid,
+            data,
+        };
+        let options = SendOptions {
+            no_answer: !want_answer,
+            ..SendOptions::default()
+        };
+        self.send_with(&raw, options).await
+    }
+
+    /// Send a pre-built packet
+    ///
+    /// A packet with SOP2 `NoResponse` is written as is and returns `None`. For
+    /// any other, a sequence number of `NO_ANSWER_SEQ` is replaced with a fresh
+    /// one, while any other is kept and fails with `Error::Busy` if a command
+    /// carrying it is still in flight. The checksum is always recomputed.
+    pub async fn send_raw_packet(
+        &self,
+        packet: SpheroCommandPacketV1,
+    ) -> Result<Option<SpheroResponsePacketV1>, Error> {
+        let raw = RawCommand {
+            did: packet.did(),
+            cid: packet.cid(),
+            data: packet.data().to_vec(),
+        };
+        if packet.sop2() == SOP2Field::NoResponse {
+            let options = SendOptions {
+                no_answer: true,
+                ..SendOptions::default()
+            };
+            return self.send_with(&raw, options).await;
+        }
+        if packet.seq() == NO_ANSWER_SEQ {
+            return self.send_with(&raw, SendOptions::default()).await;
+        }
+
+        self.observe(&packet);
+        connected(&self.shared).await?;
+        pace(&self.shared, None).await?;
+        let response = exchange(
+            &*self.transport,
+            &self.shared,
+            &raw,
+            Some(packet.seq()),
+            DEFAULT_TIMEOUT,
+        )
+        .await?;
+        Ok(Some(response))
+    }
+
+    /// Limit outgoing commands to `rate` packets per second, or lift the limit with `None`
+    ///
+    /// Commands over the budget wait for a free slot.
+    /// Roll and Set RGB LED Output coalesce: only the latest one waiting is sent.
+    /// The stop commands written when the device is dropped are never delayed.
+    pub fn set_max_command_rate(&self, rate: Option<f32>) -> Result<(), Error> {
+        self.shared.limiter.lock().unwrap().set_rate(rate)
+    }
+
+    /// Number of commands delayed or coalesced by the rate limiter
+    pub fn rate_limit_stats(&self) -> RateLimitStats {
+        self.shared.limiter.lock().unwrap().stats()
+    }
+
+    /// Reconnect automatically when the link drops, or stop doing so with `None`
+    ///
+    /// Commands in flight when the link drops fail with `Error::Disconnected`.
+    /// Commands sent while reconnecting wait until the robot has been pinged and
+    /// the last stabilization, data streaming, collision detection and back LED
+    /// commands have been sent again. Once every attempt has failed, they fail
+    /// with `Error::Disconnected`, as does anything sent afterwards.
+    pub fn set_reconnect(&self, policy: Option<ReconnectPolicy>) {
+        *self.shared.reconnect.lock().unwrap() = policy;
+    }
+
+    /// Whether the link is up
+    pub fn is_connected(&self) -> bool {
+        self.shared.link.lock().unwrap().state() == LinkState::Connected
+    }
+
+    /// Ping the robot whenever no command has been sent for `interval`
+    ///
+    /// Keeps the robot from falling asleep while the application idles: pings
+    /// request an answer (SOP2 0xff), which resets the inactivity timeout.
+    /// Replaces any previous keep-alive; `None` stops it, as does dropping the device.
+    pub fn set_keepalive(&self, interval: Option<Duration>) {
+        let mut keepalive = self.keepalive.lock().unwrap();
+        if let Some(previous) = keepalive.take() {
+            previous.abort();
+        }
+        if let Some(interval) = interval {
+            let (handle, registration) = AbortHandle::new_pair();
+            let task = keepalive_loop(self.transport.clone(), self.shared.clone(), interval);
+            self.spawner
+                .spawn(Abortable::new(task, registration).map(|_| ()).boxed());
+            *keepalive = Some(handle);
+        }
+    }
+
+    /// What the robot's firmware implements
+    /// Queried when the device connects and cached; queried again if that failed.
+    pub async fn capabilities(&self) -> Result<Capabilities, Error> {
+        let cached = *self.shared.capabilities.lock().unwrap();
+        match cached {
+            Some(capabilities) => Ok(capabilities),
+            None => fetch_capabilities(self.transport.clone(), self.shared.clone()).await,
+        }
+    }
+
+    /// Whether `send` and `query` refuse commands the firmware predates (default on)
+    /// Turn off to experiment; `send_with` and the raw sends never check.
+    pub fn set_capability_check(&self, enabled: bool) {
+        self.shared
+            .check_capabilities
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Synchronize with the robot's clock, see `clock`
+    ///
+    /// Assigns the robot clock, then runs `CLOCK_SYNC_ROUNDS` Poll Packet Times
+    /// exchanges and keeps the offset from the one with the lowest delay.
+    /// Clocks drift, so call this again now and then or use `set_clock_resync`.
+    pub async fn sync_clock(&self) -> Result<ClockOffset, Error> {
+        sync_clock(&*self.transport, &self.shared).await
+    }
+
+    /// Host time of a robot clock reading, e.g. a collision timestamp
+    /// `None` until the clock has been synchronized.
+    pub fn to_host_time(&self, robot_ms: u32) -> Option<SystemTime> {
+        self.clock_offset()
+            .map(|offset| offset.to_host_time(robot_ms))
+    }
+
+    /// Offset found by the last clock synchronization
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        *self.shared.clock.lock().unwrap()
+    }
+
+    /// Synchronize the clock every `interval`, starting now
+    /// Replaces any previous re-sync; `None` stops it, as does dropping the device.
+    pub fn set_clock_resync(&self, interval: Option<Duration>) {
+        let mut resync = self.clock_resync.lock().unwrap();
+        if let Some(previous) = resync.take() {
+            previous.abort();
+        }
+        if let Some(interval) = interval {
+            let (handle, registration) = AbortHandle::new_pair();
+            let task = clock_resync_loop(self.transport.clone(), self.shared.clone(), interval);
+            self.spawner
+                .spawn(Abortable::new(task, registration).map(|_| ()).boxed());
+            *resync = Some(handle);
+        }
+    }
+
+    /// Subscribe to asynchronous messages (collisions, power, sensor data, ...)
+    ///
+    /// Every subscriber sees every message. A subscriber more than
+    /// `EVENT_QUEUE_CAPACITY` messages behind loses the oldest ones, see `dropped_events`.
+    /// The stream ends once the link is gone for good or the client is dropped.
+    pub fn events(&self) -> impl Stream<Item = AsyncMessage> {
+        self.shared.events.subscribe()
+    }
+
+    /// Report free falls and impacts as `AsyncMessage::Motion`, or stop with `None`
+    ///
+    /// Runs on streamed frames, which must include `PICKUP_SENSORS` or
+    /// `Sensor::AccelOne`, and follows the streaming rate. To stop the motors
+    /// when the robot is picked up, send `Roll::stop` on either event.
+    pub fn set_pickup_detection(&self, config: Option<PickupConfig>) {
+        let rate = *self.shared.stream_rate.lock().unwrap();
+        // The rate is always positive and finite
+        *self.shared.pickup.lock().unwrap() =
+            config.and_then(|config| PickupDetector::new(config, rate).ok());
+    }
+
+    /// Report tilting out of and back into a pitch/roll envelope as
+    /// `AsyncMessage::Motion`, or stop with `None`
+    /// Runs on streamed frames, which must include `ORIENTATION_SENSORS`.
+    pub fn set_orientation_monitor(&self, config: Option<OrientationConfig>) {
+        let rate = *self.shared.stream_rate.lock().unwrap();
+        // The rate is always positive and finite
+        *self.shared.orientation.lock().unwrap() =
+            config.and_then(|config| OrientationMonitor::new(config, rate).ok());
+    }
+
+    /// Latest streamed attitude, while the orientation monitor is on
+    pub fn attitude(&self) -> Option<Attitude> {
+        self.shared
+            .orientation
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(OrientationMonitor::attitude)
+    }
+
+    /// Start streaming sensor data
+    ///
+    /// Only one stream may be live at a time: while one is, this returns
+    /// `Error::Busy` rather than changing how its frames are decoded. Dropping
+    /// the stream sends the stop command in the background; use
+    /// `SensorStream::stop` to know it went out before starting another.
+    /// With `StreamingConfig::with_watchdog`, stalls are reported as
+    /// `AsyncMessage::StreamStalled` until the stream is stopped.
+    pub async fn start_streaming(&self, config: StreamingConfig) -> Result<SensorStream<T>, Error> {
+        if self.shared.streaming.swap(true, Ordering::AcqRel) {
+            return Err(Error::Busy);
+        }
+
+        // Subscribe first so no frame sent right after the response is missed
+        let frames = self
+            .shared
+            .events
+            .subscribe()
+            .filter_map(|message| async move {
+                match message {
+                    AsyncMessage::SensorData(frames) => Some(frames.into_iter().map(Ok).collect()),
+                    AsyncMessage::Other { idcode: 0x03, .. } => {
+                        Some(vec![Err(Error::BadDataLength)])
+                    }
+                    _ => None,
+                }
+            })
+            .flat_map(stream::iter)
+            .boxed();
+
+        let start = config.build();
+        if let Err(e) = self.send(&start).await {
+            self.shared.streaming.store(false, Ordering::Release);
+            return Err(e);
+        }
+
+        let watchdog = config.watchdog().map(|watchdog| {
+            let (handle, registration) = AbortHandle::new_pair();
+            let task = watchdog_loop(
+                self.transport.clone(),
+                self.shared.clone(),
+                config.clone(),
+                watchdog,
+            );
+            self.spawner
+                .spawn(Abortable::new(task, registration).map(|_| ()).boxed());
+            handle
+        });
+
+        Ok(SensorStream {
+            frames,
+            transport: self.transport.clone(),
+            shared: self.shared.clone(),
+            spawner: self.spawner.clone(),
+            watchdog,
+            stop: Some(SetDataStreaming {
+                mask1: 0,
+                pcnt: 0,
+                mask2: None,
+                ..start
+            }),
+        })
+    }
+
+    /// Upload a macro built with `macros::MacroBuilder` and run it
+    ///
+    /// Aborts the running macro first. With `persist` the macro must be built
+    /// for `MacroTarget::Persistent` with that ID; it is stored with Save
+    /// Macro, so it must fit in one packet. Otherwise a temporary macro that
+    /// fits goes out in one Save Temporary Macro, and a larger one is streamed
+    /// in Append Macro Chunk packets, ending with a stream end. Every step must
+    /// be answered with OK; a chunk that fails is reported as
+    /// `Error::MacroChunk` with its position.
+    ///
+    /// The returned `MacroExecution` follows the macro from the moment it is run.
+    pub async fn run_macro_bytes(
+        &self,
+        bytes: &[u8],
+        persist: Option<u8>,
+    ) -> Result<MacroExecution<'_, T>, Error> {
+        let macro_id = match persist {
+            Some(_) if bytes.len() > MAX_MACRO_LEN => return Err(Error::BadDataLength),
+            Some(id) if bytes.first() != Some(&id) => return Err(Error::BadParameterValue),
+            Some(id) => id,
+            None => TEMPORARY_MACRO_ID,
+        };
+        drop(self.send(&AbortMacro {}).await?);
+        match persist {
+            Some(_) => {
+                let save = SaveMacro {
+                    macro_id,
+                    data: bytes[1..].to_vec(),
+                };
+                drop(self.send(&save).await?);
+            }
+            None if bytes.len() <= MAX_MACRO_LEN => {
+                let save = SaveTemporaryMacro {
+                    data: bytes.to_vec(),
+                };
+                drop(self.send(&save).await?);
+            }
+            None => {
+                let chunks = chunk_macro_bytes(&macros::streamed(bytes));
+                for (index, chunk) in chunks.iter().enumerate() {
+                    if let Err(e) = self.send(chunk).await {
+                        return Err(Error::MacroChunk {
+                            index,
+                            error: Box::new(e),
+                        });
+                    }
+                }
+            }
+        }
+        let execution = MacroExecution {
+            device: self,
+            macro_id,
+            events: AsyncMutex::new(self.events().boxed()),
+            markers: Mutex::new(vec![]),
+            progress: Mutex::new(None),
+            aborted: AtomicBool::new(false),
+        };
+        drop(self.send(&RunMacro { macro_id }).await?);
+        Ok(execution)
+    }
+
+    /// Erase `area` and upload the orbBasic program `source` into it
+    ///
+    /// Line endings become CR and a terminator is added, see
+    /// `orbbasic::program_bytes`. The program goes out in Append orbBasic
+    /// Fragment packets split on line boundaries, each of which must be
+    /// answered with OK; one that fails is reported as
+    /// `Error::OrbBasicFragment` with its position and byte offset.
+    pub async fn load_orbbasic(&self, area: OrbBasicArea, source: &str) -> Result<(), Error> {
+        drop(self.send(&EraseOrbbasicStorage { area }).await?);
+        let program = orbbasic::program_bytes(source);
+        for (index, (offset, fragment)) in orbbasic::fragments(&program).into_iter().enumerate() {
+            let cmd = AppendOrbbasicFragment {
+                area,
+                fragment: fragment.to_vec(),
+            };
+            if let Err(e) = self.send(&cmd).await {
+                return Err(Error::OrbBasicFragment {
+                    index,
+                    offset,
+                    error: Box::new(e),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the orbBasic program in `area` from `start_line`
+    /// The returned `OrbBasicSession` carries what the program prints.
+    pub async fn run_orbbasic(
+        &self,
+        area: OrbBasicArea,
+        start_line: u16,
+    ) -> Result<OrbBasicSession<'_, T>, Error> {
+        let output = self
+            .events()
+            .filter_map(|message| {
+                let output = match message {
+                    AsyncMessage::Other { idcode, data } => async_payload::parse_raw(idcode, &data)
+                        .ok()
+                        .and_then(OrbBasicOutput::from_payload),
+                    _ => None,
+                };
+                future::ready(output)
+            })
+            .boxed();
+        let cmd = ExecuteOrbbasicProgram { area, start_line };
+        drop(self.send(&cmd).await?);
+        Ok(OrbBasicSession {
+            device: self,
+            output,
+            ended: false,
+        })
+    }
+
+    /// Stop the running orbBasic program, if any
+    pub async fn abort_orbbasic(&self) -> Result<(), Error> {
+        self.send(&AbortOrbbasicProgram {}).await.map(drop)
+    }
+
+    /// Write `firmware` as the new main application, see `reflash_with`
+    pub async fn reflash(
+        &self,
+        firmware: &FirmwareImage,
+        progress: impl FnMut(ReflashProgress),
+    ) -> Result<VersioningInfo, Error> {
+        self.reflash_with(firmware, ReflashOptions::default(), progress)
+            .await
+    }
+
+    /// Write `firmware` as the new main application
+    ///
+    /// Jumps to the bootloader and sends Begin Reflash (unless resuming from
+    /// `options.start_page`), then sends every page with Here Is Page. A page
+    /// that fails to program or times out is sent again up to
+    /// `options.page_retries` times. Finally Leave Bootloader starts the new
+    /// application and its version is read back.
+    ///
+    /// Any other failure, such as `MRSPField::LowVoltageError` or
+    /// `MRSPField::IllegalPageError`, stops the reflash with `Error::Reflash`
+    /// naming the page; the robot stays in its bootloader, and calling again
+    /// with that page as `start_page` resumes.
+    pub async fn reflash_with(
+        &self,
+        firmware: &FirmwareImage,
+        options: ReflashOptions,
+        mut progress: impl FnMut(ReflashProgress),
+    ) -> Result<VersioningInfo, Error> {
+        let pages = firmware.page_count();
+        let start_page = options.start_page;
+        if start_page > pages {
+            return Err(Error::BadParameterValue);
+        }
+        let stopped = |page: u16| {
+            move |e: Error| Error::Reflash {
+                page,
+                error: Box::new(e),
+            }
+        };
+        if start_page == 0 {
+            drop(self.send(&JumpToBootloader {}).await.map_err(stopped(0))?);
+            drop(self.send(&BeginReflash {}).await.map_err(stopped(0))?);
+        }
+        progress(ReflashProgress::Started { start_page, pages });
+
+        for page in start_page..pages {
+            if options.check_blank {
+                let blank = self.query(&IsPageBlank { page }).await;
+                if !blank.map_err(stopped(page))?.blank {
+                    progress(ReflashProgress::PageSkipped { page });
+                    continue;
+                }
+            }
+            let cmd = HereIsPage {
+                page,
+                data: firmware.page(page).unwrap_or_default().to_vec(),
+            };
+            let mut attempt = 0;
+            loop {
+                match self.send(&cmd).await {
+                    Ok(_) => break,
+                    Err(Error::ResponseCode(MRSPField::FlashFailError) | Error::Timeout)
+                        if attempt < options.page_retries =>
+                    {
+                        attempt += 1;
+                        progress(ReflashProgress::PageRetry { page, attempt });
+                    }
+                    Err(e) => return Err(stopped(page)(e)),
+                }
+            }
+            progress(ReflashProgress::PageWritten { page, pages });
+        }
+
+        let leave = SendOptions {
+            no_answer: true,
+            ..SendOptions::default()
+        };
+        drop(self.send_with(&LeaveBootloader {}, leave).await?);
+        progress(ReflashProgress::Finished);
+        self.query(&GetVersioning {}).await
+    }
+
+    /// Run `script`, holding each step for its delay
+    ///
+    /// The whole script is checked with `Script::validate` before anything is
+    /// sent. A command that fails stops the script with `Error::ScriptStep`
+    /// naming its top-level step. `cancel_script` stops scripts started before it: the
+    /// robot is brought to rest and the script fails with `Error::Cancelled`.
+    #[cfg(feature = "serde")]
+    pub fn run_script<'a>(
+        &'a self,
+        script: &'a Script,
+    ) -> impl Future<Output = Result<(), Error>> + 'a {
+        let generation = self.script_generation.load(Ordering::Acquire);
+        async move {
+            script.validate()?;
+            let mut heading = 0;
+            let mut ran = Ok(());
+            for (index, step) in script.steps.iter().enumerate() {
+                ran = match self.script_step(step, generation, &mut heading).await {
+                    Err(Error::Cancelled) => Err(Error::Cancelled),
+                    result => result.map_err(|error| Error::ScriptStep {
+                        index,
+                        error: Box::new(error),
+                    }),
+                };
+                if ran.is_err() {
+                    break;
+                }
+            }
+            if let Err(Error::Cancelled) = ran {
+                drop(self.send(&Roll::stop(heading)?).await?);
+            }
+            ran
+        }
+    }
+
+    /// Stop the scripts running now, if any
+    #[cfg(feature = "serde")]
+    pub fn cancel_script(&self) {
+        let _ = self.script_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    #[cfg(feature = "serde")]
+    fn script_cancelled(&self, generation: u64) -> bool {
+        self.script_generation.load(Ordering::Acquire) != generation
+    }
+
+    /// Run `steps` in order, tracking the last heading rolled towards
+    #[cfg(feature = "serde")]
+    fn script_steps<'a>(
+        &'a self,
+        steps: &'a [ScriptStep],
+        generation: u64,
+        heading: &'a mut u16,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        async move {
+            for step in steps {
+                self.script_step(step, generation, heading).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    #[cfg(feature = "serde")]
+    async fn script_step(
+        &self,
+        step: &ScriptStep,
+        generation: u64,
+        heading: &mut u16,
+    ) -> Result<(), Error> {
+        if self.script_cancelled(generation) {
+            return Err(Error::Cancelled);
+        }
+        match step {
+            ScriptStep::SetColor { red, green, blue } => {
+                let color = RgbColor::new(*red, *green, *blue);
+                drop(
+                    self.send(&SetRGBLEDOutput::from_color(color, false))
+                        .await?,
+                );
+            }
+            ScriptStep::Roll {
+                speed,
+                heading: to,
+                duration_ms,
+            } => {
+                *heading = *to;
+                let roll = Roll {
+                    speed: Speed::new(*speed),
+                    heading: Heading::new(*to)?,
+                    state: true,
+                };
+                drop(self.send(&roll).await?);
+                if let Some(ms) = duration_ms {
+                    self.script_wait(Duration::from_millis(*ms), generation)
+                        .await?;
+                    drop(self.send(&Roll::stop(*to)?).await?);
+                }
+            }
+            ScriptStep::Wait { ms } => {
+                self.script_wait(Duration::from_millis(*ms), generation)
+                    .await?
+            }
+            ScriptStep::SetHeading { heading } => {
+                drop(self.send(&SetHeading { heading: *heading }).await?);
+            }
+            ScriptStep::RunMacro { id } => {
+                drop(self.send(&RunMacro { macro_id: *id }).await?);
+            }
+            ScriptStep::Repeat { count, steps } if !steps.is_empty() => {
+                for _ in 0..*count {
+                    self.script_steps(steps, generation, heading).await?;
+                }
+            }
+            ScriptStep::Repeat { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Sleep for `hold`, waking early if the script is cancelled
+    #[cfg(feature = "serde")]
+    async fn script_wait(&self, hold: Duration, generation: u64) -> Result<(), Error> {
+        let until = Instant::now() + hold;
+        while let Some(left) = until.checked_duration_since(Instant::now()) {
+            if left.is_zero() {
+                break;
+            }
+            if self.script_cancelled(generation) {
+                return Err(Error::Cancelled);
+            }
+            runtime::sleep(left.min(SCRIPT_CANCEL_POLL)).await;
+        }
+        Ok(())
+    }
+
+    /// Read the Application Configuration Block
+    /// Decode it with `appdata::AppData::decode`.
+    pub async fn read_app_data(&self) -> Result<[u8; ACB_LEN], Error> {
+        Ok(self.query(&GetApplicationConfigurationBlock {}).await?.data)
+    }
+
+    /// Overwrite the Application Configuration Block
+    pub async fn write_app_data(&self, data: &[u8; ACB_LEN]) -> Result<(), Error> {
+        self.send(&SetApplicationConfigurationBlock { data: *data })
+            .await
+            .map(drop)
+    }
+
+    /// Read the user configuration block
+    ///
+    /// Sends Get Configuration Block and waits up to `DEFAULT_TIMEOUT` for
+    /// the block to arrive as a Config Block Contents message.
+    pub async fn config_block(&self) -> Result<ConfigBlock, Error> {
+        let mut events = self.events().boxed();
+        let cmd = GetConfigurationBlock {
+            block: USER_CONFIG_BLOCK,
+        };
+        drop(self.send(&cmd).await?);
+        let contents = async {
+            while let Some(message) = events.next().await {
+                if let AsyncMessage::Other { idcode: 0x04, data } = message {
+                    return Ok(data);
+                }
+            }
+            Err(Error::Disconnected)
+        };
+        ConfigBlock::parse(&runtime::timeout(DEFAULT_TIMEOUT, contents).await??)
+    }
+
+    /// Write `block` as the user configuration block
+    pub async fn set_config_block(&self, block: &ConfigBlock) -> Result<(), Error> {
+        self.send(&SetConfigurationBlock::from(block))
+            .await
+            .map(drop)
+    }
+
+    /// Orderly teardown: stop streaming, stop rolling, optionally restore
+    /// stabilization and sleep, then close the transport
+    ///
+    /// Every step is attempted even if an earlier one fails. Each failure is
+    /// logged with the step it stopped, and the first is returned.
+    pub async fn shutdown(mut self, options: ShutdownOptions) -> Result<(), Error> {
+        self.shut_down = true;
+        self.set_keepalive(None);
+
+        let mut steps = vec![
+            (
+                "stop streaming",
+                self.send(&StreamingConfig::new().build()).await.map(drop),
+            ),
+            ("stop motors", self.send(&STOP_ROLL).await.map(drop)),
+        ];
+        *self.shared.mask.lock().unwrap() = None;
+        self.shared.streaming.store(false, Ordering::Release);
+        if options.restore_stabilization {
+            let enable = SetStabilization { enabled: true };
+            steps.push(("restore stabilization", self.send(&enable).await.map(drop)));
+        }
+        if options.sleep {
+            steps.push(("sleep", self.send(&Sleep::default()).await.map(drop)));
+        }
+        steps.push(("close transport", self.transport.close().await));
+
+        let mut first = None;
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        for (step, result) in steps {
+            if let Err(error) = result {
+                warn_event!(step, %error, "shutdown step failed");
+                let _ = first.get_or_insert(error);
+            }
+        }
+        first.map_or(Ok(()), Err)
+    }
+
+    /// Number of messages dropped because a subscriber fell behind
+    pub fn dropped_events(&self) -> u64 {
+        self.shared.events.dropped()
+    }
+
+    /// Number of responses received whose sequence number nobody was waiting on
+    /// Same as `stats().unknown_seq_responses()`
+    pub fn unsolicited_responses(&self) -> u64 {
+        self.shared.stats.unknown_seq_responses()
+    }
+
+    /// Link statistics: packets, bytes, failures and round-trip time
+    pub fn stats(&self) -> &Stats {
+        &self.shared.stats
+    }
+
+    /// Brightness of the last Set Back LED Output sent through this device
+    pub fn back_led_brightness(&self) -> u8 {
+        self.back_led.load(Ordering::Relaxed)
+    }
+
+    /// Color of the last Set RGB LED Output sent through this device, if any
+    pub fn led_color(&self) -> Option<RgbColor> {
+        *self.led.lock().unwrap()
+    }
+
+    /// Track state set by outgoing commands
+    /// (the streaming masks decide how async messages are decoded)
+    fn observe(&self, packet: &SpheroCommandPacketV1) {
+        if packet.did() != DeviceID::Sphero {
+            return;
+        }
+        self.shared.session.lock().unwrap().remember(packet);
+        let data = packet.data();
+        if packet.cid() == SpheroCommandID::SetBackLEDOutput as u8 && data.len() == 1 {
+            self.back_led.store(data[0], Ordering::Relaxed);
+        } else if packet.cid() == SpheroCommandID::SetRGBLEDOutput as u8 && data.len() == 4 {
+            *self.led.lock().unwrap() = Some(RgbColor::new(data[0], data[1], data[2]));
+        } else if packet.cid() == SpheroCommandID::SetDataStreaming as u8 {
+            let word =
+                |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+            let mask = match data.len() {
+                9 => Some(SensorMask {
+                    mask1: word(4),
+                    mask2: 0,
+                }),
+                13 => Some(SensorMask {
+                    mask1: word(4),
+                    mask2: word(9),
+                }),
+                _ => None,
+            };
+            *self.shared.mask.lock().unwrap() = mask;
+            let n = data
+                .get(..2)
+                .map_or(0, |n| u16::from_be_bytes([n[0], n[1]]));
+            if n > 0 {
+                let rate = MAX_SAMPLE_RATE_HZ / n as f32;
+                *self.shared.stream_rate.lock().unwrap() = rate;
+                // The rate is positive and finite
+                if let Some(pickup) = self.shared.pickup.lock().unwrap().as_mut() {
+                    let _ = pickup.set_rate_hz(rate);
+                }
+                if let Some(orientation) = self.shared.orientation.lock().unwrap().as_mut() {
+                    let _ = orientation.set_rate_hz(rate);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Transport + 'static> Drop for SpheroDevice<T> {
+    fn drop(&mut self) {
+        if let Some(keepalive) = self.keepalive.lock().unwrap().take() {
+            keepalive.abort();
+        }
+        if let Some(resync) = self.clock_resync.lock().unwrap().take() {
+            resync.abort();
+        }
+        self.reader.abort();
+        // The reader won't get to end the subscriptions itself
+        self.shared.events.close();
+        if self.shut_down {
+            return;
+        }
+
+        // Don't leave the robot rolling, e.g. when the application panicked
+        let packets = [
+            StreamingConfig::new().build().to_packet(NO_ANSWER_SEQ),
+            STOP_ROLL.to_packet(NO_ANSWER_SEQ),
+        ];
+        let transport = self.transport.clone();
+        let shared = self.shared.clone();
+        self.spawner.spawn(
+            async move {
+                for packet in packets {
+                    if let Ok(bytes) = packet.with_response_required(false).to_bytes() {
+                        drop(write(&*transport, &shared, &bytes).await);
+                    }
+                }
+            }
+            .boxed(),
+        );
+    }
+}
+
+/// Command built from raw IDs, see `SpheroDevice::send_raw`
+struct RawCommand {
+    did: DeviceID,
+    cid: u8,
+    data: Vec<u8>,
+}
+
+impl ToCommandPacket for RawCommand {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        SpheroCommandPacketV1::new(self.did, self.cid, seq, self.data.clone())
+    }
+}
+
+const STOP_ROLL: Roll = Roll::stop_at_zero();
+
+/// Sphero Sensor Stream
+/// Decoded frames from `SpheroDevice::start_streaming`; streaming stops when dropped
+pub struct SensorStream<T: Transport + 'static> {
+    frames: BoxStream<'static, Result<SensorFrame, Error>>,
+    transport: Arc<T>,
+    shared: Arc<Shared>,
+    spawner: Arc<dyn Spawner>,
+    watchdog: Option<AbortHandle>,
+    stop: Option<SetDataStreaming>,
+}
+
+impl<T: Transport + 'static> SensorStream<T> {
+    /// Stop streaming, waiting for the stop command to be written
+    pub async fn stop(mut self) -> Result<(), Error> {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        match self.stop.take() {
+            Some(stop) => stop_streaming(&*self.transport, &self.shared, &stop).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Transport + 'static> Stream for SensorStream<T> {
+    type Item = Result<SensorFrame, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.frames.poll_next_unpin(cx)
+    }
+}
+
+impl<T: Transport + 'static> Drop for SensorStream<T> {
+    fn drop(&mut self) {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        if let Some(stop) = self.stop.take() {
+            let transport = self.transport.clone();
+            let shared = self.shared.clone();
+            self.spawner.spawn(
+                async move {
+                    drop(stop_streaming(&*transport, &shared, &stop).await);
+                }
+                .boxed(),
+            );
+        }
+    }
+}
+
+async fn stop_streaming(
+    transport: &impl Transport,
+    shared: &Shared,
+    stop: &SetDataStreaming,
+) -> Result<(), Error> {
+    let packet = stop.to_packet(NO_ANSWER_SEQ).with_response_required(false);
+    shared.session.lock().unwrap().remember(&packet);
+    let result = match packet.to_bytes() {
+        Ok(bytes) => write(transport, shared, &bytes).await,
+        Err(e) => Err(e.into()),
+    };
+    *shared.mask.lock().unwrap() = None;
+    shared.streaming.store(false, Ordering::Release);
+    result
+}
+
+/// How often a wait on a macro checks whether it was aborted
+const MACRO_CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// How long `MacroExecution::await_completion` waits for a marker before
+/// asking for the macro status
+const MACRO_STATUS_POLL: Duration = Duration::from_millis(100);
+
+/// Sphero Macro Execution
+/// A macro started by `SpheroDevice::run_macro_bytes`
+///
+/// Follows the macro through the markers it emits and Get Macro Status.
+/// Waits may run concurrently with each other and with `abort`.
+pub struct MacroExecution<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    macro_id: u8,
+    events: AsyncMutex<BoxStream<'static, AsyncMessage>>,
+    markers: Mutex<Vec<MacroMarkerPayload>>,
+    progress: Mutex<Option<u16>>,
+    aborted: AtomicBool,
+}
+
+impl<T: Transport + 'static> MacroExecution<'_, T> {
+    /// ID the macro runs under
+    pub fn macro_id(&self) -> u8 {
+        self.macro_id
+    }
+
+    /// Number of the last command the macro was seen on, from its markers
+    /// and from Get Macro Status
+    pub fn progress(&self) -> Option<u16> {
+        *self.progress.lock().unwrap()
+    }
+
+    /// Wait for the macro to emit marker `marker`
+    ///
+    /// Returns at once if it was already seen, or with `Error::Cancelled`
+    /// once the macro is aborted through `abort`.
+    pub async fn await_marker(&self, marker: u8) -> Result<MacroMarkerPayload, Error> {
+        loop {
+            let seen = self
+                .markers
+                .lock()
+                .unwrap()
+                .iter()
+                .copied()
+                .find(|m| m.marker == marker);
+            if let Some(seen) = seen {
+                return Ok(seen);
+            }
+            let _ = self.pump(MACRO_CANCEL_POLL).await?;
+        }
+    }
+
+    /// Wait for the macro to finish
+    ///
+    /// Done once no marker arrived for a while and Get Macro Status reports
+    /// no macro running. Fails with `Error::Cancelled` once the macro is
+    /// aborted through `abort`.
+    pub async fn await_completion(&self) -> Result<(), Error> {
+        loop {
+            if self.pump(MACRO_STATUS_POLL).await? {
+                continue;
+            }
+            let status = self.device.query(&GetMacroStatus {}).await?;
+            if self.aborted.load(Ordering::Acquire) {
+                return Err(Error::Cancelled);
+            }
+            if status.macro_id == 0 {
+                return Ok(());
+            }
+            *self.progress.lock().unwrap() = Some(status.command);
+        }
+    }
+
+    /// Send Abort Macro; pending and later waits fail with `Error::Cancelled`
+    pub async fn abort(&self) -> Result<(), Error> {
+        self.aborted.store(true, Ordering::Release);
+        self.device.send(&AbortMacro {}).await.map(drop)
+    }
+
+    /// Read async messages for up to `duration`, returning whether a marker
+    /// of this macro arrived
+    async fn pump(&self, duration: Duration) -> Result<bool, Error> {
+        let deadline = Instant::now() + duration;
+        let mut events = self.events.lock().await;
+        loop {
+            if self.aborted.load(Ordering::Acquire) {
+                return Err(Error::Cancelled);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(false);
+            }
+            let message = match runtime::timeout(left.min(MACRO_CANCEL_POLL), events.next()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Err(Error::Disconnected),
+                Err(_) => continue,
+            };
+            let AsyncMessage::Other { idcode, data } = message else {
+                continue;
+            };
+            if let Ok(AsyncPayload::MacroMarker(marker)) = async_payload::parse_raw(idcode, &data) {
+                if marker.macro_id == self.macro_id {
+                    *self.progress.lock().unwrap() = Some(marker.command);
+                    self.markers.lock().unwrap().push(marker);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+/// Sphero orbBasic Session
+/// Output of a program started by `SpheroDevice::run_orbbasic`
+///
+/// A stream of the lines the program prints, ending after the first error
+/// or once the program is aborted through `abort`.
+pub struct OrbBasicSession<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    output: BoxStream<'static, OrbBasicOutput>,
+    ended: bool,
+}
+
+impl<T: Transport + 'static> OrbBasicSession<'_, T> {
+    /// Send Abort orbBasic Program and end the stream
+    pub async fn abort(&mut self) -> Result<(), Error> {
+        self.ended = true;
+        self.device.abort_orbbasic().await
+    }
+}
+
+impl<T: Transport + 'static> Stream for OrbBasicSession<'_, T> {
+    type Item = OrbBasicOutput;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+        let next = self.output.poll_next_unpin(cx);
+        if let Poll::Ready(output) = &next {
+            self.ended = output.as_ref().is_none_or(OrbBasicOutput::is_error);
+        }
+        next
+    }
+}
+
+/// Wait for the rate limiter to hand out a slot
+async fn pace(shared: &Shared, ticket: Option<Ticket>) -> Result<(), Error> {
+    let mut delayed = false;
+    loop {
+        let decision = shared
+            .limiter
+            .lock()
+            .unwrap()
+            .acquire(ticket, delayed, Instant::now());
+        match decision {
+            Pace::Send => return Ok(()),
+            Pace::Wait(duration) => {
+                delayed = true;
+                runtime::sleep(duration).await;
+            }
+            Pace::Coalesced => {
+                debug_event!("command superseded before it was sent");
+                return Err(Error::Superseded);
+            }
+        }
+    }
+}
+
+/// Wait until the link is up
+async fn connected(shared: &Shared) -> Result<(), Error> {
+    let waiter = shared.link.lock().unwrap().waiter()?;
+    match waiter {
+        Some(rx) => rx.await.map_err(|_| Error::Disconnected),
+        None => Ok(()),
+    }
+}
+
+/// Send `cmd` once with a fresh sequence number and wait up to `timeout` for its response
+/// Waiting for the link or a rate limiter slot doesn't count towards `timeout`.
+async fn send_once(
+    transport: &impl Transport,
+    shared: &Shared,
+    cmd: &impl ToCommandPacket,
+    ticket: Option<Ticket>,
+    timeout: Duration,
+) -> Result<SpheroResponsePacketV1, Error> {
+    connected(shared).await?;
+    pace(shared, ticket).await?;
+    exchange(transport, shared, cmd, None, timeout).await
+}
+
+/// Send `cmd` with sequence number `seq`, or a fresh one, whatever the link state
+async fn exchange(
+    transport: &impl Transport,
+    shared: &Shared,
+    cmd: &impl ToCommandPacket,
+    seq: Option<u8>,
+    timeout: Duration,
+) -> Result<SpheroResponsePacketV1, Error> {
+    let seq = {
+        let mut allocator = shared.seq.lock().unwrap();
+        match seq {
+            Some(seq) => allocator.reserve(seq).map(|()| seq),
+            None => allocator.allocate(),
+        }
+    }?;
+    let _guard = SeqGuard {
+        seq: &shared.seq,
+        value: seq,
+    };
+    let packet = cmd.to_packet(seq);
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("command", seq, did = ?packet.did(), cid = packet.cid());
+    let exchange = runtime::timeout(timeout, send_packet(transport, shared, packet));
+    #[cfg(feature = "tracing")]
+    let exchange = tracing::Instrument::instrument(exchange, span);
+    let result = exchange.await;
+    if let Err(Error::Timeout) = result {
+        warn_event!(seq, "command timed out");
+        shared.stats.record_timeout();
+    }
+    result?
+}
+
+async fn send_packet(
+    transport: &impl Transport,
+    shared: &Shared,
+    packet: SpheroCommandPacketV1,
+) -> Result<SpheroResponsePacketV1, Error> {
+    let bytes = packet.to_bytes()?;
+    let (tx, rx) = oneshot::channel();
+    let _guard = PendingGuard::register(&shared.pending, packet.seq(), tx);
+
+    write(transport, shared, &bytes).await?;
+    let sent = Instant::now();
+    let response = rx.await.map_err(|_| Error::Disconnected)?;
+    shared.stats.record_round_trip(sent.elapsed());
+    match response.mrsp() {
+        MRSPField::Ok => Ok(response),
+        mrsp => Err(Error::ResponseCode(mrsp)),
+    }
+}
+
+/// Write packet bytes to the transport, counting them in the stats
+/// Every write, answered or not, postpones the next keep-alive ping.
+async fn write(transport: &impl Transport, shared: &Shared, bytes: &[u8]) -> Result<(), Error> {
+    debug_event!(bytes = %crate::trace::hex(bytes), "sent");
+    transport.write(bytes).await?;
+    *shared.last_sent.lock().unwrap() = Instant::now();
+    shared.stats.record_sent(bytes.len());
+    Ok(())
+}
+
+async fn keepalive_loop(transport: Arc<impl Transport>, shared: Arc<Shared>, interval: Duration) {
+    loop {
+        let idle = shared.last_sent.lock().unwrap().elapsed();
+        if idle < interval {
+            runtime::sleep(interval - idle).await;
+            continue;
+        }
+        // A failed ping is not fatal, the next idle gap tries again
+        drop(send_once(&*transport, &shared, &Ping {}, None, DEFAULT_TIMEOUT).await);
+        // Don't spin when the link is down and writes fail immediately
+        *shared.last_sent.lock().unwrap() = Instant::now();
+    }
+}
+
+/// Report stalls of the stream started with `config`, re-sending it as allowed
+async fn watchdog_loop(
+    transport: Arc<impl Transport>,
+    shared: Arc<Shared>,
+    config: StreamingConfig,
+    watchdog: WatchdogConfig,
+) {
+    let mut events = shared.events.subscribe();
+    let mut dog = StreamWatchdog::new(watchdog, config.packet_interval(), Instant::now());
+    loop {
+        let wait = dog.deadline().saturating_duration_since(Instant::now());
+        match select(events.next(), Box::pin(runtime::sleep(wait))).await {
+            Either::Left((None, _)) => return,
+            Either::Left((Some(message), _)) => match message {
+                AsyncMessage::SensorData(_) | AsyncMessage::Other { idcode: 0x03, .. } => {
+                    dog.packet(Instant::now())
+                }
+                _ => {}
+            },
+            Either::Right(_) => {
+                let Some(stall) = dog.check(Instant::now()) else {
+                    continue;
+                };
+                warn_event!(attempt = stall.attempt, "sensor stream stalled");
+                shared.events.send(AsyncMessage::StreamStalled(stall));
+                if stall.resend {
+                    let start = config.build();
+                    drop(send_once(&*transport, &shared, &start, None, DEFAULT_TIMEOUT).await);
+                }
+            }
+        }
+    }
+}
+
+/// Query the firmware version and cache the capabilities it implies
+async fn fetch_capabilities(
+    transport: Arc<impl Transport>,
+    shared: Arc<Shared>,
+) -> Result<Capabilities, Error> {
+    let response = send_once(
+        &*transport,
+        &shared,
+        &GetVersioning {},
+        None,
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+    let capabilities = Capabilities::from_version(&FromResponsePacket::from_response(&response)?);
+    debug_event!(firmware = ?capabilities.firmware(), "capabilities");
+    *shared.capabilities.lock().unwrap() = Some(capabilities);
+    Ok(capabilities)
+}
+
+async fn sync_clock(transport: &impl Transport, shared: &Shared) -> Result<ClockOffset, Error> {
+    let assign = AssignTimeValue {
+        time: shared.client_ms(),
+    };
+    drop(send_once(transport, shared, &assign, None, DEFAULT_TIMEOUT).await?);
+
+    let mut samples = Vec::with_capacity(CLOCK_SYNC_ROUNDS);
+    for _ in 0..CLOCK_SYNC_ROUNDS {
+        let poll = PollPacketTimes {
+            time: shared.client_ms(),
+        };
+        let response = send_once(transport, shared, &poll, None, DEFAULT_TIMEOUT).await?;
+        let client_rx = shared.client_ms();
+        samples.push(ClockSample::new(
+            PacketTimes::from_response(&response)?,
+            client_rx,
+        ));
+    }
+
+    let best = clock::best_sample(&samples).ok_or(Error::InvalidPacket)?;
+    let offset = ClockOffset::from_sample(shared.epoch.1, best);
+    debug_event!(
+        offset_ms = offset.offset_ms,
+        delay_ms = offset.delay_ms,
+        "clock synchronized"
+    );
+    *shared.clock.lock().unwrap() = Some(offset);
+    Ok(offset)
+}
+
+async fn clock_resync_loop(
+    transport: Arc<impl Transport>,
+    shared: Arc<Shared>,
+    interval: Duration,
+) {
+    loop {
+        // A failed sync keeps the previous offset, the next round tries again
+        drop(sync_clock(&*transport, &shared).await);
+        runtime::sleep(interval).await;
+    }
+}
+
+/// Releases an allocated sequence number once its command is done with it
+struct SeqGuard<'a> {
+    seq: &'a Mutex<SeqAllocator>,
+    value: u8,
+}
+
+impl Drop for SeqGuard<'_> {
+    fn drop(&mut self) {
+        self.seq.lock().unwrap().release(self.value);
+    }
+}
+
+/// Removes a pending entry when the sender goes away, including on cancellation
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    seq: u8,
+}
+
+impl<'a> PendingGuard<'a> {
+    fn register(
+        pending: &'a Pending,
+        seq: u8,
+        tx: oneshot::Sender<SpheroResponsePacketV1>,
+    ) -> Self {
+        drop(pending.lock().unwrap().insert(seq, tx));
+        Self { pending, seq }
+    }
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        drop(self.pending.lock().unwrap().remove(&self.seq));
+    }
+}
+
+/// Read from the link until it drops for good
+async fn connection_loop(
+    transport: Arc<impl Transport>,
+    shared: Arc<Shared>,
+    inbound: BoxStream<'static, Vec<u8>>,
+) {
+    let mut reading = read_loop(inbound, shared.clone()).boxed();
+    loop {
+        reading.await;
+        // Nothing in flight will be answered now, wake its waiters with an error
+        shared.pending.lock().unwrap().clear();
+        let Some(policy) = *shared.reconnect.lock().unwrap() else {
+            break;
+        };
+        warn_event!("link dropped, reconnecting");
+        shared.link.lock().unwrap().set(LinkState::Reconnecting);
+        reading = match reconnect(&*transport, &shared, policy).await {
+            Some(reading) => reading,
+            None => break,
+        };
+        shared.link.lock().unwrap().set(LinkState::Connected);
+    }
+    shared.link.lock().unwrap().set(LinkState::Down);
+    shared.events.close();
+}
+
+/// Reconnect with backoff and restore the session
+/// Returns the reader for the new link, or `None` once every attempt failed.
+async fn reconnect(
+    transport: &impl Transport,
+    shared: &Arc<Shared>,
+    policy: ReconnectPolicy,
+) -> Option<BoxFuture<'static, ()>> {
+    for attempt in 0..policy.max_attempts {
+        runtime::sleep(policy.backoff(attempt)).await;
+        let inbound = match transport.reconnect().await {
+            Ok(()) => transport.subscribe().await,
+            Err(e) => Err(e),
+        };
+        let Ok(inbound) = inbound else {
+            continue;
+        };
+        // Keep reading while restoring, the replayed commands need their answers
+        let mut reading = read_loop(inbound, shared.clone()).boxed();
+        match select(&mut reading, restore(transport, shared).boxed()).await {
+            Either::Right((Ok(()), _)) => return Some(reading),
+            Either::Right((Err(_), _)) | Either::Left(_) => {
+                shared.pending.lock().unwrap().clear();
+            }
+        }
+    }
+    warn_event!("giving up reconnecting");
+    None
+}
+
+/// Wake the robot and re-apply the remembered session state
+/// Only the wake-up ping has to succeed.
+async fn restore(transport: &impl Transport, shared: &Shared) -> Result<(), Error> {
+    drop(exchange(transport, shared, &Ping {}, None, DEFAULT_TIMEOUT).await?);
+    let commands = shared.session.lock().unwrap().commands();
+    for command in commands {
+        if exchange(transport, shared, &command, None, DEFAULT_TIMEOUT)
+            .await
+            .is_err()
+        {
+            warn_event!("failed to restore session state");
+        }
+    }
+    Ok(())
+}
+
+/// Run the enabled motion detectors over streamed `frames`
+fn detect_motion(shared: &Shared, frames: &[SensorFrame]) -> Vec<MotionEvent> {
+    let mut pickup = shared.pickup.lock().unwrap();
+    let mut orientation = shared.orientation.lock().unwrap();
+    let mut events = vec![];
+    for frame in frames {
+        events.extend(pickup.as_mut().and_then(|pickup| pickup.push(frame)));
+        events.extend(
+            orientation
+                .as_mut()
+                .and_then(|orientation| orientation.push(frame)),
+        );
+    }
+    events
+}
+
+async fn read_loop(mut inbound: BoxStream<'static, Vec<u8>>, shared: Arc<Shared>) {
+    let mut reader = PacketReader::new();
+    while let Some(chunk) = inbound.next().await {
+        debug_event!(bytes = %crate::trace::hex(&chunk), "received");
+        shared.stats.record_bytes_received(chunk.len());
+        reader.push(&chunk);
+        while let Some(event) = reader.next_event() {
+            match event {
+                Ok(SpheroEvent::Response(response)) => {
+                    shared.stats.record_response();
+                    let waiter = shared.pending.lock().unwrap().remove(&response.seq());
+                    match waiter {
+                        Some(tx) => drop(tx.send(response)),
+                        // Late, duplicate or unsolicited: nobody is waiting on this seq
+                        None => {
+                            warn_event!(seq = response.seq(), "response with unknown seq");
+                            shared.stats.record_unknown_seq();
+                        }
+                    }
+                }
+                Ok(SpheroEvent::Async(packet)) => {
+                    shared.stats.record_async(packet.idcode());
+                    let mask = *shared.mask.lock().unwrap();
+                    let message = AsyncMessage::decode(&packet, mask.as_ref());
+                    let motion = match &message {
+                        AsyncMessage::SensorData(frames) => detect_motion(&shared, frames),
+                        _ => vec![],
+                    };
+                    shared.events.send(message);
+                    for event in motion {
+                        shared.events.send(AsyncMessage::Motion(event));
+                    }
+                }
+                Err(Error::ChecksumMismatch { .. }) => {
+                    warn_event!("dropped packet with a bad checksum");
+                    shared.stats.record_checksum_failure();
+                }
+                #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+                Err(error) => {
+                    warn_event!(%error, "dropped malformed packet");
+                    shared.stats.record_malformed();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::appdata::AppData;
+    use crate::command::{
+        GetBluetoothInfo, GetPowerState, ReadLocator, SetBackLEDOutput, MACRO_CHUNK_SIZE,
+    };
+    use crate::macros::{MacroBuilder, MacroFlags, MacroTarget};
+    use crate::orbbasic::OrbBasicError;
+    use crate::packet::{CoreCommandID, SpheroAsynchronousPacketV1};
+    use crate::packet::{SOP2Field, SpheroCommandID};
+    use crate::power::PowerState;
+    use crate::sensor::Sensor;
+    use crate::transport::mock::{ack, respond, MockTransport};
+    use deku::DekuContainerRead;
+
+    /// Get Versioning answer for main application firmware 3.59
+    const FIRMWARE: [u8; 10] = [0x02, 0x03, 0x01, 0x03, 0x3b, 0x41, 0x21, 0x04, 0x01, 0x14];
+
+    /// Commands the robot leaves unanswered until the test replies
+    type Held = Arc<Mutex<Vec<SpheroCommandPacketV1>>>;
+
+    fn is_probe(packet: &SpheroCommandPacketV1) -> bool {
+        packet.did() == DeviceID::Core
+            && packet.cid() == CoreCommandID::GetVersioningInformation as u8
+    }
+
+    /// Mock robot answering the firmware probe sent on connect, and every
+    /// other command with `answer`
+    fn robot<F>(mut answer: F) -> MockTransport
+    where
+        F: FnMut(SpheroCommandPacketV1) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        MockTransport::with_responder(move |bytes| {
+            let (_, packet) = SpheroCommandPacketV1::from_bytes((bytes, 0)).unwrap();
+            match is_probe(&packet) {
+                true => vec![respond(&packet, MRSPField::Ok, FIRMWARE.to_vec())],
+                false => answer(packet),
+            }
+        })
+    }
+
+    /// Mock robot that holds on to every command instead of answering
+    fn holding() -> (MockTransport, Held) {
+        let held = Held::default();
+        let commands = held.clone();
+        let mock = robot(move |packet| {
+            commands.lock().unwrap().push(packet);
+            vec![]
+        });
+        (mock, held)
+    }
+
+    /// Wait until `count` commands are held, and take them
+    async fn held(held: &Held, count: usize) -> Vec<SpheroCommandPacketV1> {
+        loop {
+            {
+                let mut commands = held.lock().unwrap();
+                if commands.len() >= count {
+                    return std::mem::take(&mut *commands);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    /// Connect over `mock` once the firmware probe is answered, with the
+    /// probe cleared from the writes and the stats
+    async fn connect(mock: &MockTransport) -> SpheroDevice<MockTransport> {
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        while device.shared.capabilities.lock().unwrap().is_none() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        device.stats().reset();
+        mock.clear_written();
+        device
+    }
+
+    fn power_notification(state: PowerState) -> Vec<u8> {
+        SpheroAsynchronousPacketV1::new(0x01, vec![state.into()])
+            .to_bytes()
+            .unwrap()
+    }
+
+    fn find(commands: &[SpheroCommandPacketV1], cid: CoreCommandID) -> &SpheroCommandPacketV1 {
+        commands.iter().find(|p| p.cid() == cid as u8).unwrap()
+    }
+
+    #[tokio::test]
+    async fn responses_out_of_order_reach_their_commands() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+        let mut events = device.events().boxed();
+
+        let robot = async {
+            let commands = held(&commands, 2).await;
+            let ping = find(&commands, CoreCommandID::Ping);
+            let power = find(&commands, CoreCommandID::GetPowerState);
+            assert_ne!(ping.seq(), power.seq());
+            // Answer the later command first, with an async message in between
+            mock.inject(respond(power, MRSPField::Ok, vec![0xbb]));
+            mock.inject(power_notification(PowerState::Low));
+            mock.inject(respond(ping, MRSPField::Ok, vec![0xaa]));
+        };
+        let (ping, power, ()) =
+            tokio::join!(device.send(&Ping {}), device.send(&GetPowerState {}), robot);
+        assert_eq!(ping.unwrap().data(), [0xaa]);
+        assert_eq!(power.unwrap().data(), [0xbb]);
+        assert_eq!(
+            events.next().await,
+            Some(AsyncMessage::PowerNotification(PowerState::Low))
+        );
+        assert_eq!(device.unsolicited_responses(), 0);
+    }
+
+    #[tokio::test]
+    async fn async_packet_in_the_same_chunk_as_a_response() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+        let mut events = device.events().boxed();
+
+        let robot = async {
+            let commands = held(&commands, 1).await;
+            let mut chunk = power_notification(PowerState::Charging);
+            chunk.extend(respond(&commands[0], MRSPField::Ok, vec![]));
+            mock.inject(chunk);
+        };
+        let (ping, ()) = tokio::join!(device.send(&Ping {}), robot);
+        assert!(ping.unwrap().data().is_empty());
+        assert_eq!(
+            events.next().await,
+            Some(AsyncMessage::PowerNotification(PowerState::Charging))
+        );
+        assert_eq!(device.stats().async_messages(0x01), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_seq_is_counted_not_fatal() {
+        let mock = robot(|packet| vec![respond(&packet, MRSPField::Ok, vec![])]);
+        let device = connect(&mock).await;
+        let stray = Ping {}.to_packet(0xc8);
+        mock.inject(respond(&stray, MRSPField::Ok, vec![]));
+        assert!(device.send(&Ping {}).await.is_ok());
+        assert_eq!(device.unsolicited_responses(), 1);
+    }
+
+    /// Mock robot with canned answers to the queries below
+    fn queried() -> MockTransport {
+        robot(|packet| {
+            let data = match (packet.did(), packet.cid()) {
+                (DeviceID::Core, cid) if cid == CoreCommandID::GetPowerState as u8 => {
+                    vec![0x01, 0x02, 0x02, 0xe6, 0x00, 0x2a, 0x01, 0x2c]
+                }
+                (DeviceID::Core, cid) if cid == CoreCommandID::GetBluetoothInfo as u8 => {
+                    let mut data = b"SK-9ABC\0\0\0\0\0\0\0\0\0".to_vec();
+                    data.extend(b"68864e0d5f32\0");
+                    data.extend([0x01, 0x02, 0x03]);
+                    data
+                }
+                (DeviceID::Sphero, cid) if cid == SpheroCommandID::ReadLocator as u8 => {
+                    vec![0x00, 0x64, 0xff, 0x9c, 0x01, 0xf4, 0xfe, 0x0c, 0x02, 0x9e]
+                }
+                _ => vec![],
+            };
+            vec![respond(&packet, MRSPField::Ok, data)]
+        })
+    }
+
+    #[tokio::test]
+    async fn query_decodes_typed_responses() {
+        let mock = queried();
+        let device = connect(&mock).await;
+
+        let version = device.query(&GetVersioning {}).await.unwrap();
+        assert_eq!(version.mdl, 0x03);
+        assert_eq!(version.firmware_version(), (0x01, 0x14, 0));
+
+        let power = device.query(&GetPowerState {}).await.unwrap();
+        assert_eq!(power.state, PowerState::Ok);
+        assert_eq!(power.voltage.hundredths(), 742);
+        assert_eq!(power.num_charges, 42);
+        assert_eq!(power.time_since_charge, 300);
+
+        let bluetooth = device.query(&GetBluetoothInfo {}).await.unwrap();
+        assert_eq!(bluetooth.name, "SK-9ABC");
+        assert_eq!(bluetooth.address, "68864e0d5f32");
+        assert_eq!(bluetooth.colors, [0x01, 0x02, 0x03]);
+
+        let locator = device.query(&ReadLocator {}).await.unwrap();
+        assert_eq!((locator.x, locator.y), (100, -100));
+        assert_eq!((locator.vx, locator.vy), (500, -500));
+        assert_eq!(locator.sog, 670);
+    }
+
+    #[tokio::test]
+    async fn short_query_response_is_an_error() {
+        let mock = robot(|packet| vec![respond(&packet, MRSPField::Ok, vec![0x01, 0x02])]);
+        let device = connect(&mock).await;
+
+        let power = device.query(&GetPowerState {}).await;
+        assert!(matches!(power, Err(Error::BadDataLength)));
+        let locator = device.query(&ReadLocator {}).await;
+        assert!(matches!(locator, Err(Error::BadDataLength)));
+    }
+
+    fn impatient(retries: u8) -> SendOptions {
+        SendOptions {
+            timeout: Duration::from_millis(50),
+            retries,
+            no_answer: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_response_times_out() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+
+        let result = device.send_with(&Ping {}, impatient(0)).await;
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(held(&commands, 1).await.len(), 1);
+        assert_eq!(device.stats().timeouts(), 1);
+        assert_eq!(device.stats().retries(), 0);
+    }
+
+    #[tokio::test]
+    async fn retry_after_a_dropped_response_uses_a_fresh_seq() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+
+        let robot = async {
+            let dropped = held(&commands, 1).await.remove(0);
+            let retry = held(&commands, 1).await.remove(0);
+            assert_ne!(dropped.seq(), retry.seq());
+            // The answer to the first attempt turns up after all, then the retry's
+            mock.inject(respond(&dropped, MRSPField::Ok, vec![0xaa]));
+            mock.inject(respond(&retry, MRSPField::Ok, vec![0xbb]));
+        };
+        let (result, ()) = tokio::join!(device.send_with(&Ping {}, impatient(1)), robot);
+        assert_eq!(result.unwrap().unwrap().data(), [0xbb]);
+        assert_eq!(device.stats().timeouts(), 1);
+        assert_eq!(device.stats().retries(), 1);
+        assert_eq!(device.unsolicited_responses(), 1);
+    }
+
+    fn pings(mock: &MockTransport) -> usize {
+        commands(mock)
+            .iter()
+            .filter(|&&command| command == (DeviceID::Core, CoreCommandID::Ping as u8))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn keepalive_pings_an_idle_robot() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        device.set_keepalive(Some(Duration::from_millis(20)));
+
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        assert!(pings(&mock) >= 2);
+        assert_eq!(pings(&mock), commands(&mock).len());
+    }
+
+    #[tokio::test]
+    async fn keepalive_leaves_a_busy_link_alone() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        device.set_keepalive(Some(Duration::from_millis(100)));
+
+        // Unanswered Rolls keep the link busy just as well as answered commands
+        let roll = Roll::stop_at_zero();
+        let no_answer = SendOptions {
+            no_answer: true,
+            ..SendOptions::default()
+        };
+        for _ in 0..30 {
+            assert!(device.send_with(&roll, no_answer).await.unwrap().is_none());
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(commands(&mock).len(), 30);
+        assert_eq!(pings(&mock), 0);
+    }
+
+    #[tokio::test]
+    async fn keepalive_stops_when_unset() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        device.set_keepalive(Some(Duration::from_millis(10)));
+        while pings(&mock) == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        device.set_keepalive(None);
+        mock.clear_written();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(pings(&mock), 0);
+    }
+
+    fn accelerometer() -> StreamingConfig {
+        StreamingConfig::new().with_sensors(&[Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ])
+    }
+
+    /// Set Data Streaming commands written, by mask
+    fn streaming_masks(mock: &MockTransport) -> Vec<(u32, SOP2Field)> {
+        mock.written_packets()
+            .iter()
+            .filter(|p| {
+                p.did() == DeviceID::Sphero && p.cid() == SpheroCommandID::SetDataStreaming as u8
+            })
+            .map(|p| {
+                let mask1 = u32::from_be_bytes(p.data()[4..8].try_into().unwrap());
+                (mask1, p.sop2())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn streamed_frames_reach_the_sensor_stream() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let mut stream = device.start_streaming(accelerometer()).await.unwrap();
+
+        let frames = [1i16, 2, 3, -4, -5, -6]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect();
+        mock.inject(
+            SpheroAsynchronousPacketV1::new(0x03, frames)
+                .to_bytes()
+                .unwrap(),
+        );
+        for expected in [[1, 2, 3], [-4, -5, -6]] {
+            let frame = stream.next().await.unwrap().unwrap();
+            let values: Vec<_> = frame.values.iter().map(|&(_, value)| value).collect();
+            assert_eq!(values, expected);
+        }
+
+        // A packet that isn't a whole number of frames is reported, not skipped
+        mock.inject(
+            SpheroAsynchronousPacketV1::new(0x03, vec![0; 5])
+                .to_bytes()
+                .unwrap(),
+        );
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(Error::BadDataLength))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stop_sends_the_stop_command() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let stream = device.start_streaming(accelerometer()).await.unwrap();
+        let start = accelerometer().mask().mask1;
+        assert_eq!(streaming_masks(&mock), [(start, SOP2Field::Response)]);
+
+        stream.stop().await.unwrap();
+        assert_eq!(
+            streaming_masks(&mock),
+            [(start, SOP2Field::Response), (0, SOP2Field::NoResponse)]
+        );
+        // Stopped, so another stream may start
+        assert!(device.start_streaming(accelerometer()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_stream_sends_the_stop_command() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        drop(device.start_streaming(accelerometer()).await.unwrap());
+
+        while streaming_masks(&mock).len() < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(streaming_masks(&mock)[1], (0, SOP2Field::NoResponse));
+    }
+
+    #[tokio::test]
+    async fn reconfiguring_a_live_stream_is_busy() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let _stream = device.start_streaming(accelerometer()).await.unwrap();
+        mock.clear_written();
+
+        let other = StreamingConfig::new().with_sensors(&[Sensor::ImuPitch]);
+        assert!(matches!(
+            device.start_streaming(other).await,
+            Err(Error::Busy)
+        ));
+        assert!(mock.written().is_empty());
+    }
+
+    /// Device and command IDs of everything written
+    fn commands(mock: &MockTransport) -> Vec<(DeviceID, u8)> {
+        mock.written_packets()
+            .iter()
+            .map(|p| (p.did(), p.cid()))
+            .collect()
+    }
+
+    const SHUTDOWN_ORDER: [(DeviceID, u8); 4] = [
+        (DeviceID::Sphero, SpheroCommandID::SetDataStreaming as u8),
+        (DeviceID::Sphero, SpheroCommandID::Roll as u8),
+        (DeviceID::Sphero, SpheroCommandID::SetStabilization as u8),
+        (DeviceID::Core, CoreCommandID::Sleep as u8),
+    ];
+
+    const EVERY_STEP: ShutdownOptions = ShutdownOptions {
+        restore_stabilization: true,
+        sleep: true,
+    };
+
+    #[tokio::test]
+    async fn shutdown_runs_every_step_in_order() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+
+        device.shutdown(EVERY_STEP).await.unwrap();
+        assert_eq!(commands(&mock), SHUTDOWN_ORDER);
+        assert!(mock.is_closed());
+    }
+
+    #[tokio::test]
+    async fn failed_shutdown_step_does_not_skip_the_rest() {
+        let mock = robot(|packet| match packet.cid() == SpheroCommandID::Roll as u8 {
+            true => vec![respond(&packet, MRSPField::GeneralError, vec![])],
+            false => vec![ack(&packet)],
+        });
+        let device = connect(&mock).await;
+
+        let result = device.shutdown(EVERY_STEP).await;
+        assert!(matches!(
+            result,
+            Err(Error::ResponseCode(MRSPField::GeneralError))
+        ));
+        assert_eq!(commands(&mock), SHUTDOWN_ORDER);
+        assert!(mock.is_closed());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_device_stops_streaming_and_the_motors() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        drop(connect(&mock).await);
+
+        while mock.written().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(commands(&mock), SHUTDOWN_ORDER[..2]);
+        let packets = mock.written_packets();
+        assert!(packets.iter().all(|p| p.sop2() == SOP2Field::NoResponse));
+        assert_eq!(streaming_masks(&mock), [(0, SOP2Field::NoResponse)]);
+    }
+
+    /// Response framed with a good checksum around an unknown response code
+    #[tokio::test]
+    async fn clock_sync_keeps_the_lowest_delay_round() {
+        // Robot clock runs 1000 ms ahead; it claims to have spent 40 ms
+        // processing the third poll, which makes that round the shortest
+        let mut round = 0;
+        let mock = robot(move |packet| {
+            if packet.cid() != CoreCommandID::PollPacketTimes as u8 {
+                return vec![ack(&packet)];
+            }
+            round += 1;
+            let client_tx = u32::from_be_bytes(packet.data().try_into().unwrap());
+            let robot_rx = client_tx + 1000;
+            let robot_tx = robot_rx + if round == 3 { 40 } else { 0 };
+            let data = [client_tx, robot_rx, robot_tx]
+                .iter()
+                .flat_map(|time| time.to_be_bytes())
+                .collect();
+            vec![respond(&packet, MRSPField::Ok, data)]
+        });
+        let device = connect(&mock).await;
+
+        let offset = device.sync_clock().await.unwrap();
+        let mut expected = vec![(DeviceID::Core, CoreCommandID::AssignTimeValue as u8)];
+        expected.extend(
+            [(DeviceID::Core, CoreCommandID::PollPacketTimes as u8)].repeat(CLOCK_SYNC_ROUNDS),
+        );
+        assert_eq!(commands(&mock), expected);
+        // Any other round has a delay of at least 0 ms and an offset of at most 1000 ms
+        assert!((-40..-30).contains(&offset.delay_ms), "{offset:?}");
+        assert!((1015..=1020).contains(&offset.offset_ms), "{offset:?}");
+        assert_eq!(device.clock_offset(), Some(offset));
+    }
+
+    fn unknown_response_code(seq: u8) -> Vec<u8> {
+        let fields = [0x99, seq, 0x01];
+        let mut bytes = vec![0xff, 0xff];
+        bytes.extend(fields);
+        bytes.push(crate::packet::calculate_checksum(&fields, &[]));
+        bytes
+    }
+
+    #[tokio::test]
+    async fn stats_after_a_scripted_exchange() {
+        // Pings are answered, each after a copy with a bad checksum;
+        // anything else is answered with an unparseable response
+        let mock = robot(|packet| match packet.cid() == CoreCommandID::Ping as u8 {
+            true => {
+                let mut corrupt = ack(&packet);
+                *corrupt.last_mut().unwrap() ^= 0xff;
+                vec![corrupt, ack(&packet)]
+            }
+            false => vec![unknown_response_code(packet.seq())],
+        });
+        let device = connect(&mock).await;
+
+        assert!(device.send(&Ping {}).await.is_ok());
+        let options = SendOptions {
+            timeout: Duration::from_millis(50),
+            ..SendOptions::default()
+        };
+        let result = device.send_with(&GetPowerState {}, options).await;
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let stats = device.stats();
+        assert_eq!(stats.packets_sent(), 2);
+        assert_eq!(stats.packets_received(), 1);
+        assert_eq!(stats.bytes_received(), 2 * 6 + 6);
+        assert_eq!(stats.checksum_failures(), 1);
+        assert_eq!(stats.malformed_packets(), 1);
+        assert_eq!(stats.timeouts(), 1);
+        assert_eq!(stats.retries(), 0);
+        assert_eq!(stats.unknown_seq_responses(), 0);
+        assert!(stats.round_trip_time().is_some());
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn script_failure_inside_a_repeat_names_one_step() {
+        let mock = robot(
+            |packet| match packet.cid() == SpheroCommandID::SetHeading as u8 {
+                true => vec![respond(&packet, MRSPField::GeneralError, vec![])],
+                false => vec![ack(&packet)],
+            },
+        );
+        let device = connect(&mock).await;
+        let script = Script::new(vec![
+            ScriptStep::Wait { ms: 0 },
+            ScriptStep::Repeat {
+                count: 2,
+                steps: vec![
+                    ScriptStep::Roll {
+                        speed: 50,
+                        heading: 90,
+                        duration_ms: None,
+                    },
+                    ScriptStep::SetHeading { heading: 0 },
+                ],
+            },
+        ]);
+
+        let failed = device.run_script(&script).await.unwrap_err();
+        assert_eq!(
+            failed.to_string(),
+            "script step 1: robot responded with GeneralError"
+        );
+        let Error::ScriptStep { index: 1, error } = failed else {
+            panic!("expected step 1 to fail");
+        };
+        assert!(matches!(
+            *error,
+            Error::ResponseCode(MRSPField::GeneralError)
+        ));
+        // The first failure stops the script, so the repeat doesn't go round again
+        assert_eq!(mock.written_packets().len(), 2);
+    }
+
+    /// Command IDs and data sent to the Sphero device
+    fn sphero_commands(mock: &MockTransport) -> Vec<(u8, Vec<u8>)> {
+        mock.written_packets()
+            .iter()
+            .filter(|packet| packet.did() == DeviceID::Sphero)
+            .map(|packet| (packet.cid(), packet.data().to_vec()))
+            .collect()
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn json_script_sends_its_steps_in_order() {
+        const SCRIPT: &str = r#"{ "steps": [
+            { "step": "set_color", "red": 0, "green": 255, "blue": 0 },
+            { "step": "repeat", "count": 2, "steps": [
+                { "step": "roll", "speed": 80, "heading": 90, "duration_ms": 30 },
+                { "step": "set_heading", "heading": 0 }
+            ] },
+            { "step": "wait", "ms": 20 },
+            { "step": "run_macro", "id": 2 }
+        ] }"#;
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let script: Script = serde_json::from_str(SCRIPT).unwrap();
+
+        let started = Instant::now();
+        assert!(device.run_script(&script).await.is_ok());
+
+        // Two rolls and the wait hold for their delays
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        let (led, roll, heading, run_macro) = (
+            SpheroCommandID::SetRGBLEDOutput as u8,
+            SpheroCommandID::Roll as u8,
+            SpheroCommandID::SetHeading as u8,
+            SpheroCommandID::RunMacro as u8,
+        );
+        let lap = [
+            (roll, vec![80, 0, 90, 1]),
+            (roll, vec![0, 0, 90, 0]),
+            (heading, vec![0, 0]),
+        ];
+        let mut expected = vec![(led, vec![0, 255, 0, 0])];
+        expected.extend(lap.iter().cloned().chain(lap.iter().cloned()));
+        expected.push((run_macro, vec![2]));
+        assert_eq!(sphero_commands(&mock), expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn invalid_script_is_rejected_before_sending() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let mut nested = ScriptStep::Wait { ms: 0 };
+        for _ in 0..=crate::script::MAX_REPEAT_DEPTH {
+            nested = ScriptStep::Repeat {
+                count: 1,
+                steps: vec![nested],
+            };
+        }
+        let too_deep = Script::new(vec![ScriptStep::Wait { ms: 0 }, nested]);
+
+        let rejected = device.run_script(&too_deep).await;
+
+        assert!(matches!(rejected, Err(Error::ScriptStep { index: 1, .. })));
+        assert!(mock.written().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn cancelled_script_stops_the_robot() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        let long_roll = Script::new(vec![ScriptStep::Roll {
+            speed: 100,
+            heading: 45,
+            duration_ms: Some(10_000),
+        }]);
+
+        let started = Instant::now();
+        let cancel = async {
+            while mock.written().is_empty() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            device.cancel_script();
+        };
+        let (ran, ()) = tokio::join!(device.run_script(&long_roll), cancel);
+
+        assert!(matches!(ran, Err(Error::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let roll = SpheroCommandID::Roll as u8;
+        assert_eq!(
+            sphero_commands(&mock),
+            [(roll, vec![100, 0, 45, 1]), (roll, vec![0, 0, 45, 0])]
+        );
+    }
+
+    /// Retry at once, a few times
+    const QUICK: ReconnectPolicy = ReconnectPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(1),
+    };
+
+    /// Wait until the link has gone through a reconnect and is back up
+    async fn reconnected(
+        device: &SpheroDevice<MockTransport>,
+        mock: &MockTransport,
+        writes: usize,
+    ) {
+        while mock.written().len() < writes || !device.is_connected() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn disconnect_fails_in_flight_commands_and_replays_the_session() {
+        // Leaves Roll unanswered so it is still in flight when the link drops
+        let mock = robot(|packet| match packet.cid() == SpheroCommandID::Roll as u8 {
+            true => vec![],
+            false => vec![ack(&packet)],
+        });
+        let device = connect(&mock).await;
+        device.set_reconnect(Some(QUICK));
+        drop(
+            device
+                .send(&SetStabilization { enabled: false })
+                .await
+                .unwrap(),
+        );
+        drop(
+            device
+                .send(&SetBackLEDOutput { brightness: 0x7f })
+                .await
+                .unwrap(),
+        );
+        mock.clear_written();
+
+        let drop_link = async {
+            while mock.written().is_empty() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            mock.disconnect();
+        };
+        let stop = Roll::stop_at_zero();
+        let (rolled, ()) = tokio::join!(device.send(&stop), drop_link);
+
+        assert!(matches!(rolled, Err(Error::Disconnected)));
+        reconnected(&device, &mock, 4).await;
+        let written: Vec<_> = mock
+            .written_packets()
+            .iter()
+            .map(|p| (p.did(), p.cid(), p.data().to_vec()))
+            .collect();
+        assert_eq!(
+            written,
+            [
+                (
+                    DeviceID::Sphero,
+                    SpheroCommandID::Roll as u8,
+                    vec![0, 0, 0, 0]
+                ),
+                (DeviceID::Core, CoreCommandID::Ping as u8, vec![]),
+                (
+                    DeviceID::Sphero,
+                    SpheroCommandID::SetStabilization as u8,
+                    vec![0x00]
+                ),
+                (
+                    DeviceID::Sphero,
+                    SpheroCommandID::SetBackLEDOutput as u8,
+                    vec![0x7f]
+                ),
+            ]
+        );
+        assert!(device.send(&Ping {}).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn commands_sent_while_reconnecting_wait_for_the_restore() {
+        // Once armed, holds the next command: the ping restoring the link
+        let arm = Arc::new(AtomicBool::new(false));
+        let (armed, held_commands) = (arm.clone(), Held::default());
+        let holding = held_commands.clone();
+        let mock = robot(move |packet| match armed.swap(false, Ordering::SeqCst) {
+            true => {
+                holding.lock().unwrap().push(packet);
+                vec![]
+            }
+            false => vec![ack(&packet)],
+        });
+        let device = connect(&mock).await;
+        device.set_reconnect(Some(QUICK));
+
+        arm.store(true, Ordering::SeqCst);
+        mock.disconnect();
+        let restoring = held(&held_commands, 1).await;
+        assert!(!device.is_connected());
+        let answer = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            // Nothing else goes out until the robot has answered
+            assert_eq!(mock.written().len(), 1);
+            mock.inject(ack(&restoring[0]));
+        };
+        let (pinged, ()) = tokio::join!(device.send(&Ping {}), answer);
+
+        assert!(pinged.is_ok());
+        assert_eq!(
+            commands(&mock),
+            [
+                (DeviceID::Core, CoreCommandID::Ping as u8),
+                (DeviceID::Core, CoreCommandID::Ping as u8),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_fail_once_reconnecting_gives_up() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+        device.set_reconnect(Some(QUICK));
+        let mut events = device.events().boxed();
+
+        // A closed mock refuses to reconnect
+        mock.close().await.unwrap();
+        while device.shared.link.lock().unwrap().state() != LinkState::Down {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert!(matches!(
+            device.send(&Ping {}).await,
+            Err(Error::Disconnected)
+        ));
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn raw_and_typed_responses_reach_their_commands() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+
+        let robot = async {
+            let commands = held(&commands, 2).await;
+            let ping = find(&commands, CoreCommandID::Ping);
+            let power = find(&commands, CoreCommandID::GetPowerState);
+            // Answer the raw command last
+            mock.inject(respond(power, MRSPField::Ok, vec![0xbb]));
+            mock.inject(respond(ping, MRSPField::Ok, vec![0xaa]));
+        };
+        let raw = device.send_raw(0x00, CoreCommandID::Ping as u8, vec![], true);
+        let (raw, typed, ()) = tokio::join!(raw, device.send(&GetPowerState {}), robot);
+
+        assert_eq!(raw.unwrap().unwrap().data(), [0xaa]);
+        assert_eq!(typed.unwrap().data(), [0xbb]);
+    }
+
+    #[tokio::test]
+    async fn raw_send_to_an_unknown_device_fails() {
+        let mock = robot(|packet| vec![ack(&packet)]);
+        let device = connect(&mock).await;
+
+        let result = device.send_raw(0x55, 0x01, vec![], true).await;
+
+        assert!(matches!(result, Err(Error::UnknownDeviceId(0x55))));
+        assert!(mock.written().is_empty());
+    }
+
+    #[tokio::test]
+    async fn raw_packet_without_a_seq_gets_a_fresh_one() {
+        let mock = robot(|packet| vec![respond(&packet, MRSPField::Ok, vec![0x01])]);
+        let device = connect(&mock).await;
+        let packet = SpheroCommandPacketV1::new(
+            DeviceID::Core,
+            CoreCommandID::Ping as u8,
+            NO_ANSWER_SEQ,
+            vec![],
+        );
+
+        let response = device.send_raw_packet(packet).await.unwrap().unwrap();
+
+        let sent = mock.written_packets().pop().unwrap();
+        assert_ne!(sent.seq(), NO_ANSWER_SEQ);
+        assert_eq!(response.seq(), sent.seq());
+        assert_eq!(response.data(), [0x01]);
+    }
+
+    #[tokio::test]
+    async fn raw_packet_seq_in_flight_is_busy() {
+        let (mock, commands) = holding();
+        let device = connect(&mock).await;
+        let packet = |cid: CoreCommandID| {
+            SpheroCommandPacketV1::new(DeviceID::Core, cid as u8, 0x42, vec![])
+        };
+
+        let robot = async {
+            let first = held(&commands, 1).await;
+            assert_eq!(first[0].seq(), 0x42);
+            let second = device
+                .send_raw_packet(packet(CoreCommandID::GetPowerState))
+                .await;
+            assert!(matches!(second, Err(Error::Busy)));
+            mock.inject(ack(&first[0]));
+        };
+        let (first, ()) = tokio::join!(device.send_raw_packet(packet(CoreCommandID::Ping)), robot);
+
+        assert_eq!(first.unwrap().unwrap().seq(), 0x42);
+        assert_eq!(mock.written_packets().len(), 1);
+        // Free again once answered
+        let again = device.send_raw_packet(packet(CoreCommandID::Ping));
+        let (again, ()) = tokio::join!(again, async {
+            let held = held(&commands, 1).await;
+            mock.inject(ack(&held[0]));
+        });
+        assert!(again.is_ok());
+    }
+
+    #[tokio::test]
+    async fn raw_packet_without_response_is_not_waited_for() {
+        let (mock, _commands) = holding();
+        let device = connect(&mock).await;
+        let packet =
+            SpheroCommandPacketV1::new(DeviceID::Core, CoreCommandID::Ping as u8, 0x42, vec![])
+                .with_response_required(false);
+
+        let response = device.send_raw_packet(packet).await.unwrap();
+
+        assert!(response.is_none());
+        assert_eq!(mock.written_packets()[0].sop2(), SOP2Field::NoResponse);
+    }
+
+    /// Mock robot answering every command with OK, except the Append Macro
+    /// Chunk numbered `reject` if any
+    fn macro_robot(reject: Option<u8>) -> MockTransport {
+        robot(move |packet| {
+            let rejected = packet.cid() == SpheroCommandID::AppendMacroChunk as u8
+                && Some(packet.data()[1]) == reject;
+            let mrsp = match rejected {
+                true => MRSPField::ExecuteError,
+                false => MRSPField::Ok,
+            };
+            vec![respond(&packet, mrsp, vec![])]
+        })
+    }
+
+    /// Macro too long for one packet
+    fn long_macro() -> Vec<u8> {
+        let mut builder = MacroBuilder::new().max_len(1024);
+        for i in 0..60 {
+            builder = builder
+                .set_rgb(RgbColor::new(i * 4, 0, 255 - i * 4))
+                .delay(50);
+        }
+        builder.end().unwrap()
+    }
+
+    const ABORT_MACRO: u8 = SpheroCommandID::AbortMacro as u8;
+    const RUN_MACRO: u8 = SpheroCommandID::RunMacro as u8;
+
+    #[tokio::test]
+    async fn short_macro_goes_out_in_one_save_temporary_macro() {
+        let mock = macro_robot(None);
+        let device = connect(&mock).await;
+        let blink = MacroBuilder::new()
+            .loop_start(3)
+            .set_rgb(RgbColor::RED)
+            .delay(200)
+            .set_rgb(RgbColor::BLACK)
+            .delay(200)
+            .loop_end()
+            .end()
+            .unwrap();
+
+        assert!(device.run_macro_bytes(&blink, None).await.is_ok());
+        assert_eq!(
+            sphero_commands(&mock),
+            [
+                (ABORT_MACRO, vec![]),
+                (SpheroCommandID::SaveTemporaryMacro as u8, blink),
+                (RUN_MACRO, vec![TEMPORARY_MACRO_ID]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn persisted_macro_is_saved_under_its_id() {
+        let mock = macro_robot(None);
+        let device = connect(&mock).await;
+        let spin = MacroBuilder::new()
+            .target(MacroTarget::Persistent(40))
+            .flags(MacroFlags::EXCLUSIVE_DRIVE | MacroFlags::STOP_MOTORS_ON_END)
+            .rotate_over_time(720, 2000)
+            .end()
+            .unwrap();
+
+        let wrong_id = device.run_macro_bytes(&spin, Some(41)).await;
+        assert!(matches!(wrong_id, Err(Error::BadParameterValue)));
+        assert!(mock.written().is_empty());
+
+        assert!(device.run_macro_bytes(&spin, Some(40)).await.is_ok());
+        assert_eq!(
+            sphero_commands(&mock),
+            [
+                (ABORT_MACRO, vec![]),
+                (SpheroCommandID::SaveMacro as u8, spin),
+                (RUN_MACRO, vec![40]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn long_macro_is_streamed_in_chunks() {
+        let mock = macro_robot(None);
+        let device = connect(&mock).await;
+        let fade = long_macro();
+        assert!(fade.len() > MAX_MACRO_LEN);
+
+        assert!(device.run_macro_bytes(&fade, None).await.is_ok());
+        let commands = sphero_commands(&mock);
+        let (first, rest) = commands.split_first().unwrap();
+        let (last, chunks) = rest.split_last().unwrap();
+        assert_eq!(*first, (ABORT_MACRO, vec![]));
+        assert_eq!(*last, (RUN_MACRO, vec![TEMPORARY_MACRO_ID]));
+        assert_eq!(chunks.len(), fade.len().div_ceil(MACRO_CHUNK_SIZE));
+        let mut uploaded = vec![];
+        for (index, (cid, data)) in chunks.iter().enumerate() {
+            assert_eq!(*cid, SpheroCommandID::AppendMacroChunk as u8);
+            assert_eq!(data[..2], [TEMPORARY_MACRO_ID, index as u8]);
+            uploaded.extend_from_slice(&data[2..]);
+        }
+        // The end command becomes a stream end
+        assert_eq!(uploaded[..fade.len() - 1], fade[..fade.len() - 1]);
+        assert_eq!(uploaded.last(), Some(&0x1b));
+    }
+
+    #[tokio::test]
+    async fn rejected_chunk_is_reported_with_its_position() {
+        let mock = macro_robot(Some(3));
+        let device = connect(&mock).await;
+
+        match device.run_macro_bytes(&long_macro(), None).await {
+            Err(Error::MacroChunk { index: 3, error }) => assert!(matches!(
+                *error,
+                Error::ResponseCode(MRSPField::ExecuteError)
+            )),
+            other => panic!("expected chunk 3 to fail, got {:?}", other.err()),
+        }
+        // Abort, then chunks 0 to 3; the macro is not run
+        let commands = sphero_commands(&mock);
+        assert_eq!(commands.len(), 5);
+        assert!(commands.iter().all(|&(cid, _)| cid != RUN_MACRO));
+    }
+
+    /// Macro Marker async message, as the robot sends it
+    fn macro_marker(marker: u8, macro_id: u8, command: u16) -> Vec<u8> {
+        let [hi, lo] = command.to_be_bytes();
+        SpheroAsynchronousPacketV1::new(0x06, vec![marker, macro_id, hi, lo])
+            .to_bytes()
+            .unwrap()
+    }
+
+    /// Running macro and command reported by Get Macro Status
+    type MacroStatus = Arc<Mutex<(u8, u16)>>;
+
+    /// Mock robot answering Get Macro Status from `status`, and every other
+    /// command with OK
+    fn macro_status_robot(status: MacroStatus) -> MockTransport {
+        robot(move |packet| {
+            let mut data = vec![];
+            if packet.cid() == SpheroCommandID::GetMacroStatus as u8 {
+                let (macro_id, command) = *status.lock().unwrap();
+                data.push(macro_id);
+                data.extend(command.to_be_bytes());
+            }
+            vec![respond(&packet, MRSPField::Ok, data)]
+        })
+    }
+
+    fn flash() -> Vec<u8> {
+        MacroBuilder::new()
+            .marker(1)
+            .set_rgb(RgbColor::RED)
+            .delay(500)
+            .marker(2)
+            .set_rgb(RgbColor::BLACK)
+            .end()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn macro_markers_are_awaited_and_kept() {
+        let mock = macro_status_robot(MacroStatus::default());
+        let device = connect(&mock).await;
+        let execution = device.run_macro_bytes(&flash(), None).await.unwrap();
+        assert_eq!(execution.progress(), None);
+
+        mock.inject(macro_marker(1, TEMPORARY_MACRO_ID, 1));
+        let first = execution.await_marker(1).await.unwrap();
+        assert_eq!((first.marker, first.command), (1, 1));
+        // Already seen, so found without waiting
+        assert_eq!(execution.await_marker(1).await.unwrap(), first);
+        assert_eq!(execution.progress(), Some(1));
+
+        // Markers of other macros don't count
+        mock.inject(macro_marker(2, 40, 9));
+        mock.inject(macro_marker(2, TEMPORARY_MACRO_ID, 4));
+        assert_eq!(execution.await_marker(2).await.unwrap().command, 4);
+        assert_eq!(execution.progress(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn macro_completion_follows_status_and_markers() {
+        let status = MacroStatus::new(Mutex::new((TEMPORARY_MACRO_ID, 1)));
+        let mock = macro_status_robot(status.clone());
+        let device = connect(&mock).await;
+        let execution = device.run_macro_bytes(&flash(), None).await.unwrap();
+
+        let (completion, ()) = tokio::join!(execution.await_completion(), async {
+            *status.lock().unwrap() = (TEMPORARY_MACRO_ID, 3);
+            while execution.progress() != Some(3) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            mock.inject(macro_marker(2, TEMPORARY_MACRO_ID, 4));
+            while execution.progress() != Some(4) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            *status.lock().unwrap() = (0, 0);
+        });
+        assert!(completion.is_ok());
+        assert_eq!(execution.await_marker(2).await.unwrap().command, 4);
+        assert!(mock
+            .written_packets()
+            .iter()
+            .any(|packet| packet.cid() == SpheroCommandID::GetMacroStatus as u8));
+    }
+
+    #[tokio::test]
+    async fn aborted_macro_fails_pending_and_later_waits() {
+        let status = MacroStatus::new(Mutex::new((TEMPORARY_MACRO_ID, 1)));
+        let mock = macro_status_robot(status);
+        let device = connect(&mock).await;
+        let execution = device.run_macro_bytes(&flash(), None).await.unwrap();
+        mock.clear_written();
+
+        let (waited, aborted) = tokio::join!(execution.await_marker(9), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            execution.abort().await
+        });
+        assert!(aborted.is_ok());
+        assert!(matches!(waited, Err(Error::Cancelled)));
+        assert!(matches!(
+            execution.await_completion().await,
+            Err(Error::Cancelled)
+        ));
+        assert_eq!(sphero_commands(&mock), [(ABORT_MACRO, vec![])]);
+    }
+
+    /// Mock robot answering every command with OK, except the Append
+    /// orbBasic Fragment numbered `reject` (from 0) if any
+    fn orbbasic_robot(reject: Option<usize>) -> MockTransport {
+        let mut appends = 0;
+        robot(move |packet| {
+            let mut mrsp = MRSPField::Ok;
+            if packet.cid() == SpheroCommandID::AppendOrbbasicFragment as u8 {
+                if Some(appends) == reject {
+                    mrsp = MRSPField::ExecuteError;
+                }
+                appends += 1;
+            }
+            vec![respond(&packet, mrsp, vec![])]
+        })
+    }
+
+    /// orbBasic program too long for one fragment
+    fn long_program() -> String {
+        (1..=60)
+            .map(|n| format!("{} RGB {}, 0, {}\r\n", n * 10, n * 4, 255 - n * 4))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn orbbasic_upload_splits_fragments_on_lines() {
+        let mock = orbbasic_robot(None);
+        let device = connect(&mock).await;
+        let source = long_program();
+
+        device
+            .load_orbbasic(OrbBasicArea::Area1, &source)
+            .await
+            .unwrap();
+        let commands = sphero_commands(&mock);
+        let (erase, appends) = commands.split_first().unwrap();
+        let area = OrbBasicArea::Area1 as u8;
+        assert_eq!(
+            *erase,
+            (SpheroCommandID::EraseOrbbasicStorage as u8, vec![area])
+        );
+        assert!(appends.len() > 1);
+        let mut uploaded = vec![];
+        for (cid, data) in appends {
+            assert_eq!(*cid, SpheroCommandID::AppendOrbbasicFragment as u8);
+            assert_eq!(data[0], area);
+            assert!(data.len() - 1 <= orbbasic::MAX_FRAGMENT_SIZE);
+            uploaded.extend_from_slice(&data[1..]);
+            // Only a page boundary may split a line
+            assert!(
+                matches!(data.last(), Some(b'\r' | 0)) || uploaded.len() % orbbasic::PAGE_SIZE == 0
+            );
+        }
+        assert_eq!(uploaded, orbbasic::program_bytes(&source));
+        assert_eq!(uploaded.last(), Some(&0));
+    }
+
+    #[tokio::test]
+    async fn run_and_abort_orbbasic_send_their_commands() {
+        let mock = orbbasic_robot(None);
+        let device = connect(&mock).await;
+
+        let session = device.run_orbbasic(OrbBasicArea::Area1, 10).await;
+        assert!(session.is_ok());
+        assert!(device.abort_orbbasic().await.is_ok());
+        assert_eq!(
+            sphero_commands(&mock),
+            [
+                (
+                    SpheroCommandID::ExecuteOrbbasicProgram as u8,
+                    vec![OrbBasicArea::Area1 as u8, 0, 10]
+                ),
+                (SpheroCommandID::AbortOrbbasicProgram as u8, vec![]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_fragment_is_reported_with_its_offset() {
+        let mock = orbbasic_robot(Some(1));
+        let device = connect(&mock).await;
+        let source = long_program();
+        let program = orbbasic::program_bytes(&source);
+
+        match device.load_orbbasic(OrbBasicArea::Area1, &source).await {
+            Err(Error::OrbBasicFragment {
+                index: 1,
+                offset,
+                error,
+            }) => {
+                assert_eq!(offset, orbbasic::fragments(&program)[1].0);
+                assert!(offset > 0);
+                assert!(matches!(
+                    *error,
+                    Error::ResponseCode(MRSPField::ExecuteError)
+                ));
+            }
+            other => panic!("expected fragment 1 to fail, got {other:?}"),
+        }
+        // Erase, then fragments 0 and 1; the rest are not sent
+        assert_eq!(sphero_commands(&mock).len(), 3);
+    }
+
+    /// orbBasic print message, as the robot sends it
+    fn orbbasic_print(text: &str) -> Vec<u8> {
+        let data = format!("{text}\r\n").into_bytes();
+        SpheroAsynchronousPacketV1::new(0x08, data)
+            .to_bytes()
+            .unwrap()
+    }
+
+    /// orbBasic binary error message, as the robot sends it
+    fn orbbasic_error(code: u16, line: u16) -> Vec<u8> {
+        let mut data = code.to_be_bytes().to_vec();
+        data.extend(line.to_be_bytes());
+        SpheroAsynchronousPacketV1::new(0x0a, data)
+            .to_bytes()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn orbbasic_session_ends_at_a_binary_error() {
+        let mock = orbbasic_robot(None);
+        let device = connect(&mock).await;
+        let session = device.run_orbbasic(OrbBasicArea::Area0, 10).await.unwrap();
+
+        for message in [
+            orbbasic_print("1"),
+            orbbasic_print("2"),
+            orbbasic_error(0x08, 40),
+            orbbasic_print("late"),
+        ] {
+            mock.inject(message);
+        }
+        let output: Vec<_> = session.collect().await;
+        let error = OrbBasicError {
+            code: 0x08,
+            line: 40,
+        };
+        assert_eq!(
+            output,
+            [
+                OrbBasicOutput::Print("1".to_string()),
+                OrbBasicOutput::Print("2".to_string()),
+                OrbBasicOutput::Error(error),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn orbbasic_session_ends_at_a_text_error() {
+        let mock = orbbasic_robot(None);
+        let device = connect(&mock).await;
+        let session = device.run_orbbasic(OrbBasicArea::Area0, 10).await.unwrap();
+
+        let text = b"Syntax error in line 10\r\n".to_vec();
+        mock.inject(
+            SpheroAsynchronousPacketV1::new(0x09, text)
+                .to_bytes()
+                .unwrap(),
+        );
+        mock.inject(orbbasic_print("late"));
+        let output: Vec<_> = session.collect().await;
+        assert_eq!(
+            output,
+            [OrbBasicOutput::ErrorText(
+                "Syntax error in line 10".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn aborted_orbbasic_session_ends() {
+        let mock = orbbasic_robot(None);
+        let device = connect(&mock).await;
+        let mut session = device.run_orbbasic(OrbBasicArea::Area0, 10).await.unwrap();
+
+        mock.inject(orbbasic_print("1"));
+        assert_eq!(
+            session.next().await,
+            Some(OrbBasicOutput::Print("1".to_string()))
+        );
+        mock.clear_written();
+        session.abort().await.unwrap();
+        mock.inject(orbbasic_print("2"));
+        assert_eq!(session.next().await, None);
+        assert_eq!(
+            sphero_commands(&mock),
+            [(SpheroCommandID::AbortOrbbasicProgram as u8, vec![])]
+        );
+    }
+
+    /// Mock robot storing the Application Configuration Block, all zeros at first
+    fn app_data_robot() -> (MockTransport, Arc<Mutex<[u8; ACB_LEN]>>) {
+        let acb = Arc::new(Mutex::new([0; ACB_LEN]));
+        let stored = acb.clone();
+        let mock = robot(move |packet| {
+            let mut data = vec![];
+            if packet.cid() == SpheroCommandID::SetApplicationConfigurationBlock as u8 {
+                stored.lock().unwrap().copy_from_slice(packet.data());
+            } else if packet.cid() == SpheroCommandID::GetApplicationConfigurationBlock as u8 {
+                data = stored.lock().unwrap().to_vec();
+            }
+            vec![respond(&packet, MRSPField::Ok, data)]
+        });
+        (mock, acb)
+    }
+
+    #[tokio::test]
+    async fn app_data_round_trips_through_the_robot() {
+        let (mock, acb) = app_data_robot();
+        let device = connect(&mock).await;
+
+        // Never written
+        let block = device.read_app_data().await.unwrap();
+        assert_eq!(AppData::decode(&block), None);
+
+        let saved = AppData::new(2, vec![0x00, 0x5a, 80]).unwrap();
+        device.write_app_data(&saved.encode()).await.unwrap();
+        assert_eq!(*acb.lock().unwrap(), saved.encode());
+        let loaded = AppData::decode(&device.read_app_data().await.unwrap());
+        assert_eq!(loaded, Some(saved));
+
+        // Written by an older version of the app
+        let old = AppData::new(1, vec![90]).unwrap();
+        device.write_app_data(&old.encode()).await.unwrap();
+        let loaded = AppData::decode(&device.read_app_data().await.unwrap()).unwrap();
+        assert_eq!(loaded.version, 1);
+    }
+
+    #[tokio::test]
+    async fn short_app_data_response_is_an_error() {
+        let mock = robot(|packet| vec![respond(&packet, MRSPField::Ok, vec![0xa5; ACB_LEN - 1])]);
+        let device = connect(&mock).await;
+
+        let block = device.read_app_data().await;
+        assert!(matches!(block, Err(Error::BadDataLength)));
+    }
+}
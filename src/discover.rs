@@ -0,0 +1,168 @@
+/*!
+ * Sphero Discovery
+ *
+ * Sphero robots advertise a local name starting with a model specific prefix
+ * (e.g. "SK-1A2B" for a SPRK+), which is enough to find and classify them.
+ */
+
+/// Sphero Model, as inferred from the advertised name
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum SpheroModel {
+    /// SPRK+ ("SK-")
+    SprkPlus,
+    /// BB-8 ("BB-")
+    BB8,
+    /// Ollie ("2B-")
+    Ollie,
+    /// Mini ("LM-")
+    Mini,
+    /// Bolt ("GB-")
+    Bolt,
+    /// Force Band ("FB-")
+    ForceBand,
+}
+
+impl SpheroModel {
+    /// Every known model
+    pub const ALL: [SpheroModel; 6] = [
+        SpheroModel::SprkPlus,
+        SpheroModel::BB8,
+        SpheroModel::Ollie,
+        SpheroModel::Mini,
+        SpheroModel::Bolt,
+        SpheroModel::ForceBand,
+    ];
+
+    /// BLE name prefix advertised by this model
+    pub fn name_prefix(&self) -> &'static str {
+        match self {
+            SpheroModel::SprkPlus => "SK-",
+            SpheroModel::BB8 => "BB-",
+            SpheroModel::Ollie => "2B-",
+            SpheroModel::Mini => "LM-",
+            SpheroModel::Bolt => "GB-",
+            SpheroModel::ForceBand => "FB-",
+        }
+    }
+
+    /// Classify an advertised name, `None` if it isn't a known Sphero
+    pub fn from_name(name: &str) -> Option<SpheroModel> {
+        Self::ALL
+            .into_iter()
+            .find(|model| name.starts_with(model.name_prefix()))
+    }
+}
+
+#[cfg(feature = "ble")]
+pub use self::ble::{scan_for_spheros, DiscoveredSphero};
+
+#[cfg(feature = "ble")]
+mod ble {
+    use super::SpheroModel;
+    use crate::error::Error;
+    use crate::runtime::sleep;
+    use btleplug::api::{BDAddr, Central, Peripheral, PeripheralProperties, ScanFilter};
+    use std::time::Duration;
+
+    /// A Sphero found while scanning
+    #[derive(Debug, Clone)]
+    pub struct DiscoveredSphero<P> {
+        /// Model inferred from the name prefix
+        pub model: SpheroModel,
+        /// Advertised local name
+        pub name: String,
+        /// Bluetooth address
+        pub address: BDAddr,
+        /// Signal strength, if reported
+        pub rssi: Option<i16>,
+        /// Peripheral handle, used to connect
+        pub peripheral: P,
+    }
+
+    impl<P> DiscoveredSphero<P> {
+        /// `peripheral` as a Sphero, if its properties carry a known name prefix
+        pub(super) fn from_properties(
+            peripheral: P,
+            properties: Option<PeripheralProperties>,
+        ) -> Option<Self> {
+            let properties = properties?;
+            let name = properties.local_name?;
+            let model = SpheroModel::from_name(&name)?;
+            Some(DiscoveredSphero {
+                model,
+                name,
+                address: properties.address,
+                rssi: properties.rssi,
+                peripheral,
+            })
+        }
+    }
+
+    /// Scan for `timeout` and return every Sphero seen
+    ///
+    /// Peripherals without a name or properties are skipped rather than treated as errors.
+    pub async fn scan_for_spheros<C: Central>(
+        adapter: &C,
+        timeout: Duration,
+    ) -> Result<Vec<DiscoveredSphero<C::Peripheral>>, Error> {
+        adapter.start_scan(ScanFilter::default()).await?;
+        sleep(timeout).await;
+        adapter.stop_scan().await?;
+
+        let mut found = Vec::new();
+        for peripheral in adapter.peripherals().await? {
+            let properties = peripheral.properties().await.ok().flatten();
+            found.extend(DiscoveredSphero::from_properties(peripheral, properties));
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_every_prefix() {
+        let names = [
+            ("SK-1A2B", SpheroModel::SprkPlus),
+            ("BB-8C3D", SpheroModel::BB8),
+            ("2B-0011", SpheroModel::Ollie),
+            ("LM-ABCD", SpheroModel::Mini),
+            ("GB-9F00", SpheroModel::Bolt),
+            ("FB-1234", SpheroModel::ForceBand),
+        ];
+        for (name, model) in names {
+            assert_eq!(SpheroModel::from_name(name), Some(model), "{name}");
+        }
+        for model in SpheroModel::ALL {
+            assert_eq!(SpheroModel::from_name(model.name_prefix()), Some(model));
+        }
+    }
+
+    #[test]
+    fn rejects_other_names() {
+        for name in ["", "SK", "sk-1A2B", "Neuro", " SK-1A2B", "XX-SK-"] {
+            assert_eq!(SpheroModel::from_name(name), None, "{name:?}");
+        }
+    }
+
+    #[cfg(feature = "ble")]
+    #[test]
+    fn skips_peripherals_without_name_or_properties() {
+        use btleplug::api::PeripheralProperties;
+
+        assert!(DiscoveredSphero::from_properties((), None).is_none());
+        let unnamed = PeripheralProperties::default();
+        assert!(DiscoveredSphero::from_properties((), Some(unnamed)).is_none());
+        let named = PeripheralProperties {
+            local_name: Some("SK-1A2B".to_string()),
+            rssi: Some(-60),
+            ..PeripheralProperties::default()
+        };
+        let found = DiscoveredSphero::from_properties((), Some(named)).unwrap();
+        assert_eq!(found.model, SpheroModel::SprkPlus);
+        assert_eq!(found.name, "SK-1A2B");
+        assert_eq!(found.rssi, Some(-60));
+    }
+}
@@ -0,0 +1,243 @@
+/*!
+ * Sphero Drive Controller
+ *
+ * Abrupt speed or heading changes tip the ball over and make it fishtail.
+ * The controller takes a target speed and heading at any rate and eases
+ * towards it, sending a Roll command every tick with the speed change and
+ * turn limited per second. Headings turn the short way round, through 0
+ * where that is shorter.
+ */
+use crate::command::Roll;
+use crate::device::{SendOptions, SpheroDevice};
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::runtime;
+use crate::transport::Transport;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Sphero Drive Limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriveLimits {
+    /// Time between Roll commands
+    pub tick: Duration,
+    /// Largest speed change per second, in speed units (0..255)
+    pub max_acceleration: f32,
+    /// Largest heading change per second, in degrees
+    pub max_turn_rate: f32,
+}
+
+impl Default for DriveLimits {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_millis(50),
+            max_acceleration: 200.0,
+            max_turn_rate: 180.0,
+        }
+    }
+}
+
+impl DriveLimits {
+    fn validate(&self) -> Result<(), Error> {
+        if self.tick.is_zero() || self.max_acceleration <= 0.0 || self.max_turn_rate <= 0.0 {
+            return Err(Error::BadParameterValue);
+        }
+        Ok(())
+    }
+}
+
+/// Signed turn from heading `from` to heading `to` the short way, in -180..180 degrees
+///
+/// ```
+/// use sphero_rs::drive::heading_delta;
+///
+/// assert_eq!(heading_delta(10.0, 50.0), 40.0);
+/// assert_eq!(heading_delta(10.0, 350.0), -20.0);
+/// assert_eq!(heading_delta(350.0, 10.0), 20.0);
+/// ```
+pub fn heading_delta(from: f32, to: f32) -> f32 {
+    (to - from + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Sphero Drive Ramp
+/// The rate-limited speed and heading, stepped by a caller-supplied clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriveRamp {
+    limits: DriveLimits,
+    speed: f32,
+    heading: f32,
+}
+
+impl DriveRamp {
+    /// Start at rest, facing `heading`
+    pub fn new(limits: DriveLimits, heading: u16) -> Result<Self, Error> {
+        limits.validate()?;
+        if heading > 359 {
+            return Err(Error::BadParameterValue);
+        }
+        Ok(Self {
+            limits,
+            speed: 0.0,
+            heading: heading as f32,
+        })
+    }
+
+    /// Current speed
+    pub fn speed(&self) -> u8 {
+        self.speed.round() as u8
+    }
+
+    /// Current heading, 0..359 degrees
+    pub fn heading(&self) -> u16 {
+        self.heading.round() as u16 % 360
+    }
+
+    /// Advance `dt` towards the target and return the Roll to send
+    ///
+    /// ```
+    /// use sphero_rs::drive::{DriveLimits, DriveRamp};
+    /// use std::time::Duration;
+    ///
+    /// let limits = DriveLimits {
+    ///     tick: Duration::from_millis(100),
+    ///     max_acceleration: 100.0,
+    ///     max_turn_rate: 90.0,
+    /// };
+    /// let mut ramp = DriveRamp::new(limits, 10).unwrap();
+    ///
+    /// // 10 speed units and 9 degrees per tick, turning back through 0 towards 340
+    /// let rolls: Vec<_> = (0..4)
+    ///     .map(|_| ramp.step(25, 340, limits.tick))
+    ///     .map(|roll| (roll.speed.value(), roll.heading.degrees()))
+    ///     .collect();
+    /// assert_eq!(rolls, vec![(10, 1), (20, 352), (25, 343), (25, 340)]);
+    ///
+    /// // Stopping ramps down too, and only the last Roll stops
+    /// let stop: Vec<_> = (0..3).map(|_| ramp.step(0, 340, limits.tick)).collect();
+    /// assert_eq!(stop.iter().map(|roll| roll.speed.value()).collect::<Vec<_>>(), vec![15, 5, 0]);
+    /// assert!(!stop[1].is_stop() && stop[2].is_stop());
+    /// ```
+    pub fn step(&mut self, speed: u8, heading: u16, dt: Duration) -> Roll {
+        let dt = dt.as_secs_f32();
+
+        let max_speed_step = self.limits.max_acceleration * dt;
+        let speed_step = (speed as f32 - self.speed).clamp(-max_speed_step, max_speed_step);
+        self.speed = (self.speed + speed_step).clamp(0.0, 255.0);
+
+        let max_turn = self.limits.max_turn_rate * dt;
+        let turn = heading_delta(self.heading, (heading % 360) as f32).clamp(-max_turn, max_turn);
+        self.heading = (self.heading + turn).rem_euclid(360.0);
+
+        Roll {
+            speed: self.speed().into(),
+            heading: Heading::from_degrees_clamped(self.heading),
+            state: self.speed() > 0 || speed > 0,
+        }
+    }
+}
+
+/// Target and progress shared between the controller's callers and its loop
+#[derive(Debug)]
+struct DriveState {
+    ramp: DriveRamp,
+    target: (u8, u16),
+    /// Cap on the target speed, e.g. while the battery is low
+    limit: Option<u8>,
+    stopping: bool,
+}
+
+/// Sphero Drive Controller
+///
+/// Set targets with `drive` from anywhere while `run` sends the Rolls.
+/// `run` returns once `stop` has ramped the robot down to rest.
+pub struct DriveController<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    tick: Duration,
+    state: Mutex<DriveState>,
+}
+
+impl<'a, T: Transport + 'static> DriveController<'a, T> {
+    /// Control `device`, starting at rest facing `heading`
+    pub fn new(
+        device: &'a SpheroDevice<T>,
+        limits: DriveLimits,
+        heading: u16,
+    ) -> Result<Self, Error> {
+        let ramp = DriveRamp::new(limits, heading)?;
+        Ok(Self {
+            device,
+            tick: limits.tick,
+            state: Mutex::new(DriveState {
+                ramp,
+                target: (0, heading),
+                limit: None,
+                stopping: false,
+            }),
+        })
+    }
+
+    /// Head for `speed` and `heading` (0..359 degrees)
+    /// Cancels a `stop` that hasn't finished yet.
+    pub fn drive(&self, speed: u8, heading: u16) -> Result<(), Error> {
+        if heading > 359 {
+            return Err(Error::BadParameterValue);
+        }
+        let mut state = self.state.lock().unwrap();
+        state.target = (speed, heading);
+        state.stopping = false;
+        Ok(())
+    }
+
+    /// Ramp down to rest, keeping the current target heading
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.target.0 = 0;
+        state.stopping = true;
+    }
+
+    /// Cap target speeds at `limit`, or lift the cap with `None`
+    /// Applies from the next tick, ramping down at the usual rate.
+    pub fn set_speed_limit(&self, limit: Option<u8>) {
+        self.state.lock().unwrap().limit = limit;
+    }
+
+    /// Cap on target speeds, if any
+    pub fn speed_limit(&self) -> Option<u8> {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Current (speed, heading), as last sent
+    pub fn current(&self) -> (u8, u16) {
+        let state = self.state.lock().unwrap();
+        (state.ramp.speed(), state.ramp.heading())
+    }
+
+    /// Send a Roll every tick until stopped
+    /// Unchanged Rolls are skipped. Fails if a Roll can't be written.
+    pub async fn run(&self) -> Result<(), Error> {
+        let options = SendOptions {
+            no_answer: true,
+            ..SendOptions::default()
+        };
+        let mut last_sent = None;
+        loop {
+            runtime::sleep(self.tick).await;
+            let (roll, done) = {
+                let mut state = self.state.lock().unwrap();
+                let (speed, heading) = state.target;
+                let speed = state.limit.map_or(speed, |limit| speed.min(limit));
+                let roll = state.ramp.step(speed, heading, self.tick);
+                let done = state.stopping && roll.is_stop();
+                (roll, done)
+            };
+            let sent = (roll.speed, roll.heading, roll.state);
+            if last_sent != Some(sent) {
+                drop(self.device.send_with(&roll, options).await?);
+                last_sent = Some(sent);
+            }
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+/*!
+ * Sphero Packet Dump
+ *
+ * Annotated hex dumps of V1 packets, one field per line, for matching bytes
+ * seen on the wire to the fields they belong to.
+ */
+use crate::packet::{
+    BootloaderCommandID, CoreCommandID, DeviceID, SOP2Field, SpheroCommandID,
+    SpheroCommandPacketV1, SpheroResponsePacketV1,
+};
+use deku::DekuContainerWrite;
+use std::fmt;
+
+/// Write one field: its bytes in hex, then its name
+fn field(f: &mut fmt::Formatter<'_>, bytes: &[u8], label: &str) -> fmt::Result {
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if hex.len() < 8 {
+        writeln!(f, "{hex:<8} {label}")
+    } else {
+        writeln!(f, "{hex}  {label}")
+    }
+}
+
+fn sop2_label(byte: u8) -> &'static str {
+    match byte {
+        b if b == SOP2Field::Response as u8 => "SOP2 (response required)",
+        b if b == SOP2Field::NoResponse as u8 => "SOP2 (no response)",
+        b if b == SOP2Field::Async as u8 => "SOP2 (async)",
+        _ => "SOP2",
+    }
+}
+
+/// Name of command `cid` of device `did`
+fn command_name(did: DeviceID, cid: u8) -> Option<String> {
+    match did {
+        DeviceID::Core => CoreCommandID::try_from(cid).ok().map(|c| format!("{c:?}")),
+        DeviceID::Bootloader => BootloaderCommandID::try_from(cid)
+            .ok()
+            .map(|c| format!("{c:?}")),
+        DeviceID::Sphero => SpheroCommandID::try_from(cid)
+            .ok()
+            .map(|c| format!("{c:?}")),
+    }
+}
+
+/// Annotated hex dump of a command packet
+///
+/// ```
+/// use sphero_rs::dump::PacketDump;
+/// use sphero_rs::packet::{DeviceID, SpheroCommandPacketV1};
+///
+/// let packet = SpheroCommandPacketV1::new(DeviceID::Sphero, 0x20, 7, vec![0xff, 0x00, 0xff, 0x01]);
+/// assert_eq!(
+///     PacketDump(&packet).to_string(),
+///     "\
+/// FF       SOP1
+/// FF       SOP2 (response required)
+/// 02       DID (Sphero)
+/// 20       CID (SetRGBLEDOutput)
+/// 07       SEQ
+/// 05       DLEN
+/// FF 00 FF 01  DATA
+/// D2       CHK
+/// "
+/// );
+/// ```
+pub struct PacketDump<'a>(pub &'a SpheroCommandPacketV1);
+
+impl fmt::Display for PacketDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let packet = self.0;
+        let bytes = packet.to_bytes().map_err(|_| fmt::Error)?;
+        let did = packet.did();
+        field(f, &bytes[..1], "SOP1")?;
+        field(f, &bytes[1..2], sop2_label(bytes[1]))?;
+        field(f, &bytes[2..3], &format!("DID ({did:?})"))?;
+        let cid = match command_name(did, packet.cid()) {
+            Some(name) => format!("CID ({name})"),
+            None => "CID (unknown)".to_string(),
+        };
+        field(f, &bytes[3..4], &cid)?;
+        field(f, &bytes[4..5], "SEQ")?;
+        field(f, &bytes[5..6], "DLEN")?;
+        if !packet.data().is_empty() {
+            field(f, packet.data(), "DATA")?;
+        }
+        field(f, &bytes[bytes.len() - 1..], "CHK")
+    }
+}
+
+/// Annotated hex dump of a response packet
+///
+/// ```
+/// use sphero_rs::dump::ResponseDump;
+/// use sphero_rs::packet::{MRSPField, SpheroResponsePacketV1};
+///
+/// let response = SpheroResponsePacketV1::new(MRSPField::Ok, 7, vec![]);
+/// assert_eq!(
+///     ResponseDump(&response).to_string(),
+///     "FF       SOP1\nFF       SOP2 (response required)\n00       MRSP (Ok)\n07       SEQ\n01       DLEN\nF7       CHK\n"
+/// );
+/// ```
+pub struct ResponseDump<'a>(pub &'a SpheroResponsePacketV1);
+
+impl fmt::Display for ResponseDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let packet = self.0;
+        let bytes = packet.to_bytes().map_err(|_| fmt::Error)?;
+        field(f, &bytes[..1], "SOP1")?;
+        field(f, &bytes[1..2], sop2_label(bytes[1]))?;
+        field(f, &bytes[2..3], &format!("MRSP ({:?})", packet.mrsp()))?;
+        field(f, &bytes[3..4], "SEQ")?;
+        field(f, &bytes[4..5], "DLEN")?;
+        if !packet.data().is_empty() {
+            field(f, packet.data(), "DATA")?;
+        }
+        field(f, &bytes[bytes.len() - 1..], "CHK")
+    }
+}
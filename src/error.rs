@@ -1,16 +1,39 @@
 /*!
  * Sphero Error
  */
+use crate::packet::{DeviceID, MRSPField};
 
 /// Sphero API Error Codes
 #[derive(Debug)]
 pub enum Error {
     /// Packet is invalid
     InvalidPacket,
+    /// Packet checksum doesn't match its contents
+    ChecksumMismatch {
+        /// Checksum computed over the packet
+        expected: u8,
+        /// Checksum byte received
+        actual: u8,
+    },
     /// Device ID is invalid (or is invisible with current permissions)
+    #[deprecated(note = "use `UnknownDeviceId`, which carries the byte")]
     BadDeviceId,
     /// Command ID is invalid (or is invisible with current permissions)
+    #[deprecated(note = "use `UnknownCommandId`, which carries the byte")]
     BadCommandId,
+    /// Device ID byte isn't a known device
+    UnknownDeviceId(u8),
+    /// Command ID byte isn't a known command of its device
+    UnknownCommandId(u8),
+    /// Asynchronous ID code byte isn't a known message
+    UnknownAsyncIdCode(u8),
+    /// Macro bytes hold an undocumented opcode
+    UnknownMacroOpcode {
+        /// Opcode byte
+        opcode: u8,
+        /// Offset of the opcode in the macro
+        offset: usize,
+    },
     /// Command is not yet implemented or has a null handler
     NotImplemented,
     /// Command cannot be executed in the current state or mode
@@ -29,8 +52,83 @@ pub enum Error {
     TargetUnavailable,
     /// Currently unused
     Unused(u8),
+    /// A required BLE characteristic was not found on the peripheral
+    CharacteristicNotFound(&'static str),
+    /// The underlying transport failed
+    Transport(String),
+    /// A chunk of a macro upload failed, see `SpheroDevice::run_macro_bytes`
+    MacroChunk {
+        /// Position of the chunk in the upload, from 0
+        index: usize,
+        /// Why it failed
+        error: Box<Error>,
+    },
+    /// A fragment of an orbBasic upload failed, see `SpheroDevice::load_orbbasic`
+    OrbBasicFragment {
+        /// Position of the fragment in the upload, from 0
+        index: usize,
+        /// Offset of its first byte in the program
+        offset: usize,
+        /// Why it failed
+        error: Box<Error>,
+    },
+    /// A reflash stopped at a page, see `SpheroDevice::reflash`
+    Reflash {
+        /// Page to resume from
+        page: u16,
+        /// Why it stopped
+        error: Box<Error>,
+    },
+    /// Firmware image is larger than the main application area
+    FirmwareTooLarge {
+        /// Size of the image in bytes
+        len: usize,
+        /// Largest size that fits
+        max: usize,
+    },
+    /// An Intel HEX record could not be read
+    HexRecord {
+        /// Line of the record, from 1
+        line: usize,
+        /// What was wrong with it
+        error: Box<Error>,
+    },
+    /// A step of a script is invalid or failed, see `script::Script`
+    ScriptStep {
+        /// Position of the top-level step in the script, from 0
+        index: usize,
+        /// What was wrong with it
+        error: Box<Error>,
+    },
+    /// The robot answered with a non-OK message response code
+    ResponseCode(MRSPField),
+    /// No response arrived in time
+    Timeout,
+    /// Connecting to the robot did not finish in time
+    ConnectionTimeout,
+    /// A newer command of the same kind replaced this one before it was sent
+    Superseded,
+    /// The link to the robot dropped before the command was answered
+    Disconnected,
+    /// The robot stopped making progress, e.g. it is stuck against a wall
+    Stalled,
+    /// The operation was cancelled before it finished
+    Cancelled,
+    /// The robot's firmware predates the command, so it was not sent
+    RequiresFirmware {
+        /// Device ID of the command
+        did: DeviceID,
+        /// Command ID
+        cid: u8,
+        /// First main application version implementing the command
+        minimum: (u8, u8),
+        /// Main application version on the robot
+        found: (u8, u8),
+    },
 }
 
+/// Map a robot error code; the robot doesn't say which DID or CID it rejected
+#[allow(deprecated)]
 impl From<u8> for Error {
     fn from(code: u8) -> Self {
         match code {
@@ -48,3 +146,71 @@ impl From<u8> for Error {
         }
     }
 }
+
+impl From<deku::DekuError> for Error {
+    fn from(_: deku::DekuError) -> Self {
+        Error::InvalidPacket
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::CharacteristicNotFound(name) => write!(f, "{} characteristic not found", name),
+            Error::Transport(msg) => write!(f, "transport error: {}", msg),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:#04x}, got {:#04x}",
+                expected, actual
+            ),
+            Error::UnknownDeviceId(b) => write!(f, "unknown device id {:#04x}", b),
+            Error::UnknownCommandId(b) => write!(f, "unknown command id {:#04x}", b),
+            Error::UnknownAsyncIdCode(b) => write!(f, "unknown async id code {:#04x}", b),
+            Error::UnknownMacroOpcode { opcode, offset } => write!(
+                f,
+                "unknown macro opcode {:#04x} at offset {}",
+                opcode, offset
+            ),
+            Error::MacroChunk { index, error } => {
+                write!(f, "macro chunk {} failed: {}", index, error)
+            }
+            Error::OrbBasicFragment {
+                index,
+                offset,
+                error,
+            } => write!(
+                f,
+                "orbBasic fragment {} at byte {} failed: {}",
+                index, offset, error
+            ),
+            Error::Reflash { page, error } => {
+                write!(f, "reflash stopped at page {}: {}", page, error)
+            }
+            Error::FirmwareTooLarge { len, max } => write!(
+                f,
+                "firmware image is {} bytes, at most {} fit",
+                len, max
+            ),
+            Error::HexRecord { line, error } => {
+                write!(f, "Intel HEX line {}: {}", line, error)
+            }
+            Error::ScriptStep { index, error } => {
+                write!(f, "script step {}: {}", index, error)
+            }
+            Error::ResponseCode(mrsp) => write!(f, "robot responded with {:?}", mrsp),
+            Error::RequiresFirmware {
+                did,
+                cid,
+                minimum,
+                found,
+            } => write!(
+                f,
+                "{:?} command {:#04x} requires firmware {}.{} or later, robot has {}.{}",
+                did, cid, minimum.0, minimum.1, found.0, found.1
+            ),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
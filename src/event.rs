@@ -0,0 +1,203 @@
+/*!
+ * Sphero Event
+ *
+ * Everything the robot sends to the host is either a response to a command
+ * or an asynchronous message, distinguished by the SOP2 byte.
+ */
+use crate::error::Error;
+use crate::packet::{
+    SOP2Field, SpheroAsynchronousPacketV1, SpheroCommandPacketV1, SpheroResponsePacketV1,
+};
+use crate::power::PowerState;
+use crate::sensor::{MotionEvent, SensorFrame, SensorMask, StreamStall};
+use deku::DekuContainerRead;
+
+/// Sphero Event
+#[derive(Debug, PartialEq)]
+pub enum SpheroEvent {
+    /// Simple (synchronous) response to a command
+    Response(SpheroResponsePacketV1),
+    /// Asynchronous message
+    Async(SpheroAsynchronousPacketV1),
+}
+
+impl SpheroEvent {
+    /// Whether this is the response to `cmd`, matched by sequence number
+    pub fn is_response_for(&self, cmd: &SpheroCommandPacketV1) -> bool {
+        self.as_response()
+            .is_some_and(|response| cmd.matches_response(response))
+    }
+
+    /// The response packet, if this is a response
+    pub fn as_response(&self) -> Option<&SpheroResponsePacketV1> {
+        match self {
+            SpheroEvent::Response(response) => Some(response),
+            SpheroEvent::Async(_) => None,
+        }
+    }
+
+    /// Take the response packet, if this is a response
+    pub fn into_response(self) -> Option<SpheroResponsePacketV1> {
+        match self {
+            SpheroEvent::Response(response) => Some(response),
+            SpheroEvent::Async(_) => None,
+        }
+    }
+}
+
+/// Parse a single packet received from the robot
+pub fn parse_notification(data: &[u8]) -> Result<SpheroEvent, Error> {
+    match data.get(1) {
+        Some(&sop2) if sop2 == SOP2Field::Response as u8 => {
+            let (_, packet) = SpheroResponsePacketV1::from_bytes((data, 0))?;
+            Ok(SpheroEvent::Response(packet))
+        }
+        Some(&sop2) if sop2 == SOP2Field::Async as u8 => {
+            let (_, packet) = SpheroAsynchronousPacketV1::from_bytes((data, 0))?;
+            Ok(SpheroEvent::Async(packet))
+        }
+        _ => Err(Error::InvalidPacket),
+    }
+}
+
+/// Sphero Collision Data
+pub use crate::async_payload::CollisionDetectedPayload as CollisionData;
+
+/// Sphero Asynchronous Message
+#[derive(Debug, PartialEq, Clone)]
+pub enum AsyncMessage {
+    /// Power notification
+    PowerNotification(PowerState),
+    /// Sensor data streaming frames
+    SensorData(Vec<SensorFrame>),
+    /// The robot will go to sleep in 10 seconds
+    PreSleepWarning,
+    /// Collision detected
+    Collision(CollisionData),
+    /// Free fall, impact or tilt, detected on the host from streamed frames
+    /// See `SpheroDevice::set_pickup_detection` and `set_orientation_monitor`.
+    Motion(MotionEvent),
+    /// No sensor data for too long, reported by the stream's watchdog
+    StreamStalled(StreamStall),
+    /// Any other message, or one that could not be decoded
+    Other {
+        /// Asynchronous ID code
+        idcode: u8,
+        /// Raw data payload
+        data: Vec<u8>,
+    },
+}
+
+impl AsyncMessage {
+    /// Decode an async packet
+    /// `mask` is the streaming configuration in effect, needed to decode sensor data.
+    pub fn decode(packet: &SpheroAsynchronousPacketV1, mask: Option<&SensorMask>) -> Self {
+        let data = packet.data();
+        let decoded = match packet.idcode() {
+            0x01 if data.len() == 1 => Some(AsyncMessage::PowerNotification(PowerState::from(data[0]))),
+            0x03 => mask
+                .and_then(|mask| SensorFrame::decode(mask, data).ok())
+                .map(AsyncMessage::SensorData),
+            0x05 => Some(AsyncMessage::PreSleepWarning),
+            0x07 => CollisionData::decode(data)
+                .ok()
+                .map(AsyncMessage::Collision),
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| AsyncMessage::Other {
+            idcode: packet.idcode(),
+            data: data.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Ping, ToCommandPacket};
+    use crate::packet::MRSPField;
+    use crate::sensor::Sensor;
+    use deku::DekuContainerWrite;
+
+    fn async_packet(idcode: u8, data: Vec<u8>) -> SpheroAsynchronousPacketV1 {
+        SpheroAsynchronousPacketV1::new(idcode, data)
+    }
+
+    #[test]
+    fn parse_notification_tells_responses_from_async_messages() {
+        let response = SpheroResponsePacketV1::new(MRSPField::Ok, 7, vec![0x01]);
+        let event = parse_notification(&response.to_bytes().unwrap()).unwrap();
+        assert!(event.is_response_for(&Ping {}.to_packet(7)));
+        assert!(!event.is_response_for(&Ping {}.to_packet(8)));
+        assert_eq!(event.into_response(), Some(response));
+
+        let power = async_packet(0x01, vec![0x02]);
+        let event = parse_notification(&power.to_bytes().unwrap()).unwrap();
+        assert!(!event.is_response_for(&Ping {}.to_packet(7)));
+        assert_eq!(event.as_response(), None);
+        assert_eq!(event, SpheroEvent::Async(power));
+    }
+
+    #[test]
+    fn parse_notification_rejects_other_sop2() {
+        assert!(matches!(parse_notification(&[]), Err(Error::InvalidPacket)));
+        let mut bytes = Ping {}.to_packet(1).to_bytes().unwrap();
+        bytes[1] = 0xfd;
+        assert!(matches!(parse_notification(&bytes), Err(Error::InvalidPacket)));
+    }
+
+    #[test]
+    fn decodes_power_notifications() {
+        let decoded = AsyncMessage::decode(&async_packet(0x01, vec![0x03]), None);
+        assert_eq!(decoded, AsyncMessage::PowerNotification(PowerState::Low));
+        // Anything but a single state byte is left raw
+        let decoded = AsyncMessage::decode(&async_packet(0x01, vec![0x03, 0x00]), None);
+        assert_eq!(decoded, AsyncMessage::Other { idcode: 0x01, data: vec![0x03, 0x00] });
+    }
+
+    #[test]
+    fn decodes_sensor_data_only_with_its_mask() {
+        let mask = SensorMask::from_sensors(&[Sensor::ImuPitch]);
+        let packet = async_packet(0x03, vec![0x00, 0x05, 0xff, 0xfb]);
+        match AsyncMessage::decode(&packet, Some(&mask)) {
+            AsyncMessage::SensorData(frames) => {
+                let pitch: Vec<_> = frames.iter().map(|f| f.get(Sensor::ImuPitch)).collect();
+                assert_eq!(pitch, [Some(5), Some(-5)]);
+            }
+            other => panic!("expected sensor data, got {other:?}"),
+        }
+        assert!(matches!(
+            AsyncMessage::decode(&packet, None),
+            AsyncMessage::Other { idcode: 0x03, .. }
+        ));
+        let odd = async_packet(0x03, vec![0x00, 0x05, 0xff]);
+        assert!(matches!(
+            AsyncMessage::decode(&odd, Some(&mask)),
+            AsyncMessage::Other { idcode: 0x03, .. }
+        ));
+    }
+
+    #[test]
+    fn decodes_pre_sleep_warnings_and_collisions() {
+        let decoded = AsyncMessage::decode(&async_packet(0x05, vec![]), None);
+        assert_eq!(decoded, AsyncMessage::PreSleepWarning);
+
+        let mut data = vec![0x00, 0x10, 0x00, 0x20, 0x00, 0x30, 0x01];
+        data.extend([0x00, 0x40, 0x00, 0x50, 0x7f, 0x00, 0x00, 0x01, 0x00]);
+        match AsyncMessage::decode(&async_packet(0x07, data), None) {
+            AsyncMessage::Collision(collision) => {
+                assert_eq!((collision.x, collision.y, collision.z), (0x10, 0x20, 0x30));
+                assert_eq!((collision.speed, collision.timestamp), (0x7f, 0x100));
+            }
+            other => panic!("expected a collision, got {other:?}"),
+        }
+        let short = AsyncMessage::decode(&async_packet(0x07, vec![0; 15]), None);
+        assert!(matches!(short, AsyncMessage::Other { idcode: 0x07, .. }));
+    }
+
+    #[test]
+    fn other_idcodes_are_left_raw() {
+        let decoded = AsyncMessage::decode(&async_packet(0x08, b"hi".to_vec()), None);
+        assert_eq!(decoded, AsyncMessage::Other { idcode: 0x08, data: b"hi".to_vec() });
+    }
+}
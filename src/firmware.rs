@@ -0,0 +1,220 @@
+/*!
+ * Sphero Firmware Images
+ *
+ * A main application image for `SpheroDevice::reflash`, split into the pages
+ * Here Is Page carries. Images load from a raw binary or from Intel HEX, in
+ * which case the lowest address in the file is taken as the start of the
+ * application and gaps are left erased (0xff).
+ *
+ * The page size is what fits one Here Is Page packet after its page number;
+ * the page count is the size of the application area, assumed to be 128 KiB.
+ */
+use crate::error::Error;
+
+/// Bytes of firmware carried by each Here Is Page command
+pub const FIRMWARE_PAGE_SIZE: usize = 128;
+/// Pages in the main application area
+pub const FIRMWARE_MAX_PAGES: u16 = 1024;
+/// Largest image that fits the main application area, in bytes
+pub const FIRMWARE_MAX_SIZE: usize = FIRMWARE_PAGE_SIZE * FIRMWARE_MAX_PAGES as usize;
+
+/// Sphero Firmware Image
+///
+/// ```
+/// use sphero_rs::error::Error;
+/// use sphero_rs::firmware::{FirmwareImage, FIRMWARE_MAX_SIZE, FIRMWARE_PAGE_SIZE};
+///
+/// let image = FirmwareImage::from_binary(&[0x42; FIRMWARE_PAGE_SIZE + 2])?;
+/// assert_eq!(image.page_count(), 2);
+/// assert_eq!(image.page(0), Some(&[0x42; FIRMWARE_PAGE_SIZE][..]));
+/// // The last page is padded with erased flash
+/// assert_eq!(image.page(1).unwrap()[..3], [0x42, 0x42, 0xff]);
+/// assert_eq!(image.page(2), None);
+/// assert_eq!(image.checksum(), 0x4a10447f);
+///
+/// // Too large for the application area, or empty
+/// assert!(matches!(
+///     FirmwareImage::from_binary(&vec![0; FIRMWARE_MAX_SIZE + 1]),
+///     Err(Error::FirmwareTooLarge { .. })
+/// ));
+/// assert!(matches!(FirmwareImage::from_binary(&[]), Err(Error::BadDataLength)));
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct FirmwareImage {
+    data: Vec<u8>,
+}
+
+impl FirmwareImage {
+    /// Image of the main application from a raw binary, padded to whole
+    /// pages with 0xff
+    ///
+    /// Fails with `Error::BadDataLength` if `data` is empty, or
+    /// `Error::FirmwareTooLarge` if it is over `FIRMWARE_MAX_SIZE`.
+    pub fn from_binary(data: &[u8]) -> Result<Self, Error> {
+        if data.is_empty() {
+            return Err(Error::BadDataLength);
+        }
+        if data.len() > FIRMWARE_MAX_SIZE {
+            return Err(Error::FirmwareTooLarge {
+                len: data.len(),
+                max: FIRMWARE_MAX_SIZE,
+            });
+        }
+        let mut data = data.to_vec();
+        let padded = data.len().div_ceil(FIRMWARE_PAGE_SIZE) * FIRMWARE_PAGE_SIZE;
+        data.resize(padded, 0xff);
+        Ok(Self { data })
+    }
+
+    /// Image of the main application from Intel HEX text
+    ///
+    /// Data, end of file and address records are understood; start address
+    /// records are ignored. A malformed record fails with `Error::HexRecord`
+    /// naming its line, as does a file without an end of file record.
+    ///
+    /// ```
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::firmware::FirmwareImage;
+    ///
+    /// // Two records at 0x0800_0000 and 0x0800_0080
+    /// let hex = ":020000040800F2\n:04000000DEADBEEFC4\n:02008000123438\n:00000001FF\n";
+    /// let image = FirmwareImage::from_ihex(hex)?;
+    /// assert_eq!(image.page_count(), 2);
+    /// assert_eq!(image.page(0).unwrap()[..5], [0xde, 0xad, 0xbe, 0xef, 0xff]);
+    /// assert_eq!(image.page(1).unwrap()[..3], [0x12, 0x34, 0xff]);
+    /// assert_eq!(image.checksum(), 0x0425d089);
+    ///
+    /// let corrupt = hex.replace("C4", "C5");
+    /// assert!(matches!(
+    ///     FirmwareImage::from_ihex(&corrupt),
+    ///     Err(Error::HexRecord { line: 2, .. })
+    /// ));
+    /// let truncated = ":04000000DEADBEEFC4\n";
+    /// assert!(matches!(
+    ///     FirmwareImage::from_ihex(truncated),
+    ///     Err(Error::HexRecord { line: 2, .. })
+    /// ));
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn from_ihex(text: &str) -> Result<Self, Error> {
+        let mut chunks: Vec<(u32, Vec<u8>)> = vec![];
+        let mut base = 0u32;
+        let mut lines = 0;
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            lines = line_no;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at_line = |error: Error| Error::HexRecord {
+                line: line_no,
+                error: Box::new(error),
+            };
+            let (kind, address, data) = parse_record(line).map_err(at_line)?;
+            match kind {
+                0x00 => chunks.push((base.wrapping_add(address as u32), data)),
+                0x01 => return Self::from_chunks(&chunks),
+                0x02 | 0x04 if data.len() == 2 => {
+                    let value = u16::from_be_bytes([data[0], data[1]]) as u32;
+                    base = if kind == 0x02 {
+                        value << 4
+                    } else {
+                        value << 16
+                    };
+                }
+                0x03 | 0x05 => {}
+                _ => return Err(at_line(Error::InvalidPacket)),
+            }
+        }
+        Err(Error::HexRecord {
+            line: lines + 1,
+            error: Box::new(Error::BadDataLength),
+        })
+    }
+
+    /// Lay data records out from the lowest address, gaps erased
+    fn from_chunks(chunks: &[(u32, Vec<u8>)]) -> Result<Self, Error> {
+        let Some(start) = chunks.iter().map(|(address, _)| *address).min() else {
+            return Err(Error::BadDataLength);
+        };
+        let end = chunks
+            .iter()
+            .map(|(address, data)| (address - start) as usize + data.len())
+            .max()
+            .unwrap_or(0);
+        if end > FIRMWARE_MAX_SIZE {
+            return Err(Error::FirmwareTooLarge {
+                len: end,
+                max: FIRMWARE_MAX_SIZE,
+            });
+        }
+        let mut data = vec![0xff; end];
+        for (address, chunk) in chunks {
+            let offset = (address - start) as usize;
+            data[offset..offset + chunk.len()].copy_from_slice(chunk);
+        }
+        Self::from_binary(&data)
+    }
+
+    /// Number of pages
+    pub fn page_count(&self) -> u16 {
+        (self.data.len() / FIRMWARE_PAGE_SIZE) as u16
+    }
+
+    /// Contents of page `page`, if the image has it
+    pub fn page(&self, page: u16) -> Option<&[u8]> {
+        self.data.chunks(FIRMWARE_PAGE_SIZE).nth(page as usize)
+    }
+
+    /// Size in bytes, padding included
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether the image has no pages
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// CRC-32 (as used by zip and `crc32`) of the padded image, for logging
+    pub fn checksum(&self) -> u32 {
+        let crc = self.data.iter().fold(!0u32, |crc, &byte| {
+            (0..8).fold(crc ^ byte as u32, |crc, _| {
+                (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+            })
+        });
+        !crc
+    }
+}
+
+/// Record type, address and data of one Intel HEX line
+fn parse_record(line: &str) -> Result<(u8, u16, Vec<u8>), Error> {
+    let hex = line.strip_prefix(':').ok_or(Error::InvalidPacket)?;
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(Error::InvalidPacket);
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| Error::InvalidPacket))
+        .collect::<Result<Vec<u8>, Error>>()?;
+    let Some((&actual, body)) = bytes.split_last() else {
+        return Err(Error::BadDataLength);
+    };
+    if body.len() < 4 || body.len() != 4 + body[0] as usize {
+        return Err(Error::BadDataLength);
+    }
+    let expected = body
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg();
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+    Ok((
+        body[3],
+        u16::from_be_bytes([body[1], body[2]]),
+        body[4..].to_vec(),
+    ))
+}
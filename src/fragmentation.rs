@@ -0,0 +1,96 @@
+/*!
+ * Sphero Packet Fragmentation
+ *
+ * Packets larger than the link MTU have to be written in several pieces;
+ * the robot reassembles them from the byte stream.
+ */
+use crate::error::Error;
+use crate::packet::{SpheroCommandPacketV1, SpheroResponsePacketV1};
+use deku::{DekuContainerRead, DekuContainerWrite};
+
+/// Split the serialized `packet` into writes of at most `mtu` bytes
+pub fn fragment_packet(packet: &SpheroCommandPacketV1, mtu: usize) -> Result<Vec<Vec<u8>>, Error> {
+    if mtu == 0 {
+        return Err(Error::BadParameterValue);
+    }
+    Ok(packet
+        .to_bytes()?
+        .chunks(mtu)
+        .map(|chunk| chunk.to_vec())
+        .collect())
+}
+
+/// Join received fragments back into a single response packet
+pub fn reassemble_response(chunks: &[Vec<u8>]) -> Result<SpheroResponsePacketV1, Error> {
+    let bytes = chunks.concat();
+    let ((rest, _), packet) = SpheroResponsePacketV1::from_bytes((&bytes, 0))?;
+    if !rest.is_empty() {
+        return Err(Error::InvalidPacket);
+    }
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{SaveTemporaryMacro, ToCommandPacket};
+    use crate::packet::MRSPField;
+
+    fn payload() -> Vec<u8> {
+        (0..40).collect()
+    }
+
+    #[test]
+    fn fragments_are_at_most_mtu_bytes_in_order() {
+        let packet = SaveTemporaryMacro { data: payload() }.to_packet(9);
+        let fragments = fragment_packet(&packet, 20).unwrap();
+        let sizes: Vec<usize> = fragments.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [20, 20, 7]);
+        assert_eq!(fragments.concat(), packet.to_bytes().unwrap());
+        assert_eq!(
+            SpheroCommandPacketV1::parse(&fragments.concat()).unwrap(),
+            packet
+        );
+    }
+
+    #[test]
+    fn small_packet_is_one_fragment() {
+        let packet = SaveTemporaryMacro { data: vec![1] }.to_packet(9);
+        assert_eq!(
+            fragment_packet(&packet, 20).unwrap(),
+            [packet.to_bytes().unwrap()]
+        );
+    }
+
+    #[test]
+    fn zero_mtu_is_rejected() {
+        let packet = SaveTemporaryMacro { data: payload() }.to_packet(9);
+        assert!(matches!(
+            fragment_packet(&packet, 0),
+            Err(Error::BadParameterValue)
+        ));
+    }
+
+    #[test]
+    fn response_reassembles_from_20_byte_fragments() {
+        let response = SpheroResponsePacketV1::new(MRSPField::Ok, 9, payload());
+        let bytes = response.to_bytes().unwrap();
+        let fragments: Vec<Vec<u8>> = bytes.chunks(20).map(<[u8]>::to_vec).collect();
+        assert_eq!(fragments.len(), 3);
+        assert_eq!(reassemble_response(&fragments).unwrap(), response);
+    }
+
+    #[test]
+    fn reassembly_rejects_missing_and_extra_bytes() {
+        let bytes = SpheroResponsePacketV1::new(MRSPField::Ok, 9, payload())
+            .to_bytes()
+            .unwrap();
+        let missing = [bytes[..20].to_vec(), bytes[20..40].to_vec()];
+        assert!(reassemble_response(&missing).is_err());
+        let extra = [bytes.clone(), vec![0xff]];
+        assert!(matches!(
+            reassemble_response(&extra),
+            Err(Error::InvalidPacket)
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+/*!
+ * Sphero Heading
+ *
+ * Headings are whole degrees, 0..359, growing clockwise from the robot's
+ * heading 0. Arithmetic wraps around, so a `Heading` is always in range.
+ */
+use crate::error::Error;
+use std::fmt;
+use std::ops::Add;
+
+/// Heading, 0..359 degrees
+///
+/// ```
+/// use sphero_rs::heading::Heading;
+///
+/// let east = Heading::new(90)?;
+/// assert_eq!(east.opposite().degrees(), 270);
+/// assert_eq!(east.rotate(-100).degrees(), 350);
+/// assert_eq!((east + 300).degrees(), 30);
+/// assert_eq!((east + i16::MIN).degrees(), 82);
+/// assert!(Heading::new(360).is_err());
+///
+/// assert_eq!(Heading::from_degrees_clamped(-90.0).degrees(), 270);
+/// assert_eq!(Heading::from_degrees_clamped(719.6).degrees(), 0);
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Heading(u16);
+
+impl Heading {
+    /// Heading 0
+    pub const ZERO: Heading = Heading(0);
+
+    /// `deg` as a heading; fails with `Error::BadParameterValue` unless it is 0..359
+    pub const fn new(deg: u16) -> Result<Self, Error> {
+        if deg < 360 {
+            Ok(Self(deg))
+        } else {
+            Err(Error::BadParameterValue)
+        }
+    }
+
+    /// Any angle in degrees, rounded and wrapped into 0..359
+    pub fn from_degrees_clamped(deg: f32) -> Self {
+        Self(deg.round().rem_euclid(360.0) as u16 % 360)
+    }
+
+    /// Degrees, 0..359
+    pub const fn degrees(&self) -> u16 {
+        self.0
+    }
+
+    /// Turned half way round
+    pub fn opposite(&self) -> Self {
+        self.rotate(180)
+    }
+
+    /// Turned by `delta` degrees, clockwise when positive
+    pub fn rotate(&self, delta: i16) -> Self {
+        Self((self.0 as i32 + delta as i32).rem_euclid(360) as u16)
+    }
+}
+
+impl Add<i16> for Heading {
+    type Output = Heading;
+
+    fn add(self, delta: i16) -> Heading {
+        self.rotate(delta)
+    }
+}
+
+impl TryFrom<u16> for Heading {
+    type Error = Error;
+
+    fn try_from(deg: u16) -> Result<Self, Error> {
+        Heading::new(deg)
+    }
+}
+
+impl From<Heading> for u16 {
+    fn from(heading: Heading) -> u16 {
+        heading.0
+    }
+}
+
+impl fmt::Display for Heading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}°", self.0)
+    }
+}
@@ -0,0 +1,141 @@
+/*!
+ * Sphero Input Mapping
+ *
+ * Turns a joystick position into a Roll. The stick's +y (pushed away) is
+ * heading 0 and +x (pushed right) is heading 90, matching the way headings
+ * grow clockwise; both axes run from -1 to 1.
+ */
+use crate::command::Roll;
+use crate::heading::Heading;
+
+/// Sphero Stick Mapping Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickOptions {
+    /// Deflection treated as centred, 0..1
+    pub deadzone: f32,
+    /// Speed at full deflection
+    pub max_speed: u8,
+    /// Blend towards a cubic response for finer control near the centre, 0..1
+    pub expo: f32,
+    /// Added to every heading, e.g. the offset found while aiming, in degrees
+    pub heading_offset: u16,
+}
+
+impl Default for StickOptions {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.1,
+            max_speed: 255,
+            expo: 0.0,
+            heading_offset: 0,
+        }
+    }
+}
+
+impl StickOptions {
+    fn deadzone(&self) -> f32 {
+        self.deadzone.clamp(0.0, 0.99)
+    }
+
+    fn expo(&self) -> f32 {
+        self.expo.clamp(0.0, 1.0)
+    }
+}
+
+/// Expo curve over 0..1
+fn curve(t: f32, expo: f32) -> f32 {
+    (1.0 - expo) * t + expo * t * t * t
+}
+
+/// Roll for stick position (`x`, `y`), or `None` inside the deadzone
+///
+/// Deflection past the deadzone is rescaled to 0..1, so speed starts from 0
+/// at its edge; positions beyond the unit circle count as full deflection.
+/// The caller should send a stop for `None`.
+///
+/// ```
+/// use sphero_rs::input::{stick_to_roll, StickOptions};
+///
+/// let options = StickOptions::default();
+/// let cases = [
+///     // Axes, at full deflection
+///     ((0.0, 1.0), Some((255, 0))),
+///     ((1.0, 0.0), Some((255, 90))),
+///     ((0.0, -1.0), Some((255, 180))),
+///     ((-1.0, 0.0), Some((255, 270))),
+///     // One in each quadrant, at 0.71 deflection
+///     ((0.5, 0.5), Some((172, 45))),
+///     ((0.5, -0.5), Some((172, 135))),
+///     ((-0.5, -0.5), Some((172, 225))),
+///     ((-0.5, 0.5), Some((172, 315))),
+///     // Deadzone boundary
+///     ((0.0, 0.0), None),
+///     ((0.1, 0.0), None),
+///     ((0.0, 0.11), Some((3, 0))),
+///     // Past the unit circle
+///     ((1.0, 1.0), Some((255, 45))),
+/// ];
+/// for ((x, y), expected) in cases {
+///     let roll = stick_to_roll(x, y, options).map(|roll| (roll.speed.value(), roll.heading.degrees()));
+///     assert_eq!(roll, expected, "stick ({}, {})", x, y);
+/// }
+///
+/// // The aiming offset turns every heading, wrapping past 359
+/// let aimed = StickOptions { heading_offset: 350, ..options };
+/// assert_eq!(stick_to_roll(1.0, 0.0, aimed).unwrap().heading.degrees(), 80);
+///
+/// // Expo softens the middle of the range but keeps full speed at the edge
+/// let expo = StickOptions { deadzone: 0.0, expo: 0.5, ..options };
+/// assert_eq!(stick_to_roll(0.0, 0.5, expo).unwrap().speed.value(), 80);
+/// assert_eq!(stick_to_roll(0.0, 1.0, expo).unwrap().speed.value(), 255);
+/// ```
+pub fn stick_to_roll(x: f32, y: f32, opts: StickOptions) -> Option<Roll> {
+    let deadzone = opts.deadzone();
+    let deflection = x.hypot(y).min(1.0);
+    if deflection.is_nan() || deflection <= deadzone {
+        return None;
+    }
+    let t = (deflection - deadzone) / (1.0 - deadzone);
+    let speed = (curve(t, opts.expo()) * opts.max_speed as f32).round() as u8;
+    let bearing = x.atan2(y).to_degrees() + opts.heading_offset as f32;
+    Some(Roll {
+        speed: speed.into(),
+        heading: Heading::from_degrees_clamped(bearing),
+        state: true,
+    })
+}
+
+/// Stick position that `stick_to_roll` maps to `roll`, for drawing it on screen
+///
+/// Zero speed maps to the centre.
+///
+/// ```
+/// use sphero_rs::input::{roll_to_stick, stick_to_roll, StickOptions};
+///
+/// let options = StickOptions { expo: 0.3, heading_offset: 20, ..StickOptions::default() };
+/// for (x, y) in [(0.3, -0.6), (-0.8, 0.1), (0.0, 0.9)] {
+///     let roll = stick_to_roll(x, y, options).unwrap();
+///     let (sx, sy) = roll_to_stick(&roll, options);
+///     assert!((sx - x).abs() < 0.02 && (sy - y).abs() < 0.02, "({}, {})", sx, sy);
+/// }
+/// ```
+pub fn roll_to_stick(roll: &Roll, opts: StickOptions) -> (f32, f32) {
+    if roll.speed.is_zero() || opts.max_speed == 0 {
+        return (0.0, 0.0);
+    }
+    let target = (roll.speed.value() as f32 / opts.max_speed as f32).min(1.0);
+    // The curve rises monotonically over 0..1, so bisect for its inverse
+    let (mut low, mut high) = (0.0f32, 1.0f32);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.0;
+        if curve(mid, opts.expo()) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let deadzone = opts.deadzone();
+    let deflection = deadzone + (low + high) / 2.0 * (1.0 - deadzone);
+    let bearing = (roll.heading.degrees() as f32 - opts.heading_offset as f32).to_radians();
+    (deflection * bearing.sin(), deflection * bearing.cos())
+}
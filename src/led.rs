@@ -0,0 +1,340 @@
+/*!
+ * Sphero LED Patterns
+ *
+ * Canned effects on the main LED for status on a robot without a screen.
+ * Each pattern is a list of colors to hold in turn; afterwards, or when
+ * cancelled, the LED goes back to the color it showed before.
+ */
+use crate::color::{HsvColor, RgbColor};
+use crate::command::{GetRGBLEDOutput, SetRGBLEDOutput};
+use crate::device::SpheroDevice;
+use crate::error::Error;
+use crate::runtime;
+use crate::transport::Transport;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Steps in one pulse, each way
+const PULSE_STEPS: u32 = 8;
+/// How long cancellation may take to be noticed
+const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// How a pattern is played
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PatternStrategy {
+    /// The host sends every color change
+    #[default]
+    HostDriven,
+    /// Compiled to a macro and run by the robot
+    /// Fails with `Error::NotImplemented`: there is no macro compiler yet.
+    Macro,
+}
+
+/// Sphero LED Pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// `times` flashes of `color`, each on then off for half the `period`
+    Blink {
+        /// Color when on
+        color: RgbColor,
+        /// Number of flashes
+        times: u8,
+        /// Time from one flash to the next
+        period: Duration,
+    },
+    /// Fade up to `color` and back down once over `period`
+    Pulse {
+        /// Brightest color
+        color: RgbColor,
+        /// Length of the pulse
+        period: Duration,
+    },
+    /// Red and blue, alternating
+    Police,
+    /// Red to green by charge, held for two seconds
+    BatteryGauge(u8),
+}
+
+impl Pattern {
+    /// Colors to show in turn, each with how long to hold it
+    ///
+    /// ```
+    /// use sphero_rs::color::RgbColor;
+    /// use sphero_rs::led::Pattern;
+    /// use std::time::Duration;
+    ///
+    /// let blink = Pattern::Blink {
+    ///     color: RgbColor::RED,
+    ///     times: 2,
+    ///     period: Duration::from_millis(400),
+    /// };
+    /// let half = Duration::from_millis(200);
+    /// assert_eq!(
+    ///     blink.frames(),
+    ///     vec![
+    ///         (RgbColor::RED, half),
+    ///         (RgbColor::BLACK, half),
+    ///         (RgbColor::RED, half),
+    ///         (RgbColor::BLACK, half),
+    ///     ]
+    /// );
+    ///
+    /// assert_eq!(Pattern::BatteryGauge(100).frames()[0].0, RgbColor::GREEN);
+    /// assert_eq!(Pattern::BatteryGauge(0).frames()[0].0, RgbColor::RED);
+    /// ```
+    pub fn frames(&self) -> Vec<(RgbColor, Duration)> {
+        match *self {
+            Pattern::Blink {
+                color,
+                times,
+                period,
+            } => (0..times)
+                .flat_map(|_| [(color, period / 2), (RgbColor::BLACK, period / 2)])
+                .collect(),
+            Pattern::Pulse { color, period } => {
+                let step = period / (2 * PULSE_STEPS);
+                let up = (1..=PULSE_STEPS).map(|i| i as f32 / PULSE_STEPS as f32);
+                let down = (0..PULSE_STEPS)
+                    .rev()
+                    .map(|i| i as f32 / PULSE_STEPS as f32);
+                up.chain(down)
+                    .map(|level| (RgbColor::BLACK.lerp(color, level), step))
+                    .collect()
+            }
+            Pattern::Police => {
+                let flash = Duration::from_millis(150);
+                (0..4)
+                    .flat_map(|_| [(RgbColor::RED, flash), (RgbColor::BLUE, flash)])
+                    .collect()
+            }
+            Pattern::BatteryGauge(percent) => {
+                let hue = 120.0 * percent.min(100) as f32 / 100.0;
+                let color = RgbColor::from(HsvColor::new(hue, 1.0, 1.0));
+                vec![(color, Duration::from_secs(2))]
+            }
+        }
+    }
+}
+
+/// Sphero LED Patterns
+///
+/// Plays one pattern at a time on a device's main LED. `cancel` ends the
+/// pattern playing now, which then restores the LED and fails with
+/// `Error::Cancelled`.
+pub struct Patterns<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    strategy: PatternStrategy,
+    /// Bumped by `cancel`; patterns started before then stop
+    generation: AtomicU64,
+}
+
+impl<'a, T: Transport + 'static> Patterns<'a, T> {
+    /// Play patterns on `device`'s LED
+    pub fn new(device: &'a SpheroDevice<T>, strategy: PatternStrategy) -> Self {
+        Self {
+            device,
+            strategy,
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Flash `color` `times` times
+    pub fn blink(
+        &self,
+        color: RgbColor,
+        times: u8,
+        period: Duration,
+    ) -> impl Future<Output = Result<(), Error>> + '_ {
+        self.play(Pattern::Blink {
+            color,
+            times,
+            period,
+        })
+    }
+
+    /// Fade up to `color` and back down
+    pub fn pulse(
+        &self,
+        color: RgbColor,
+        period: Duration,
+    ) -> impl Future<Output = Result<(), Error>> + '_ {
+        self.play(Pattern::Pulse { color, period })
+    }
+
+    /// Alternate red and blue
+    pub fn police(&self) -> impl Future<Output = Result<(), Error>> + '_ {
+        self.play(Pattern::Police)
+    }
+
+    /// Show the battery charge, from red at 0 % to green at 100 %
+    pub fn battery_gauge(&self, percent: u8) -> impl Future<Output = Result<(), Error>> + '_ {
+        self.play(Pattern::BatteryGauge(percent))
+    }
+
+    /// Stop the pattern playing now, if any
+    pub fn cancel(&self) {
+        let _ = self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Play `pattern`, then restore the LED
+    ///
+    /// The color to restore is the last one sent through the device, or
+    /// else the one Get RGB LED Output reports.
+    pub fn play(&self, pattern: Pattern) -> impl Future<Output = Result<(), Error>> + '_ {
+        let generation = self.generation.load(Ordering::Acquire);
+        async move {
+            if self.strategy == PatternStrategy::Macro {
+                return Err(Error::NotImplemented);
+            }
+            let previous = match self.device.led_color() {
+                Some(color) => color,
+                None => self.device.query(&GetRGBLEDOutput {}).await?.color,
+            };
+            let played = self.frames(&pattern, generation).await;
+            self.set(previous).await?;
+            played
+        }
+    }
+
+    fn cancelled(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Acquire) != generation
+    }
+
+    async fn frames(&self, pattern: &Pattern, generation: u64) -> Result<(), Error> {
+        for (color, hold) in pattern.frames() {
+            if self.cancelled(generation) {
+                return Err(Error::Cancelled);
+            }
+            self.set(color).await?;
+            let until = Instant::now() + hold;
+            while let Some(left) = until.checked_duration_since(Instant::now()) {
+                if left.is_zero() || self.cancelled(generation) {
+                    break;
+                }
+                runtime::sleep(left.min(CANCEL_POLL)).await;
+            }
+        }
+        if self.cancelled(generation) {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    async fn set(&self, color: RgbColor) -> Result<(), Error> {
+        drop(
+            self.device
+                .send(&SetRGBLEDOutput::from_color(color, false))
+                .await?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::packet::{MRSPField, SpheroCommandID, SpheroCommandPacketV1};
+    use crate::transport::mock::{respond, MockTransport};
+
+    const TEAL: RgbColor = RgbColor::new(0, 0x40, 0x40);
+
+    /// Mock robot reporting its LED as teal
+    fn robot() -> MockTransport {
+        MockTransport::with_responder(|bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            let data = match packet.cid() == SpheroCommandID::GetRGBLEDOutput as u8 {
+                true => vec![TEAL.red, TEAL.green, TEAL.blue],
+                false => vec![],
+            };
+            vec![respond(&packet, MRSPField::Ok, data)]
+        })
+    }
+
+    /// Colors sent with Set RGB LED Output so far
+    fn led_writes(mock: &MockTransport) -> Vec<RgbColor> {
+        mock.written_packets()
+            .iter()
+            .filter(|packet| packet.cid() == SpheroCommandID::SetRGBLEDOutput as u8)
+            .map(|packet| {
+                let data = packet.data();
+                RgbColor::new(data[0], data[1], data[2])
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn blink_restores_the_color_read_from_the_robot() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        let patterns = Patterns::new(&device, PatternStrategy::HostDriven);
+
+        let blinked = patterns
+            .blink(RgbColor::RED, 2, Duration::from_millis(10))
+            .await;
+
+        assert!(blinked.is_ok());
+        let (red, black) = (RgbColor::RED, RgbColor::BLACK);
+        assert_eq!(led_writes(&mock), [red, black, red, black, TEAL]);
+        assert_eq!(device.led_color(), Some(TEAL));
+    }
+
+    #[tokio::test]
+    async fn last_color_sent_is_restored_without_asking() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        let green = SetRGBLEDOutput::from_color(RgbColor::GREEN, false);
+        drop(device.send(&green).await.unwrap());
+        let patterns = Patterns::new(&device, PatternStrategy::HostDriven);
+
+        let blinked = patterns
+            .blink(RgbColor::RED, 1, Duration::from_millis(2))
+            .await;
+
+        assert!(blinked.is_ok());
+        let (red, black, green) = (RgbColor::RED, RgbColor::BLACK, RgbColor::GREEN);
+        assert_eq!(led_writes(&mock), [green, red, black, green]);
+        assert!(!mock
+            .written_packets()
+            .iter()
+            .any(|packet| packet.cid() == SpheroCommandID::GetRGBLEDOutput as u8));
+    }
+
+    #[tokio::test]
+    async fn cancelled_pulse_still_restores_the_color() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        let patterns = Patterns::new(&device, PatternStrategy::HostDriven);
+
+        let pulse = patterns.pulse(RgbColor::WHITE, Duration::from_secs(5));
+        let cancel = async {
+            while led_writes(&mock).is_empty() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            patterns.cancel();
+        };
+        let (pulsed, ()) = tokio::join!(pulse, cancel);
+
+        assert!(matches!(pulsed, Err(Error::Cancelled)));
+        let writes = led_writes(&mock);
+        assert!(
+            writes.len() < 2 * PULSE_STEPS as usize,
+            "pulse ran to the end"
+        );
+        assert_eq!(writes.last(), Some(&TEAL));
+    }
+
+    #[tokio::test]
+    async fn macro_strategy_is_not_implemented() {
+        let mock = robot();
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        mock.clear_written();
+        let patterns = Patterns::new(&device, PatternStrategy::Macro);
+
+        assert!(matches!(
+            patterns.police().await,
+            Err(Error::NotImplemented)
+        ));
+        assert!(led_writes(&mock).is_empty());
+    }
+}
@@ -11,6 +11,63 @@
 #![warn(unused_imports)]
 #![allow(missing_copy_implementations)]
 
+#[cfg(feature = "async")]
+pub mod aim;
+pub mod appdata;
+pub mod async_payload;
+#[cfg(feature = "async")]
+mod broadcast;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod capabilities;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod client;
+pub mod clock;
+pub mod collision;
+pub mod color;
 pub mod command;
+pub mod config;
+#[cfg(feature = "async")]
+pub mod device;
+pub mod discover;
+#[cfg(feature = "async")]
+pub mod drive;
+pub mod dump;
 pub mod error;
+pub mod event;
+pub mod firmware;
+pub mod fragmentation;
+pub mod heading;
+pub mod input;
+#[cfg(feature = "async")]
+pub mod led;
+pub mod logging;
+pub mod macros;
+#[cfg(feature = "async")]
+pub mod nav;
+pub mod orbbasic;
 pub mod packet;
+pub mod power;
+pub mod ratelimit;
+pub mod reader;
+pub mod reflash;
+#[cfg(feature = "async")]
+pub mod reconnect;
+pub mod response;
+#[cfg(feature = "async")]
+pub mod runtime;
+#[cfg(feature = "serde")]
+pub mod script;
+pub mod sensor;
+pub mod sequence;
+pub mod seq;
+pub mod speed;
+#[cfg(feature = "async")]
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod swarm;
+mod trace;
+pub mod transport;
+
+pub use async_payload::AsyncPayload;
@@ -0,0 +1,309 @@
+/*!
+ * Sphero Sensor Logging
+ *
+ * Writes streamed sensor frames as CSV or JSON lines, one row per frame,
+ * for analysis off the robot. Columns are the host time, optionally the
+ * robot's clock reading from clock sync, and every source enabled in the
+ * streaming masks, converted to the unit noted on the source.
+ */
+use crate::clock::ClockOffset;
+use crate::error::Error;
+use crate::sensor::{Sensor, SensorFrame, SensorMask};
+use futures::{Stream, StreamExt};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Row layout
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Comma-separated values, with a header at the start of every file
+    #[default]
+    Csv,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Sensor Logger Options
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogOptions {
+    /// Row layout
+    pub format: LogFormat,
+    /// Adds a `robot_ms` column converted from the host time when set
+    pub clock: Option<ClockOffset>,
+    /// Host time between flushes of the writer
+    pub flush_interval: Duration,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Csv,
+            clock: None,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Column name of `sensor`, with its unit
+fn column(sensor: Sensor) -> &'static str {
+    match sensor {
+        Sensor::AccelXRaw => "accel_x_raw",
+        Sensor::AccelYRaw => "accel_y_raw",
+        Sensor::AccelZRaw => "accel_z_raw",
+        Sensor::GyroXRaw => "gyro_x_raw",
+        Sensor::GyroYRaw => "gyro_y_raw",
+        Sensor::GyroZRaw => "gyro_z_raw",
+        Sensor::RightMotorEmfRaw => "right_motor_emf_raw",
+        Sensor::LeftMotorEmfRaw => "left_motor_emf_raw",
+        Sensor::LeftMotorPwmRaw => "left_motor_pwm_raw",
+        Sensor::RightMotorPwmRaw => "right_motor_pwm_raw",
+        Sensor::ImuPitch => "imu_pitch_deg",
+        Sensor::ImuRoll => "imu_roll_deg",
+        Sensor::ImuYaw => "imu_yaw_deg",
+        Sensor::AccelX => "accel_x_g",
+        Sensor::AccelY => "accel_y_g",
+        Sensor::AccelZ => "accel_z_g",
+        Sensor::GyroX => "gyro_x_dps",
+        Sensor::GyroY => "gyro_y_dps",
+        Sensor::GyroZ => "gyro_z_dps",
+        Sensor::RightMotorEmf => "right_motor_emf",
+        Sensor::LeftMotorEmf => "left_motor_emf",
+        Sensor::QuaternionQ0 => "quaternion_q0",
+        Sensor::QuaternionQ1 => "quaternion_q1",
+        Sensor::QuaternionQ2 => "quaternion_q2",
+        Sensor::QuaternionQ3 => "quaternion_q3",
+        Sensor::OdometerX => "odometer_x_cm",
+        Sensor::OdometerY => "odometer_y_cm",
+        Sensor::AccelOne => "accel_one_mg",
+        Sensor::VelocityX => "velocity_x_mm_s",
+        Sensor::VelocityY => "velocity_y_mm_s",
+    }
+}
+
+/// Starts the next file once the current one is full
+type Rotate<W> = Box<dyn FnMut(&mut W) -> io::Result<W> + Send>;
+
+/// Sphero Sensor Logger
+///
+/// Writes to any `io::Write`; wrap files in a `BufWriter`, since every row
+/// is a separate write and the writer is only flushed every
+/// `LogOptions::flush_interval` of host time.
+///
+/// ```
+/// use sphero_rs::clock::ClockOffset;
+/// use sphero_rs::logging::{LogFormat, LogOptions, SensorLogger};
+/// use sphero_rs::sensor::{Sensor, SensorFrame, SensorMask};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let mask = SensorMask::from_sensors(&[Sensor::AccelZ, Sensor::ImuPitch]);
+/// let frame = SensorFrame {
+///     values: vec![(Sensor::ImuPitch, -12), (Sensor::AccelZ, 2048)],
+/// };
+/// let at = UNIX_EPOCH + Duration::from_millis(1_500);
+///
+/// let mut csv = SensorLogger::new(Vec::new(), mask, LogOptions::default());
+/// assert_eq!(csv.header(), "host_time_s,imu_pitch_deg,accel_z_g");
+/// csv.log(&frame, at)?;
+/// csv.log(&SensorFrame::default(), at)?;
+/// let text = String::from_utf8(csv.into_inner()?).unwrap();
+/// assert_eq!(text, "host_time_s,imu_pitch_deg,accel_z_g\n1.500,-12,0.5\n1.500,,\n");
+///
+/// // Robot clock 1 s ahead of the host
+/// let clock = ClockOffset { epoch: UNIX_EPOCH, offset_ms: 1_000, delay_ms: 0 };
+/// let options = LogOptions {
+///     format: LogFormat::JsonLines,
+///     clock: Some(clock),
+///     ..LogOptions::default()
+/// };
+/// let mut json = SensorLogger::new(Vec::new(), mask, options);
+/// json.log(&frame, at)?;
+/// let text = String::from_utf8(json.into_inner()?).unwrap();
+/// assert_eq!(
+///     text,
+///     "{\"host_time_s\":1.500,\"robot_ms\":2500,\"imu_pitch_deg\":-12,\"accel_z_g\":0.5}\n"
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct SensorLogger<W: Write> {
+    writer: W,
+    sensors: Vec<Sensor>,
+    options: LogOptions,
+    rotate: Option<(u64, Rotate<W>)>,
+    /// Bytes written to the current file
+    written: u64,
+    last_flush: Option<SystemTime>,
+}
+
+impl<W: Write> SensorLogger<W> {
+    /// Log frames streamed with `mask` to `writer`
+    pub fn new(writer: W, mask: SensorMask, options: LogOptions) -> Self {
+        Self {
+            writer,
+            sensors: mask.sensors(),
+            options,
+            rotate: None,
+            written: 0,
+            last_flush: None,
+        }
+    }
+
+    /// Start a new file rather than let this one grow past `max_bytes`
+    ///
+    /// `next` is handed the full writer, already flushed, and returns the
+    /// one to continue with; the full one is dropped afterwards. A single
+    /// row larger than `max_bytes` still gets a file to itself.
+    ///
+    /// ```
+    /// use sphero_rs::logging::{LogOptions, SensorLogger};
+    /// use sphero_rs::sensor::{Sensor, SensorFrame, SensorMask};
+    /// use std::sync::mpsc;
+    /// use std::time::UNIX_EPOCH;
+    ///
+    /// let mask = SensorMask::from_sensors(&[Sensor::OdometerX]);
+    /// let (full, parts) = mpsc::channel();
+    /// let mut logger = SensorLogger::new(Vec::new(), mask, LogOptions::default())
+    ///     .with_rotation(45, move |part: &mut Vec<u8>| {
+    ///         full.send(String::from_utf8(std::mem::take(part)).unwrap()).unwrap();
+    ///         Ok(Vec::new())
+    ///     });
+    /// for x in 0..5 {
+    ///     let frame = SensorFrame { values: vec![(Sensor::OdometerX, x)] };
+    ///     logger.log(&frame, UNIX_EPOCH)?;
+    /// }
+    /// let last = String::from_utf8(logger.into_inner()?).unwrap();
+    ///
+    /// let header = "host_time_s,odometer_x_cm\n";
+    /// let parts: Vec<String> = parts.try_iter().collect();
+    /// assert_eq!(parts, vec![
+    ///     format!("{header}0.000,0\n0.000,1\n"),
+    ///     format!("{header}0.000,2\n0.000,3\n"),
+    /// ]);
+    /// assert_eq!(last, format!("{header}0.000,4\n"));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn with_rotation(
+        mut self,
+        max_bytes: u64,
+        next: impl FnMut(&mut W) -> io::Result<W> + Send + 'static,
+    ) -> Self {
+        self.rotate = Some((max_bytes, Box::new(next)));
+        self
+    }
+
+    /// Column names, comma-separated
+    pub fn header(&self) -> String {
+        let mut columns = vec!["host_time_s"];
+        if self.options.clock.is_some() {
+            columns.push("robot_ms");
+        }
+        columns.extend(self.sensors.iter().map(|&sensor| column(sensor)));
+        columns.join(",")
+    }
+
+    /// Write `frame`, received at host time `at`
+    /// Sources missing from the frame are left empty, or `null` in JSON.
+    pub fn log(&mut self, frame: &SensorFrame, at: SystemTime) -> io::Result<()> {
+        let row = self.row(frame, at);
+        if let Some((max_bytes, next)) = &mut self.rotate {
+            if self.written > 0 && self.written + row.len() as u64 > *max_bytes {
+                self.writer.flush()?;
+                self.writer = next(&mut self.writer)?;
+                self.written = 0;
+            }
+        }
+        if self.written == 0 && self.options.format == LogFormat::Csv {
+            let header = self.header() + "\n";
+            self.write(header.as_bytes())?;
+        }
+        self.write(row.as_bytes())?;
+
+        match self.last_flush {
+            Some(last)
+                if at.duration_since(last).unwrap_or_default() < self.options.flush_interval => {}
+            Some(_) => {
+                self.writer.flush()?;
+                self.last_flush = Some(at);
+            }
+            None => self.last_flush = Some(at),
+        }
+        Ok(())
+    }
+
+    /// Log every frame of `frames`, stamped as it arrives, until the stream ends
+    ///
+    /// Frames that failed to decode are skipped. Takes a `SensorStream` by
+    /// `&mut`, so it can still be stopped afterwards. Returns the rows written.
+    pub async fn record<S>(&mut self, mut frames: S) -> io::Result<u64>
+    where
+        S: Stream<Item = Result<SensorFrame, Error>> + Unpin,
+    {
+        let mut rows = 0;
+        while let Some(frame) = frames.next().await {
+            if let Ok(frame) = frame {
+                self.log(&frame, SystemTime::now())?;
+                rows += 1;
+            }
+        }
+        self.writer.flush()?;
+        Ok(rows)
+    }
+
+    /// Flush the writer now
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flush and return the writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn row(&self, frame: &SensorFrame, at: SystemTime) -> String {
+        let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut cells = vec![(
+            "host_time_s",
+            Some(format!(
+                "{}.{:03}",
+                since_epoch.as_secs(),
+                since_epoch.subsec_millis()
+            )),
+        )];
+        if let Some(clock) = &self.options.clock {
+            cells.push(("robot_ms", Some(clock.to_robot_time(at).to_string())));
+        }
+        cells.extend(self.sensors.iter().map(|&sensor| {
+            let value = frame.get(sensor).map(|v| sensor.convert(v).to_string());
+            (column(sensor), value)
+        }));
+
+        let mut row = String::new();
+        match self.options.format {
+            LogFormat::Csv => {
+                let cells: Vec<&str> = cells
+                    .iter()
+                    .map(|(_, value)| value.as_deref().unwrap_or(""))
+                    .collect();
+                row.push_str(&cells.join(","));
+            }
+            LogFormat::JsonLines => {
+                row.push('{');
+                for (i, (name, value)) in cells.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { "," };
+                    let value = value.as_deref().unwrap_or("null");
+                    let _ = write!(row, "{sep}\"{name}\":{value}");
+                }
+                row.push('}');
+            }
+        }
+        row.push('\n');
+        row
+    }
+}
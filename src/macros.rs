@@ -0,0 +1,814 @@
+/*!
+ * Sphero Macros
+ *
+ * The macro executive runs bytecode on the robot. A macro starts with a
+ * header, `MacroHeader`: its ID if it is persistent, then a flags byte and,
+ * when extended flags are set, an extended flags byte. Then come the
+ * commands: each is an opcode byte followed by big-endian operands, and
+ * commands that act on the robot end with a post-command delay (PCD) in ms.
+ * Upload the bytes with `SpheroDevice::run_macro_bytes`.
+ */
+use crate::color::RgbColor;
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::speed::Speed;
+use std::ops::{BitOr, BitOrAssign};
+
+/// Largest macro sent in one Save Temporary Macro packet
+pub const MAX_MACRO_LEN: usize = 254;
+/// Deepest loop nesting the builder accepts
+pub const MAX_LOOP_DEPTH: usize = 4;
+
+/// Sphero Macro Opcodes
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum MacroOpcode {
+    /// End of the macro
+    End = 0x00,
+    /// Set system delay 1
+    SetSd1 = 0x01,
+    /// Set system delay 2
+    SetSd2 = 0x02,
+    /// Turn stabilization on or off
+    SetStabilization = 0x03,
+    /// Set the heading offset
+    SetHeading = 0x04,
+    /// Roll
+    Roll = 0x05,
+    /// Set the main LED
+    SetRgbLed = 0x07,
+    /// Set the back LED
+    SetBackLed = 0x08,
+    /// Drive the motors directly
+    SetRawMotors = 0x0a,
+    /// Wait
+    Delay = 0x0b,
+    /// Jump to another macro
+    Goto = 0x0c,
+    /// Call another macro
+    Gosub = 0x0d,
+    /// Fade the main LED to a color over time
+    FadeToLed = 0x14,
+    /// Send a macro marker async message
+    EmitMarker = 0x15,
+    /// Wait until the robot stops, or a timeout
+    WaitUntilStopped = 0x19,
+    /// Turn by an angle over time
+    RotateOverTime = 0x1a,
+    /// End of a streamed macro
+    StreamEnd = 0x1b,
+    /// Roll, then wait
+    Roll2 = 0x1d,
+    /// Start of a loop
+    LoopStart = 0x1e,
+    /// End of a loop
+    LoopEnd = 0x1f,
+}
+
+impl MacroOpcode {
+    /// Opcode of `byte`, `None` if it isn't a documented one
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        use MacroOpcode::*;
+        [
+            End,
+            SetSd1,
+            SetSd2,
+            SetStabilization,
+            SetHeading,
+            Roll,
+            SetRgbLed,
+            SetBackLed,
+            SetRawMotors,
+            Delay,
+            Goto,
+            Gosub,
+            FadeToLed,
+            EmitMarker,
+            WaitUntilStopped,
+            RotateOverTime,
+            StreamEnd,
+            Roll2,
+            LoopStart,
+            LoopEnd,
+        ]
+        .into_iter()
+        .find(|op| *op as u8 == byte)
+    }
+
+    /// Bytes of operands following the opcode
+    pub fn operand_len(self) -> usize {
+        match self {
+            MacroOpcode::End | MacroOpcode::StreamEnd | MacroOpcode::LoopEnd => 0,
+            MacroOpcode::Goto
+            | MacroOpcode::Gosub
+            | MacroOpcode::EmitMarker
+            | MacroOpcode::LoopStart => 1,
+            MacroOpcode::SetSd1
+            | MacroOpcode::SetSd2
+            | MacroOpcode::SetStabilization
+            | MacroOpcode::SetBackLed
+            | MacroOpcode::Delay
+            | MacroOpcode::WaitUntilStopped => 2,
+            MacroOpcode::SetHeading => 3,
+            MacroOpcode::Roll | MacroOpcode::SetRgbLed | MacroOpcode::RotateOverTime => 4,
+            MacroOpcode::SetRawMotors | MacroOpcode::FadeToLed | MacroOpcode::Roll2 => 5,
+        }
+    }
+}
+
+/// One decoded macro command
+///
+/// `pcd` is the post-command delay in ms; headings are in degrees.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MacroInstruction {
+    /// End of the macro
+    End,
+    /// Set system delay 1, in ms
+    SetSd1(u16),
+    /// Set system delay 2, in ms
+    SetSd2(u16),
+    /// Turn stabilization on or off
+    SetStabilization {
+        /// Stabilization on
+        enabled: bool,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Set the heading offset
+    SetHeading {
+        /// New heading
+        heading: u16,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Roll
+    Roll {
+        /// Speed, 0 to 255
+        speed: u8,
+        /// Heading
+        heading: u16,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Set the main LED
+    SetRgbLed {
+        /// Color
+        color: RgbColor,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Set the back LED
+    SetBackLed {
+        /// Brightness
+        brightness: u8,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Drive the motors directly
+    SetRawMotors {
+        /// Left motor mode
+        left_mode: u8,
+        /// Left motor power
+        left_power: u8,
+        /// Right motor mode
+        right_mode: u8,
+        /// Right motor power
+        right_power: u8,
+        /// Post-command delay
+        pcd: u8,
+    },
+    /// Wait, in ms
+    Delay(u16),
+    /// Jump to the macro with this ID
+    Goto(u8),
+    /// Call the macro with this ID
+    Gosub(u8),
+    /// Fade the main LED to a color
+    FadeToLed {
+        /// Final color
+        color: RgbColor,
+        /// Duration of the fade
+        ms: u16,
+    },
+    /// Send a macro marker async message with this ID
+    EmitMarker(u8),
+    /// Wait until the robot stops, for at most this many ms
+    WaitUntilStopped(u16),
+    /// Turn by an angle
+    RotateOverTime {
+        /// Angle, clockwise when positive
+        degrees: i16,
+        /// Duration of the turn
+        ms: u16,
+    },
+    /// End of a streamed macro
+    StreamEnd,
+    /// Roll, then wait
+    Roll2 {
+        /// Speed, 0 to 255
+        speed: u8,
+        /// Heading
+        heading: u16,
+        /// Wait after the roll, in ms
+        delay: u16,
+    },
+    /// Start of a loop run this many times
+    LoopStart(u8),
+    /// End of a loop
+    LoopEnd,
+}
+
+impl MacroInstruction {
+    /// Opcode of the instruction
+    pub fn opcode(&self) -> MacroOpcode {
+        match self {
+            MacroInstruction::End => MacroOpcode::End,
+            MacroInstruction::SetSd1(_) => MacroOpcode::SetSd1,
+            MacroInstruction::SetSd2(_) => MacroOpcode::SetSd2,
+            MacroInstruction::SetStabilization { .. } => MacroOpcode::SetStabilization,
+            MacroInstruction::SetHeading { .. } => MacroOpcode::SetHeading,
+            MacroInstruction::Roll { .. } => MacroOpcode::Roll,
+            MacroInstruction::SetRgbLed { .. } => MacroOpcode::SetRgbLed,
+            MacroInstruction::SetBackLed { .. } => MacroOpcode::SetBackLed,
+            MacroInstruction::SetRawMotors { .. } => MacroOpcode::SetRawMotors,
+            MacroInstruction::Delay(_) => MacroOpcode::Delay,
+            MacroInstruction::Goto(_) => MacroOpcode::Goto,
+            MacroInstruction::Gosub(_) => MacroOpcode::Gosub,
+            MacroInstruction::FadeToLed { .. } => MacroOpcode::FadeToLed,
+            MacroInstruction::EmitMarker(_) => MacroOpcode::EmitMarker,
+            MacroInstruction::WaitUntilStopped(_) => MacroOpcode::WaitUntilStopped,
+            MacroInstruction::RotateOverTime { .. } => MacroOpcode::RotateOverTime,
+            MacroInstruction::StreamEnd => MacroOpcode::StreamEnd,
+            MacroInstruction::Roll2 { .. } => MacroOpcode::Roll2,
+            MacroInstruction::LoopStart(_) => MacroOpcode::LoopStart,
+            MacroInstruction::LoopEnd => MacroOpcode::LoopEnd,
+        }
+    }
+
+    /// Append the instruction's bytes to `out`
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(self.opcode() as u8);
+        match *self {
+            MacroInstruction::End | MacroInstruction::StreamEnd | MacroInstruction::LoopEnd => {}
+            MacroInstruction::SetSd1(ms)
+            | MacroInstruction::SetSd2(ms)
+            | MacroInstruction::Delay(ms)
+            | MacroInstruction::WaitUntilStopped(ms) => out.extend_from_slice(&ms.to_be_bytes()),
+            MacroInstruction::SetStabilization { enabled, pcd } => {
+                out.extend_from_slice(&[enabled as u8, pcd])
+            }
+            MacroInstruction::SetHeading { heading, pcd } => {
+                out.extend_from_slice(&heading.to_be_bytes());
+                out.push(pcd);
+            }
+            MacroInstruction::Roll {
+                speed,
+                heading,
+                pcd,
+            } => {
+                out.push(speed);
+                out.extend_from_slice(&heading.to_be_bytes());
+                out.push(pcd);
+            }
+            MacroInstruction::SetRgbLed { color, pcd } => {
+                out.extend_from_slice(&[color.red, color.green, color.blue, pcd])
+            }
+            MacroInstruction::SetBackLed { brightness, pcd } => {
+                out.extend_from_slice(&[brightness, pcd])
+            }
+            MacroInstruction::SetRawMotors {
+                left_mode,
+                left_power,
+                right_mode,
+                right_power,
+                pcd,
+            } => out.extend_from_slice(&[left_mode, left_power, right_mode, right_power, pcd]),
+            MacroInstruction::Goto(id)
+            | MacroInstruction::Gosub(id)
+            | MacroInstruction::EmitMarker(id)
+            | MacroInstruction::LoopStart(id) => out.push(id),
+            MacroInstruction::FadeToLed { color, ms } => {
+                out.extend_from_slice(&[color.red, color.green, color.blue]);
+                out.extend_from_slice(&ms.to_be_bytes());
+            }
+            MacroInstruction::RotateOverTime { degrees, ms } => {
+                out.extend_from_slice(&degrees.to_be_bytes());
+                out.extend_from_slice(&ms.to_be_bytes());
+            }
+            MacroInstruction::Roll2 {
+                speed,
+                heading,
+                delay,
+            } => {
+                out.push(speed);
+                out.extend_from_slice(&heading.to_be_bytes());
+                out.extend_from_slice(&delay.to_be_bytes());
+            }
+        }
+    }
+
+    /// Decode the operands `b` of `opcode`; `b` is `opcode.operand_len()` long
+    fn decode(opcode: MacroOpcode, b: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_be_bytes([b[i], b[i + 1]]);
+        match opcode {
+            MacroOpcode::End => MacroInstruction::End,
+            MacroOpcode::SetSd1 => MacroInstruction::SetSd1(u16_at(0)),
+            MacroOpcode::SetSd2 => MacroInstruction::SetSd2(u16_at(0)),
+            MacroOpcode::SetStabilization => MacroInstruction::SetStabilization {
+                enabled: b[0] != 0,
+                pcd: b[1],
+            },
+            MacroOpcode::SetHeading => MacroInstruction::SetHeading {
+                heading: u16_at(0),
+                pcd: b[2],
+            },
+            MacroOpcode::Roll => MacroInstruction::Roll {
+                speed: b[0],
+                heading: u16_at(1),
+                pcd: b[3],
+            },
+            MacroOpcode::SetRgbLed => MacroInstruction::SetRgbLed {
+                color: RgbColor::new(b[0], b[1], b[2]),
+                pcd: b[3],
+            },
+            MacroOpcode::SetBackLed => MacroInstruction::SetBackLed {
+                brightness: b[0],
+                pcd: b[1],
+            },
+            MacroOpcode::SetRawMotors => MacroInstruction::SetRawMotors {
+                left_mode: b[0],
+                left_power: b[1],
+                right_mode: b[2],
+                right_power: b[3],
+                pcd: b[4],
+            },
+            MacroOpcode::Delay => MacroInstruction::Delay(u16_at(0)),
+            MacroOpcode::Goto => MacroInstruction::Goto(b[0]),
+            MacroOpcode::Gosub => MacroInstruction::Gosub(b[0]),
+            MacroOpcode::FadeToLed => MacroInstruction::FadeToLed {
+                color: RgbColor::new(b[0], b[1], b[2]),
+                ms: u16_at(3),
+            },
+            MacroOpcode::EmitMarker => MacroInstruction::EmitMarker(b[0]),
+            MacroOpcode::WaitUntilStopped => MacroInstruction::WaitUntilStopped(u16_at(0)),
+            MacroOpcode::RotateOverTime => MacroInstruction::RotateOverTime {
+                degrees: u16_at(0) as i16,
+                ms: u16_at(2),
+            },
+            MacroOpcode::StreamEnd => MacroInstruction::StreamEnd,
+            MacroOpcode::Roll2 => MacroInstruction::Roll2 {
+                speed: b[0],
+                heading: u16_at(1),
+                delay: u16_at(3),
+            },
+            MacroOpcode::LoopStart => MacroInstruction::LoopStart(b[0]),
+            MacroOpcode::LoopEnd => MacroInstruction::LoopEnd,
+        }
+    }
+}
+
+/// Sphero Macro Flags
+///
+/// The low byte is the flags byte of the header and the high byte the
+/// extended flags byte, sent only when one of its bits is set. Every flag in
+/// the flags byte works on all firmware that runs macros; the extended flags
+/// byte, and so `INLINE_PID`, needs main application 3.10 or later.
+///
+/// ```
+/// use sphero_rs::macros::MacroFlags;
+///
+/// let flags = MacroFlags::EXCLUSIVE_DRIVE | MacroFlags::END_MARKER;
+/// assert!(flags.contains(MacroFlags::END_MARKER));
+/// assert!(!flags.contains(MacroFlags::UNKILLABLE));
+/// assert_eq!(flags.bits(), 0x000a);
+/// assert_eq!(MacroFlags::default(), MacroFlags::STOP_MOTORS_ON_END);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct MacroFlags(u16);
+
+impl MacroFlags {
+    /// No flags
+    pub const NONE: MacroFlags = MacroFlags(0);
+    /// Stop the motors when the macro ends or is aborted
+    pub const STOP_MOTORS_ON_END: MacroFlags = MacroFlags(0x0001);
+    /// Ignore drive commands from the API while the macro runs
+    pub const EXCLUSIVE_DRIVE: MacroFlags = MacroFlags(0x0002);
+    /// Send a macro marker with ID 0 when the macro ends
+    pub const END_MARKER: MacroFlags = MacroFlags(0x0008);
+    /// Don't send macro marker async messages
+    pub const NO_MARKERS: MacroFlags = MacroFlags(0x0010);
+    /// Ignore Abort Macro
+    pub const UNKILLABLE: MacroFlags = MacroFlags(0x0020);
+    /// The macro carries its own drive PID constants (extended)
+    pub const INLINE_PID: MacroFlags = MacroFlags(0x0100);
+
+    /// Bit of the flags byte announcing an extended flags byte
+    const EXTENDED: u8 = 0x80;
+
+    /// Flags from their bits, kept as they are
+    pub const fn from_bits(bits: u16) -> Self {
+        MacroFlags(bits)
+    }
+
+    /// Bits, extended flags in the high byte
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether every flag of `other` is set
+    pub const fn contains(self, other: MacroFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Extended flags byte, if any of its bits are set
+    fn extended(self) -> Option<u8> {
+        match (self.0 >> 8) as u8 {
+            0 => None,
+            ext => Some(ext),
+        }
+    }
+}
+
+impl Default for MacroFlags {
+    fn default() -> Self {
+        MacroFlags::STOP_MOTORS_ON_END
+    }
+}
+
+impl BitOr for MacroFlags {
+    type Output = MacroFlags;
+
+    fn bitor(self, rhs: MacroFlags) -> MacroFlags {
+        MacroFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MacroFlags {
+    fn bitor_assign(&mut self, rhs: MacroFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Where a macro is stored
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum MacroTarget {
+    /// The temporary macro, replaced by the next upload
+    #[default]
+    Temporary,
+    /// A persistent macro with this ID
+    Persistent(u8),
+}
+
+/// Sphero Macro Header
+///
+/// ```
+/// use sphero_rs::macros::{MacroFlags, MacroHeader, MacroTarget};
+///
+/// let temporary = MacroHeader { target: MacroTarget::Temporary, flags: MacroFlags::default() };
+/// assert_eq!(temporary.to_bytes(), vec![0x01]);
+/// let persistent = MacroHeader {
+///     target: MacroTarget::Persistent(40),
+///     flags: MacroFlags::EXCLUSIVE_DRIVE | MacroFlags::INLINE_PID,
+/// };
+/// assert_eq!(persistent.to_bytes(), vec![40, 0x82, 0x01]);
+///
+/// let (header, body) = MacroHeader::parse(&[40, 0x82, 0x01, 0x00], true)?;
+/// assert_eq!((header, body), (persistent, &[0x00][..]));
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct MacroHeader {
+    /// Where the macro is stored
+    pub target: MacroTarget,
+    /// Flags
+    pub flags: MacroFlags,
+}
+
+impl MacroHeader {
+    /// Append the header's bytes to `out`
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        if let MacroTarget::Persistent(id) = self.target {
+            out.push(id);
+        }
+        let extended = self.flags.extended();
+        let flags = self.flags.0 as u8;
+        match extended {
+            Some(ext) => out.extend_from_slice(&[flags | MacroFlags::EXTENDED, ext]),
+            None => out.push(flags),
+        }
+    }
+
+    /// Header bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.encode_into(&mut bytes);
+        bytes
+    }
+
+    /// Split a macro into its header and commands
+    ///
+    /// `persistent` says whether the macro starts with its ID. Fails with
+    /// `Error::BadDataLength` if the header is cut off.
+    pub fn parse(bytes: &[u8], persistent: bool) -> Result<(MacroHeader, &[u8]), Error> {
+        let (target, rest) = match (persistent, bytes) {
+            (false, rest) => (MacroTarget::Temporary, rest),
+            (true, [id, rest @ ..]) => (MacroTarget::Persistent(*id), rest),
+            (true, []) => return Err(Error::BadDataLength),
+        };
+        let (flags, rest) = match rest {
+            [flags, ext, rest @ ..] if flags & MacroFlags::EXTENDED != 0 => {
+                let bits = u16::from_be_bytes([*ext, flags & !MacroFlags::EXTENDED]);
+                (MacroFlags(bits), rest)
+            }
+            [flags, rest @ ..] if flags & MacroFlags::EXTENDED == 0 => {
+                (MacroFlags(*flags as u16), rest)
+            }
+            _ => return Err(Error::BadDataLength),
+        };
+        Ok((MacroHeader { target, flags }, rest))
+    }
+}
+
+/// Decode the commands of a macro into instructions, up to and including the first `End`
+///
+/// Pass the bytes after the header, see `MacroHeader::parse`. Bytes after `End`, such as the padding of a config block, are ignored.
+/// Fails with `Error::UnknownMacroOpcode` at an undocumented opcode and
+/// `Error::BadDataLength` if the macro is cut off mid-instruction.
+///
+/// ```
+/// use sphero_rs::color::RgbColor;
+/// use sphero_rs::heading::Heading;
+/// use sphero_rs::macros::{disassemble, MacroBuilder, MacroHeader, MacroInstruction};
+///
+/// let blink = MacroBuilder::new()
+///     .loop_start(5)
+///     .set_rgb(RgbColor::GREEN)
+///     .delay(100)
+///     .set_rgb(RgbColor::BLACK)
+///     .delay(100)
+///     .loop_end()
+///     .roll(60, Heading::new(180)?)
+///     .marker(9)
+///     .end()?;
+/// let (_, commands) = MacroHeader::parse(&blink, false)?;
+/// assert_eq!(
+///     disassemble(commands)?,
+///     vec![
+///         MacroInstruction::LoopStart(5),
+///         MacroInstruction::SetRgbLed { color: RgbColor::GREEN, pcd: 0 },
+///         MacroInstruction::Delay(100),
+///         MacroInstruction::SetRgbLed { color: RgbColor::BLACK, pcd: 0 },
+///         MacroInstruction::Delay(100),
+///         MacroInstruction::LoopEnd,
+///         MacroInstruction::Roll { speed: 60, heading: 180, pcd: 0 },
+///         MacroInstruction::EmitMarker(9),
+///         MacroInstruction::End,
+///     ]
+/// );
+///
+/// let result = disassemble(&[0x0b, 0x00, 0x10, 0xee, 0x00]);
+/// assert_eq!(result.unwrap_err().to_string(), "unknown macro opcode 0xee at offset 3");
+/// assert!(disassemble(&[0x05, 0x80]).is_err());
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<MacroInstruction>, Error> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+    while let Some(&byte) = bytes.get(offset) {
+        let opcode = MacroOpcode::from_byte(byte).ok_or(Error::UnknownMacroOpcode {
+            opcode: byte,
+            offset,
+        })?;
+        let operands = bytes
+            .get(offset + 1..offset + 1 + opcode.operand_len())
+            .ok_or(Error::BadDataLength)?;
+        instructions.push(MacroInstruction::decode(opcode, operands));
+        if opcode == MacroOpcode::End {
+            break;
+        }
+        offset += 1 + operands.len();
+    }
+    Ok(instructions)
+}
+
+/// Temporary macro bytes ready to stream with Append Macro Chunk
+///
+/// A streamed macro ends with `StreamEnd` rather than `End`: the final `End`
+/// is replaced, or a `StreamEnd` appended if there is none. The header is
+/// left as it is.
+///
+/// ```
+/// use sphero_rs::macros::{streamed, MacroBuilder, MacroFlags};
+///
+/// let bytes = MacroBuilder::new().flags(MacroFlags::NONE).delay(0x0100).end()?;
+/// assert_eq!(bytes, vec![0x00, 0x0b, 0x01, 0x00, 0x00]);
+/// assert_eq!(streamed(&bytes), vec![0x00, 0x0b, 0x01, 0x00, 0x1b]);
+/// assert_eq!(streamed(&[0x00, 0x0b, 0x01, 0x00]), vec![0x00, 0x0b, 0x01, 0x00, 0x1b]);
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+pub fn streamed(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    // Only an `End` that decodes as the last instruction, not an operand byte
+    let ends = MacroHeader::parse(&bytes, false).is_ok_and(|(_, commands)| {
+        disassemble(commands).is_ok_and(|instructions| {
+            let mut decoded = vec![];
+            instructions
+                .iter()
+                .for_each(|instruction| instruction.encode_into(&mut decoded));
+            decoded == commands && instructions.last() == Some(&MacroInstruction::End)
+        })
+    });
+    if ends {
+        let _ = bytes.pop();
+    }
+    MacroInstruction::StreamEnd.encode_into(&mut bytes);
+    bytes
+}
+
+/// Sphero Macro Builder
+///
+/// Commands are appended in order; `end` checks the macro and returns its
+/// bytes, header first. Macros are temporary with `MacroFlags::default()`
+/// unless set otherwise. Commands with a post-command delay are given none,
+/// so pace the macro with `delay`.
+///
+/// ```
+/// use sphero_rs::color::RgbColor;
+/// use sphero_rs::heading::Heading;
+/// use sphero_rs::macros::{MacroBuilder, MacroFlags, MacroTarget};
+///
+/// // Flash red and blue three times, spin, then drive off
+/// let show = MacroBuilder::new()
+///     .marker(1)
+///     .loop_start(3)
+///     .set_rgb(RgbColor::RED)
+///     .delay(250)
+///     .set_rgb(RgbColor::BLUE)
+///     .delay(250)
+///     .loop_end()
+///     .rotate_over_time(-360, 1000)
+///     .set_back_led(255)
+///     .roll(128, Heading::new(270)?)
+///     .delay(1500)
+///     .end()?;
+/// assert_eq!(
+///     show,
+///     vec![
+///         0x01, // flags: stop the motors at the end
+///         0x15, 0x01, // marker 1
+///         0x1e, 0x03, // loop 3 times
+///         0x07, 0xff, 0x00, 0x00, 0x00, // red
+///         0x0b, 0x00, 0xfa, // 250 ms
+///         0x07, 0x00, 0x00, 0xff, 0x00, // blue
+///         0x0b, 0x00, 0xfa, // 250 ms
+///         0x1f, // end of loop
+///         0x1a, 0xfe, 0x98, 0x03, 0xe8, // -360 degrees over 1000 ms
+///         0x08, 0xff, 0x00, // back LED on
+///         0x05, 0x80, 0x01, 0x0e, 0x00, // speed 128 at 270 degrees
+///         0x0b, 0x05, 0xdc, // 1500 ms
+///         0x00, // end
+///     ]
+/// );
+///
+/// assert!(MacroBuilder::new().loop_start(2).end().is_err());
+/// assert!(MacroBuilder::new().loop_end().end().is_err());
+///
+/// // Persistent macros start with their ID; extended flags add a byte
+/// let persistent = MacroBuilder::new()
+///     .target(MacroTarget::Persistent(40))
+///     .flags(MacroFlags::EXCLUSIVE_DRIVE | MacroFlags::END_MARKER)
+///     .delay(10)
+///     .end()?;
+/// assert_eq!(persistent, vec![40, 0x0a, 0x0b, 0x00, 0x0a, 0x00]);
+/// let extended = MacroBuilder::new()
+///     .flags(MacroFlags::NO_MARKERS | MacroFlags::INLINE_PID)
+///     .end()?;
+/// assert_eq!(extended, vec![0x90, 0x01, 0x00]);
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroBuilder {
+    header: MacroHeader,
+    bytes: Vec<u8>,
+    max_len: usize,
+    depth: usize,
+    deepest: usize,
+    /// Set once a loop end has no matching start
+    unbalanced: bool,
+}
+
+impl Default for MacroBuilder {
+    fn default() -> Self {
+        Self {
+            header: MacroHeader::default(),
+            bytes: vec![],
+            max_len: MAX_MACRO_LEN,
+            depth: 0,
+            deepest: 0,
+            unbalanced: false,
+        }
+    }
+}
+
+impl MacroBuilder {
+    /// An empty macro
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the macro as `target`
+    pub fn target(mut self, target: MacroTarget) -> Self {
+        self.header.target = target;
+        self
+    }
+
+    /// Run the macro with `flags`
+    pub fn flags(mut self, flags: MacroFlags) -> Self {
+        self.header.flags = flags;
+        self
+    }
+
+    /// Allow macros up to `len` bytes, header included, e.g. for an upload in chunks
+    pub fn max_len(mut self, len: usize) -> Self {
+        self.max_len = len;
+        self
+    }
+
+    /// Set the main LED to `color`
+    pub fn set_rgb(self, color: RgbColor) -> Self {
+        self.instruction(MacroInstruction::SetRgbLed { color, pcd: 0 })
+    }
+
+    /// Set the back LED to `brightness`
+    pub fn set_back_led(self, brightness: u8) -> Self {
+        self.instruction(MacroInstruction::SetBackLed { brightness, pcd: 0 })
+    }
+
+    /// Roll at `speed` towards `heading`
+    pub fn roll(self, speed: impl Into<Speed>, heading: Heading) -> Self {
+        self.instruction(MacroInstruction::Roll {
+            speed: speed.into().value(),
+            heading: heading.degrees(),
+            pcd: 0,
+        })
+    }
+
+    /// Wait `ms` milliseconds
+    pub fn delay(self, ms: u16) -> Self {
+        self.instruction(MacroInstruction::Delay(ms))
+    }
+
+    /// Turn by `degrees`, clockwise when positive, over `ms` milliseconds
+    pub fn rotate_over_time(self, degrees: i16, ms: u16) -> Self {
+        self.instruction(MacroInstruction::RotateOverTime { degrees, ms })
+    }
+
+    /// Emit a macro marker async message carrying `id`
+    pub fn marker(self, id: u8) -> Self {
+        self.instruction(MacroInstruction::EmitMarker(id))
+    }
+
+    /// Repeat the commands up to the matching `loop_end` `count` times
+    pub fn loop_start(self, count: u8) -> Self {
+        self.instruction(MacroInstruction::LoopStart(count))
+    }
+
+    /// Close the innermost loop
+    pub fn loop_end(self) -> Self {
+        self.instruction(MacroInstruction::LoopEnd)
+    }
+
+    /// Append any instruction; use `end` rather than `MacroInstruction::End`
+    pub fn instruction(mut self, instruction: MacroInstruction) -> Self {
+        match instruction {
+            MacroInstruction::LoopStart(_) => {
+                self.depth += 1;
+                self.deepest = self.deepest.max(self.depth);
+            }
+            MacroInstruction::LoopEnd => match self.depth.checked_sub(1) {
+                Some(depth) => self.depth = depth,
+                None => self.unbalanced = true,
+            },
+            _ => {}
+        }
+        instruction.encode_into(&mut self.bytes);
+        self
+    }
+
+    /// Finish the macro and return its bytes
+    ///
+    /// Fails with `Error::BadParameterValue` if a loop is left open, closed
+    /// without being started or nested more than `MAX_LOOP_DEPTH` deep, and
+    /// with `Error::BadDataLength` if the macro is longer than its limit.
+    pub fn end(self) -> Result<Vec<u8>, Error> {
+        if self.unbalanced || self.depth != 0 || self.deepest > MAX_LOOP_DEPTH {
+            return Err(Error::BadParameterValue);
+        }
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.bytes);
+        MacroInstruction::End.encode_into(&mut bytes);
+        if bytes.len() > self.max_len {
+            return Err(Error::BadDataLength);
+        }
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,803 @@
+/*!
+ * Sphero Navigation
+ *
+ * The locator reports position as signed 16-bit centimetres that wrap
+ * around after about 327 m, in a frame where +Y is heading 0 and headings
+ * grow clockwise. Odometry unwraps the position and reports a conventional
+ * right-handed pose instead: +x is heading 0, +y is 90 degrees to the left
+ * and angles grow counter-clockwise, in -180..180 degrees.
+ *
+ * Paths recorded from the pose can be played back through the navigator;
+ * with the `serde` feature they can also be saved and loaded.
+ */
+use crate::command::{ConfigureLocator, ReadLocator};
+use crate::device::SpheroDevice;
+use crate::drive::DriveController;
+use crate::error::Error;
+use crate::event::AsyncMessage;
+use crate::response::LocatorData;
+use crate::runtime;
+use crate::sensor::{Sensor, SensorFrame};
+use crate::transport::Transport;
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Sensors to stream for odometry; the IMU yaw is optional
+pub const LOCATOR_SENSORS: [Sensor; 5] = [
+    Sensor::OdometerX,
+    Sensor::OdometerY,
+    Sensor::VelocityX,
+    Sensor::VelocityY,
+    Sensor::ImuYaw,
+];
+
+/// One locator reading, in the robot's own frame
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct LocatorSample {
+    /// X position, in cm
+    pub x: i16,
+    /// Y position, in cm
+    pub y: i16,
+    /// X velocity, in mm/s
+    pub vx: i16,
+    /// Y velocity, in mm/s
+    pub vy: i16,
+    /// IMU yaw, in degrees clockwise like headings, if streamed
+    pub yaw: Option<i16>,
+    /// Host time the reading was taken, if known
+    pub at: Option<SystemTime>,
+}
+
+impl LocatorSample {
+    /// Reading from a streamed frame, `None` unless it has both odometer values
+    pub fn from_frame(frame: &SensorFrame) -> Option<Self> {
+        Some(Self {
+            x: frame.get(Sensor::OdometerX)?,
+            y: frame.get(Sensor::OdometerY)?,
+            vx: frame.get(Sensor::VelocityX).unwrap_or(0),
+            vy: frame.get(Sensor::VelocityY).unwrap_or(0),
+            yaw: frame.get(Sensor::ImuYaw),
+            at: None,
+        })
+    }
+}
+
+impl From<LocatorData> for LocatorSample {
+    fn from(data: LocatorData) -> Self {
+        Self {
+            x: data.x,
+            y: data.y,
+            vx: data.vx,
+            vy: data.vy,
+            yaw: None,
+            at: None,
+        }
+    }
+}
+
+/// Wrap `degrees` into -180..180
+fn normalize(degrees: f32) -> f32 {
+    180.0 - (180.0 - degrees).rem_euclid(360.0)
+}
+
+/// Sphero Odometry State
+/// Position integrated from locator readings, without any I/O.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct OdometryState {
+    last: Option<(i16, i16)>,
+    /// Unwrapped position in the robot's frame, in cm
+    x: i64,
+    y: i64,
+    vx: i16,
+    vy: i16,
+    heading: f32,
+    updated: Option<SystemTime>,
+}
+
+impl OdometryState {
+    /// At the origin, facing heading 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold in a reading
+    ///
+    /// Position steps are taken modulo 2^16, so a reading that wraps from
+    /// +32767 to -32768 moves the robot 1 cm on rather than 655 m back.
+    ///
+    /// ```
+    /// use sphero_rs::nav::{LocatorSample, OdometryState};
+    ///
+    /// let mut odometry = OdometryState::new();
+    /// let forward = |y: i16| LocatorSample { y, vy: 500, ..Default::default() };
+    ///
+    /// odometry.update(&forward(32760));
+    /// odometry.update(&forward(-32766));
+    /// // Robot +Y is the pose's +x, and the robot heads along it
+    /// assert_eq!(odometry.pose(), (32770, 0, 0.0));
+    ///
+    /// // Moving right (robot +X) is -y, heading -90
+    /// odometry.update(&LocatorSample { x: 20, y: -32766, vx: 300, ..Default::default() });
+    /// assert_eq!(odometry.pose(), (32770, -20, -90.0));
+    /// assert_eq!(odometry.velocity(), (0, -300));
+    ///
+    /// // A streamed IMU yaw wins over the direction of travel
+    /// odometry.update(&LocatorSample { x: 20, y: -32766, yaw: Some(-45), ..Default::default() });
+    /// assert_eq!(odometry.pose().2, 45.0);
+    /// ```
+    pub fn update(&mut self, sample: &LocatorSample) {
+        match self.last {
+            Some((x, y)) => {
+                self.x += sample.x.wrapping_sub(x) as i64;
+                self.y += sample.y.wrapping_sub(y) as i64;
+            }
+            None => {
+                self.x = sample.x as i64;
+                self.y = sample.y as i64;
+            }
+        }
+        self.last = Some((sample.x, sample.y));
+        self.vx = sample.vx;
+        self.vy = sample.vy;
+
+        if let Some(yaw) = sample.yaw {
+            self.heading = normalize(-(yaw as f32));
+        } else if sample.vx != 0 || sample.vy != 0 {
+            let (vx, vy) = self.velocity();
+            self.heading = normalize((vy as f32).atan2(vx as f32).to_degrees());
+        }
+        if sample.at.is_some() {
+            self.updated = sample.at;
+        }
+    }
+
+    /// (x in cm, y in cm, heading in degrees), right-handed
+    pub fn pose(&self) -> (i64, i64, f32) {
+        (self.y, -self.x, self.heading)
+    }
+
+    /// (x, y) velocity in mm/s, right-handed
+    pub fn velocity(&self) -> (i32, i32) {
+        (self.vy as i32, -(self.vx as i32))
+    }
+
+    /// Host time of the latest timestamped reading
+    pub fn updated(&self) -> Option<SystemTime> {
+        self.updated
+    }
+
+    /// Back to the origin, e.g. after the robot's locator was reset
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Sphero Odometry
+///
+/// Tracks a device's position from streamed locator frames (`follow_stream`)
+/// or by polling Read Locator (`poll`). Readings are stamped with the host
+/// time they arrived, less half the link delay measured by the last clock
+/// synchronization, if any.
+pub struct Odometry<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    state: Mutex<OdometryState>,
+}
+
+impl<'a, T: Transport + 'static> Odometry<'a, T> {
+    /// Track `device`, starting at the origin
+    pub fn new(device: &'a SpheroDevice<T>) -> Self {
+        Self {
+            device,
+            state: Mutex::new(OdometryState::new()),
+        }
+    }
+
+    /// (x in cm, y in cm, heading in degrees), see the module docs for the frame
+    pub fn pose(&self) -> (i64, i64, f32) {
+        self.state.lock().unwrap().pose()
+    }
+
+    /// Snapshot of the full state
+    pub fn state(&self) -> OdometryState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Fold in `sample`, stamping it with the estimated time it was taken
+    pub fn update(&self, mut sample: LocatorSample) {
+        if sample.at.is_none() {
+            let delay = self
+                .device
+                .clock_offset()
+                .map_or(0, |clock| clock.delay_ms.max(0) as u64 / 2);
+            sample.at = SystemTime::now().checked_sub(Duration::from_millis(delay));
+        }
+        self.state.lock().unwrap().update(&sample);
+    }
+
+    /// Feed streamed locator frames until the device's link is gone
+    /// Streaming must include `Sensor::OdometerX` and `Sensor::OdometerY`, see `LOCATOR_SENSORS`.
+    pub async fn follow_stream(&self) {
+        let mut events = Box::pin(self.device.events());
+        while let Some(message) = events.next().await {
+            if let AsyncMessage::SensorData(frames) = message {
+                frames
+                    .iter()
+                    .filter_map(LocatorSample::from_frame)
+                    .for_each(|sample| self.update(sample));
+            }
+        }
+    }
+
+    /// Read the locator every `interval` until a read fails
+    pub async fn poll(&self, interval: Duration) -> Result<(), Error> {
+        loop {
+            let data: LocatorData = self.device.query(&ReadLocator {}).await?;
+            self.update(data.into());
+            runtime::sleep(interval).await;
+        }
+    }
+
+    /// Make the robot's current position the origin
+    pub async fn reset_origin(&self) -> Result<(), Error> {
+        drop(self.device.send(&ConfigureLocator::default()).await?);
+        self.state.lock().unwrap().reset();
+        Ok(())
+    }
+}
+
+/// Sphero heading (clockwise from heading 0) pointing along `bearing`, in pose degrees
+fn to_heading(bearing: f32) -> u16 {
+    (-bearing).rem_euclid(360.0).round() as u16 % 360
+}
+
+/// Sphero Navigator Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavigatorConfig {
+    /// Speed commanded per cm still to go
+    pub gain: f32,
+    /// Slowest speed commanded outside the tolerance
+    pub min_speed: u8,
+    /// Fastest speed commanded
+    pub max_speed: u8,
+    /// Time between re-plans
+    pub interval: Duration,
+    /// Give up on a waypoint after this long
+    pub timeout: Duration,
+    /// Give up once the distance hasn't shrunk by `stall_distance_cm` for this long
+    pub stall_time: Duration,
+    /// Progress that counts as moving, in cm
+    pub stall_distance_cm: f32,
+}
+
+impl Default for NavigatorConfig {
+    fn default() -> Self {
+        Self {
+            gain: 1.5,
+            min_speed: 30,
+            max_speed: 120,
+            interval: Duration::from_millis(50),
+            timeout: Duration::from_secs(30),
+            stall_time: Duration::from_secs(3),
+            stall_distance_cm: 5.0,
+        }
+    }
+}
+
+/// Point to drive to, in the odometry frame
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Waypoint {
+    /// X position, in cm
+    pub x_cm: i64,
+    /// Y position, in cm
+    pub y_cm: i64,
+    /// How close counts as arrived, in cm
+    pub tolerance_cm: u32,
+}
+
+/// Sphero Navigator
+///
+/// Steers a drive controller towards waypoints using an odometry's pose,
+/// re-planning every `NavigatorConfig::interval`. Speed is proportional to
+/// the distance left, so the robot slows down on approach, and it comes to
+/// rest at each waypoint. The controller's `run` and one of the odometry's
+/// feeds (`poll` or `follow_stream`) must be running alongside.
+pub struct Navigator<'a, T: Transport + 'static> {
+    odometry: &'a Odometry<'a, T>,
+    drive: &'a DriveController<'a, T>,
+    config: NavigatorConfig,
+    route: Mutex<VecDeque<Waypoint>>,
+    /// Bumped by `cancel`; routes started before then give up
+    generation: AtomicU64,
+}
+
+impl<'a, T: Transport + 'static> Navigator<'a, T> {
+    /// Navigate with `drive`, tracking progress with `odometry`
+    pub fn new(
+        odometry: &'a Odometry<'a, T>,
+        drive: &'a DriveController<'a, T>,
+        config: NavigatorConfig,
+    ) -> Self {
+        Self {
+            odometry,
+            drive,
+            config,
+            route: Mutex::new(VecDeque::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Drive to (`x_cm`, `y_cm`), resolving once within `tolerance_cm`
+    ///
+    /// Fails with `Error::Timeout` or `Error::Stalled` after bringing the
+    /// robot to rest, or with `Error::Cancelled` if `cancel` is called after
+    /// this returns.
+    pub fn drive_to(
+        &self,
+        x_cm: i64,
+        y_cm: i64,
+        tolerance_cm: u32,
+    ) -> impl Future<Output = Result<(), Error>> + '_ {
+        let generation = self.generation.load(Ordering::Acquire);
+        let waypoint = Waypoint {
+            x_cm,
+            y_cm,
+            tolerance_cm,
+        };
+        async move { self.go(waypoint, generation).await }
+    }
+
+    /// Queue a waypoint for `follow_route`
+    pub fn push_waypoint(&self, waypoint: Waypoint) {
+        self.route.lock().unwrap().push_back(waypoint);
+    }
+
+    /// Waypoints still queued
+    pub fn remaining(&self) -> usize {
+        self.route.lock().unwrap().len()
+    }
+
+    /// Drive to each queued waypoint in turn until the queue is empty
+    /// Resolves to the number of waypoints reached; waypoints may be queued on the way.
+    pub fn follow_route(&self) -> impl Future<Output = Result<usize, Error>> + '_ {
+        let generation = self.generation.load(Ordering::Acquire);
+        async move {
+            let mut reached = 0;
+            loop {
+                let next = self.route.lock().unwrap().pop_front();
+                match next {
+                    Some(waypoint) => self.go(waypoint, generation).await?,
+                    None => return Ok(reached),
+                }
+                reached += 1;
+            }
+        }
+    }
+
+    /// Abandon the route: clear the queue, fail pending drives and bring the robot to rest
+    pub fn cancel(&self) {
+        let _ = self.generation.fetch_add(1, Ordering::AcqRel);
+        self.route.lock().unwrap().clear();
+        self.rest();
+    }
+
+    /// Ramp down to rest without stopping the controller's loop
+    fn rest(&self) {
+        let (_, heading) = self.drive.current();
+        // Heading is always 0..359
+        let _ = self.drive.drive(0, heading);
+    }
+
+    /// Replay `path`, driving to each scheduled waypoint no earlier than its time
+    ///
+    /// Waypoints run late if the robot can't keep up, but are never skipped.
+    /// Resolves to the number of waypoints reached, and fails like `drive_to`.
+    pub fn play<'p>(
+        &'p self,
+        path: &'p RecordedPath,
+        options: PlaybackOptions,
+    ) -> impl Future<Output = Result<usize, Error>> + 'p {
+        let generation = self.generation.load(Ordering::Acquire);
+        async move {
+            let schedule = path.schedule(options)?;
+            let started = Instant::now();
+            for (at, waypoint) in &schedule {
+                if let Some(wait) = at.checked_sub(started.elapsed()) {
+                    runtime::sleep(wait).await;
+                }
+                self.go(*waypoint, generation).await?;
+            }
+            Ok(schedule.len())
+        }
+    }
+
+    async fn go(&self, waypoint: Waypoint, generation: u64) -> Result<(), Error> {
+        let config = &self.config;
+        let started = Instant::now();
+        let mut best = (f32::INFINITY, started);
+        loop {
+            if self.generation.load(Ordering::Acquire) != generation {
+                return Err(Error::Cancelled);
+            }
+            let (x, y, _) = self.odometry.pose();
+            let (dx, dy) = ((waypoint.x_cm - x) as f32, (waypoint.y_cm - y) as f32);
+            let distance = dx.hypot(dy);
+            let heading = to_heading(dy.atan2(dx).to_degrees());
+            if distance <= waypoint.tolerance_cm as f32 {
+                self.rest();
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if distance < best.0 - config.stall_distance_cm {
+                best = (distance, now);
+            }
+            let failure = if now - started > config.timeout {
+                Some(Error::Timeout)
+            } else if now - best.1 > config.stall_time {
+                Some(Error::Stalled)
+            } else {
+                None
+            };
+            if let Some(error) = failure {
+                self.rest();
+                return Err(error);
+            }
+
+            let speed = (config.gain * distance)
+                .min(config.max_speed as f32)
+                .max(config.min_speed as f32);
+            self.drive.drive(speed as u8, heading)?;
+            runtime::sleep(config.interval).await;
+        }
+    }
+}
+
+/// One recorded pose
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathSample {
+    /// Time since recording started, in ms
+    pub t_ms: u64,
+    /// X position, in cm
+    pub x_cm: i64,
+    /// Y position, in cm
+    pub y_cm: i64,
+    /// Heading, in pose degrees
+    pub heading: f32,
+}
+
+/// Sphero Playback Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackOptions {
+    /// Playback rate: 2.0 plays twice as fast, 0.5 at half speed
+    pub time_scale: f32,
+    /// Skip samples closer than this to the previous waypoint, in cm
+    pub spacing_cm: u32,
+    /// Tolerance of each waypoint, in cm
+    pub tolerance_cm: u32,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            time_scale: 1.0,
+            spacing_cm: 10,
+            tolerance_cm: 5,
+        }
+    }
+}
+
+/// Sphero Recorded Path
+/// Poses in the odometry frame of the recording, oldest first.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedPath {
+    /// Samples, in time order
+    pub samples: Vec<PathSample>,
+}
+
+impl RecordedPath {
+    /// Empty path
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time from the first sample to the last
+    pub fn duration(&self) -> Duration {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => Duration::from_millis(last.t_ms - first.t_ms),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The path rotated by `rotation` degrees counter-clockwise about the
+    /// origin, then moved by (`dx_cm`, `dy_cm`)
+    ///
+    /// Maps a path recorded with a different origin into the current frame.
+    ///
+    /// ```
+    /// use sphero_rs::nav::{PathSample, RecordedPath};
+    ///
+    /// let path = RecordedPath {
+    ///     samples: vec![PathSample { t_ms: 0, x_cm: 100, y_cm: 0, heading: 0.0 }],
+    /// };
+    /// let moved = path.transform(10, 20, 90.0);
+    /// assert_eq!(moved.samples[0], PathSample { t_ms: 0, x_cm: 10, y_cm: 120, heading: 90.0 });
+    /// ```
+    pub fn transform(&self, dx_cm: i64, dy_cm: i64, rotation: f32) -> Self {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let samples = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let (x, y) = (sample.x_cm as f32, sample.y_cm as f32);
+                PathSample {
+                    t_ms: sample.t_ms,
+                    x_cm: (x * cos - y * sin).round() as i64 + dx_cm,
+                    y_cm: (x * sin + y * cos).round() as i64 + dy_cm,
+                    heading: normalize(sample.heading + rotation),
+                }
+            })
+            .collect();
+        Self { samples }
+    }
+
+    /// Waypoints to drive through, each with its time from the start of playback
+    ///
+    /// The first and last samples are always kept; samples in between are
+    /// dropped until one is `spacing_cm` from the previous waypoint.
+    /// Fails with `Error::BadParameterValue` unless `time_scale` is positive.
+    ///
+    /// ```
+    /// use sphero_rs::nav::{PathSample, PlaybackOptions, RecordedPath};
+    /// use std::time::Duration;
+    ///
+    /// // Synthetic motion: 2 cm every 100 ms along x
+    /// let samples = (0..=50)
+    ///     .map(|i| PathSample { t_ms: 1000 + i * 100, x_cm: 2 * i as i64, y_cm: 0, heading: 0.0 })
+    ///     .collect();
+    /// let path = RecordedPath { samples };
+    /// assert_eq!(path.duration(), Duration::from_secs(5));
+    ///
+    /// let options = PlaybackOptions { spacing_cm: 25, ..PlaybackOptions::default() };
+    /// let at_speed = |time_scale| {
+    ///     path.schedule(PlaybackOptions { time_scale, ..options })
+    ///         .unwrap()
+    ///         .into_iter()
+    ///         .map(|(at, waypoint)| (at.as_millis(), waypoint.x_cm))
+    ///         .collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(at_speed(1.0), vec![(0, 0), (1300, 26), (2600, 52), (3900, 78), (5000, 100)]);
+    /// assert_eq!(at_speed(2.0), vec![(0, 0), (650, 26), (1300, 52), (1950, 78), (2500, 100)]);
+    /// assert_eq!(at_speed(0.5), vec![(0, 0), (2600, 26), (5200, 52), (7800, 78), (10000, 100)]);
+    ///
+    /// assert!(path.schedule(PlaybackOptions { time_scale: 0.0, ..options }).is_err());
+    /// ```
+    pub fn schedule(&self, options: PlaybackOptions) -> Result<Vec<(Duration, Waypoint)>, Error> {
+        if !(options.time_scale > 0.0 && options.time_scale.is_finite()) {
+            return Err(Error::BadParameterValue);
+        }
+        let Some(first) = self.samples.first() else {
+            return Ok(vec![]);
+        };
+        let waypoint = |sample: &PathSample| {
+            let at =
+                Duration::from_millis(sample.t_ms - first.t_ms).div_f64(options.time_scale as f64);
+            let waypoint = Waypoint {
+                x_cm: sample.x_cm,
+                y_cm: sample.y_cm,
+                tolerance_cm: options.tolerance_cm,
+            };
+            (at, waypoint)
+        };
+
+        let mut schedule = vec![waypoint(first)];
+        let mut last = first;
+        for sample in &self.samples[1..] {
+            let distance =
+                ((sample.x_cm - last.x_cm) as f32).hypot((sample.y_cm - last.y_cm) as f32);
+            if distance >= options.spacing_cm as f32 {
+                schedule.push(waypoint(sample));
+                last = sample;
+            }
+        }
+        let end = &self.samples[self.samples.len() - 1];
+        if !std::ptr::eq(end, last) {
+            schedule.push(waypoint(end));
+        }
+        Ok(schedule)
+    }
+}
+
+/// Sphero Path Recorder
+///
+/// Samples an odometry's pose into a `RecordedPath`. Call `sample` as
+/// needed, or run `record` alongside the odometry's feed until `stop`.
+pub struct PathRecorder<'a, T: Transport + 'static> {
+    odometry: &'a Odometry<'a, T>,
+    started: Mutex<Option<Instant>>,
+    path: Mutex<RecordedPath>,
+    stopped: AtomicBool,
+}
+
+impl<'a, T: Transport + 'static> PathRecorder<'a, T> {
+    /// Record from `odometry`
+    pub fn new(odometry: &'a Odometry<'a, T>) -> Self {
+        Self {
+            odometry,
+            started: Mutex::new(None),
+            path: Mutex::new(RecordedPath::new()),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Append the current pose; the first sample is at time 0
+    pub fn sample(&self) {
+        let started = *self
+            .started
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+        let (x_cm, y_cm, heading) = self.odometry.pose();
+        self.path.lock().unwrap().samples.push(PathSample {
+            t_ms: started.elapsed().as_millis() as u64,
+            x_cm,
+            y_cm,
+            heading,
+        });
+    }
+
+    /// Sample every `interval` until `stop`, then take a last sample
+    pub async fn record(&self, interval: Duration) {
+        while !self.stopped.load(Ordering::Acquire) {
+            self.sample();
+            runtime::sleep(interval).await;
+        }
+        self.sample();
+    }
+
+    /// Make `record` return
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+
+    /// Copy of the samples so far
+    pub fn path(&self) -> RecordedPath {
+        self.path.lock().unwrap().clone()
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::drive::DriveLimits;
+    use crate::transport::sim::SimulatedSphero;
+
+    const LIMITS: DriveLimits = DriveLimits {
+        tick: Duration::from_millis(20),
+        max_acceleration: 400.0,
+        max_turn_rate: 720.0,
+    };
+
+    /// Locator polling interval
+    const POLL: Duration = Duration::from_millis(20);
+
+    const CONFIG: NavigatorConfig = NavigatorConfig {
+        gain: 1.5,
+        min_speed: 30,
+        max_speed: 120,
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_secs(10),
+        stall_time: Duration::from_secs(3),
+        stall_distance_cm: 5.0,
+    };
+
+    #[tokio::test]
+    async fn drive_to_reaches_the_waypoint() {
+        let sim = SimulatedSphero::default();
+        let device = SpheroDevice::new(sim.clone()).await.unwrap();
+        let drive = DriveController::new(&device, LIMITS, 0).unwrap();
+        let odometry = Odometry::new(&device);
+        let navigator = Navigator::new(&odometry, &drive, CONFIG);
+
+        let arrival = tokio::select! {
+            result = navigator.drive_to(100, 50, 5) => result,
+            result = drive.run() => panic!("drive loop ended: {result:?}"),
+            result = odometry.poll(POLL) => panic!("polling ended: {result:?}"),
+        };
+
+        assert!(arrival.is_ok());
+        let (x, y, _) = odometry.pose();
+        assert!(
+            (x - 100).pow(2) + (y - 50).pow(2) <= 25,
+            "stopped at ({x}, {y})"
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_route_brings_the_robot_to_rest() {
+        let sim = SimulatedSphero::default();
+        let device = SpheroDevice::new(sim.clone()).await.unwrap();
+        let drive = DriveController::new(&device, LIMITS, 0).unwrap();
+        let odometry = Odometry::new(&device);
+        let navigator = Navigator::new(&odometry, &drive, CONFIG);
+        for (x_cm, y_cm) in [(-100, -100), (0, 0)] {
+            navigator.push_waypoint(Waypoint {
+                x_cm,
+                y_cm,
+                tolerance_cm: 5,
+            });
+        }
+
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            navigator.cancel();
+            // Give the drive controller time to ramp down
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        };
+        let cancelled = tokio::select! {
+            (result, ()) = async { tokio::join!(navigator.follow_route(), cancel) } => result,
+            result = drive.run() => panic!("drive loop ended: {result:?}"),
+            result = odometry.poll(POLL) => panic!("polling ended: {result:?}"),
+        };
+
+        assert!(matches!(cancelled, Err(Error::Cancelled)));
+        assert_eq!(navigator.remaining(), 0);
+        assert_eq!(sim.speed(), 0, "robot still moving after cancel");
+    }
+
+    #[tokio::test]
+    async fn recorded_path_turned_about_leads_back_home() {
+        let sim = SimulatedSphero::default();
+        let device = SpheroDevice::new(sim.clone()).await.unwrap();
+        let drive = DriveController::new(&device, LIMITS, 0).unwrap();
+        let odometry = Odometry::new(&device);
+        let navigator = Navigator::new(&odometry, &drive, CONFIG);
+        let background = async {
+            tokio::select! {
+                result = drive.run() => panic!("drive loop ended: {result:?}"),
+                result = odometry.poll(POLL) => panic!("polling ended: {result:?}"),
+            }
+        };
+        tokio::pin!(background);
+
+        let recorder = PathRecorder::new(&odometry);
+        let leg = async {
+            let result = navigator.drive_to(100, 50, 5).await;
+            recorder.stop();
+            result
+        };
+        let recording = recorder.record(Duration::from_millis(50));
+        let arrival = tokio::select! {
+            (result, ()) = async { tokio::join!(leg, recording) } => result,
+            () = &mut background => unreachable!(),
+        };
+        assert!(arrival.is_ok());
+
+        // The same path turned about and moved to start here ends at the origin
+        let (x, y, _) = odometry.pose();
+        let path = recorder.path().transform(x, y, 180.0);
+        let options = PlaybackOptions {
+            time_scale: 2.0,
+            ..PlaybackOptions::default()
+        };
+        let schedule = path.schedule(options).unwrap();
+        let (_, last) = schedule.last().unwrap();
+        assert!(last.x_cm.abs() <= 10 && last.y_cm.abs() <= 10);
+
+        let started = Instant::now();
+        let played = tokio::select! {
+            result = navigator.play(&path, options) => result,
+            () = &mut background => unreachable!(),
+        };
+
+        assert_eq!(played.unwrap(), schedule.len());
+        assert!(started.elapsed() >= recorder.path().duration() / 2);
+        let (x, y, _) = odometry.pose();
+        assert!(x.abs() <= 15 && y.abs() <= 15, "stopped at ({x}, {y})");
+    }
+}
@@ -0,0 +1,217 @@
+/*!
+ * Sphero orbBasic Upload
+ *
+ * Programs are uploaded as a series of fragments appended to a storage area.
+ * A fragment must fit in a single packet and must not straddle a flash page.
+ */
+use crate::async_payload::AsyncPayload;
+use crate::client::SpheroClient;
+use crate::command::{
+    AppendOrbbasicFragment, EraseOrbbasicStorage, ExecuteOrbbasicProgram, OrbBasicArea,
+};
+use crate::error::Error;
+use crate::transport::Transport;
+use std::fmt;
+
+/// Size of a storage page; fragments are split so none crosses a page boundary
+pub const PAGE_SIZE: usize = 512;
+/// Largest fragment that fits in one packet next to the area byte
+pub const MAX_FRAGMENT_SIZE: usize = 253;
+
+/// Program text as uploaded: lines ending in CR, then a NUL terminator
+///
+/// ```
+/// use sphero_rs::orbbasic::program_bytes;
+///
+/// assert_eq!(program_bytes("10 RGB 255, 0, 0\r\n20 END"), b"10 RGB 255, 0, 0\r20 END\r\0");
+/// assert_eq!(program_bytes("10 END\n"), b"10 END\r\0");
+/// ```
+pub fn program_bytes(source: &str) -> Vec<u8> {
+    let mut bytes = source
+        .replace("\r\n", "\r")
+        .replace('\n', "\r")
+        .into_bytes();
+    if bytes.last().is_some_and(|&b| b != b'\r') {
+        bytes.push(b'\r');
+    }
+    bytes.push(0);
+    bytes
+}
+
+/// Split a program into fragments, each paired with its offset
+///
+/// Fragments end on line boundaries, hold at most `MAX_FRAGMENT_SIZE` bytes
+/// and never cross a page boundary. A line is only split where it is too
+/// long for that.
+///
+/// ```
+/// use sphero_rs::orbbasic::{fragments, program_bytes, MAX_FRAGMENT_SIZE};
+///
+/// let source: String = (1..=40).map(|n| format!("{} PRINT {}\n", n * 10, n)).collect();
+/// let program = program_bytes(&source);
+/// let fragments = fragments(&program);
+/// assert_eq!(fragments.len(), 3);
+/// for (offset, fragment) in &fragments {
+///     assert!(fragment.len() <= MAX_FRAGMENT_SIZE);
+///     assert!(*offset == 0 || program[offset - 1] == b'\r');
+/// }
+/// ```
+pub fn fragments(program: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut fragments = vec![];
+    let mut start = 0;
+    while start < program.len() {
+        let page_end = start - start % PAGE_SIZE + PAGE_SIZE;
+        let limit = program.len().min(start + MAX_FRAGMENT_SIZE).min(page_end);
+        let end = if limit == program.len() {
+            limit
+        } else {
+            program[start..limit]
+                .iter()
+                .rposition(|&b| b == b'\r')
+                .map_or(limit, |cr| start + cr + 1)
+        };
+        fragments.push((start, &program[start..end]));
+        start = end;
+    }
+    fragments
+}
+
+/// Sphero orbBasic Uploader
+/// Tracks the upload offset within an area so fragments can be appended blindly
+pub struct OrbBasicUploader<'a, T: Transport> {
+    client: &'a mut SpheroClient<T>,
+    area: OrbBasicArea,
+    offset: usize,
+}
+
+impl<'a, T: Transport> OrbBasicUploader<'a, T> {
+    /// Erase `area` and start a new upload into it
+    pub async fn new(client: &'a mut SpheroClient<T>, area: OrbBasicArea) -> Result<Self, Error> {
+        drop(client.send(&EraseOrbbasicStorage { area }).await?);
+        Ok(Self {
+            client,
+            area,
+            offset: 0,
+        })
+    }
+
+    /// Bytes uploaded so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Append program text, splitting it into as many fragments as needed
+    pub async fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let to_boundary = PAGE_SIZE - self.offset % PAGE_SIZE;
+            let (fragment, tail) =
+                rest.split_at(rest.len().min(to_boundary).min(MAX_FRAGMENT_SIZE));
+            let cmd = AppendOrbbasicFragment {
+                area: self.area,
+                fragment: fragment.to_vec(),
+            };
+            drop(self.client.send(&cmd).await?);
+            self.offset += fragment.len();
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Run the uploaded program from `start_line`
+    pub async fn execute(&mut self, start_line: u16) -> Result<(), Error> {
+        let cmd = ExecuteOrbbasicProgram {
+            area: self.area,
+            start_line,
+        };
+        self.client.send(&cmd).await.map(|_| ())
+    }
+}
+
+/// Names of the orbBasic runtime error codes, from the orbBasic manual
+const ERROR_NAMES: [(u16, &str); 14] = [
+    (0x01, "syntax error"),
+    (0x02, "unknown statement"),
+    (0x03, "GOSUB depth exceeded"),
+    (0x04, "RETURN without GOSUB"),
+    (0x05, "NEXT without FOR"),
+    (0x06, "FOR depth exceeded"),
+    (0x07, "bad STEP value"),
+    (0x08, "divide by zero"),
+    (0x09, "bad line number"),
+    (0x0a, "illegal index"),
+    (0x0b, "expression too complex"),
+    (0x0c, "bad numeric parameter"),
+    (0x0d, "user abort"),
+    (0x0e, "out of data"),
+];
+
+/// Error reported by a running program in an orbBasic Binary Error Message
+///
+/// ```
+/// use sphero_rs::orbbasic::OrbBasicError;
+///
+/// let error = OrbBasicError { code: 0x08, line: 40 };
+/// assert_eq!(error.name(), Some("divide by zero"));
+/// assert_eq!(error.to_string(), "divide by zero in line 40");
+/// assert_eq!(OrbBasicError { code: 0x99, line: 10 }.to_string(), "error 0x99 in line 10");
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct OrbBasicError {
+    /// Error code
+    pub code: u16,
+    /// Line the error occurred on
+    pub line: u16,
+}
+
+impl OrbBasicError {
+    /// Documented name of the error, if the code is known
+    pub fn name(&self) -> Option<&'static str> {
+        ERROR_NAMES
+            .iter()
+            .find(|(code, _)| *code == self.code)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl fmt::Display for OrbBasicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name} in line {}", self.line),
+            None => write!(f, "error {:#x} in line {}", self.code, self.line),
+        }
+    }
+}
+
+/// Output of a running orbBasic program
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OrbBasicOutput {
+    /// A line written with PRINT
+    Print(String),
+    /// The program stopped on an error, reported in binary
+    Error(OrbBasicError),
+    /// The program stopped on an error, reported as text
+    ErrorText(String),
+}
+
+impl OrbBasicOutput {
+    /// Output carried by an async payload, if any
+    pub fn from_payload(payload: AsyncPayload) -> Option<Self> {
+        match payload {
+            AsyncPayload::OrbBasicPrint(print) => Some(OrbBasicOutput::Print(print.text)),
+            AsyncPayload::OrbBasicErrorBinary(error) => {
+                Some(OrbBasicOutput::Error(OrbBasicError {
+                    code: error.code,
+                    line: error.line,
+                }))
+            }
+            AsyncPayload::OrbBasicErrorAscii(error) => Some(OrbBasicOutput::ErrorText(error.text)),
+            _ => None,
+        }
+    }
+
+    /// Whether the program stopped
+    pub fn is_error(&self) -> bool {
+        !matches!(self, OrbBasicOutput::Print(_))
+    }
+}
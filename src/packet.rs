@@ -8,10 +8,19 @@
  * 
  * Multi-byte numbers are sent MSB first in both directions
  */
+// deku's DekuRead derive rounds bit counts up by hand
+#![allow(clippy::manual_div_ceil)]
+use crate::command::ToCommandPacket;
+use crate::error::Error;
 use deku::prelude::*;
+use std::str::FromStr;
 
 /// Sphero Command Packet V1
 /// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 7)
+///
+/// A BLE write larger than the link MTU (as small as 20 bytes on a SPRK+) is
+/// silently dropped, so callers must check `within_mtu` before writing a
+/// packet in one piece.
 #[derive(Default, Debug, PartialEq, DekuRead, DekuWrite)]
 pub struct SpheroCommandPacketV1 {
     sop1: SOP1Field,
@@ -52,7 +61,7 @@ pub struct SpheroAsynchronousPacketV1 {
     sop1: SOP1Field,
     sop2: SOP2Field,
     idcode: u8,
-    #[deku(update = "self.data.len() + 1")]
+    #[deku(endian = "big", update = "self.data.len() + 1")]
     dlen: u16,
     #[deku(count = "dlen - 1")]
     data: Vec<u8>,
@@ -69,19 +78,370 @@ impl SpheroCommandPacketV1 {
         Self {
             sop1: SOP1Field::All,
             sop2: SOP2Field::Response,
-            did: did,
+            did,
             cid: sid,
-            seq: seq,
+            seq,
             dlen: data.len() as u8 + 1,
-            data: data,
-            chk: chk,
+            data,
+            chk,
+        }
+    }
+
+    /// Parse a command packet from the start of `bytes`
+    ///
+    /// The DID is checked before the rest of the packet, so an unknown one
+    /// surfaces as `Error::UnknownDeviceId` rather than a generic parse failure:
+    ///
+    /// ```
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::packet::SpheroCommandPacketV1;
+    ///
+    /// let bytes = [0xff, 0xff, 0xff, 0x01, 0x01, 0x01, 0xfc];
+    /// let result = SpheroCommandPacketV1::parse(&bytes);
+    /// assert!(matches!(result, Err(Error::UnknownDeviceId(0xff))));
+    /// assert!(matches!(SpheroCommandPacketV1::parse(&bytes[..2]), Err(Error::InvalidPacket)));
+    /// ```
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if let Some(&did) = bytes.get(2) {
+            let _ = DeviceID::try_from_byte(did)?;
+        }
+        let (_, packet) = Self::from_bytes((bytes, 0))?;
+        Ok(packet)
+    }
+
+    /// Packet for `cmd` with sequence number `seq`; same as `cmd.to_packet(seq)`
+    ///
+    /// ```
+    /// use sphero_rs::command::{Ping, ToCommandPacket};
+    /// use sphero_rs::packet::SpheroCommandPacketV1;
+    ///
+    /// let packet = SpheroCommandPacketV1::from_command(&Ping {}, 7);
+    /// assert_eq!(packet, Ping {}.to_packet(7));
+    /// assert_eq!(packet, SpheroCommandPacketV1::from((Ping {}, 7)));
+    /// let packet: SpheroCommandPacketV1 = (Ping {}, 7).into();
+    /// assert_eq!(packet.seq(), 7);
+    /// ```
+    pub fn from_command<C: ToCommandPacket>(cmd: &C, seq: u8) -> Self {
+        cmd.to_packet(seq)
+    }
+
+    /// Size of the serialized packet: header, data payload and checksum
+    pub fn total_byte_count(&self) -> usize {
+        6 + self.data.len() + 1
+    }
+
+    /// Whether the serialized packet fits in a single write of `mtu` bytes
+    ///
+    /// With a 20 byte MTU a packet carries at most 13 bytes of data:
+    ///
+    /// ```
+    /// use deku::DekuContainerWrite;
+    /// use sphero_rs::command::{SaveTemporaryMacro, ToCommandPacket};
+    ///
+    /// let fits = SaveTemporaryMacro { data: vec![0; 13] }.to_packet(1);
+    /// assert_eq!(fits.total_byte_count(), 20);
+    /// assert_eq!(fits.to_bytes().unwrap().len(), fits.total_byte_count());
+    /// assert!(fits.within_mtu(20));
+    ///
+    /// let too_big = SaveTemporaryMacro { data: vec![0; 14] }.to_packet(1);
+    /// assert_eq!(too_big.total_byte_count(), 21);
+    /// assert!(!too_big.within_mtu(20));
+    /// assert!(too_big.within_mtu(23));
+    /// ```
+    pub fn within_mtu(&self, mtu: usize) -> bool {
+        self.total_byte_count() <= mtu
+    }
+
+    /// Whether sending this packet twice has the same effect as sending it once
+    ///
+    /// Not idempotent: Sleep, Jump To Bootloader, the bootloader commands
+    /// (Reflash, Here Is Page, Leave Bootloader, Erase User Config), Self Level,
+    /// Set Boost With Time, Run Macro, Append Macro Chunk, Append orbBasic Fragment
+    /// and Execute orbBasic Program.
+    pub fn is_idempotent(&self) -> bool {
+        match self.did {
+            DeviceID::Core => ![
+                CoreCommandID::Sleep as u8,
+                CoreCommandID::JumpToBootloader as u8,
+            ]
+            .contains(&self.cid),
+            DeviceID::Bootloader => false,
+            DeviceID::Sphero => ![
+                SpheroCommandID::SelfLevel as u8,
+                SpheroCommandID::SetBoostWithTime as u8,
+                SpheroCommandID::RunMacro as u8,
+                SpheroCommandID::AppendMacroChunk as u8,
+                SpheroCommandID::AppendOrbbasicFragment as u8,
+                SpheroCommandID::ExecuteOrbbasicProgram as u8,
+            ]
+            .contains(&self.cid),
+        }
+    }
+
+    /// Whether `response` answers this packet, i.e. carries its sequence number
+    ///
+    /// ```
+    /// use sphero_rs::command::{Ping, SetDataStreaming, ToCommandPacket};
+    /// use sphero_rs::packet::{MRSPField, SpheroAsynchronousPacketV1, SpheroResponsePacketV1};
+    ///
+    /// let ping = Ping {}.to_packet(7);
+    /// assert!(ping.matches_response(&SpheroResponsePacketV1::new(MRSPField::Ok, 7, vec![])));
+    /// assert!(!ping.matches_response(&SpheroResponsePacketV1::new(MRSPField::Ok, 8, vec![])));
+    ///
+    /// let streaming = SetDataStreaming::default().to_packet(9);
+    /// assert!(streaming.matches_async(&SpheroAsynchronousPacketV1::new(0x03, vec![])));
+    /// assert!(!streaming.matches_async(&SpheroAsynchronousPacketV1::new(0x07, vec![])));
+    /// assert!(!ping.matches_async(&SpheroAsynchronousPacketV1::new(0x03, vec![])));
+    /// ```
+    pub fn matches_response(&self, response: &SpheroResponsePacketV1) -> bool {
+        self.seq == response.seq()
+    }
+
+    /// Whether `async_pkt` is the kind of asynchronous message this command
+    /// sets off, see `async_idcodes`
+    ///
+    /// Async messages carry no sequence number, so this can't tell which of two
+    /// such commands in flight caused it.
+    pub fn matches_async(&self, async_pkt: &SpheroAsynchronousPacketV1) -> bool {
+        self.async_idcodes().contains(&async_pkt.idcode())
+    }
+
+    /// ID codes of the asynchronous messages this command sets off
+    ///
+    /// | Command                       | Async messages                            |
+    /// |-------------------------------|-------------------------------------------|
+    /// | Set Power Notification        | 01h Power Notification                    |
+    /// | Perform Level 1 Diagnostics   | 02h Level 1 Diagnostic Response           |
+    /// | Set Data Streaming            | 03h Sensor Data Streaming                 |
+    /// | Get Configuration Block       | 04h Config Block Contents                 |
+    /// | Run Macro                     | 06h Macro Markers                         |
+    /// | Configure Collision Detection | 07h Collision Detected                    |
+    /// | Execute orbBasic Program      | 08h PRINT, 09h ASCII and 0Ah binary error |
+    /// | Self Level                    | 0Bh Self Level Result                     |
+    ///
+    /// Every other command is answered by its simple response alone.
+    pub fn async_idcodes(&self) -> &'static [u8] {
+        match self.did {
+            DeviceID::Core if self.cid == CoreCommandID::SetPowerNotification as u8 => &[0x01],
+            DeviceID::Core if self.cid == CoreCommandID::PerformLevel1Diagnostics as u8 => &[0x02],
+            DeviceID::Sphero => match SpheroCommandID::try_from(self.cid) {
+                Ok(SpheroCommandID::SetDataStreaming) => &[0x03],
+                Ok(SpheroCommandID::GetConfigurationBlock) => &[0x04],
+                Ok(SpheroCommandID::RunMacro) => &[0x06],
+                Ok(SpheroCommandID::ConfigureCollisionDetection) => &[0x07],
+                Ok(SpheroCommandID::ExecuteOrbbasicProgram) => &[0x08, 0x09, 0x0A],
+                Ok(SpheroCommandID::SelfLevel) => &[0x0B],
+                _ => &[],
+            },
+            _ => &[],
+        }
+    }
+
+    /// Device ID
+    pub fn did(&self) -> DeviceID {
+        self.did
+    }
+
+    /// Command ID
+    pub fn cid(&self) -> u8 {
+        self.cid
+    }
+
+    /// Sequence number
+    pub fn seq(&self) -> u8 {
+        self.seq
+    }
+
+    /// SOP2 field
+    pub fn sop2(&self) -> SOP2Field {
+        self.sop2
+    }
+
+    /// Set whether the robot should answer this packet
+    /// `SOP2Field::Response` if `required`, otherwise `SOP2Field::NoResponse`
+    pub fn with_response_required(mut self, required: bool) -> Self {
+        self.sop2 = if required {
+            SOP2Field::Response
+        } else {
+            SOP2Field::NoResponse
+        };
+        self
+    }
+
+    /// Data payload
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Equality of every field but the checksum
+    ///
+    /// `==` compares `chk` too, so a packet whose checksum is stale or was
+    /// never computed differs from a freshly built one:
+    ///
+    /// ```
+    /// use deku::{DekuContainerRead, DekuContainerWrite};
+    /// use sphero_rs::packet::{DeviceID, SpheroCommandPacketV1};
+    ///
+    /// let data = vec![0x40, 0x00, 0xb4, 0x01];
+    /// let packet = SpheroCommandPacketV1::new(DeviceID::Sphero, 0x30, 0x01, data);
+    /// let mut bytes = packet.to_bytes().unwrap();
+    /// *bytes.last_mut().unwrap() = 0x00;
+    /// let (_, zeroed) = SpheroCommandPacketV1::from_bytes((&bytes, 0)).unwrap();
+    ///
+    /// assert_ne!(packet, zeroed);
+    /// assert!(packet.eq_payload(&zeroed));
+    /// ```
+    pub fn eq_payload(&self, other: &Self) -> bool {
+        self.sop1 == other.sop1
+            && self.sop2 == other.sop2
+            && self.did == other.did
+            && self.cid == other.cid
+            && self.seq == other.seq
+            && self.dlen == other.dlen
+            && self.data == other.data
+    }
+}
+
+/// `(command, seq)` as a packet
+impl<C: ToCommandPacket> From<(C, u8)> for SpheroCommandPacketV1 {
+    fn from((cmd, seq): (C, u8)) -> Self {
+        cmd.to_packet(seq)
+    }
+}
+
+impl SpheroResponsePacketV1 {
+    /// Create a new packet
+    pub fn new(mrsp: MRSPField, seq: u8, data: Vec<u8>) -> Self {
+        let dlen = data.len() as u8 + 1;
+        let chk = calculate_checksum(&[mrsp as u8, seq, dlen], &data);
+        Self {
+            sop1: SOP1Field::All,
+            sop2: SOP2Field::Response,
+            mrsp,
+            seq,
+            dlen,
+            data,
+            chk,
+        }
+    }
+
+    /// Message response code
+    pub fn mrsp(&self) -> MRSPField {
+        self.mrsp
+    }
+
+    /// Sequence number (echoed from the command)
+    pub fn seq(&self) -> u8 {
+        self.seq
+    }
+
+    /// Data payload
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl SpheroAsynchronousPacketV1 {
+    /// Create a new packet
+    pub fn new(idcode: u8, data: Vec<u8>) -> Self {
+        let dlen = data.len() as u16 + 1;
+        let chk = calculate_checksum(&[idcode, (dlen >> 8) as u8, dlen as u8], &data);
+        Self {
+            sop1: SOP1Field::All,
+            sop2: SOP2Field::Async,
+            idcode,
+            dlen,
+            data,
+            chk,
+        }
+    }
+
+    /// Asynchronous ID code
+    pub fn idcode(&self) -> u8 {
+        self.idcode
+    }
+
+    /// Whether the checksum matches the ID code, length and data payload
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use sphero_rs::packet::SpheroAsynchronousPacketV1;
+    ///
+    /// // Power notification, checksum byte corrupted from 0xfa
+    /// let bytes = [0xff, 0xfe, 0x01, 0x00, 0x02, 0x02, 0xfb];
+    /// let (_, packet) = SpheroAsynchronousPacketV1::from_bytes((&bytes, 0)).unwrap();
+    /// assert!(!packet.validate_checksum());
+    /// assert!(packet.into_validated().is_err());
+    /// ```
+    pub fn validate_checksum(&self) -> bool {
+        self.expected_checksum() == self.chk
+    }
+
+    /// The packet itself if its checksum is valid, otherwise `Error::ChecksumMismatch`
+    ///
+    /// ```
+    /// use deku::DekuContainerRead;
+    /// use sphero_rs::error::Error;
+    /// use sphero_rs::packet::SpheroAsynchronousPacketV1;
+    ///
+    /// let bytes = [0xff, 0xfe, 0x01, 0x00, 0x02, 0x02, 0xfb];
+    /// let (_, packet) = SpheroAsynchronousPacketV1::from_bytes((&bytes, 0)).unwrap();
+    /// assert!(matches!(
+    ///     packet.into_validated(),
+    ///     Err(Error::ChecksumMismatch { expected: 0xfa, actual: 0xfb })
+    /// ));
+    ///
+    /// // Too short for its length field: malformed rather than corrupt
+    /// let truncated = SpheroAsynchronousPacketV1::from_bytes((&bytes[..5], 0));
+    /// assert!(matches!(truncated.map_err(Error::from), Err(Error::InvalidPacket)));
+    /// ```
+    pub fn into_validated(self) -> Result<Self, Error> {
+        let expected = self.expected_checksum();
+        if expected == self.chk {
+            Ok(self)
+        } else {
+            Err(Error::ChecksumMismatch {
+                expected,
+                actual: self.chk,
+            })
         }
     }
+
+    /// Checksum over the ID code, length and data payload
+    fn expected_checksum(&self) -> u8 {
+        let fields = [self.idcode, (self.dlen >> 8) as u8, self.dlen as u8];
+        calculate_checksum(&fields, &self.data)
+    }
+
+    /// Data payload
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 /// Checksum calculation
 /// modulo 256 sum of all the bytes from the DID through the end of the data payload,
 /// bit inverted (1's complement)
+///
+/// `fields` are the header bytes after SOP1 and SOP2, in wire order:
+/// `[did, cid, seq, dlen]` for a command packet, `[mrsp, seq, dlen]` for a
+/// response and `[idcode, dlen_msb, dlen_lsb]` for an asynchronous message.
+/// DLEN counts the data payload plus the checksum byte itself.
+///
+/// ```
+/// use sphero_rs::packet::calculate_checksum;
+///
+/// // Ping: DID 00h, CID 01h, SEQ 01h, DLEN 01h, no data
+/// // !(0x00 + 0x01 + 0x01 + 0x01) = !0x03
+/// assert_eq!(calculate_checksum(&[0x00, 0x01, 0x01, 0x01], &[]), 0xfc);
+///
+/// // Set Back LED Output to 0xff: DID 02h, CID 21h, SEQ 02h, DLEN 02h
+/// assert_eq!(calculate_checksum(&[0x02, 0x21, 0x02, 0x02], &[0xff]), 0xd9);
+///
+/// // Its answer: MRSP 00h, SEQ 02h, DLEN 01h, no data
+/// assert_eq!(calculate_checksum(&[0x00, 0x02, 0x01], &[]), 0xfc);
+/// ```
+#[doc(alias = "chk")]
 pub fn calculate_checksum(fields: &[u8], data: &[u8]) -> u8 {
     let sum: u8 = fields
         .iter()
@@ -90,6 +450,39 @@ pub fn calculate_checksum(fields: &[u8], data: &[u8]) -> u8 {
     !sum
 }
 
+/// Sphero wire values of the SOP1, SOP2 and DID header bytes
+///
+/// The same values as `SOP1Field`, `SOP2Field` and `DeviceID`, as plain bytes:
+///
+/// ```
+/// use sphero_rs::packet::constants::*;
+/// use sphero_rs::packet::{DeviceID, SOP1Field, SOP2Field};
+///
+/// assert_eq!(SOP1Field::All as u8, SOP1_ALL);
+/// assert_eq!(SOP2Field::Response as u8, SOP2_RESPONSE);
+/// assert_eq!(SOP2Field::Async as u8, SOP2_ASYNC);
+/// assert_eq!(SOP2Field::NoResponse as u8, SOP2_NO_RESPONSE);
+/// assert_eq!(DeviceID::Core as u8, DID_CORE);
+/// assert_eq!(DeviceID::Bootloader as u8, DID_BOOTLOADER);
+/// assert_eq!(DeviceID::Sphero as u8, DID_SPHERO);
+/// ```
+pub mod constants {
+    /// SOP1 of every packet
+    pub const SOP1_ALL: u8 = 0xff;
+    /// SOP2 of a command requesting a response, and of that response
+    pub const SOP2_RESPONSE: u8 = 0xff;
+    /// SOP2 of an asynchronous message
+    pub const SOP2_ASYNC: u8 = 0xfe;
+    /// SOP2 of a command requesting no response
+    pub const SOP2_NO_RESPONSE: u8 = 0xfc;
+    /// DID of the core
+    pub const DID_CORE: u8 = 0x00;
+    /// DID of the bootloader
+    pub const DID_BOOTLOADER: u8 = 0x01;
+    /// DID of the Sphero device
+    pub const DID_SPHERO: u8 = 0x02;
+}
+
 /// Sphero Packet SOP1 Values
 #[derive(Default, Debug, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
 #[deku(type = "u8", endian = "big")]
@@ -111,6 +504,10 @@ pub enum SOP2Field {
     /// Asynchronous Message
     #[deku(id = "0xfe")]
     Async = 0xfe,
+    /// No Acknowledgement Requested (Command)
+    /// Leaves the inactivity timeout untouched; 0xfe is taken by `Async`
+    #[deku(id = "0xfc")]
+    NoResponse = 0xfc,
 }
 
 /// Sphero Message Response Codes
@@ -184,6 +581,19 @@ pub enum DeviceID {
     Sphero = 0x02,
 }
 
+impl DeviceID {
+    /// Map a DID byte to a known device, `Error::UnknownDeviceId` for anything else
+    /// `SpheroCommandPacketV1::parse` checks the DID with this.
+    pub fn try_from_byte(b: u8) -> Result<DeviceID, Error> {
+        match b {
+            0x00 => Ok(DeviceID::Core),
+            0x01 => Ok(DeviceID::Bootloader),
+            0x02 => Ok(DeviceID::Sphero),
+            _ => Err(Error::UnknownDeviceId(b)),
+        }
+    }
+}
+
 /// Device ID 00h – The Core
 /// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 11)
 /// The Core Device encapsulates actions that are fundamental to all Orbotix devices.
@@ -306,6 +716,12 @@ pub enum SpheroCommandID {
     /// Configure Collision Detection
     #[deku(id = "0x12")]
     ConfigureCollisionDetection = 0x12,
+    /// Configure Locator
+    #[deku(id = "0x13")]
+    ConfigureLocator = 0x13,
+    /// Read Locator
+    #[deku(id = "0x15")]
+    ReadLocator = 0x15,
     /// Set RGB LED Output
     #[deku(id = "0x20")]
     SetRGBLEDOutput = 0x20,
@@ -382,3 +798,507 @@ pub enum SpheroCommandID {
     #[deku(id = "0x63")]
     AbortOrbbasicProgram = 0x63,
 }
+
+impl TryFrom<u8> for CoreCommandID {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match CoreCommandID::from_bytes((&[value], 0)) {
+            Ok((_, cid)) => Ok(cid),
+            Err(_) => Err(Error::UnknownCommandId(value)),
+        }
+    }
+}
+
+impl TryFrom<u8> for BootloaderCommandID {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match BootloaderCommandID::from_bytes((&[value], 0)) {
+            Ok((_, cid)) => Ok(cid),
+            Err(_) => Err(Error::UnknownCommandId(value)),
+        }
+    }
+}
+
+impl TryFrom<u8> for SpheroCommandID {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match SpheroCommandID::from_bytes((&[value], 0)) {
+            Ok((_, cid)) => Ok(cid),
+            Err(_) => Err(Error::UnknownCommandId(value)),
+        }
+    }
+}
+
+/// Look up a command by its variant name, ignoring case
+#[allow(deprecated)]
+impl FromStr for CoreCommandID {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ping" => Ok(CoreCommandID::Ping),
+            "getversioninginformation" => Ok(CoreCommandID::GetVersioningInformation),
+            "setdevicename" => Ok(CoreCommandID::SetDeviceName),
+            "getbluetoothinfo" => Ok(CoreCommandID::GetBluetoothInfo),
+            "getautoreconnect" => Ok(CoreCommandID::GetAutoReconnect),
+            "setautoreconnect" => Ok(CoreCommandID::SetAutoReconnect),
+            "getpowerstate" => Ok(CoreCommandID::GetPowerState),
+            "setpowernotification" => Ok(CoreCommandID::SetPowerNotification),
+            "sleep" => Ok(CoreCommandID::Sleep),
+            "getvoltagetrippoints" => Ok(CoreCommandID::GetVoltageTripPoints),
+            "setvoltagetrippoints" => Ok(CoreCommandID::SetVoltageTripPoints),
+            "setinactivitytimeout" => Ok(CoreCommandID::SetInactivityTimeout),
+            "jumptobootloader" => Ok(CoreCommandID::JumpToBootloader),
+            "performlevel1diagnostics" => Ok(CoreCommandID::PerformLevel1Diagnostics),
+            "performlevel2diagnostics" => Ok(CoreCommandID::PerformLevel2Diagnostics),
+            "clearcounters" => Ok(CoreCommandID::ClearCounters),
+            "assigntimevalue" => Ok(CoreCommandID::AssignTimeValue),
+            "pollpackettimes" => Ok(CoreCommandID::PollPacketTimes),
+            _ => Err(Error::BadCommandId),
+        }
+    }
+}
+
+/// Look up a command by its variant name, ignoring case
+#[allow(deprecated)]
+impl FromStr for SpheroCommandID {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "setheading" => Ok(SpheroCommandID::SetHeading),
+            "setstabilization" => Ok(SpheroCommandID::SetStabilization),
+            "setrotationrate" => Ok(SpheroCommandID::SetRotationRate),
+            "setapplicationconfigurationblock" => Ok(SpheroCommandID::SetApplicationConfigurationBlock),
+            "getapplicationconfigurationblock" => Ok(SpheroCommandID::GetApplicationConfigurationBlock),
+            "reenabledemo" => Ok(SpheroCommandID::ReEnableDemo),
+            "getchassisid" => Ok(SpheroCommandID::GetChassisID),
+            "setchassisid" => Ok(SpheroCommandID::SetChassisID),
+            "selflevel" => Ok(SpheroCommandID::SelfLevel),
+            "setdatastreaming" => Ok(SpheroCommandID::SetDataStreaming),
+            "configurecollisiondetection" => Ok(SpheroCommandID::ConfigureCollisionDetection),
+            "configurelocator" => Ok(SpheroCommandID::ConfigureLocator),
+            "readlocator" => Ok(SpheroCommandID::ReadLocator),
+            "setrgbledoutput" => Ok(SpheroCommandID::SetRGBLEDOutput),
+            "setbackledoutput" => Ok(SpheroCommandID::SetBackLEDOutput),
+            "getrgbledoutput" => Ok(SpheroCommandID::GetRGBLEDOutput),
+            "roll" => Ok(SpheroCommandID::Roll),
+            "setboostwithtime" => Ok(SpheroCommandID::SetBoostWithTime),
+            "setrawmotorvalues" => Ok(SpheroCommandID::SetRawMotorValues),
+            "setmotiontimeout" => Ok(SpheroCommandID::SetMotionTimeout),
+            "setoptionsflags" => Ok(SpheroCommandID::SetOptionsFlags),
+            "getoptionsflags" => Ok(SpheroCommandID::GetOptionsFlags),
+            "getconfigurationblock" => Ok(SpheroCommandID::GetConfigurationBlock),
+            "setdevicemode" => Ok(SpheroCommandID::SetDeviceMode),
+            "setconfigurationblock" => Ok(SpheroCommandID::SetConfigurationBlock),
+            "getdevicemode" => Ok(SpheroCommandID::GetDeviceMode),
+            "runmacro" => Ok(SpheroCommandID::RunMacro),
+            "savetemporarymacro" => Ok(SpheroCommandID::SaveTemporaryMacro),
+            "savemacro" => Ok(SpheroCommandID::SaveMacro),
+            "reinitmacroexecutive" => Ok(SpheroCommandID::ReinitMacroExecutive),
+            "abortmacro" => Ok(SpheroCommandID::AbortMacro),
+            "getmacrostatus" => Ok(SpheroCommandID::GetMacroStatus),
+            "setmacroparameter" => Ok(SpheroCommandID::SetMacroParameter),
+            "appendmacrochunk" => Ok(SpheroCommandID::AppendMacroChunk),
+            "eraseorbbasicstorage" => Ok(SpheroCommandID::EraseOrbbasicStorage),
+            "appendorbbasicfragment" => Ok(SpheroCommandID::AppendOrbbasicFragment),
+            "executeorbbasicprogram" => Ok(SpheroCommandID::ExecuteOrbbasicProgram),
+            "abortorbbasicprogram" => Ok(SpheroCommandID::AbortOrbbasicProgram),
+            _ => Err(Error::BadCommandId),
+        }
+    }
+}
+
+/// Sphero Asynchronous Message ID Codes
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 9)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, DekuRead, DekuWrite)]
+#[deku(type = "u8", endian = "big")]
+pub enum AsynchronousIDCode {
+    /// Power Notifications
+    #[deku(id = "0x01")]
+    PowerNotification = 0x01,
+    /// Level 1 Diagnostic Response
+    #[deku(id = "0x02")]
+    Level1Diagnostic = 0x02,
+    /// Sensor Data Streaming
+    #[deku(id = "0x03")]
+    SensorData = 0x03,
+    /// Config Block Contents
+    #[deku(id = "0x04")]
+    ConfigBlock = 0x04,
+    /// Pre-sleep Warning (10 sec)
+    #[deku(id = "0x05")]
+    PreSleepWarning = 0x05,
+    /// Macro Markers
+    #[deku(id = "0x06")]
+    MacroMarker = 0x06,
+    /// Collision Detected
+    #[deku(id = "0x07")]
+    CollisionDetected = 0x07,
+    /// orbBasic PRINT Message
+    #[deku(id = "0x08")]
+    OrbBasicPrint = 0x08,
+    /// orbBasic ASCII Error Message
+    #[deku(id = "0x09")]
+    OrbBasicErrorAscii = 0x09,
+    /// orbBasic Binary Error Message
+    #[deku(id = "0x0A")]
+    OrbBasicErrorBinary = 0x0A,
+    /// Self Level Result
+    #[deku(id = "0x0B")]
+    SelfLevelResult = 0x0B,
+    /// Gyro Axis Limit Exceeded
+    #[deku(id = "0x0C")]
+    GyroAxisLimitExceeded = 0x0C,
+    /// Sphero's Soul Data
+    #[deku(id = "0x0D")]
+    SoulData = 0x0D,
+    /// Level Up Notification
+    #[deku(id = "0x0E")]
+    LevelUp = 0x0E,
+    /// Shield Damage Notification
+    #[deku(id = "0x0F")]
+    ShieldDamage = 0x0F,
+    /// XP Update Notification
+    #[deku(id = "0x10")]
+    XpUpdate = 0x10,
+    /// Boost Update Notification
+    #[deku(id = "0x11")]
+    BoostUpdate = 0x11,
+}
+
+impl TryFrom<u8> for AsynchronousIDCode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match AsynchronousIDCode::from_bytes((&[value], 0)) {
+            Ok((_, code)) => Ok(code),
+            Err(_) => Err(Error::UnknownAsyncIdCode(value)),
+        }
+    }
+}
+
+/// Sphero API V2 packets (BOLT, RVR and newer)
+/// <https://sdk.sphero.com/docs/api_spec/general_api>
+///
+/// A V2 packet is framed by start and end of packet bytes and carries no
+/// length field: the data payload runs up to the checksum. The structures here
+/// describe a packet after byte stuffing has been removed.
+#[cfg(feature = "v2")]
+pub mod v2 {
+    use super::calculate_checksum;
+    use deku::bitvec::{BitSlice, Msb0};
+    use deku::ctx::Limit;
+    use deku::prelude::*;
+
+    /// Sphero Command Packet V2
+    #[derive(Default, Debug, PartialEq, DekuRead, DekuWrite)]
+    pub struct SpheroCommandPacketV2 {
+        sop: SOPField,
+        flags: FlagsField,
+        #[deku(cond = "flags.has_target_id")]
+        tid: Option<u8>,
+        #[deku(cond = "flags.has_source_id")]
+        sid: Option<u8>,
+        did: u8,
+        cid: u8,
+        seq: u8,
+        #[deku(reader = "read_payload(deku::rest)")]
+        data: Vec<u8>,
+        #[deku(update = "self.checksum()")]
+        chk: u8,
+        eop: EOPField,
+    }
+
+    /// Sphero Response Packet V2
+    #[derive(Default, Debug, PartialEq, DekuRead, DekuWrite)]
+    pub struct SpheroResponsePacketV2 {
+        sop: SOPField,
+        flags: FlagsField,
+        #[deku(cond = "flags.has_target_id")]
+        tid: Option<u8>,
+        #[deku(cond = "flags.has_source_id")]
+        sid: Option<u8>,
+        did: u8,
+        cid: u8,
+        seq: u8,
+        err: ErrorCodeField,
+        #[deku(reader = "read_payload(deku::rest)")]
+        data: Vec<u8>,
+        #[deku(update = "self.checksum()")]
+        chk: u8,
+        eop: EOPField,
+    }
+
+    impl SpheroCommandPacketV2 {
+        /// Create a new packet that requests a response and resets the inactivity timeout
+        pub fn new(did: u8, cid: u8, seq: u8, data: Vec<u8>) -> Self {
+            let mut packet = Self {
+                flags: FlagsField {
+                    requests_response: true,
+                    is_activity: true,
+                    ..Default::default()
+                },
+                did,
+                cid,
+                seq,
+                data,
+                ..Default::default()
+            };
+            packet.chk = packet.checksum();
+            packet
+        }
+
+        /// Address the packet to a target processor
+        pub fn with_target(mut self, tid: u8) -> Self {
+            self.flags.has_target_id = true;
+            self.tid = Some(tid);
+            self.chk = self.checksum();
+            self
+        }
+
+        /// Flags field
+        pub fn flags(&self) -> FlagsField {
+            self.flags
+        }
+
+        /// Target ID
+        pub fn tid(&self) -> Option<u8> {
+            self.tid
+        }
+
+        /// Device ID
+        pub fn did(&self) -> u8 {
+            self.did
+        }
+
+        /// Command ID
+        pub fn cid(&self) -> u8 {
+            self.cid
+        }
+
+        /// Sequence number
+        pub fn seq(&self) -> u8 {
+            self.seq
+        }
+
+        /// Data payload
+        pub fn data(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn checksum(&self) -> u8 {
+            let mut fields = vec![self.flags.to_bytes().unwrap_or_default()[0]];
+            fields.extend(self.tid.iter().chain(self.sid.iter()));
+            fields.extend([self.did, self.cid, self.seq]);
+            calculate_checksum(&fields, &self.data)
+        }
+    }
+
+    impl SpheroResponsePacketV2 {
+        /// Create a new packet
+        pub fn new(did: u8, cid: u8, seq: u8, err: ErrorCodeField, data: Vec<u8>) -> Self {
+            let mut packet = Self {
+                flags: FlagsField {
+                    is_response: true,
+                    ..Default::default()
+                },
+                did,
+                cid,
+                seq,
+                err,
+                data,
+                ..Default::default()
+            };
+            packet.chk = packet.checksum();
+            packet
+        }
+
+        /// Flags field
+        pub fn flags(&self) -> FlagsField {
+            self.flags
+        }
+
+        /// Source ID
+        pub fn sid(&self) -> Option<u8> {
+            self.sid
+        }
+
+        /// Device ID (echoed from the command)
+        pub fn did(&self) -> u8 {
+            self.did
+        }
+
+        /// Command ID (echoed from the command)
+        pub fn cid(&self) -> u8 {
+            self.cid
+        }
+
+        /// Sequence number (echoed from the command)
+        pub fn seq(&self) -> u8 {
+            self.seq
+        }
+
+        /// Error code
+        pub fn err(&self) -> ErrorCodeField {
+            self.err
+        }
+
+        /// Data payload
+        pub fn data(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn checksum(&self) -> u8 {
+            let mut fields = vec![self.flags.to_bytes().unwrap_or_default()[0]];
+            fields.extend(self.tid.iter().chain(self.sid.iter()));
+            fields.extend([self.did, self.cid, self.seq, self.err as u8]);
+            calculate_checksum(&fields, &self.data)
+        }
+    }
+
+    /// Read the data payload: everything but the trailing checksum and EOP
+    fn read_payload(
+        rest: &BitSlice<u8, Msb0>,
+    ) -> Result<(&BitSlice<u8, Msb0>, Vec<u8>), DekuError> {
+        let count = (rest.len() / 8).saturating_sub(2);
+        Vec::<u8>::read(rest, (Limit::new_count(count), ()))
+    }
+
+    /// Sphero Packet V2 Start of Packet
+    #[derive(Default, Debug, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+    #[deku(type = "u8", endian = "big")]
+    pub enum SOPField {
+        /// Start of Packet
+        #[default]
+        #[deku(id = "0x8d")]
+        Start = 0x8d,
+    }
+
+    /// Sphero Packet V2 End of Packet
+    #[derive(Default, Debug, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+    #[deku(type = "u8", endian = "big")]
+    pub enum EOPField {
+        /// End of Packet
+        #[default]
+        #[deku(id = "0xd8")]
+        End = 0xd8,
+    }
+
+    /// Sphero Packet V2 Flags
+    /// Takes the place of SOP2 in V1, most significant bit first
+    #[derive(Default, Debug, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+    pub struct FlagsField {
+        /// Another flags byte follows (unsupported)
+        #[deku(bits = 1)]
+        pub extended_flags: bool,
+        /// Reserved
+        #[deku(bits = 1)]
+        pub reserved: bool,
+        /// Packet carries a source ID
+        #[deku(bits = 1)]
+        pub has_source_id: bool,
+        /// Packet carries a target ID
+        #[deku(bits = 1)]
+        pub has_target_id: bool,
+        /// Command resets the inactivity timeout
+        #[deku(bits = 1)]
+        pub is_activity: bool,
+        /// Only answer if the command fails
+        #[deku(bits = 1)]
+        pub requests_error_response: bool,
+        /// Answer the command
+        #[deku(bits = 1)]
+        pub requests_response: bool,
+        /// Packet is a response
+        #[deku(bits = 1)]
+        pub is_response: bool,
+    }
+
+    /// Sphero V2 Response Error Codes
+    #[derive(Default, Debug, PartialEq, Clone, Copy, DekuRead, DekuWrite)]
+    #[deku(type = "u8", endian = "big")]
+    pub enum ErrorCodeField {
+        /// Command succeeded
+        #[default]
+        #[deku(id = "0x00")]
+        Success = 0x00,
+        /// Unknown device ID
+        #[deku(id = "0x01")]
+        BadDeviceId = 0x01,
+        /// Unknown command ID
+        #[deku(id = "0x02")]
+        BadCommandId = 0x02,
+        /// Command not yet implemented
+        #[deku(id = "0x03")]
+        NotYetImplemented = 0x03,
+        /// Command restricted in the current mode
+        #[deku(id = "0x04")]
+        CommandIsRestricted = 0x04,
+        /// Bad data length
+        #[deku(id = "0x05")]
+        BadDataLength = 0x05,
+        /// Command failed
+        #[deku(id = "0x06")]
+        CommandFailed = 0x06,
+        /// Parameter value(s) invalid
+        #[deku(id = "0x07")]
+        BadParameterValue = 0x07,
+        /// Device busy
+        #[deku(id = "0x08")]
+        Busy = 0x08,
+        /// Unknown target ID
+        #[deku(id = "0x09")]
+        BadTargetId = 0x09,
+        /// Target processor unavailable
+        #[deku(id = "0x0a")]
+        TargetUnavailable = 0x0a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_core_command_parses_from_its_name() {
+        let cids: Vec<_> = (0..=u8::MAX)
+            .filter_map(|b| CoreCommandID::try_from(b).ok())
+            .collect();
+        assert_eq!(cids.len(), 18);
+        for cid in cids {
+            let name = format!("{:?}", cid);
+            assert_eq!(name.parse::<CoreCommandID>().unwrap(), cid);
+            assert_eq!(name.to_lowercase().parse::<CoreCommandID>().unwrap(), cid);
+            assert_eq!(name.to_uppercase().parse::<CoreCommandID>().unwrap(), cid);
+        }
+    }
+
+    #[test]
+    fn every_sphero_command_parses_from_its_name() {
+        let cids: Vec<_> = (0..=u8::MAX)
+            .filter_map(|b| SpheroCommandID::try_from(b).ok())
+            .collect();
+        assert_eq!(cids.len(), 38);
+        for cid in cids {
+            let name = format!("{:?}", cid);
+            assert_eq!(name.parse::<SpheroCommandID>().unwrap(), cid);
+            assert_eq!(name.to_lowercase().parse::<SpheroCommandID>().unwrap(), cid);
+            assert_eq!(name.to_uppercase().parse::<SpheroCommandID>().unwrap(), cid);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn unknown_command_names_are_bad_command_ids() {
+        for name in ["", "roll ", "Rol", "Ping", "SetRGBLED"] {
+            assert!(matches!(name.parse::<SpheroCommandID>(), Err(Error::BadCommandId)));
+        }
+        for name in ["", "Roll", "ping!"] {
+            assert!(matches!(name.parse::<CoreCommandID>(), Err(Error::BadCommandId)));
+        }
+    }
+}
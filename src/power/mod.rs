@@ -0,0 +1,134 @@
+/*!
+ * Sphero Power
+ *
+ * The battery as the robot reports it: a `PowerState` in power notifications
+ * and Get Power State, and `Voltage`s in hundredths of a volt for the
+ * battery and the low and critical trip points.
+ */
+
+#[cfg(feature = "async")]
+mod policy;
+
+#[cfg(feature = "async")]
+pub use self::policy::*;
+
+/// Sphero Power State
+///
+/// ```
+/// use sphero_rs::power::PowerState;
+///
+/// assert_eq!(PowerState::from(3), PowerState::Low);
+/// assert!(PowerState::Low.is_usable());
+/// assert!(!PowerState::Critical.is_usable());
+///
+/// // Codes newer firmware may add are kept
+/// assert_eq!(PowerState::from(9), PowerState::Unknown(9));
+/// assert_eq!(u8::from(PowerState::Unknown(9)), 9);
+/// assert!(!PowerState::Unknown(9).is_usable());
+/// for code in 1..=4 {
+///     assert_eq!(u8::from(PowerState::from(code)), code);
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum PowerState {
+    /// On the charger
+    Charging,
+    /// Battery OK
+    Ok,
+    /// Battery low
+    Low,
+    /// Battery critical, shutting down soon
+    Critical,
+    /// Undocumented state code
+    Unknown(u8),
+}
+
+impl Default for PowerState {
+    /// State code 0, as in a zeroed record
+    fn default() -> Self {
+        PowerState::Unknown(0)
+    }
+}
+
+impl PowerState {
+    /// Whether the robot can be driven: off the charger, and the battery is
+    /// OK or low but not critical
+    pub fn is_usable(&self) -> bool {
+        matches!(self, PowerState::Ok | PowerState::Low)
+    }
+}
+
+impl From<u8> for PowerState {
+    /// From the state byte of a power notification or Get Power State
+    fn from(code: u8) -> Self {
+        match code {
+            1 => PowerState::Charging,
+            2 => PowerState::Ok,
+            3 => PowerState::Low,
+            4 => PowerState::Critical,
+            _ => PowerState::Unknown(code),
+        }
+    }
+}
+
+impl From<PowerState> for u8 {
+    fn from(state: PowerState) -> Self {
+        match state {
+            PowerState::Charging => 1,
+            PowerState::Ok => 2,
+            PowerState::Low => 3,
+            PowerState::Critical => 4,
+            PowerState::Unknown(code) => code,
+        }
+    }
+}
+
+/// Sphero Voltage
+/// Battery voltage or trip point, in hundredths of a volt
+///
+/// ```
+/// use sphero_rs::power::Voltage;
+///
+/// let battery = Voltage::from_hundredths(742);
+/// assert!((battery.volts() - 7.42).abs() < 1e-6);
+/// assert_eq!(battery.hundredths(), 742);
+/// assert_eq!(battery.to_string(), "7.42 V");
+/// assert!(battery > Voltage::from_hundredths(700));
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Voltage(u16);
+
+impl Voltage {
+    /// `hundredths` of a volt
+    pub const fn from_hundredths(hundredths: u16) -> Self {
+        Self(hundredths)
+    }
+
+    /// Hundredths of a volt
+    pub const fn hundredths(&self) -> u16 {
+        self.0
+    }
+
+    /// Volts
+    pub fn volts(&self) -> f32 {
+        self.0 as f32 / 100.0
+    }
+}
+
+impl From<u16> for Voltage {
+    fn from(hundredths: u16) -> Self {
+        Self(hundredths)
+    }
+}
+
+impl From<Voltage> for u16 {
+    fn from(voltage: Voltage) -> Self {
+        voltage.0
+    }
+}
+
+impl std::fmt::Display for Voltage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02} V", self.0 / 100, self.0 % 100)
+    }
+}
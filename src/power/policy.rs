@@ -0,0 +1,328 @@
+/*!
+ * Sphero Power Policy
+ *
+ * The robot reports its `PowerState` by power notification whenever it
+ * changes and in answer to Get Power State. A power policy reacts to those
+ * changes: while the battery is low it caps the drive controller's speed and
+ * shows a warning color, undoing both if the battery recovers, and on
+ * critical it stops and hands back to the caller to shut down.
+ */
+use crate::color::RgbColor;
+use crate::command::{GetPowerState, SetRGBLEDOutput};
+use crate::device::SpheroDevice;
+use crate::drive::DriveController;
+use crate::error::Error;
+use crate::event::AsyncMessage;
+use crate::power::PowerState;
+use crate::runtime;
+use crate::transport::Transport;
+use futures::future::{select, Either};
+use futures::StreamExt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Sphero Power Policy Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerPolicyConfig {
+    /// Speed cap while the battery is low
+    pub low_max_speed: u8,
+    /// LED color while the battery is low, `None` to leave the LED alone
+    pub warning_color: Option<RgbColor>,
+    /// Also ask for the power state this often, for robots that don't notify
+    pub poll_interval: Option<Duration>,
+}
+
+impl Default for PowerPolicyConfig {
+    fn default() -> Self {
+        Self {
+            low_max_speed: 60,
+            warning_color: Some(RgbColor::new(0xff, 0x40, 0)),
+            poll_interval: None,
+        }
+    }
+}
+
+/// What the policy has changed, to undo on recovery
+#[derive(Debug, Default)]
+struct PolicyState {
+    power: Option<PowerState>,
+    /// LED color before the warning, if the warning is showing
+    saved_color: Option<Option<RgbColor>>,
+}
+
+/// Sphero Power Policy
+///
+/// Run `run` alongside the rest of the application; it returns once the
+/// battery is critical, after stopping the drive controller, so the caller
+/// can `shutdown` the device.
+pub struct PowerPolicy<'a, T: Transport + 'static> {
+    device: &'a SpheroDevice<T>,
+    drive: Option<&'a DriveController<'a, T>>,
+    config: PowerPolicyConfig,
+    on_change: Option<Box<dyn Fn(PowerState) + Send + Sync + 'a>>,
+    state: Mutex<PolicyState>,
+}
+
+impl<'a, T: Transport + 'static> PowerPolicy<'a, T> {
+    /// Watch `device`'s battery
+    pub fn new(device: &'a SpheroDevice<T>, config: PowerPolicyConfig) -> Self {
+        Self {
+            device,
+            drive: None,
+            config,
+            on_change: None,
+            state: Mutex::new(PolicyState::default()),
+        }
+    }
+
+    /// Cap `drive`'s speed while the battery is low and stop it when critical
+    pub fn with_drive(mut self, drive: &'a DriveController<'a, T>) -> Self {
+        self.drive = Some(drive);
+        self
+    }
+
+    /// Call `callback` on every change of power state, before acting on it
+    pub fn on_change(mut self, callback: impl Fn(PowerState) + Send + Sync + 'a) -> Self {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Last power state seen
+    pub fn state(&self) -> Option<PowerState> {
+        self.state.lock().unwrap().power
+    }
+
+    /// Act on `power`, as if the robot had reported it
+    /// Returns whether the battery is critical.
+    pub async fn apply(&self, power: PowerState) -> Result<bool, Error> {
+        let previous = self.state.lock().unwrap().power.replace(power);
+        if previous == Some(power) {
+            return Ok(power == PowerState::Critical);
+        }
+        if let Some(callback) = &self.on_change {
+            callback(power);
+        }
+
+        match power {
+            PowerState::Low => {
+                if let Some(drive) = self.drive {
+                    drive.set_speed_limit(Some(self.config.low_max_speed));
+                }
+                self.show_warning().await?;
+            }
+            PowerState::Ok | PowerState::Charging => {
+                if let Some(drive) = self.drive {
+                    drive.set_speed_limit(None);
+                }
+                self.clear_warning().await?;
+            }
+            PowerState::Critical => {
+                if let Some(drive) = self.drive {
+                    drive.stop();
+                }
+            }
+            PowerState::Unknown(_) => {}
+        }
+        Ok(power == PowerState::Critical)
+    }
+
+    /// Follow power notifications, and polls if configured, until the battery is critical
+    /// Fails if the device's link is gone or a command fails.
+    pub async fn run(&self) -> Result<(), Error> {
+        let mut events = Box::pin(self.device.events());
+        if let Some(interval) = self.config.poll_interval {
+            if self.poll().await? {
+                return Ok(());
+            }
+            loop {
+                let critical = match select(events.next(), Box::pin(runtime::sleep(interval))).await
+                {
+                    Either::Left((Some(message), _)) => self.notified(message).await?,
+                    Either::Left((None, _)) => return Err(Error::TargetUnavailable),
+                    Either::Right(_) => self.poll().await?,
+                };
+                if critical {
+                    return Ok(());
+                }
+            }
+        }
+        while let Some(message) = events.next().await {
+            if self.notified(message).await? {
+                return Ok(());
+            }
+        }
+        Err(Error::TargetUnavailable)
+    }
+
+    async fn notified(&self, message: AsyncMessage) -> Result<bool, Error> {
+        match message {
+            AsyncMessage::PowerNotification(PowerState::Unknown(_)) => Ok(false),
+            AsyncMessage::PowerNotification(power) => self.apply(power).await,
+            _ => Ok(false),
+        }
+    }
+
+    async fn poll(&self) -> Result<bool, Error> {
+        let info = self.device.query(&GetPowerState {}).await?;
+        match info.state {
+            PowerState::Unknown(_) => Ok(false),
+            power => self.apply(power).await,
+        }
+    }
+
+    async fn show_warning(&self) -> Result<(), Error> {
+        let Some(warning) = self.config.warning_color else {
+            return Ok(());
+        };
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.saved_color.is_some() {
+                return Ok(());
+            }
+            state.saved_color = Some(self.device.led_color());
+        }
+        drop(
+            self.device
+                .send(&SetRGBLEDOutput::from_color(warning, false))
+                .await?,
+        );
+        Ok(())
+    }
+
+    /// Put the user's color back, unless they changed it while the warning showed
+    async fn clear_warning(&self) -> Result<(), Error> {
+        let Some(saved) = self.state.lock().unwrap().saved_color.take() else {
+            return Ok(());
+        };
+        if self.device.led_color() != self.config.warning_color {
+            return Ok(());
+        }
+        let color = saved.unwrap_or(RgbColor::BLACK);
+        drop(
+            self.device
+                .send(&SetRGBLEDOutput::from_color(color, false))
+                .await?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::drive::DriveLimits;
+    use crate::packet::{
+        CoreCommandID, MRSPField, SpheroAsynchronousPacketV1, SpheroCommandID,
+        SpheroCommandPacketV1,
+    };
+    use crate::transport::mock::{ack, respond, MockTransport};
+    use deku::DekuContainerWrite;
+
+    const USER_COLOR: RgbColor = RgbColor::BLUE;
+
+    /// Colors sent with Set RGB LED Output so far
+    fn led_writes(mock: &MockTransport) -> Vec<RgbColor> {
+        mock.written_packets()
+            .iter()
+            .filter(|packet| packet.cid() == SpheroCommandID::SetRGBLEDOutput as u8)
+            .map(|packet| {
+                let data = packet.data();
+                RgbColor::new(data[0], data[1], data[2])
+            })
+            .collect()
+    }
+
+    /// Device over `mock` showing `USER_COLOR`
+    async fn colored(mock: &MockTransport) -> SpheroDevice<MockTransport> {
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        let color = SetRGBLEDOutput::from_color(USER_COLOR, false);
+        drop(device.send(&color).await.unwrap());
+        device
+    }
+
+    #[tokio::test]
+    async fn low_battery_caps_speed_and_warns_until_it_recovers() {
+        let mock = MockTransport::acknowledging();
+        let device = colored(&mock).await;
+        let drive = DriveController::new(&device, DriveLimits::default(), 0).unwrap();
+        let config = PowerPolicyConfig::default();
+        let warning = config.warning_color.unwrap();
+        let seen = Mutex::new(vec![]);
+        let policy = PowerPolicy::new(&device, config)
+            .with_drive(&drive)
+            .on_change(|state| seen.lock().unwrap().push(state));
+
+        assert!(!policy.apply(PowerState::Low).await.unwrap());
+        assert_eq!(drive.speed_limit(), Some(config.low_max_speed));
+        assert_eq!(led_writes(&mock), [USER_COLOR, warning]);
+
+        // A repeated state changes nothing
+        assert!(!policy.apply(PowerState::Low).await.unwrap());
+        assert_eq!(led_writes(&mock).len(), 2);
+
+        assert!(!policy.apply(PowerState::Ok).await.unwrap());
+        assert_eq!(drive.speed_limit(), None);
+        assert_eq!(led_writes(&mock), [USER_COLOR, warning, USER_COLOR]);
+        assert_eq!(*seen.lock().unwrap(), [PowerState::Low, PowerState::Ok]);
+        assert_eq!(policy.state(), Some(PowerState::Ok));
+    }
+
+    #[tokio::test]
+    async fn color_changed_during_the_warning_is_kept() {
+        let mock = MockTransport::acknowledging();
+        let device = colored(&mock).await;
+        let policy = PowerPolicy::new(&device, PowerPolicyConfig::default());
+
+        assert!(!policy.apply(PowerState::Low).await.unwrap());
+        let green = SetRGBLEDOutput::from_color(RgbColor::GREEN, false);
+        drop(device.send(&green).await.unwrap());
+        assert!(!policy.apply(PowerState::Charging).await.unwrap());
+
+        assert_eq!(led_writes(&mock).last(), Some(&RgbColor::GREEN));
+        assert_eq!(led_writes(&mock).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn critical_notification_stops_the_drive_and_ends_the_run() {
+        let mock = MockTransport::acknowledging();
+        let device = colored(&mock).await;
+        let drive = DriveController::new(&device, DriveLimits::default(), 0).unwrap();
+        let policy = PowerPolicy::new(&device, PowerPolicyConfig::default()).with_drive(&drive);
+        drive.drive(100, 90).unwrap();
+
+        let robot = async {
+            // `join` polls `run` first, so it has subscribed by now
+            let critical = SpheroAsynchronousPacketV1::new(0x01, vec![PowerState::Critical.into()]);
+            mock.inject(critical.to_bytes().unwrap());
+        };
+        let (result, ()) = tokio::join!(policy.run(), robot);
+
+        assert!(result.is_ok());
+        assert_eq!(policy.state(), Some(PowerState::Critical));
+        // Stopped at rest, so the drive's loop ends at once
+        assert!(drive.run().await.is_ok());
+        assert_eq!(drive.current().0, 0);
+    }
+
+    #[tokio::test]
+    async fn polling_covers_robots_that_do_not_notify() {
+        let mock = MockTransport::with_responder(|bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            match packet.cid() == CoreCommandID::GetPowerState as u8 {
+                true => vec![respond(
+                    &packet,
+                    MRSPField::Ok,
+                    vec![1, PowerState::Critical.into(), 0x02, 0x58, 0, 1, 0, 1],
+                )],
+                false => vec![ack(&packet)],
+            }
+        });
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        let config = PowerPolicyConfig {
+            poll_interval: Some(Duration::from_millis(5)),
+            ..PowerPolicyConfig::default()
+        };
+
+        assert!(PowerPolicy::new(&device, config).run().await.is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+/*!
+ * Sphero Rate Limiting
+ *
+ * Writing packets faster than the BLE connection interval makes the robot
+ * drop commands without answering. The limiter spaces outgoing commands to a
+ * packets-per-second budget. Roll and Set RGB LED Output only matter for
+ * their latest value, so a newer one replaces an older one still waiting
+ * for its slot instead of queueing behind it.
+ *
+ * The limiter is handed the current time rather than reading a clock.
+ */
+use crate::error::Error;
+use crate::packet::{DeviceID, SpheroCommandID, SpheroCommandPacketV1};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Counters kept by a `RateLimiter`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// Commands that waited for a slot before being sent
+    pub delayed: u64,
+    /// Commands skipped because a newer one of the same kind replaced them
+    pub coalesced: u64,
+}
+
+/// Claim on the latest value of a coalescing command, see `RateLimiter::ticket`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ticket {
+    cid: u8,
+    generation: u64,
+}
+
+/// What a command waiting on the limiter should do next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// Send now
+    Send,
+    /// Ask again after this long
+    Wait(Duration),
+    /// Don't send, a newer command of the same kind replaced this one
+    Coalesced,
+}
+
+/// Sphero Outbound Rate Limiter
+/// Unlimited until a rate is set.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Option<Instant>,
+    latest: HashMap<u8, u64>,
+    generation: u64,
+    stats: RateLimitStats,
+}
+
+impl RateLimiter {
+    /// Create an unlimited limiter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit to `rate` packets per second, or lift the limit with `None`
+    /// Returns `Error::BadParameterValue` unless `rate` is positive and finite.
+    pub fn set_rate(&mut self, rate: Option<f32>) -> Result<(), Error> {
+        self.interval = match rate {
+            Some(rate) if rate.is_finite() && rate > 0.0 => {
+                Some(Duration::from_secs_f32(1.0 / rate))
+            }
+            Some(_) => return Err(Error::BadParameterValue),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Register `packet` as the latest of its kind
+    /// Returns `None` for commands that never coalesce.
+    pub fn ticket(&mut self, packet: &SpheroCommandPacketV1) -> Option<Ticket> {
+        let coalesces = packet.did() == DeviceID::Sphero
+            && [
+                SpheroCommandID::Roll as u8,
+                SpheroCommandID::SetRGBLEDOutput as u8,
+            ]
+            .contains(&packet.cid());
+        if !coalesces {
+            return None;
+        }
+        self.generation += 1;
+        let _ = self.latest.insert(packet.cid(), self.generation);
+        Some(Ticket {
+            cid: packet.cid(),
+            generation: self.generation,
+        })
+    }
+
+    /// Ask for a send slot at `now`
+    ///
+    /// `delayed` says whether the command already waited, so it is counted once
+    /// when it is finally sent. Granting a slot reserves the next one a full
+    /// interval later: idle time doesn't build up into a burst.
+    pub fn acquire(&mut self, ticket: Option<Ticket>, delayed: bool, now: Instant) -> Pace {
+        if let Some(ticket) = ticket {
+            if self.latest.get(&ticket.cid) != Some(&ticket.generation) {
+                self.stats.coalesced += 1;
+                return Pace::Coalesced;
+            }
+        }
+        let Some(interval) = self.interval else {
+            return Pace::Send;
+        };
+        match self.next_slot {
+            Some(slot) if slot > now => Pace::Wait(slot - now),
+            _ => {
+                self.next_slot = Some(now + interval);
+                if delayed {
+                    self.stats.delayed += 1;
+                }
+                Pace::Send
+            }
+        }
+    }
+
+    /// Counters since the limiter was created
+    pub fn stats(&self) -> RateLimitStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RgbColor;
+    use crate::command::{Ping, Roll, SetRGBLEDOutput, ToCommandPacket};
+
+    const INTERVAL: Duration = Duration::from_millis(125);
+
+    fn eight_per_second() -> RateLimiter {
+        let mut limiter = RateLimiter::new();
+        limiter.set_rate(Some(8.0)).unwrap();
+        limiter
+    }
+
+    #[test]
+    fn unlimited_sends_at_once() {
+        let mut limiter = RateLimiter::new();
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            assert_eq!(limiter.acquire(None, false, now), Pace::Send);
+        }
+        assert_eq!(limiter.stats(), RateLimitStats::default());
+    }
+
+    #[test]
+    fn rate_must_be_positive_and_finite() {
+        let mut limiter = RateLimiter::new();
+
+        for rate in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                limiter.set_rate(Some(rate)),
+                Err(Error::BadParameterValue)
+            ));
+        }
+        assert!(limiter.set_rate(None).is_ok());
+    }
+
+    #[test]
+    fn sends_are_spaced_one_interval_apart() {
+        let mut limiter = eight_per_second();
+        let start = Instant::now();
+
+        assert_eq!(limiter.acquire(None, false, start), Pace::Send);
+        assert_eq!(limiter.acquire(None, false, start), Pace::Wait(INTERVAL));
+        let later = start + Duration::from_millis(40);
+        assert_eq!(
+            limiter.acquire(None, true, later),
+            Pace::Wait(Duration::from_millis(85))
+        );
+        assert_eq!(limiter.acquire(None, true, start + INTERVAL), Pace::Send);
+
+        // Counted once, when the waiting command is finally sent
+        assert_eq!(limiter.stats().delayed, 1);
+    }
+
+    #[test]
+    fn idle_time_does_not_build_up_a_burst() {
+        let mut limiter = eight_per_second();
+        let start = Instant::now();
+        assert_eq!(limiter.acquire(None, false, start), Pace::Send);
+
+        let idle = start + INTERVAL * 10;
+        assert_eq!(limiter.acquire(None, false, idle), Pace::Send);
+        assert_eq!(limiter.acquire(None, false, idle), Pace::Wait(INTERVAL));
+    }
+
+    #[test]
+    fn newer_roll_replaces_a_waiting_one() {
+        let mut limiter = eight_per_second();
+        let start = Instant::now();
+        assert_eq!(limiter.acquire(None, false, start), Pace::Send);
+
+        let older = limiter.ticket(&Roll::stop_at_zero().to_packet(1));
+        let newer = limiter.ticket(&Roll::stop_at_zero().to_packet(2));
+        assert!(older.is_some() && newer.is_some());
+
+        assert_eq!(limiter.acquire(older, true, start), Pace::Coalesced);
+        assert_eq!(limiter.acquire(newer, true, start + INTERVAL), Pace::Send);
+        assert_eq!(
+            limiter.stats(),
+            RateLimitStats {
+                delayed: 1,
+                coalesced: 1
+            }
+        );
+    }
+
+    #[test]
+    fn only_commands_of_the_same_kind_coalesce() {
+        let mut limiter = eight_per_second();
+        let now = Instant::now();
+
+        let roll = limiter.ticket(&Roll::stop_at_zero().to_packet(1));
+        let color = SetRGBLEDOutput::from_color(RgbColor::RED, false);
+        let led = limiter.ticket(&color.to_packet(2));
+
+        assert_eq!(limiter.ticket(&Ping {}.to_packet(3)), None);
+        assert_eq!(limiter.acquire(roll, false, now), Pace::Send);
+        assert_eq!(limiter.acquire(led, false, now), Pace::Wait(INTERVAL));
+        assert_eq!(limiter.stats().coalesced, 0);
+    }
+}
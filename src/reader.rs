@@ -0,0 +1,167 @@
+/*!
+ * Sphero Packet Reader
+ *
+ * Reassembles packets from the raw byte stream received from the robot.
+ * A single chunk of bytes (e.g. a BLE notification) may hold part of a
+ * packet, exactly one packet, or several packets back to back.
+ */
+use crate::error::Error;
+use crate::event::{parse_notification, SpheroEvent};
+use crate::packet::{calculate_checksum, SOP1Field, SOP2Field};
+use futures::ready;
+use futures::stream::{BoxStream, Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Length of the header shared by response and async packets (SOP1 through DLEN)
+const HEADER_LEN: usize = 5;
+
+/// Sphero Packet Reader
+///
+/// ```
+/// use deku::DekuContainerWrite;
+/// use sphero_rs::error::Error;
+/// use sphero_rs::event::SpheroEvent;
+/// use sphero_rs::packet::{MRSPField, SpheroAsynchronousPacketV1, SpheroResponsePacketV1};
+/// use sphero_rs::reader::PacketReader;
+///
+/// let mut reader = PacketReader::new();
+///
+/// // A 90 byte async packet split over five 20 byte BLE notifications
+/// let bytes = SpheroAsynchronousPacketV1::new(0x03, vec![0x55; 84]).to_bytes().unwrap();
+/// assert_eq!(bytes.len(), 90);
+/// for chunk in bytes.chunks(20) {
+///     assert!(reader.next_event().is_none());
+///     reader.push(chunk);
+/// }
+/// assert!(matches!(reader.next_event(), Some(Ok(SpheroEvent::Async(_)))));
+///
+/// // Two responses in one notification
+/// let mut bytes = SpheroResponsePacketV1::new(MRSPField::Ok, 1, vec![]).to_bytes().unwrap();
+/// bytes.extend(SpheroResponsePacketV1::new(MRSPField::Ok, 2, vec![]).to_bytes().unwrap());
+/// reader.push(&bytes);
+/// assert!(matches!(reader.next_event(), Some(Ok(SpheroEvent::Response(r))) if r.seq() == 1));
+/// assert!(matches!(reader.next_event(), Some(Ok(SpheroEvent::Response(r))) if r.seq() == 2));
+/// assert!(reader.next_event().is_none());
+///
+/// // A corrupted checksum is reported, then skipped
+/// let mut bytes = SpheroResponsePacketV1::new(MRSPField::Ok, 3, vec![]).to_bytes().unwrap();
+/// bytes[5] ^= 0x01;
+/// reader.push(&bytes);
+/// assert!(matches!(
+///     reader.next_event(),
+///     Some(Err(Error::ChecksumMismatch { expected: 0xfb, actual: 0xfa }))
+/// ));
+/// assert!(reader.next_event().is_none());
+/// ```
+#[derive(Debug, Default)]
+pub struct PacketReader {
+    buffer: Vec<u8>,
+}
+
+impl PacketReader {
+    /// Create an empty reader
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received bytes
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Number of bytes buffered but not yet consumed
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Take the next complete packet, if one has been received
+    ///
+    /// Bytes that cannot start a packet are skipped. A framed packet with a bad
+    /// checksum yields `Error::ChecksumMismatch` and the reader resynchronizes on
+    /// the following bytes.
+    pub fn next_event(&mut self) -> Option<Result<SpheroEvent, Error>> {
+        loop {
+            self.resync();
+            if self.buffer.len() < HEADER_LEN {
+                return None;
+            }
+
+            let dlen = if self.buffer[1] == SOP2Field::Response as u8 {
+                self.buffer[4] as usize
+            } else {
+                u16::from_be_bytes([self.buffer[3], self.buffer[4]]) as usize
+            };
+            if dlen == 0 {
+                // DLEN always covers at least the checksum
+                drop(self.buffer.drain(..1));
+                continue;
+            }
+
+            let total = HEADER_LEN + dlen;
+            if self.buffer.len() < total {
+                return None;
+            }
+
+            let expected = calculate_checksum(&self.buffer[2..total - 1], &[]);
+            let actual = self.buffer[total - 1];
+            if expected != actual {
+                drop(self.buffer.drain(..1));
+                return Some(Err(Error::ChecksumMismatch { expected, actual }));
+            }
+
+            let event = parse_notification(&self.buffer[..total]);
+            drop(self.buffer.drain(..total));
+            return Some(event);
+        }
+    }
+
+    /// Drop bytes until the buffer starts with a plausible SOP1/SOP2 pair
+    fn resync(&mut self) {
+        let sop1 = SOP1Field::All as u8;
+        let is_sop2 = |b: u8| b == SOP2Field::Response as u8 || b == SOP2Field::Async as u8;
+        let start = (0..self.buffer.len()).find(|&i| {
+            self.buffer[i] == sop1 && self.buffer.get(i + 1).is_none_or(|&b| is_sop2(b))
+        });
+        match start {
+            Some(start) => drop(self.buffer.drain(..start)),
+            None => self.buffer.clear(),
+        }
+    }
+}
+
+/// Sphero Event Stream
+/// Events parsed from a stream of raw chunks, such as BLE notifications
+///
+/// Chunks are reassembled with a `PacketReader`, so a packet may span
+/// several of them. The stream ends when the chunks do.
+pub struct SpheroEventStream<S = BoxStream<'static, Vec<u8>>> {
+    chunks: S,
+    reader: PacketReader,
+}
+
+impl<S: Stream<Item = Vec<u8>> + Unpin> SpheroEventStream<S> {
+    /// Parse the events carried by `chunks`
+    pub fn new(chunks: S) -> Self {
+        Self {
+            chunks,
+            reader: PacketReader::new(),
+        }
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>> + Unpin> Stream for SpheroEventStream<S> {
+    type Item = Result<SpheroEvent, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.reader.next_event() {
+                return Poll::Ready(Some(event));
+            }
+            match ready!(self.chunks.poll_next_unpin(cx)) {
+                Some(chunk) => self.reader.push(&chunk),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+/*!
+ * Sphero Reconnect
+ *
+ * BLE links to the robots drop often. With a `ReconnectPolicy` set, the
+ * device client reconnects the transport with exponential backoff, pings the
+ * robot and re-applies the session state (stabilization, data streaming,
+ * collision detection, back LED) before releasing commands queued meanwhile.
+ */
+use crate::command::ToCommandPacket;
+use crate::error::Error;
+use crate::packet::{DeviceID, SpheroCommandID, SpheroCommandPacketV1};
+use futures::channel::oneshot;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How the device client reconnects a dropped link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Connection attempts before giving up
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failed one
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before attempt number `attempt`, counting from 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Commands re-applied after a reconnect, in this order
+const REPLAYED: [u8; 4] = [
+    SpheroCommandID::SetStabilization as u8,
+    SpheroCommandID::SetDataStreaming as u8,
+    SpheroCommandID::ConfigureCollisionDetection as u8,
+    SpheroCommandID::SetBackLEDOutput as u8,
+];
+
+/// Payload of the last command sent for each replayed command ID
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    last: BTreeMap<usize, (u8, Vec<u8>)>,
+}
+
+impl Session {
+    /// Remember `packet` if it sets session state
+    pub(crate) fn remember(&mut self, packet: &SpheroCommandPacketV1) {
+        if packet.did() != DeviceID::Sphero {
+            return;
+        }
+        let position = REPLAYED.iter().position(|cid| *cid == packet.cid());
+        if let Some(position) = position {
+            let _ = self
+                .last
+                .insert(position, (packet.cid(), packet.data().to_vec()));
+        }
+    }
+
+    /// Commands restoring the remembered state
+    pub(crate) fn commands(&self) -> Vec<Replay> {
+        self.last
+            .values()
+            .map(|(cid, data)| Replay {
+                cid: *cid,
+                data: data.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A remembered command, resent as is
+pub(crate) struct Replay {
+    cid: u8,
+    data: Vec<u8>,
+}
+
+impl ToCommandPacket for Replay {
+    fn to_packet(&self, seq: u8) -> SpheroCommandPacketV1 {
+        SpheroCommandPacketV1::new(DeviceID::Sphero, self.cid, seq, self.data.clone())
+    }
+}
+
+/// State of the link to the robot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinkState {
+    /// Commands go out as usual
+    Connected,
+    /// Commands wait until the link is back
+    Reconnecting,
+    /// The link is gone for good, commands fail with `Error::Disconnected`
+    Down,
+}
+
+/// Link state and the commands waiting for it to come back
+#[derive(Debug)]
+pub(crate) struct Link {
+    state: LinkState,
+    waiters: Vec<oneshot::Sender<()>>,
+}
+
+impl Link {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: LinkState::Connected,
+            waiters: Vec::new(),
+        }
+    }
+
+    pub(crate) fn state(&self) -> LinkState {
+        self.state
+    }
+
+    /// Change state, releasing the waiters once reconnecting is over
+    pub(crate) fn set(&mut self, state: LinkState) {
+        self.state = state;
+        match state {
+            LinkState::Connected => {
+                for tx in self.waiters.drain(..) {
+                    let _ = tx.send(());
+                }
+            }
+            // Dropping the senders fails the waiters
+            LinkState::Down => self.waiters.clear(),
+            LinkState::Reconnecting => {}
+        }
+    }
+
+    /// `None` if commands may go out now, otherwise a receiver resolved once they may
+    pub(crate) fn waiter(&mut self) -> Result<Option<oneshot::Receiver<()>>, Error> {
+        match self.state {
+            LinkState::Connected => Ok(None),
+            LinkState::Down => Err(Error::Disconnected),
+            LinkState::Reconnecting => {
+                let (tx, rx) = oneshot::channel();
+                self.waiters.push(tx);
+                Ok(Some(rx))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Roll, SetBackLEDOutput, SetStabilization};
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let policy = ReconnectPolicy::default();
+        let backoffs: Vec<u64> = (0..7)
+            .map(|attempt| policy.backoff(attempt).as_millis() as u64)
+            .collect();
+
+        assert_eq!(backoffs, [250, 500, 1000, 2000, 4000, 8000, 8000]);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+    }
+
+    #[test]
+    fn session_replays_the_last_of_each_in_a_fixed_order() {
+        let mut session = Session::default();
+        session.remember(&SetBackLEDOutput { brightness: 0x10 }.to_packet(1));
+        session.remember(&Roll::stop_at_zero().to_packet(2));
+        session.remember(&SetStabilization { enabled: false }.to_packet(3));
+        session.remember(&SetBackLEDOutput { brightness: 0x7f }.to_packet(4));
+
+        let replayed: Vec<(u8, Vec<u8>)> = session
+            .commands()
+            .iter()
+            .map(|command| {
+                let packet = command.to_packet(0);
+                (packet.cid(), packet.data().to_vec())
+            })
+            .collect();
+
+        assert_eq!(
+            replayed,
+            [
+                (SpheroCommandID::SetStabilization as u8, vec![0x00]),
+                (SpheroCommandID::SetBackLEDOutput as u8, vec![0x7f]),
+            ]
+        );
+    }
+
+    #[test]
+    fn waiters_are_released_once_connected() {
+        let mut link = Link::new();
+        assert!(matches!(link.waiter(), Ok(None)));
+
+        link.set(LinkState::Reconnecting);
+        let mut waiter = link.waiter().unwrap().unwrap();
+        assert_eq!(waiter.try_recv(), Ok(None));
+
+        link.set(LinkState::Connected);
+        assert_eq!(waiter.try_recv(), Ok(Some(())));
+    }
+
+    #[test]
+    fn waiters_fail_once_the_link_is_down() {
+        let mut link = Link::new();
+        link.set(LinkState::Reconnecting);
+        let mut waiter = link.waiter().unwrap().unwrap();
+
+        link.set(LinkState::Down);
+
+        assert!(waiter.try_recv().is_err());
+        assert!(matches!(link.waiter(), Err(Error::Disconnected)));
+    }
+}
@@ -0,0 +1,269 @@
+/*!
+ * Sphero Firmware Reflash
+ *
+ * The main application is rewritten from the bootloader: Begin Reflash
+ * erases it, then each page goes out in a Here Is Page command carrying the
+ * page number (big-endian u16) and `firmware::FIRMWARE_PAGE_SIZE` bytes.
+ * Leave Bootloader starts the new application. See `SpheroDevice::reflash`.
+ */
+
+/// Times a page that failed to program is sent again before giving up
+pub const DEFAULT_PAGE_RETRIES: u8 = 2;
+
+/// Options for `SpheroDevice::reflash_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReflashOptions {
+    /// Page to start from; above 0 the robot is taken to be in its bootloader
+    /// already, so an interrupted reflash picks up where it failed
+    pub start_page: u16,
+    /// Ask Is Page Blank before each page and skip pages already written
+    pub check_blank: bool,
+    /// Times a page that failed to program or timed out is sent again
+    pub page_retries: u8,
+}
+
+impl Default for ReflashOptions {
+    fn default() -> Self {
+        Self {
+            start_page: 0,
+            check_blank: false,
+            page_retries: DEFAULT_PAGE_RETRIES,
+        }
+    }
+}
+
+/// Sphero Reflash Progress
+/// Reported by `SpheroDevice::reflash` as it goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflashProgress {
+    /// The bootloader is ready for pages
+    Started {
+        /// First page to be sent
+        start_page: u16,
+        /// Pages in the image
+        pages: u16,
+    },
+    /// A page was programmed
+    PageWritten {
+        /// Page number
+        page: u16,
+        /// Pages in the image
+        pages: u16,
+    },
+    /// A page was found already written and skipped
+    PageSkipped {
+        /// Page number
+        page: u16,
+    },
+    /// A page failed and is being sent again
+    PageRetry {
+        /// Page number
+        page: u16,
+        /// Retry number, from 1
+        attempt: u8,
+    },
+    /// Every page is written and the new application was started
+    Finished,
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::device::SpheroDevice;
+    use crate::error::Error;
+    use crate::firmware::{FirmwareImage, FIRMWARE_PAGE_SIZE};
+    use crate::packet::{
+        BootloaderCommandID, CoreCommandID, DeviceID, MRSPField, SpheroCommandPacketV1,
+    };
+    use crate::transport::mock::{respond, MockTransport};
+    use std::sync::{Arc, Mutex};
+
+    /// Flash of a robot in its bootloader, and the faults to play back
+    #[derive(Default)]
+    struct Robot {
+        pages: Vec<Option<Vec<u8>>>,
+        app_version: u8,
+        /// Page that fails to program once
+        flaky_page: Option<u16>,
+        /// Page at which the battery is too low to go on
+        low_voltage_page: Option<u16>,
+    }
+
+    impl Robot {
+        fn answer(&mut self, packet: &SpheroCommandPacketV1) -> Vec<Vec<u8>> {
+            let page = || u16::from_be_bytes([packet.data()[0], packet.data()[1]]);
+            let (mrsp, data) = match (packet.did(), packet.cid()) {
+                (DeviceID::Core, cid) if cid == CoreCommandID::GetVersioningInformation as u8 => {
+                    let version = [2, 3, 1, self.app_version, 0, 0x41, 0x21, 4, 1, 0x12];
+                    (MRSPField::Ok, version.to_vec())
+                }
+                (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::Reflash as u8 => {
+                    self.pages.iter_mut().for_each(|page| *page = None);
+                    (MRSPField::Ok, vec![])
+                }
+                (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::IsPageBlank as u8 => {
+                    let blank = self.pages[page() as usize].is_none();
+                    (MRSPField::Ok, vec![blank as u8])
+                }
+                (DeviceID::Bootloader, cid) if cid == BootloaderCommandID::HereIsPage as u8 => {
+                    let page = page();
+                    if self.low_voltage_page == Some(page) {
+                        self.low_voltage_page = None;
+                        (MRSPField::LowVoltageError, vec![])
+                    } else if self.flaky_page == Some(page) {
+                        self.flaky_page = None;
+                        (MRSPField::FlashFailError, vec![])
+                    } else {
+                        self.pages[page as usize] = Some(packet.data()[2..].to_vec());
+                        (MRSPField::Ok, vec![])
+                    }
+                }
+                (DeviceID::Bootloader, cid)
+                    if cid == BootloaderCommandID::LeaveBootloader as u8 =>
+                {
+                    // Starts the new application without answering
+                    self.app_version += 1;
+                    return vec![];
+                }
+                _ => (MRSPField::Ok, vec![]),
+            };
+            vec![respond(packet, mrsp, data)]
+        }
+    }
+
+    /// Image of four pages, the last one short
+    fn image() -> FirmwareImage {
+        let data: Vec<u8> = (0..FIRMWARE_PAGE_SIZE * 4 - 10).map(|i| i as u8).collect();
+        FirmwareImage::from_binary(&data).unwrap()
+    }
+
+    async fn connect(
+        robot: Robot,
+    ) -> (
+        SpheroDevice<MockTransport>,
+        MockTransport,
+        Arc<Mutex<Robot>>,
+    ) {
+        let robot = Arc::new(Mutex::new(robot));
+        let flash = robot.clone();
+        let mock = MockTransport::with_responder(move |bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            flash.lock().unwrap().answer(&packet)
+        });
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+        (device, mock, robot)
+    }
+
+    #[tokio::test]
+    async fn failed_page_is_retried_and_low_voltage_stops_the_reflash() {
+        let image = image();
+        let (device, _, _) = connect(Robot {
+            pages: vec![None; 4],
+            app_version: 1,
+            flaky_page: Some(1),
+            low_voltage_page: Some(2),
+        })
+        .await;
+
+        let mut events = vec![];
+        let stopped = device.reflash(&image, |event| events.push(event)).await;
+
+        let Err(Error::Reflash { page: 2, error }) = stopped else {
+            panic!("expected the reflash to stop at page 2, got {stopped:?}");
+        };
+        assert!(matches!(
+            *error,
+            Error::ResponseCode(MRSPField::LowVoltageError)
+        ));
+        assert_eq!(
+            events,
+            [
+                ReflashProgress::Started {
+                    start_page: 0,
+                    pages: 4
+                },
+                ReflashProgress::PageWritten { page: 0, pages: 4 },
+                ReflashProgress::PageRetry {
+                    page: 1,
+                    attempt: 1
+                },
+                ReflashProgress::PageWritten { page: 1, pages: 4 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resuming_skips_pages_already_written() {
+        let image = image();
+        let mut pages = vec![None; 4];
+        pages[0] = image.page(0).map(<[u8]>::to_vec);
+        pages[3] = image.page(3).map(<[u8]>::to_vec);
+        let (device, mock, robot) = connect(Robot {
+            pages,
+            app_version: 1,
+            ..Robot::default()
+        })
+        .await;
+        let options = ReflashOptions {
+            start_page: 1,
+            check_blank: true,
+            ..ReflashOptions::default()
+        };
+
+        let mut events = vec![];
+        let version = device
+            .reflash_with(&image, options, |event| events.push(event))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                ReflashProgress::Started {
+                    start_page: 1,
+                    pages: 4
+                },
+                ReflashProgress::PageWritten { page: 1, pages: 4 },
+                ReflashProgress::PageWritten { page: 2, pages: 4 },
+                ReflashProgress::PageSkipped { page: 3 },
+                ReflashProgress::Finished,
+            ]
+        );
+        // Resuming doesn't erase what is already there
+        assert!(!mock.written_packets().iter().any(|packet| {
+            packet.did() == DeviceID::Bootloader
+                && packet.cid() == BootloaderCommandID::Reflash as u8
+        }));
+        assert_eq!(version.msa_ver, 2);
+        for page in 0..image.page_count() {
+            let written = robot.lock().unwrap().pages[page as usize].clone();
+            assert_eq!(written.as_deref(), image.page(page));
+        }
+    }
+
+    #[tokio::test]
+    async fn page_failing_past_its_retries_stops_the_reflash() {
+        let image = image();
+        let (device, _, _) = connect(Robot {
+            pages: vec![None; 4],
+            app_version: 1,
+            flaky_page: Some(0),
+            ..Robot::default()
+        })
+        .await;
+        let options = ReflashOptions {
+            page_retries: 0,
+            ..ReflashOptions::default()
+        };
+
+        let stopped = device.reflash_with(&image, options, drop).await;
+
+        let Err(Error::Reflash { page: 0, error }) = stopped else {
+            panic!("expected the reflash to stop at page 0, got {stopped:?}");
+        };
+        assert!(matches!(
+            *error,
+            Error::ResponseCode(MRSPField::FlashFailError)
+        ));
+    }
+}
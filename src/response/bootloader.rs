@@ -0,0 +1,25 @@
+/*!
+ * Sphero Bootloader Responses
+ */
+use super::FromResponsePacket;
+use crate::error::Error;
+use crate::packet::SpheroResponsePacketV1;
+
+/// Answer to Is Page Blank
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct PageBlankResponse {
+    /// Whether the page is erased
+    pub blank: bool,
+}
+
+impl FromResponsePacket for PageBlankResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.is_empty() {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            blank: data[0] != 0,
+        })
+    }
+}
@@ -0,0 +1,212 @@
+/*!
+ * Sphero Core Responses
+ */
+use super::FromResponsePacket;
+use crate::error::Error;
+use crate::packet::SpheroResponsePacketV1;
+use crate::power::{PowerState, Voltage};
+
+/// Sphero Versioning Info
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 11)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct VersioningInfo {
+    /// Record version code
+    pub recv: u8,
+    /// Model number
+    pub mdl: u8,
+    /// Hardware version code
+    pub hw: u8,
+    /// Main Sphero Application version byte
+    pub msa_ver: u8,
+    /// Main Sphero Application revision byte
+    pub msa_rev: u8,
+    /// Bootloader version (packed nibble format)
+    pub bl: u8,
+    /// orbBasic version (packed nibble format)
+    pub bas: u8,
+    /// Macro executive version (MACRO)
+    pub macro_ver: u8,
+    /// API major revision code
+    pub api_maj: u8,
+    /// API minor revision code
+    pub api_min: u8,
+    /// Patch level, zero if not reported
+    pub patch: u8,
+}
+
+impl VersioningInfo {
+    /// Firmware version as (API major, API minor, patch)
+    pub fn firmware_version(&self) -> (u8, u8, u8) {
+        (self.api_maj, self.api_min, self.patch)
+    }
+
+    /// Whether the firmware implements at least API version `maj.min`
+    pub fn supports_api_version(&self, maj: u8, min: u8) -> bool {
+        (self.api_maj, self.api_min) >= (maj, min)
+    }
+}
+
+impl FromResponsePacket for VersioningInfo {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 10 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            recv: data[0],
+            mdl: data[1],
+            hw: data[2],
+            msa_ver: data[3],
+            msa_rev: data[4],
+            bl: data[5],
+            bas: data[6],
+            macro_ver: data[7],
+            api_maj: data[8],
+            api_min: data[9],
+            patch: data.get(10).copied().unwrap_or(0),
+        })
+    }
+}
+
+/// Sphero Bluetooth Info
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 12)
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct BluetoothInfo {
+    /// Advertised name
+    pub name: String,
+    /// Bluetooth address, as ASCII hex
+    pub address: String,
+    /// ID colors (the three colors flashed when the ball is shaken)
+    pub colors: [u8; 3],
+}
+
+impl FromResponsePacket for BluetoothInfo {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 32 {
+            return Err(Error::BadDataLength);
+        }
+        let text = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        Ok(Self {
+            name: text(&data[0..16]),
+            address: text(&data[16..28]),
+            colors: [data[29], data[30], data[31]],
+        })
+    }
+}
+
+/// Sphero Power State Info
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 14)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct PowerStateInfo {
+    /// Record version code
+    pub rec_ver: u8,
+    /// Power state
+    pub state: PowerState,
+    /// Battery voltage
+    pub voltage: Voltage,
+    /// Number of battery recharges in the life of this Sphero
+    pub num_charges: u16,
+    /// Seconds awake since last recharge
+    pub time_since_charge: u16,
+}
+
+impl FromResponsePacket for PowerStateInfo {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 8 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            rec_ver: data[0],
+            state: PowerState::from(data[1]),
+            voltage: Voltage::from_hundredths(u16::from_be_bytes([data[2], data[3]])),
+            num_charges: u16::from_be_bytes([data[4], data[5]]),
+            time_since_charge: u16::from_be_bytes([data[6], data[7]]),
+        })
+    }
+}
+
+/// Sphero Packet Times
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 21)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct PacketTimes {
+    /// Client clock when the command was sent (echoed), in ms
+    pub client_tx: u32,
+    /// Robot clock when the command was received, in ms
+    pub robot_rx: u32,
+    /// Robot clock when the response was sent, in ms
+    pub robot_tx: u32,
+}
+
+impl FromResponsePacket for PacketTimes {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 12 {
+            return Err(Error::BadDataLength);
+        }
+        let word = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        Ok(Self {
+            client_tx: word(0),
+            robot_rx: word(4),
+            robot_tx: word(8),
+        })
+    }
+}
+
+/// Sphero Voltage Trip Points
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 15)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct VoltageTripPointsResponse {
+    /// Low battery threshold
+    pub low: Voltage,
+    /// Critical battery threshold
+    pub critical: Voltage,
+}
+
+impl FromResponsePacket for VoltageTripPointsResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 4 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            low: Voltage::from_hundredths(u16::from_be_bytes([data[0], data[1]])),
+            critical: Voltage::from_hundredths(u16::from_be_bytes([data[2], data[3]])),
+        })
+    }
+}
+
+/// Sphero Auto Reconnect Setting
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 13)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct AutoReconnectResponse {
+    /// Flag - true = reconnect to the last device on power up
+    pub enabled: bool,
+    /// Seconds after power up before reconnecting
+    pub time: u8,
+}
+
+impl FromResponsePacket for AutoReconnectResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 2 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            enabled: data[0] != 0,
+            time: data[1],
+        })
+    }
+}
+
+/// Response to Get Versioning
+pub type VersioningInfoResponse = VersioningInfo;
+/// Response to Get Bluetooth Info
+pub type BluetoothInfoResponse = BluetoothInfo;
+/// Response to Get Power State
+pub type PowerStateResponse = PowerStateInfo;
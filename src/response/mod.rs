@@ -0,0 +1,36 @@
+/*!
+ * Sphero Responses
+ *
+ * Typed views of the data returned in simple response packets, grouped like
+ * `command` by the virtual device that answers. Everything is re-exported here.
+ *
+ * ```
+ * use sphero_rs::packet::{MRSPField, SpheroResponsePacketV1};
+ * use sphero_rs::response::{FromResponsePacket, MacroStatusResponse, VoltageTripPointsResponse};
+ *
+ * let packet = SpheroResponsePacketV1::new(MRSPField::Ok, 1, vec![0x02, 0x9e, 0x02, 0x6c]);
+ * let trip = VoltageTripPointsResponse::from_response(&packet).unwrap();
+ * assert_eq!((trip.low.hundredths(), trip.critical.hundredths()), (670, 620));
+ *
+ * let packet = SpheroResponsePacketV1::new(MRSPField::Ok, 2, vec![0xff, 0x00, 0x07]);
+ * let status = MacroStatusResponse::from_response(&packet).unwrap();
+ * assert_eq!((status.macro_id, status.command), (0xff, 7));
+ * assert!(MacroStatusResponse::from_response(&SpheroResponsePacketV1::default()).is_err());
+ * ```
+ */
+use crate::error::Error;
+use crate::packet::SpheroResponsePacketV1;
+
+pub mod bootloader;
+pub mod core;
+pub mod sphero;
+
+pub use self::bootloader::*;
+pub use self::core::*;
+pub use self::sphero::*;
+
+/// Sphero Response Conversion
+pub trait FromResponsePacket: Sized {
+    /// Decode from the data of a response packet
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error>;
+}
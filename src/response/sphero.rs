@@ -0,0 +1,146 @@
+/*!
+ * Sphero Device Responses
+ */
+use super::FromResponsePacket;
+use crate::color::RgbColor;
+use crate::command::ACB_LEN;
+use crate::error::Error;
+use crate::packet::SpheroResponsePacketV1;
+
+/// Sphero Locator Data
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 31)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct LocatorData {
+    /// X position, in cm
+    pub x: i16,
+    /// Y position, in cm
+    pub y: i16,
+    /// X velocity, in mm/s
+    pub vx: i16,
+    /// Y velocity, in mm/s
+    pub vy: i16,
+    /// Speed over ground, in mm/s
+    pub sog: u16,
+}
+
+impl FromResponsePacket for LocatorData {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 10 {
+            return Err(Error::BadDataLength);
+        }
+        let word = |i: usize| [data[i], data[i + 1]];
+        Ok(Self {
+            x: i16::from_be_bytes(word(0)),
+            y: i16::from_be_bytes(word(2)),
+            vx: i16::from_be_bytes(word(4)),
+            vy: i16::from_be_bytes(word(6)),
+            sog: u16::from_be_bytes(word(8)),
+        })
+    }
+}
+
+/// Sphero RGB LED Color
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 26)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct RGBLEDColorResponse {
+    /// User LED color, as last set with the persist flag
+    pub color: RgbColor,
+}
+
+impl FromResponsePacket for RGBLEDColorResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 3 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            color: RgbColor::new(data[0], data[1], data[2]),
+        })
+    }
+}
+
+/// Sphero Chassis ID
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 23)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ChassisIDResponse {
+    /// Chassis ID set at the factory
+    pub chassis_id: u16,
+}
+
+impl FromResponsePacket for ChassisIDResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 2 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            chassis_id: u16::from_be_bytes([data[0], data[1]]),
+        })
+    }
+}
+
+/// Sphero Application Configuration Block
+/// 32 bytes the robot keeps for applications; it never interprets them
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ApplicationConfigurationBlock {
+    /// Block contents
+    pub data: [u8; ACB_LEN],
+}
+
+impl FromResponsePacket for ApplicationConfigurationBlock {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data().get(..ACB_LEN).ok_or(Error::BadDataLength)?;
+        let mut block = Self::default();
+        block.data.copy_from_slice(data);
+        Ok(block)
+    }
+}
+
+/// Sphero Macro Status
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 39)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct MacroStatusResponse {
+    /// ID of the running macro, 0 if none
+    pub macro_id: u8,
+    /// Number of the command being executed
+    pub command: u16,
+}
+
+impl FromResponsePacket for MacroStatusResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.len() < 3 {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self {
+            macro_id: data[0],
+            command: u16::from_be_bytes([data[1], data[2]]),
+        })
+    }
+}
+
+/// Sphero Device Mode
+/// <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 35)
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct DeviceModeResponse {
+    /// Mode: 0 = normal, 1 = user hack mode
+    pub mode: u8,
+}
+
+impl DeviceModeResponse {
+    /// Whether user hack mode is on
+    pub fn is_user_hack(&self) -> bool {
+        self.mode == 1
+    }
+}
+
+impl FromResponsePacket for DeviceModeResponse {
+    fn from_response(packet: &SpheroResponsePacketV1) -> Result<Self, Error> {
+        let data = packet.data();
+        if data.is_empty() {
+            return Err(Error::BadDataLength);
+        }
+        Ok(Self { mode: data[0] })
+    }
+}
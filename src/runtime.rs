@@ -0,0 +1,118 @@
+/*!
+ * Sphero Runtime Glue
+ *
+ * The device client runs background tasks (the packet reader, keep-alive
+ * pings, best-effort stops) and timers. Timers don't depend on any executor;
+ * tasks are handed to a `Spawner`, so any executor can drive the client.
+ */
+use crate::error::Error;
+use futures::future::{select, BoxFuture, Either};
+use futures_timer::Delay;
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+
+/// Runs the device client's background tasks
+///
+/// Implemented for closures, e.g. for async-std:
+/// `|future| drop(async_std::task::spawn(future))`.
+pub trait Spawner: Send + Sync + 'static {
+    /// Run `future` in the background
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+}
+
+impl<F> Spawner for F
+where
+    F: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+{
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        self(future)
+    }
+}
+
+/// Spawns onto the current tokio runtime
+/// Tasks spawned while no runtime is running are dropped.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioSpawner;
+
+#[cfg(feature = "tokio")]
+impl Spawner for TokioSpawner {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        // `Drop` impls may spawn after the runtime is gone
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            drop(handle.spawn(future));
+        }
+    }
+}
+
+/// Wait for `future`, giving up with `Error::Timeout` after `duration`
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Error> {
+    match select(pin!(future), Delay::new(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Error::Timeout),
+    }
+}
+
+/// Wait for `duration`
+pub(crate) async fn sleep(duration: Duration) {
+    Delay::new(duration).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Ping;
+    use crate::device::{SendOptions, SpheroDevice};
+    use crate::transport::mock::MockTransport;
+    use futures::executor::block_on;
+    use futures::future::{pending, ready};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn timers_run_without_a_runtime() {
+        let expired = block_on(timeout(Duration::from_millis(10), pending::<()>()));
+        assert!(matches!(expired, Err(Error::Timeout)));
+        let ready = block_on(timeout(Duration::from_secs(1), ready(7)));
+        assert!(matches!(ready, Ok(7)));
+    }
+
+    /// Spawner running each task on its own thread, counting them
+    fn threads(spawned: Arc<AtomicUsize>) -> impl Spawner {
+        move |task: BoxFuture<'static, ()>| {
+            let _ = spawned.fetch_add(1, Ordering::Relaxed);
+            drop(std::thread::spawn(move || block_on(task)));
+        }
+    }
+
+    #[test]
+    fn device_runs_on_any_executor() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let mock = MockTransport::acknowledging();
+        block_on(async {
+            let device = SpheroDevice::with_spawner(mock.clone(), threads(spawned.clone()))
+                .await
+                .unwrap();
+            assert!(device.send(&Ping {}).await.unwrap().data().is_empty());
+        });
+        assert!(spawned.load(Ordering::Relaxed) >= 1);
+        assert!(!mock.written().is_empty());
+    }
+
+    #[test]
+    fn device_times_out_without_a_runtime() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        block_on(async {
+            let device = SpheroDevice::with_spawner(MockTransport::new(), threads(spawned))
+                .await
+                .unwrap();
+            let options = SendOptions {
+                timeout: Duration::from_millis(20),
+                ..SendOptions::default()
+            };
+            let result = device.send_with(&Ping {}, options).await;
+            assert!(matches!(result, Err(Error::Timeout)));
+        });
+    }
+}
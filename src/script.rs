@@ -0,0 +1,147 @@
+/*!
+ * Sphero Command Scripts
+ *
+ * A routine written as data, in JSON, YAML or any other serde format, and
+ * run with `SpheroDevice::run_script`. Each step is tagged with its kind:
+ *
+ * ```json
+ * { "steps": [
+ *     { "step": "set_color", "red": 0, "green": 255, "blue": 0 },
+ *     { "step": "repeat", "count": 4, "steps": [
+ *         { "step": "roll", "speed": 80, "heading": 0, "duration_ms": 500 },
+ *         { "step": "set_heading", "heading": 90 }
+ *     ] },
+ *     { "step": "wait", "ms": 250 },
+ *     { "step": "run_macro", "id": 2 }
+ * ] }
+ * ```
+ *
+ * Steps are checked before anything is sent; a bad step is reported as
+ * `Error::ScriptStep` with the index of its top-level step, so a step
+ * inside a repeat is reported as that repeat.
+ */
+use crate::error::Error;
+use crate::heading::Heading;
+
+/// Repeats that may be nested inside each other
+pub const MAX_REPEAT_DEPTH: usize = 4;
+
+/// One step of a `Script`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum ScriptStep {
+    /// Set the LED color, without persisting it
+    SetColor {
+        /// Red
+        red: u8,
+        /// Green
+        green: u8,
+        /// Blue
+        blue: u8,
+    },
+    /// Roll at `speed` towards `heading`, and stop after `duration_ms` if given
+    Roll {
+        /// Speed, 0..255
+        speed: u8,
+        /// Heading, 0..359 degrees
+        heading: u16,
+        /// How long to roll for before stopping; without it the robot keeps rolling
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<u64>,
+    },
+    /// Do nothing for `ms` milliseconds
+    Wait {
+        /// Milliseconds
+        ms: u64,
+    },
+    /// Make the current direction `heading`, see `command::SetHeading`
+    SetHeading {
+        /// Heading adjustment, 0..359 degrees
+        heading: u16,
+    },
+    /// Run a macro stored on the robot
+    RunMacro {
+        /// Macro ID
+        id: u8,
+    },
+    /// Run `steps` `count` times over
+    Repeat {
+        /// Times to run the steps
+        count: u32,
+        /// Steps to repeat
+        steps: Vec<ScriptStep>,
+    },
+}
+
+impl ScriptStep {
+    /// Check this step, inside `depth` repeats
+    fn validate(&self, depth: usize) -> Result<(), Error> {
+        match self {
+            ScriptStep::Roll { heading, .. } | ScriptStep::SetHeading { heading } => {
+                Heading::new(*heading).map(drop)
+            }
+            ScriptStep::Repeat { .. } if depth >= MAX_REPEAT_DEPTH => Err(Error::BadParameterValue),
+            ScriptStep::Repeat { steps, .. } => {
+                steps.iter().try_for_each(|step| step.validate(depth + 1))
+            }
+            ScriptStep::SetColor { .. } | ScriptStep::Wait { .. } | ScriptStep::RunMacro { .. } => {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Sphero Command Script
+///
+/// ```
+/// use sphero_rs::error::Error;
+/// use sphero_rs::script::{Script, ScriptStep};
+///
+/// let json = r#"{ "steps": [
+///     { "step": "set_color", "red": 255, "green": 0, "blue": 0 },
+///     { "step": "repeat", "count": 2, "steps": [
+///         { "step": "roll", "speed": 60, "heading": 90, "duration_ms": 100 },
+///         { "step": "set_heading", "heading": 400 }
+///     ] }
+/// ] }"#;
+/// let script: Script = serde_json::from_str(json).unwrap();
+/// assert_eq!(
+///     script.steps[0],
+///     ScriptStep::SetColor { red: 255, green: 0, blue: 0 }
+/// );
+///
+/// // Heading 400 is inside the repeat at step 1
+/// let rejected = script.validate().unwrap_err();
+/// assert_eq!(rejected.to_string(), "script step 1: BadParameterValue");
+/// let Error::ScriptStep { index: 1, error } = rejected else {
+///     panic!("expected step 1 to be rejected");
+/// };
+/// assert!(matches!(*error, Error::BadParameterValue));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Script {
+    /// Steps, run in order
+    pub steps: Vec<ScriptStep>,
+}
+
+impl Script {
+    /// Script of `steps`
+    pub fn new(steps: Vec<ScriptStep>) -> Self {
+        Self { steps }
+    }
+
+    /// Check every step before running any
+    ///
+    /// A heading over 359 degrees, or a repeat nested more than
+    /// `MAX_REPEAT_DEPTH` deep, fails with `Error::BadParameterValue` wrapped
+    /// in `Error::ScriptStep` with the index of its top-level step.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (index, step) in self.steps.iter().enumerate() {
+            step.validate(0).map_err(|error| Error::ScriptStep {
+                index,
+                error: Box::new(error),
+            })?;
+        }
+        Ok(())
+    }
+}
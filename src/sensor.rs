@@ -0,0 +1,805 @@
+/*!
+ * Sphero Sensor Streaming
+ *
+ * Sensor data is streamed as async packets whose layout depends on the masks
+ * sent with Set Data Streaming: each frame holds one signed 16-bit value per
+ * enabled source, in mask bit order (MASK1 MSB first, then MASK2 MSB first).
+ * <https://docs.gosphero.com/api/Sphero_API_1.20.pdf> (Page 28)
+ */
+use crate::command::SetDataStreaming;
+use crate::error::Error;
+use std::time::{Duration, Instant};
+
+/// Sphero Streamable Data Source
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Sensor {
+    /// Accelerometer axis X, raw
+    AccelXRaw,
+    /// Accelerometer axis Y, raw
+    AccelYRaw,
+    /// Accelerometer axis Z, raw
+    AccelZRaw,
+    /// Gyro axis X, raw
+    GyroXRaw,
+    /// Gyro axis Y, raw
+    GyroYRaw,
+    /// Gyro axis Z, raw
+    GyroZRaw,
+    /// Right motor back EMF, raw
+    RightMotorEmfRaw,
+    /// Left motor back EMF, raw
+    LeftMotorEmfRaw,
+    /// Left motor PWM, raw
+    LeftMotorPwmRaw,
+    /// Right motor PWM, raw
+    RightMotorPwmRaw,
+    /// IMU pitch angle, filtered (degrees)
+    ImuPitch,
+    /// IMU roll angle, filtered (degrees)
+    ImuRoll,
+    /// IMU yaw angle, filtered (degrees)
+    ImuYaw,
+    /// Accelerometer axis X, filtered (1/4096 G)
+    AccelX,
+    /// Accelerometer axis Y, filtered (1/4096 G)
+    AccelY,
+    /// Accelerometer axis Z, filtered (1/4096 G)
+    AccelZ,
+    /// Gyro axis X, filtered (0.1 dps)
+    GyroX,
+    /// Gyro axis Y, filtered (0.1 dps)
+    GyroY,
+    /// Gyro axis Z, filtered (0.1 dps)
+    GyroZ,
+    /// Right motor back EMF, filtered
+    RightMotorEmf,
+    /// Left motor back EMF, filtered
+    LeftMotorEmf,
+    /// Quaternion Q0 (1/10000 Q)
+    QuaternionQ0,
+    /// Quaternion Q1 (1/10000 Q)
+    QuaternionQ1,
+    /// Quaternion Q2 (1/10000 Q)
+    QuaternionQ2,
+    /// Quaternion Q3 (1/10000 Q)
+    QuaternionQ3,
+    /// Odometer X (cm)
+    OdometerX,
+    /// Odometer Y (cm)
+    OdometerY,
+    /// Acceleration magnitude (mG)
+    AccelOne,
+    /// Velocity X (mm/s)
+    VelocityX,
+    /// Velocity Y (mm/s)
+    VelocityY,
+}
+
+impl Sensor {
+    /// Every source, in the order values appear in a frame
+    pub const ALL: [Sensor; 30] = [
+        Sensor::AccelXRaw,
+        Sensor::AccelYRaw,
+        Sensor::AccelZRaw,
+        Sensor::GyroXRaw,
+        Sensor::GyroYRaw,
+        Sensor::GyroZRaw,
+        Sensor::RightMotorEmfRaw,
+        Sensor::LeftMotorEmfRaw,
+        Sensor::LeftMotorPwmRaw,
+        Sensor::RightMotorPwmRaw,
+        Sensor::ImuPitch,
+        Sensor::ImuRoll,
+        Sensor::ImuYaw,
+        Sensor::AccelX,
+        Sensor::AccelY,
+        Sensor::AccelZ,
+        Sensor::GyroX,
+        Sensor::GyroY,
+        Sensor::GyroZ,
+        Sensor::RightMotorEmf,
+        Sensor::LeftMotorEmf,
+        Sensor::QuaternionQ0,
+        Sensor::QuaternionQ1,
+        Sensor::QuaternionQ2,
+        Sensor::QuaternionQ3,
+        Sensor::OdometerX,
+        Sensor::OdometerY,
+        Sensor::AccelOne,
+        Sensor::VelocityX,
+        Sensor::VelocityY,
+    ];
+
+    /// Bit selecting this source in MASK1 and MASK2
+    pub fn mask(&self) -> SensorMask {
+        let (mask1, mask2) = match self {
+            Sensor::AccelXRaw => (0x8000_0000, 0),
+            Sensor::AccelYRaw => (0x4000_0000, 0),
+            Sensor::AccelZRaw => (0x2000_0000, 0),
+            Sensor::GyroXRaw => (0x1000_0000, 0),
+            Sensor::GyroYRaw => (0x0800_0000, 0),
+            Sensor::GyroZRaw => (0x0400_0000, 0),
+            Sensor::RightMotorEmfRaw => (0x0040_0000, 0),
+            Sensor::LeftMotorEmfRaw => (0x0020_0000, 0),
+            Sensor::LeftMotorPwmRaw => (0x0010_0000, 0),
+            Sensor::RightMotorPwmRaw => (0x0008_0000, 0),
+            Sensor::ImuPitch => (0x0004_0000, 0),
+            Sensor::ImuRoll => (0x0002_0000, 0),
+            Sensor::ImuYaw => (0x0001_0000, 0),
+            Sensor::AccelX => (0x0000_8000, 0),
+            Sensor::AccelY => (0x0000_4000, 0),
+            Sensor::AccelZ => (0x0000_2000, 0),
+            Sensor::GyroX => (0x0000_1000, 0),
+            Sensor::GyroY => (0x0000_0800, 0),
+            Sensor::GyroZ => (0x0000_0400, 0),
+            Sensor::RightMotorEmf => (0x0000_0040, 0),
+            Sensor::LeftMotorEmf => (0x0000_0020, 0),
+            Sensor::QuaternionQ0 => (0, 0x8000_0000),
+            Sensor::QuaternionQ1 => (0, 0x4000_0000),
+            Sensor::QuaternionQ2 => (0, 0x2000_0000),
+            Sensor::QuaternionQ3 => (0, 0x1000_0000),
+            Sensor::OdometerX => (0, 0x0800_0000),
+            Sensor::OdometerY => (0, 0x0400_0000),
+            Sensor::AccelOne => (0, 0x0200_0000),
+            Sensor::VelocityX => (0, 0x0100_0000),
+            Sensor::VelocityY => (0, 0x0080_0000),
+        };
+        SensorMask { mask1, mask2 }
+    }
+
+    /// Streamed counts per unit noted on the source
+    fn counts_per_unit(&self) -> f32 {
+        match self {
+            Sensor::AccelX | Sensor::AccelY | Sensor::AccelZ => 4096.0,
+            Sensor::GyroX | Sensor::GyroY | Sensor::GyroZ => 10.0,
+            Sensor::QuaternionQ0
+            | Sensor::QuaternionQ1
+            | Sensor::QuaternionQ2
+            | Sensor::QuaternionQ3 => 10000.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Streamed `value` in the unit noted on the source
+    /// G for filtered acceleration, dps for filtered rotation and Q for
+    /// quaternions; raw and already-scaled sources are passed through.
+    ///
+    /// ```
+    /// use sphero_rs::sensor::Sensor;
+    ///
+    /// assert_eq!(Sensor::AccelZ.convert(4096), 1.0);
+    /// assert_eq!(Sensor::GyroX.convert(-900), -90.0);
+    /// assert_eq!(Sensor::ImuPitch.convert(12), 12.0);
+    /// ```
+    pub fn convert(&self, value: i16) -> f32 {
+        value as f32 / self.counts_per_unit()
+    }
+}
+
+/// Sphero Streaming Masks
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct SensorMask {
+    /// Bitwise selector of data sources (MASK1)
+    pub mask1: u32,
+    /// Bitwise selector of more data sources (MASK2)
+    pub mask2: u32,
+}
+
+impl SensorMask {
+    /// Masks selecting every source in `sensors`
+    pub fn from_sensors(sensors: &[Sensor]) -> Self {
+        sensors.iter().fold(Self::default(), |acc, sensor| {
+            let mask = sensor.mask();
+            SensorMask {
+                mask1: acc.mask1 | mask.mask1,
+                mask2: acc.mask2 | mask.mask2,
+            }
+        })
+    }
+
+    /// Enabled sources, in frame order
+    pub fn sensors(&self) -> Vec<Sensor> {
+        Sensor::ALL
+            .into_iter()
+            .filter(|sensor| {
+                let mask = sensor.mask();
+                self.mask1 & mask.mask1 != 0 || self.mask2 & mask.mask2 != 0
+            })
+            .collect()
+    }
+
+    /// Size of one frame in bytes
+    pub fn frame_len(&self) -> usize {
+        self.sensors().len() * 2
+    }
+}
+
+/// Sphero Sensor Frame
+/// One sample of every enabled source
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SensorFrame {
+    /// Values, in frame order
+    pub values: Vec<(Sensor, i16)>,
+}
+
+impl SensorFrame {
+    /// Value of `sensor`, if it was streamed
+    pub fn get(&self, sensor: Sensor) -> Option<i16> {
+        self.values
+            .iter()
+            .find(|(s, _)| *s == sensor)
+            .map(|&(_, value)| value)
+    }
+
+    /// Total acceleration in G, from the filtered axes or else the magnitude
+    pub fn accel_g(&self) -> Option<f32> {
+        match (
+            self.get(Sensor::AccelX),
+            self.get(Sensor::AccelY),
+            self.get(Sensor::AccelZ),
+        ) {
+            (Some(x), Some(y), Some(z)) => {
+                let (x, y, z) = (x as f32, y as f32, z as f32);
+                Some((x * x + y * y + z * z).sqrt() / 4096.0)
+            }
+            _ => self.get(Sensor::AccelOne).map(|mg| mg as f32 / 1000.0),
+        }
+    }
+
+    /// Decode the frames of a sensor data async packet streamed with `mask`
+    pub fn decode(mask: &SensorMask, data: &[u8]) -> Result<Vec<SensorFrame>, Error> {
+        let sensors = mask.sensors();
+        let frame_len = sensors.len() * 2;
+        if frame_len == 0 || !data.len().is_multiple_of(frame_len) {
+            return Err(Error::BadDataLength);
+        }
+        Ok(data
+            .chunks(frame_len)
+            .map(|frame| SensorFrame {
+                values: sensors
+                    .iter()
+                    .zip(frame.chunks(2))
+                    .map(|(&sensor, word)| (sensor, i16::from_be_bytes([word[0], word[1]])))
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+/// Rate the control system samples sensors at, in Hz
+pub const MAX_SAMPLE_RATE_HZ: f32 = 400.0;
+
+/// Sphero Streaming Configuration
+/// Describes a Set Data Streaming command in terms of sensors
+///
+/// ```
+/// use sphero_rs::sensor::{Sensor, StreamingConfig};
+///
+/// let cmd = StreamingConfig::new()
+///     .sample_rate_hz(20.0)?
+///     .frames_per_packet(2)?
+///     .packets(10)
+///     .with_sensors(&[Sensor::ImuPitch, Sensor::ImuRoll])
+///     .build();
+/// assert_eq!((cmd.n, cmd.m, cmd.pcnt), (20, 2, 10));
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct StreamingConfig {
+    n: u16,
+    m: u16,
+    pcnt: u8,
+    mask: SensorMask,
+    watchdog: Option<WatchdogConfig>,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        // 40Hz, one frame per packet, until stopped
+        Self {
+            n: 10,
+            m: 1,
+            pcnt: 0,
+            mask: SensorMask::default(),
+            watchdog: None,
+        }
+    }
+}
+
+impl StreamingConfig {
+    /// Default configuration streaming no sensors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample at approximately `rate` Hz
+    /// N is `400 / rate` rounded to the nearest integer and must fit 1..=65535.
+    pub fn sample_rate_hz(mut self, rate: f32) -> Result<Self, Error> {
+        let n = (MAX_SAMPLE_RATE_HZ / rate).round();
+        if !n.is_finite() || n < 1.0 || n > u16::MAX as f32 {
+            return Err(Error::BadParameterValue);
+        }
+        self.n = n as u16;
+        Ok(self)
+    }
+
+    /// Rate actually sampled at, in Hz
+    pub fn effective_rate_hz(&self) -> f32 {
+        MAX_SAMPLE_RATE_HZ / self.n as f32
+    }
+
+    /// Collect `m` frames into each packet (M)
+    pub fn frames_per_packet(mut self, m: u16) -> Result<Self, Error> {
+        if m < 1 {
+            return Err(Error::BadParameterValue);
+        }
+        self.m = m;
+        Ok(self)
+    }
+
+    /// Stream until stopped (PCNT = 0)
+    pub fn unlimited(mut self) -> Self {
+        self.pcnt = 0;
+        self
+    }
+
+    /// Stop after `count` packets (PCNT), 0 meaning unlimited
+    pub fn packets(mut self, count: u8) -> Self {
+        self.pcnt = count;
+        self
+    }
+
+    /// Stream `sensors`, replacing any previous selection
+    pub fn with_sensors(mut self, sensors: &[Sensor]) -> Self {
+        self.mask = SensorMask::from_sensors(sensors);
+        self
+    }
+
+    /// Time between packets: M frames at the sample rate
+    pub fn packet_interval(&self) -> Duration {
+        // N samples at 400 Hz are N * 2.5 ms
+        Duration::from_micros(self.m as u64 * self.n as u64 * 2500)
+    }
+
+    /// Watch the stream for stalls, see `StreamWatchdog`
+    /// Only `SpheroDevice::start_streaming` acts on this; it isn't part of the command.
+    pub fn with_watchdog(mut self, watchdog: WatchdogConfig) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Watchdog settings, if watched
+    pub fn watchdog(&self) -> Option<WatchdogConfig> {
+        self.watchdog
+    }
+
+    /// Masks the data will be streamed with
+    pub fn mask(&self) -> SensorMask {
+        self.mask
+    }
+
+    /// Convert to the command
+    /// MASK2 is only sent when a MASK2 source is selected, for older firmware.
+    pub fn build(&self) -> SetDataStreaming {
+        SetDataStreaming {
+            n: self.n,
+            m: self.m,
+            mask1: self.mask.mask1,
+            pcnt: self.pcnt,
+            mask2: (self.mask.mask2 != 0).then_some(self.mask.mask2),
+        }
+    }
+}
+
+/// Sensors to stream for pickup detection
+pub const PICKUP_SENSORS: [Sensor; 3] = [Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ];
+
+/// Sphero Pickup Detection Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickupConfig {
+    /// Total acceleration below this counts as falling, in G
+    pub free_fall_g: f32,
+    /// How long the robot must fall before it is reported
+    pub free_fall_time: Duration,
+    /// Total acceleration above this is an impact, in G
+    pub impact_g: f32,
+    /// Impacts this soon after the last one are not reported again
+    pub impact_holdoff: Duration,
+}
+
+impl Default for PickupConfig {
+    fn default() -> Self {
+        Self {
+            free_fall_g: 0.3,
+            free_fall_time: Duration::from_millis(80),
+            impact_g: 2.5,
+            impact_holdoff: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Motion detected locally from streamed sensor data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotionEvent {
+    /// Near weightless for `PickupConfig::free_fall_time`: dropped or thrown
+    FreeFall,
+    /// Acceleration spike, e.g. grabbed or landing, with its peak in G
+    Impact(f32),
+    /// Outside the pitch/roll envelope for `OrientationConfig::debounce`
+    Tilted(Attitude),
+    /// Back inside the envelope for `OrientationConfig::debounce`
+    Level(Attitude),
+}
+
+/// Sphero Pickup Detector
+///
+/// Watches total acceleration sample by sample. Windows are given as times
+/// and converted to sample counts at the stream's rate, so the same settings
+/// work at any rate.
+///
+/// ```
+/// use sphero_rs::sensor::{MotionEvent, PickupConfig, PickupDetector};
+///
+/// let replay = |rate_hz: f32, trace: &[f32]| {
+///     let mut detector = PickupDetector::new(PickupConfig::default(), rate_hz).unwrap();
+///     trace.iter().filter_map(|&g| detector.push_g(g)).collect::<Vec<_>>()
+/// };
+///
+/// // Normal rolling, bumpy but never near 0 G or a hard hit
+/// let rolling = [1.0, 1.2, 0.8, 1.4, 0.7, 1.1, 1.6, 0.9, 1.0, 1.3];
+/// assert_eq!(replay(40.0, &rolling), vec![]);
+///
+/// // Picked up: grabbed, lifted and carried
+/// let pickup = [1.0, 1.0, 2.8, 3.1, 1.5, 1.2, 1.1, 0.9, 1.0];
+/// assert_eq!(replay(40.0, &pickup), vec![MotionEvent::Impact(2.8)]);
+///
+/// // Dropped: 150 ms falling (6 samples at 40 Hz), then landing
+/// let drop = [1.0, 0.1, 0.05, 0.0, 0.1, 0.05, 0.1, 4.2, 1.9, 1.0];
+/// assert_eq!(replay(40.0, &drop), vec![MotionEvent::FreeFall, MotionEvent::Impact(4.2)]);
+///
+/// // At 400 Hz the same six samples are only 15 ms, too short to be a fall
+/// assert_eq!(replay(400.0, &drop), vec![MotionEvent::Impact(4.2)]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickupDetector {
+    config: PickupConfig,
+    free_fall_samples: u32,
+    holdoff_samples: u32,
+    /// Consecutive samples below the free fall threshold
+    falling: u32,
+    /// Samples since the last impact, if any
+    since_impact: Option<u32>,
+}
+
+impl PickupDetector {
+    /// Detect with `config` on a stream sampled at `rate_hz`
+    pub fn new(config: PickupConfig, rate_hz: f32) -> Result<Self, Error> {
+        let mut detector = Self {
+            config,
+            free_fall_samples: 1,
+            holdoff_samples: 0,
+            falling: 0,
+            since_impact: None,
+        };
+        detector.set_rate_hz(rate_hz)?;
+        Ok(detector)
+    }
+
+    /// Settings in use
+    pub fn config(&self) -> PickupConfig {
+        self.config
+    }
+
+    /// Follow a change of the stream's sample rate
+    pub fn set_rate_hz(&mut self, rate_hz: f32) -> Result<(), Error> {
+        if !(rate_hz > 0.0 && rate_hz.is_finite()) {
+            return Err(Error::BadParameterValue);
+        }
+        let samples = |time: Duration| (time.as_secs_f32() * rate_hz).ceil() as u32;
+        self.free_fall_samples = samples(self.config.free_fall_time).max(1);
+        self.holdoff_samples = samples(self.config.impact_holdoff);
+        Ok(())
+    }
+
+    /// Feed one sample of total acceleration, in G
+    /// A fall is reported once, when it has lasted long enough.
+    pub fn push_g(&mut self, g: f32) -> Option<MotionEvent> {
+        if let Some(since) = self.since_impact.as_mut() {
+            *since = since.saturating_add(1);
+        }
+
+        if g < self.config.free_fall_g {
+            self.falling = self.falling.saturating_add(1);
+            return (self.falling == self.free_fall_samples).then_some(MotionEvent::FreeFall);
+        }
+        self.falling = 0;
+
+        let held_off = self
+            .since_impact
+            .is_some_and(|since| since <= self.holdoff_samples);
+        if g > self.config.impact_g && !held_off {
+            self.since_impact = Some(0);
+            return Some(MotionEvent::Impact(g));
+        }
+        None
+    }
+
+    /// Feed one streamed frame, ignoring frames without accelerometer data
+    pub fn push(&mut self, frame: &SensorFrame) -> Option<MotionEvent> {
+        frame.accel_g().and_then(|g| self.push_g(g))
+    }
+}
+
+/// Sensors to stream for orientation monitoring
+pub const ORIENTATION_SENSORS: [Sensor; 2] = [Sensor::ImuPitch, Sensor::ImuRoll];
+
+/// IMU pitch and roll, in degrees
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Attitude {
+    /// Pitch, -179..180 degrees
+    pub pitch: i16,
+    /// Roll, -179..180 degrees
+    pub roll: i16,
+}
+
+impl Attitude {
+    /// Attitude from a streamed frame, `None` unless it has both angles
+    pub fn from_frame(frame: &SensorFrame) -> Option<Self> {
+        Some(Self {
+            pitch: frame.get(Sensor::ImuPitch)?,
+            roll: frame.get(Sensor::ImuRoll)?,
+        })
+    }
+}
+
+/// Sphero Orientation Monitor Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientationConfig {
+    /// Largest pitch either way still counted as level, in degrees
+    pub max_pitch: u16,
+    /// Largest roll either way still counted as level, in degrees
+    pub max_roll: u16,
+    /// How long the attitude must stay out of (or back in) the envelope
+    pub debounce: Duration,
+}
+
+impl Default for OrientationConfig {
+    fn default() -> Self {
+        Self {
+            max_pitch: 30,
+            max_roll: 30,
+            debounce: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Sphero Orientation Monitor
+///
+/// Reports `MotionEvent::Tilted` once the ball has stayed outside the
+/// pitch/roll envelope for the debounce time, e.g. climbing a wall or sitting
+/// in its cradle, and `MotionEvent::Level` once it has stayed back inside as
+/// long. Like `PickupDetector`, the debounce follows the stream's rate.
+///
+/// ```
+/// use sphero_rs::sensor::{Attitude, MotionEvent, OrientationConfig, OrientationMonitor};
+/// use std::time::Duration;
+///
+/// // 100 ms debounce at 40 Hz is 4 samples
+/// let config = OrientationConfig { debounce: Duration::from_millis(100), ..Default::default() };
+/// let mut monitor = OrientationMonitor::new(config, 40.0).unwrap();
+/// let pitched = |pitch| Attitude { pitch, roll: 0 };
+///
+/// // Three samples out is just short of the debounce
+/// let events: Vec<_> = [40, 45, 50, 10, 0]
+///     .into_iter()
+///     .filter_map(|pitch| monitor.push_attitude(pitched(pitch)))
+///     .collect();
+/// assert_eq!(events, vec![]);
+///
+/// // The fourth in a row raises the alert, once
+/// let events: Vec<_> = [40, -45, 50, 31, 35, 60]
+///     .into_iter()
+///     .filter_map(|pitch| monitor.push_attitude(pitched(pitch)))
+///     .collect();
+/// assert_eq!(events, vec![MotionEvent::Tilted(pitched(31))]);
+/// assert!(monitor.is_tilted());
+/// assert_eq!(monitor.attitude(), Some(pitched(60)));
+///
+/// // Rolled over but level in pitch is still tilted; settling back clears it
+/// let rolled = Attitude { pitch: 0, roll: -90 };
+/// assert_eq!(monitor.push_attitude(rolled), None);
+/// let events: Vec<_> = [5, 30, -30, 0]
+///     .into_iter()
+///     .filter_map(|pitch| monitor.push_attitude(pitched(pitch)))
+///     .collect();
+/// assert_eq!(events, vec![MotionEvent::Level(pitched(0))]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrientationMonitor {
+    config: OrientationConfig,
+    debounce_samples: u32,
+    attitude: Option<Attitude>,
+    tilted: bool,
+    /// Consecutive samples disagreeing with `tilted`
+    changing: u32,
+}
+
+impl OrientationMonitor {
+    /// Monitor with `config` on a stream sampled at `rate_hz`, starting level
+    pub fn new(config: OrientationConfig, rate_hz: f32) -> Result<Self, Error> {
+        let mut monitor = Self {
+            config,
+            debounce_samples: 1,
+            attitude: None,
+            tilted: false,
+            changing: 0,
+        };
+        monitor.set_rate_hz(rate_hz)?;
+        Ok(monitor)
+    }
+
+    /// Settings in use
+    pub fn config(&self) -> OrientationConfig {
+        self.config
+    }
+
+    /// Follow a change of the stream's sample rate
+    pub fn set_rate_hz(&mut self, rate_hz: f32) -> Result<(), Error> {
+        if !(rate_hz > 0.0 && rate_hz.is_finite()) {
+            return Err(Error::BadParameterValue);
+        }
+        self.debounce_samples =
+            ((self.config.debounce.as_secs_f32() * rate_hz).ceil() as u32).max(1);
+        Ok(())
+    }
+
+    /// Latest attitude
+    pub fn attitude(&self) -> Option<Attitude> {
+        self.attitude
+    }
+
+    /// Whether the last event was `Tilted`
+    pub fn is_tilted(&self) -> bool {
+        self.tilted
+    }
+
+    /// Whether `attitude` is outside the envelope
+    pub fn is_outside(&self, attitude: Attitude) -> bool {
+        attitude.pitch.unsigned_abs() > self.config.max_pitch
+            || attitude.roll.unsigned_abs() > self.config.max_roll
+    }
+
+    /// Feed one attitude sample
+    pub fn push_attitude(&mut self, attitude: Attitude) -> Option<MotionEvent> {
+        self.attitude = Some(attitude);
+        if self.is_outside(attitude) == self.tilted {
+            self.changing = 0;
+            return None;
+        }
+        self.changing += 1;
+        if self.changing < self.debounce_samples {
+            return None;
+        }
+        self.changing = 0;
+        self.tilted = !self.tilted;
+        Some(if self.tilted {
+            MotionEvent::Tilted(attitude)
+        } else {
+            MotionEvent::Level(attitude)
+        })
+    }
+
+    /// Feed one streamed frame, ignoring frames without IMU angles
+    pub fn push(&mut self, frame: &SensorFrame) -> Option<MotionEvent> {
+        Attitude::from_frame(frame).and_then(|attitude| self.push_attitude(attitude))
+    }
+}
+
+/// Sphero Stream Watchdog Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogConfig {
+    /// Packet intervals without a packet before the stream counts as stalled
+    pub stall_after: f32,
+    /// Re-send the streaming command on a stall
+    pub resend: bool,
+    /// Stalls in a row that may re-send; later ones are only reported
+    pub max_recoveries: u8,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_after: 3.0,
+            resend: true,
+            max_recoveries: 3,
+        }
+    }
+}
+
+/// A stalled sensor stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamStall {
+    /// Stalls in a row, counting this one
+    pub attempt: u8,
+    /// Whether the streaming command is re-sent
+    pub resend: bool,
+}
+
+/// Sphero Stream Watchdog
+///
+/// Expects a packet every interval and reports a stall once none has come
+/// for `stall_after` intervals, then again after every further window of
+/// silence. A packet resets the count. Time is passed in, so any clock will do.
+///
+/// ```
+/// use sphero_rs::sensor::{StreamStall, StreamWatchdog, StreamingConfig, WatchdogConfig};
+/// use std::time::{Duration, Instant};
+///
+/// // 10 Hz, two frames per packet: a packet every 200 ms, stalled after 600 ms
+/// let config = StreamingConfig::new().sample_rate_hz(10.0)?.frames_per_packet(2)?;
+/// assert_eq!(config.packet_interval(), Duration::from_millis(200));
+/// let watchdog = WatchdogConfig { max_recoveries: 2, ..WatchdogConfig::default() };
+///
+/// let t0 = Instant::now();
+/// let ms = |ms| t0 + Duration::from_millis(ms);
+/// let mut dog = StreamWatchdog::new(watchdog, config.packet_interval(), t0);
+/// dog.packet(ms(200));
+/// assert_eq!(dog.deadline(), ms(800));
+/// assert_eq!(dog.check(ms(799)), None);
+/// assert_eq!(dog.check(ms(800)), Some(StreamStall { attempt: 1, resend: true }));
+///
+/// // Each further 600 ms of silence is another stall, resending up to the cap
+/// assert_eq!(dog.check(ms(1000)), None);
+/// assert_eq!(dog.check(ms(1400)), Some(StreamStall { attempt: 2, resend: true }));
+/// assert_eq!(dog.check(ms(2000)), Some(StreamStall { attempt: 3, resend: false }));
+///
+/// // Packets again: recovered
+/// dog.packet(ms(2100));
+/// assert_eq!(dog.check(ms(2699)), None);
+/// assert_eq!(dog.check(ms(2700)), Some(StreamStall { attempt: 1, resend: true }));
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamWatchdog {
+    config: WatchdogConfig,
+    window: Duration,
+    /// Last packet, or last stall report
+    last: Instant,
+    stalls: u8,
+}
+
+impl StreamWatchdog {
+    /// Watch a stream sending a packet every `interval`, starting at `now`
+    pub fn new(config: WatchdogConfig, interval: Duration, now: Instant) -> Self {
+        Self {
+            config,
+            window: interval.mul_f64(config.stall_after.max(1.0) as f64),
+            last: now,
+            stalls: 0,
+        }
+    }
+
+    /// A packet arrived at `now`
+    pub fn packet(&mut self, now: Instant) {
+        self.last = now;
+        self.stalls = 0;
+    }
+
+    /// When the stream will count as stalled, unless a packet comes first
+    pub fn deadline(&self) -> Instant {
+        self.last + self.window
+    }
+
+    /// The stall due by `now`, if any
+    pub fn check(&mut self, now: Instant) -> Option<StreamStall> {
+        if now < self.deadline() {
+            return None;
+        }
+        self.last = now;
+        self.stalls = self.stalls.saturating_add(1);
+        Some(StreamStall {
+            attempt: self.stalls,
+            resend: self.config.resend && self.stalls <= self.config.max_recoveries,
+        })
+    }
+}
@@ -0,0 +1,150 @@
+/*!
+ * Sphero Sequence Numbers
+ *
+ * The robot echoes the SEQ byte of a command in its response, which is the
+ * only way to tell responses apart. A sequence number must therefore not be
+ * reused while a command carrying it is still waiting for an answer.
+ */
+use crate::error::Error;
+
+/// Sequence number reserved for commands sent without requesting an answer
+pub const NO_ANSWER_SEQ: u8 = 0;
+
+/// Sphero Sequence Number Allocator
+/// Hands out 1..=255 in order, wrapping around and skipping numbers still in flight.
+#[derive(Debug, Clone)]
+pub struct SeqAllocator {
+    last: u8,
+    outstanding: [bool; 256],
+}
+
+impl Default for SeqAllocator {
+    fn default() -> Self {
+        Self {
+            last: NO_ANSWER_SEQ,
+            outstanding: [false; 256],
+        }
+    }
+}
+
+impl SeqAllocator {
+    /// Create an allocator with nothing in flight
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocate the next free sequence number
+    /// Returns `Error::Busy` if all 255 are outstanding.
+    pub fn allocate(&mut self) -> Result<u8, Error> {
+        let mut seq = self.last;
+        for _ in 0..u8::MAX {
+            seq = if seq == u8::MAX { 1 } else { seq + 1 };
+            if !self.outstanding[seq as usize] {
+                self.outstanding[seq as usize] = true;
+                self.last = seq;
+                return Ok(seq);
+            }
+        }
+        Err(Error::Busy)
+    }
+
+    /// Allocate a specific sequence number
+    /// Returns `Error::Busy` if it is in flight and `Error::BadParameterValue`
+    /// for `NO_ANSWER_SEQ`.
+    pub fn reserve(&mut self, seq: u8) -> Result<(), Error> {
+        if seq == NO_ANSWER_SEQ {
+            return Err(Error::BadParameterValue);
+        }
+        if self.outstanding[seq as usize] {
+            return Err(Error::Busy);
+        }
+        self.outstanding[seq as usize] = true;
+        Ok(())
+    }
+
+    /// Return a sequence number once its command has been answered or abandoned
+    pub fn release(&mut self, seq: u8) {
+        self.outstanding[seq as usize] = false;
+    }
+
+    /// Whether `seq` is currently in flight
+    pub fn is_outstanding(&self, seq: u8) -> bool {
+        self.outstanding[seq as usize]
+    }
+
+    /// Number of sequence numbers currently in flight
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.iter().filter(|&&o| o).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_allocations_wrap_past_255_to_1() {
+        let mut seqs = SeqAllocator::new();
+        let allocated: Vec<u8> = (0..300)
+            .map(|_| {
+                let seq = seqs.allocate().unwrap();
+                seqs.release(seq);
+                seq
+            })
+            .collect();
+        let expected: Vec<u8> = (1..=255).chain(1..=45).collect();
+        assert_eq!(allocated, expected);
+        assert!(!allocated.contains(&NO_ANSWER_SEQ));
+        assert_eq!(seqs.outstanding(), 0);
+    }
+
+    #[test]
+    fn in_flight_numbers_are_skipped() {
+        let mut seqs = SeqAllocator::new();
+        let first = seqs.allocate().unwrap();
+        for _ in 1..u8::MAX {
+            let seq = seqs.allocate().unwrap();
+            seqs.release(seq);
+        }
+        // Wrapped round to the start, where `first` is still waiting
+        assert!(seqs.is_outstanding(first));
+        assert_eq!(seqs.allocate().unwrap(), first + 1);
+    }
+
+    #[test]
+    fn fails_with_busy_once_all_255_are_outstanding() {
+        let mut seqs = SeqAllocator::new();
+        for _ in 0..255 {
+            let _ = seqs.allocate().unwrap();
+        }
+        assert_eq!(seqs.outstanding(), 255);
+        assert!(matches!(seqs.allocate(), Err(Error::Busy)));
+
+        // Releasing any one makes it available again, and only it
+        seqs.release(42);
+        assert_eq!(seqs.allocate().unwrap(), 42);
+        assert!(matches!(seqs.allocate(), Err(Error::Busy)));
+    }
+
+    #[test]
+    fn reserved_numbers_are_skipped_until_released() {
+        let mut seqs = SeqAllocator::new();
+        seqs.reserve(1).unwrap();
+
+        assert!(matches!(seqs.reserve(1), Err(Error::Busy)));
+        assert_eq!(seqs.allocate().unwrap(), 2);
+
+        seqs.release(1);
+        assert!(seqs.reserve(1).is_ok());
+    }
+
+    #[test]
+    fn no_answer_seq_cannot_be_reserved() {
+        let mut seqs = SeqAllocator::new();
+        assert!(matches!(
+            seqs.reserve(NO_ANSWER_SEQ),
+            Err(Error::BadParameterValue)
+        ));
+        assert_eq!(seqs.outstanding(), 0);
+    }
+}
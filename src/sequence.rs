@@ -0,0 +1,161 @@
+/*!
+ * Sphero Movement Sequences
+ *
+ * A path declared as data: a list of steps run one after another by
+ * `MovementSequence::execute`, each holding for its duration before the next
+ * starts. Without the `async` feature there is no timer and the steps go out
+ * back to back.
+ */
+use crate::client::SpheroClient;
+use crate::color::RgbColor;
+use crate::command::{Roll, SetRGBLEDOutput};
+use crate::error::Error;
+use crate::heading::Heading;
+use crate::speed::Speed;
+use crate::transport::Transport;
+use std::time::Duration;
+
+/// One step of a `MovementSequence`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementStep {
+    /// Roll at `speed` towards `heading` for `duration`
+    Roll {
+        /// Speed
+        speed: Speed,
+        /// Heading
+        heading: Heading,
+        /// How long to roll for
+        duration: Duration,
+    },
+    /// Stop, keeping the last heading, and wait
+    Stop(Duration),
+    /// Set the LED color, without persisting it; takes no time
+    SetLed(RgbColor),
+    /// Turn on the spot, see `SpheroClient::spin`
+    Spin {
+        /// Full turns, clockwise
+        rotations: f32,
+        /// How long the turns take
+        duration: Duration,
+    },
+}
+
+impl MovementStep {
+    /// Time the step holds for
+    pub fn duration(&self) -> Duration {
+        match self {
+            MovementStep::Roll { duration, .. } | MovementStep::Spin { duration, .. } => *duration,
+            MovementStep::Stop(duration) => *duration,
+            MovementStep::SetLed(_) => Duration::ZERO,
+        }
+    }
+}
+
+/// Sphero Movement Sequence
+///
+/// ```
+/// use futures::executor::block_on;
+/// use sphero_rs::client::SpheroClient;
+/// use sphero_rs::color::RgbColor;
+/// use sphero_rs::heading::Heading;
+/// use sphero_rs::packet::SpheroCommandID;
+/// use sphero_rs::sequence::{MovementSequence, MovementStep};
+/// use sphero_rs::speed::Speed;
+/// use sphero_rs::transport::mock::MockTransport;
+/// use std::time::Duration;
+///
+/// let ms = Duration::from_millis;
+/// let mut lap = MovementSequence::new();
+/// lap.push(MovementStep::SetLed(RgbColor::GREEN));
+/// lap.push(MovementStep::Roll { speed: Speed::new(80), heading: Heading::new(90)?, duration: ms(20) });
+/// lap.push(MovementStep::Stop(ms(10)));
+/// let twice = lap.repeat(2);
+/// assert_eq!(twice.len(), 6);
+/// assert_eq!(twice.duration(), ms(60));
+///
+/// let mock = MockTransport::acknowledging();
+/// let mut client = SpheroClient::new(mock.clone());
+/// block_on(twice.execute(&mut client))?;
+/// let sent: Vec<_> = mock.written_packets().iter().map(|packet| packet.cid()).collect();
+/// let (led, roll) = (SpheroCommandID::SetRGBLEDOutput as u8, SpheroCommandID::Roll as u8);
+/// assert_eq!(sent, vec![led, roll, roll, led, roll, roll]);
+/// // The stop keeps the heading rolled towards
+/// assert_eq!(mock.written_packets()[2].data(), [0, 0, 90, 0]);
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MovementSequence(pub Vec<MovementStep>);
+
+impl MovementSequence {
+    /// Empty sequence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `step` at the end
+    pub fn push(&mut self, step: MovementStep) {
+        self.0.push(step);
+    }
+
+    /// This sequence run `n` times over
+    pub fn repeat(&self, n: usize) -> Self {
+        Self(self.0.repeat(n))
+    }
+
+    /// Number of steps
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no steps
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Time the whole sequence takes, not counting sending
+    pub fn duration(&self) -> Duration {
+        self.0.iter().map(MovementStep::duration).sum()
+    }
+
+    /// Run the steps in order on `client`
+    ///
+    /// Stops at the first command that fails; a Spin with no rotations or no
+    /// duration fails with `Error::BadParameterValue` when it is reached.
+    pub async fn execute<T: Transport>(&self, client: &mut SpheroClient<T>) -> Result<(), Error> {
+        let mut heading = Heading::ZERO;
+        for step in &self.0 {
+            match *step {
+                MovementStep::Roll {
+                    speed, heading: to, ..
+                } => {
+                    heading = to;
+                    let roll = Roll {
+                        speed,
+                        heading,
+                        state: true,
+                    };
+                    drop(client.send(&roll).await?);
+                }
+                MovementStep::Stop(_) => {
+                    drop(client.send(&Roll::stop(heading.degrees())?).await?);
+                }
+                MovementStep::SetLed(color) => {
+                    let led = SetRGBLEDOutput::from_color(color, false);
+                    drop(client.send(&led).await?);
+                }
+                MovementStep::Spin {
+                    rotations,
+                    duration,
+                } => {
+                    // Spins start from heading 0 and pace themselves
+                    client.spin(rotations, duration).await?;
+                    heading = Heading::from_degrees_clamped(rotations * 360.0);
+                    continue;
+                }
+            }
+            #[cfg(feature = "async")]
+            crate::runtime::sleep(step.duration()).await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+/*!
+ * Sphero Speed
+ *
+ * Roll speed, from 0 (stopped) to 255 (full speed). Every byte is a valid
+ * speed, so a `Speed` converts from and to `u8` freely.
+ */
+use crate::error::Error;
+
+/// Speed, 0..255
+///
+/// ```
+/// use sphero_rs::speed::Speed;
+///
+/// let half = Speed::from_percent(0.5)?;
+/// assert_eq!(half.value(), 128);
+/// assert!((half.as_percent() - 0.5).abs() < 0.01);
+/// assert_eq!(Speed::from(255), Speed::max());
+/// assert_eq!(u8::from(Speed::zero()), 0);
+/// assert!(Speed::from_percent(1.5).is_err());
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Speed(u8);
+
+impl Speed {
+    /// Speed `v`
+    pub const fn new(v: u8) -> Self {
+        Self(v)
+    }
+
+    /// Full speed
+    pub const fn max() -> Self {
+        Self(255)
+    }
+
+    /// Stopped
+    pub const fn zero() -> Self {
+        Self(0)
+    }
+
+    /// Speed as a fraction from 0.0 (stopped) to 1.0 (full speed)
+    /// Fails with `Error::BadParameterValue` outside that range.
+    pub fn from_percent(pct: f32) -> Result<Self, Error> {
+        if !(0.0..=1.0).contains(&pct) {
+            return Err(Error::BadParameterValue);
+        }
+        Ok(Self((pct * 255.0).round() as u8))
+    }
+
+    /// Fraction of full speed, 0.0..1.0
+    pub fn as_percent(&self) -> f32 {
+        self.0 as f32 / 255.0
+    }
+
+    /// Raw speed byte
+    pub const fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// Whether this is zero
+    pub const fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<u8> for Speed {
+    fn from(v: u8) -> Self {
+        Self(v)
+    }
+}
+
+impl From<Speed> for u8 {
+    fn from(speed: Speed) -> u8 {
+        speed.0
+    }
+}
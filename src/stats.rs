@@ -0,0 +1,269 @@
+/*!
+ * Sphero Link Statistics
+ *
+ * Counters describing the health of the link to a robot, updated by the
+ * device client as packets go out and come in.
+ */
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Weight of a new sample in the round-trip time estimate, as a right shift (1/8)
+const RTT_GAIN_SHIFT: u32 = 3;
+
+/// Sphero Link Statistics
+#[derive(Debug)]
+pub struct Stats {
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    checksum_failures: AtomicU64,
+    malformed_packets: AtomicU64,
+    timeouts: AtomicU64,
+    retries: AtomicU64,
+    unknown_seq: AtomicU64,
+    async_messages: [AtomicU64; 256],
+    /// Smoothed round-trip time in microseconds, 0 before the first sample
+    rtt_micros: AtomicU64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            packets_sent: AtomicU64::new(0),
+            packets_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            checksum_failures: AtomicU64::new(0),
+            malformed_packets: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            unknown_seq: AtomicU64::new(0),
+            async_messages: [const { AtomicU64::new(0) }; 256],
+            rtt_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Stats {
+    /// Create zeroed statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packets written to the transport
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent.load(Ordering::Relaxed)
+    }
+
+    /// Well-formed packets received, responses and asynchronous messages alike
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to the transport
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Bytes received from the transport, including any that were discarded
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Framed packets dropped for a bad checksum
+    pub fn checksum_failures(&self) -> u64 {
+        self.checksum_failures.load(Ordering::Relaxed)
+    }
+
+    /// Packets dropped with a good checksum that still failed to parse,
+    /// e.g. for an unknown response code
+    pub fn malformed_packets(&self) -> u64 {
+        self.malformed_packets.load(Ordering::Relaxed)
+    }
+
+    /// Commands that got no response in time, counting each attempt
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Commands resent after a timeout
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Responses whose sequence number nobody was waiting on
+    pub fn unknown_seq_responses(&self) -> u64 {
+        self.unknown_seq.load(Ordering::Relaxed)
+    }
+
+    /// Asynchronous messages received with ID code `idcode`
+    pub fn async_messages(&self, idcode: u8) -> u64 {
+        self.async_messages[idcode as usize].load(Ordering::Relaxed)
+    }
+
+    /// Smoothed time from writing a command to receiving its response
+    /// `None` until a command has been answered.
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        match self.rtt_micros.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    /// Zero every counter and forget the round-trip time
+    pub fn reset(&self) {
+        let counters = [
+            &self.packets_sent,
+            &self.packets_received,
+            &self.bytes_sent,
+            &self.bytes_received,
+            &self.checksum_failures,
+            &self.malformed_packets,
+            &self.timeouts,
+            &self.retries,
+            &self.unknown_seq,
+            &self.rtt_micros,
+        ];
+        for counter in counters.into_iter().chain(&self.async_messages) {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        let _ = self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        let _ = self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_bytes_received(&self, bytes: usize) {
+        let _ = self
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_response(&self) {
+        let _ = self.packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_async(&self, idcode: u8) {
+        let _ = self.packets_received.fetch_add(1, Ordering::Relaxed);
+        let _ = self.async_messages[idcode as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_checksum_failure(&self) {
+        let _ = self.checksum_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_malformed(&self) {
+        let _ = self.malformed_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        let _ = self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        let _ = self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_unknown_seq(&self) {
+        let _ = self.unknown_seq.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fold a round-trip sample into the estimate (exponentially weighted, like TCP's SRTT)
+    pub(crate) fn record_round_trip(&self, rtt: Duration) {
+        // Never store 0, it means "no sample"
+        let sample = (rtt.as_micros() as u64).max(1);
+        let _ = self
+            .rtt_micros
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |srtt| {
+                Some(match srtt {
+                    0 => sample,
+                    srtt => (srtt - (srtt >> RTT_GAIN_SHIFT) + (sample >> RTT_GAIN_SHIFT)).max(1),
+                })
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_start_at_zero() {
+        let stats = Stats::new();
+        assert_eq!(stats.packets_sent(), 0);
+        assert_eq!(stats.packets_received(), 0);
+        assert_eq!(stats.checksum_failures(), 0);
+        assert_eq!(stats.malformed_packets(), 0);
+        assert_eq!(stats.round_trip_time(), None);
+    }
+
+    #[test]
+    fn responses_and_async_messages_are_both_received_packets() {
+        let stats = Stats::new();
+        stats.record_response();
+        stats.record_async(0x03);
+        stats.record_async(0x03);
+        stats.record_async(0x07);
+        assert_eq!(stats.packets_received(), 4);
+        assert_eq!(stats.async_messages(0x03), 2);
+        assert_eq!(stats.async_messages(0x07), 1);
+        assert_eq!(stats.async_messages(0x01), 0);
+    }
+
+    #[test]
+    fn sent_counts_packets_and_bytes() {
+        let stats = Stats::new();
+        stats.record_sent(7);
+        stats.record_sent(9);
+        stats.record_bytes_received(5);
+        assert_eq!((stats.packets_sent(), stats.bytes_sent()), (2, 16));
+        assert_eq!(stats.bytes_received(), 5);
+    }
+
+    #[test]
+    fn round_trip_time_is_smoothed() {
+        let stats = Stats::new();
+        stats.record_round_trip(Duration::from_millis(80));
+        assert_eq!(stats.round_trip_time(), Some(Duration::from_millis(80)));
+        // One eighth of the way towards the new sample
+        stats.record_round_trip(Duration::from_millis(160));
+        assert_eq!(stats.round_trip_time(), Some(Duration::from_millis(90)));
+    }
+
+    #[test]
+    fn instant_round_trip_still_counts_as_a_sample() {
+        let stats = Stats::new();
+        stats.record_round_trip(Duration::ZERO);
+        assert_eq!(stats.round_trip_time(), Some(Duration::from_micros(1)));
+    }
+
+    #[test]
+    fn reset_zeroes_everything() {
+        let stats = Stats::new();
+        stats.record_sent(7);
+        stats.record_bytes_received(7);
+        stats.record_response();
+        stats.record_async(0xff);
+        stats.record_checksum_failure();
+        stats.record_malformed();
+        stats.record_timeout();
+        stats.record_retry();
+        stats.record_unknown_seq();
+        stats.record_round_trip(Duration::from_millis(10));
+
+        stats.reset();
+        assert_eq!(stats.packets_sent(), 0);
+        assert_eq!(stats.packets_received(), 0);
+        assert_eq!(stats.bytes_sent(), 0);
+        assert_eq!(stats.bytes_received(), 0);
+        assert_eq!(stats.checksum_failures(), 0);
+        assert_eq!(stats.malformed_packets(), 0);
+        assert_eq!(stats.timeouts(), 0);
+        assert_eq!(stats.retries(), 0);
+        assert_eq!(stats.unknown_seq_responses(), 0);
+        assert_eq!(stats.async_messages(0xff), 0);
+        assert_eq!(stats.round_trip_time(), None);
+    }
+}
@@ -0,0 +1,210 @@
+/*!
+ * Sphero Swarm
+ *
+ * Drives several robots at once, e.g. for a synchronized light show. Each
+ * device keeps its own connection; a failure on one never stops the others.
+ */
+use crate::command::ToCommandPacket;
+use crate::device::{ShutdownOptions, SpheroDevice};
+use crate::error::Error;
+use crate::event::AsyncMessage;
+use crate::packet::SpheroResponsePacketV1;
+use crate::transport::Transport;
+use futures::future::join_all;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::BTreeMap;
+
+/// Sphero Swarm
+/// Devices keyed by a name of the caller's choosing, e.g. the robot's name or address.
+pub struct SpheroSwarm<T: Transport + 'static> {
+    devices: BTreeMap<String, SpheroDevice<T>>,
+}
+
+impl<T: Transport + 'static> Default for SpheroSwarm<T> {
+    fn default() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Transport + 'static> SpheroSwarm<T> {
+    /// Create an empty swarm
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a device, returning the one previously under `name`
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        device: SpheroDevice<T>,
+    ) -> Option<SpheroDevice<T>> {
+        self.devices.insert(name.into(), device)
+    }
+
+    /// Remove a device
+    pub fn remove(&mut self, name: &str) -> Option<SpheroDevice<T>> {
+        self.devices.remove(name)
+    }
+
+    /// Device under `name`
+    pub fn get(&self, name: &str) -> Option<&SpheroDevice<T>> {
+        self.devices.get(name)
+    }
+
+    /// Names of every device, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.devices.keys().map(String::as_str)
+    }
+
+    /// Number of devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Whether the swarm has no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Send `cmd` to every device concurrently
+    /// Waits for every device to answer or fail, and returns each result by name.
+    pub async fn broadcast(
+        &self,
+        cmd: &(impl ToCommandPacket + Sync),
+    ) -> BTreeMap<String, Result<SpheroResponsePacketV1, Error>> {
+        let sends = self
+            .devices
+            .iter()
+            .map(|(name, device)| async move { (name.clone(), device.send(cmd).await) });
+        join_all(sends).await.into_iter().collect()
+    }
+
+    /// Asynchronous messages from every device, tagged with the device name
+    ///
+    /// Covers the devices in the swarm when called. Ends once every one of
+    /// their links is gone.
+    pub fn events(&self) -> impl Stream<Item = (String, AsyncMessage)> {
+        stream::select_all(self.devices.iter().map(|(name, device)| {
+            let name = name.clone();
+            device
+                .events()
+                .map(move |message| (name.clone(), message))
+                .boxed()
+        }))
+    }
+
+    /// Shut every device down concurrently, see `SpheroDevice::shutdown`
+    pub async fn shutdown(self, options: ShutdownOptions) -> BTreeMap<String, Result<(), Error>> {
+        let shutdowns = self
+            .devices
+            .into_iter()
+            .map(|(name, device)| async move { (name, device.shutdown(options).await) });
+        join_all(shutdowns).await.into_iter().collect()
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::command::Ping;
+    use crate::packet::{
+        CoreCommandID, DeviceID, MRSPField, SpheroAsynchronousPacketV1, SpheroCommandPacketV1,
+    };
+    use crate::power::PowerState;
+    use crate::transport::mock::{respond, MockTransport};
+    use deku::DekuContainerWrite;
+
+    /// Get Versioning answer for main application firmware 3.59
+    const FIRMWARE: [u8; 10] = [0x02, 0x03, 0x01, 0x03, 0x3b, 0x41, 0x21, 0x04, 0x01, 0x14];
+
+    fn is_probe(packet: &SpheroCommandPacketV1) -> bool {
+        packet.did() == DeviceID::Core
+            && packet.cid() == CoreCommandID::GetVersioningInformation as u8
+    }
+
+    /// Mock robot answering the firmware probe, and every other command with `mrsp`
+    fn robot(mrsp: MRSPField) -> MockTransport {
+        MockTransport::with_responder(move |bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            match is_probe(&packet) {
+                true => vec![respond(&packet, MRSPField::Ok, FIRMWARE.to_vec())],
+                false => vec![respond(&packet, mrsp, vec![])],
+            }
+        })
+    }
+
+    /// Swarm of one device per mock, named after its position in the alphabet
+    async fn swarm(mocks: &[MockTransport]) -> SpheroSwarm<MockTransport> {
+        let mut swarm = SpheroSwarm::new();
+        for (name, mock) in ["alpha", "bravo", "charlie"].iter().zip(mocks) {
+            let device = SpheroDevice::new(mock.clone()).await.unwrap();
+            assert!(swarm.insert(*name, device).is_none());
+        }
+        swarm
+    }
+
+    fn pings(mock: &MockTransport) -> usize {
+        mock.written_packets()
+            .iter()
+            .filter(|packet| packet.cid() == CoreCommandID::Ping as u8 && !is_probe(packet))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_every_device_despite_a_failure() {
+        let mocks = [
+            robot(MRSPField::Ok),
+            robot(MRSPField::GeneralError),
+            robot(MRSPField::Ok),
+        ];
+        let swarm = swarm(&mocks).await;
+
+        let results = swarm.broadcast(&Ping {}).await;
+
+        assert_eq!(
+            results.keys().collect::<Vec<_>>(),
+            ["alpha", "bravo", "charlie"]
+        );
+        assert!(results["alpha"].is_ok());
+        assert!(matches!(
+            results["bravo"],
+            Err(Error::ResponseCode(MRSPField::GeneralError))
+        ));
+        assert!(results["charlie"].is_ok());
+        assert!(mocks.iter().all(|mock| pings(mock) == 1));
+    }
+
+    #[tokio::test]
+    async fn events_are_tagged_with_the_device_name() {
+        let mocks = [robot(MRSPField::Ok), robot(MRSPField::Ok)];
+        let swarm = swarm(&mocks).await;
+        let events = swarm.events();
+
+        for (mock, state) in mocks.iter().zip([PowerState::Low, PowerState::Charging]) {
+            let notification = SpheroAsynchronousPacketV1::new(0x01, vec![state.into()]);
+            mock.inject(notification.to_bytes().unwrap());
+        }
+        let shutdown = swarm.shutdown(ShutdownOptions::default()).await;
+
+        // Every link is gone, so the merged stream ends
+        let mut events: Vec<_> = events.collect().await;
+        events.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            events,
+            [
+                (
+                    "alpha".to_string(),
+                    AsyncMessage::PowerNotification(PowerState::Low)
+                ),
+                (
+                    "bravo".to_string(),
+                    AsyncMessage::PowerNotification(PowerState::Charging)
+                ),
+            ]
+        );
+        assert!(shutdown.values().all(Result::is_ok));
+        assert!(mocks.iter().all(MockTransport::is_closed));
+    }
+}
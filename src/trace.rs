@@ -0,0 +1,122 @@
+/*!
+ * Sphero Tracing
+ *
+ * Wrappers over `tracing` that compile to nothing without the `tracing` feature.
+ */
+
+/// Emit a debug event
+macro_rules! debug_event {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    }};
+}
+
+/// Emit a warn event
+#[cfg_attr(not(feature = "async"), allow(unused_macros))]
+macro_rules! warn_event {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    }};
+}
+
+pub(crate) use debug_event;
+#[cfg_attr(not(feature = "async"), allow(unused_imports))]
+pub(crate) use warn_event;
+
+/// Hex dump of raw bytes, e.g. `ff ff 00 01 01 01 fc`
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_separates_bytes_with_spaces() {
+        assert_eq!(hex(&[0xff, 0xff, 0x00, 0x01, 0x0a]), "ff ff 00 01 0a");
+        assert_eq!(hex(&[]), "");
+    }
+
+    #[cfg(all(feature = "tracing", feature = "tokio"))]
+    #[tokio::test]
+    async fn round_trip_is_traced() {
+        use crate::command::Ping;
+        use crate::device::SpheroDevice;
+        use crate::packet::SpheroCommandPacketV1;
+        use crate::transport::mock::{ack, MockTransport};
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Message and `bytes` field of every event
+        type Events = Arc<Mutex<Vec<(String, String)>>>;
+
+        struct Capture(Events);
+
+        #[derive(Default)]
+        struct Fields {
+            message: String,
+            bytes: String,
+        }
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                match field.name() {
+                    "message" => self.message = format!("{value:?}"),
+                    "bytes" => self.bytes = format!("{value:?}"),
+                    _ => (),
+                }
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0.lock().unwrap().push((fields.message, fields.bytes));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Events::default();
+        // The current thread runtime polls the reader task on this thread too
+        let _capturing = tracing::subscriber::set_default(Capture(events.clone()));
+        let mock = MockTransport::with_responder(|bytes| {
+            let packet = SpheroCommandPacketV1::parse(bytes).unwrap();
+            vec![ack(&packet)]
+        });
+        let device = SpheroDevice::new(mock.clone()).await.unwrap();
+
+        drop(device.send(&Ping {}).await.unwrap());
+
+        let sent = mock.written().pop().unwrap();
+        let received = ack(&SpheroCommandPacketV1::parse(&sent).unwrap());
+        let events = events.lock().unwrap();
+        assert!(events.contains(&("sent".to_string(), hex(&sent))));
+        assert!(events.contains(&("received".to_string(), hex(&received))));
+    }
+}
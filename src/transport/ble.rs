@@ -0,0 +1,278 @@
+/*!
+ * Sphero BLE Transport
+ *
+ * SPRK+, Ollie and BB-8 speak the v1.20 API over a BLE "robot control" service.
+ * Before the robot accepts any command packets it must be woken up by a short
+ * sequence of writes to vendor characteristics.
+ */
+use crate::error::Error;
+use crate::runtime::{self, sleep};
+use crate::transport::Transport;
+use btleplug::api::{Characteristic, Peripheral, WriteType};
+use futures::future::ready;
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Sphero BLE Characteristic UUIDs
+pub mod uuids {
+    use uuid::Uuid;
+
+    /// Anti-DoS characteristic, unlocked by writing `"011i3"`
+    pub const ANTI_DOS: Uuid = Uuid::from_u128(0x22bb746f_2bbd_7554_2d6f_726568705327);
+    /// TX power characteristic
+    pub const TX_POWER: Uuid = Uuid::from_u128(0x22bb746f_2bb2_7554_2d6f_726568705327);
+    /// Wake-up characteristic
+    pub const WAKEUP: Uuid = Uuid::from_u128(0x22bb746f_2bbf_7554_2d6f_726568705327);
+    /// Command characteristic (host to robot)
+    pub const COMMAND: Uuid = Uuid::from_u128(0x22bb746f_2ba1_7554_2d6f_726568705327);
+    /// Response characteristic (robot to host, notify)
+    pub const RESPONSE: Uuid = Uuid::from_u128(0x22bb746f_2ba6_7554_2d6f_726568705327);
+}
+
+/// Payload that unlocks the anti-DoS characteristic
+pub const ANTI_DOS_UNLOCK: &[u8] = b"011i3";
+/// TX power level written during wake-up
+pub const TX_POWER_LEVEL: u8 = 0x07;
+/// Delay between each write of the wake-up sequence
+pub const WAKE_WRITE_DELAY: Duration = Duration::from_millis(100);
+/// How long connecting may take unless overridden
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The part of a BLE peripheral the wake-up sequence relies on
+///
+/// Implemented for every btleplug [`Peripheral`].
+pub trait BlePeripheral {
+    /// Discovered characteristics
+    fn characteristics(&self) -> BTreeSet<Characteristic>;
+
+    /// Write to a characteristic without waiting for a response
+    fn write_without_response(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl<P: Peripheral> BlePeripheral for P {
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        Peripheral::characteristics(self)
+    }
+
+    async fn write_without_response(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.write(characteristic, data, WriteType::WithoutResponse)
+            .await
+            .map_err(Error::from)
+    }
+}
+
+impl From<btleplug::Error> for Error {
+    fn from(e: btleplug::Error) -> Self {
+        Error::Transport(e.to_string())
+    }
+}
+
+/// Find a discovered characteristic by UUID
+/// `name` is used to describe the characteristic if it is missing
+pub fn find_characteristic(
+    peripheral: &impl BlePeripheral,
+    uuid: Uuid,
+    name: &'static str,
+) -> Result<Characteristic, Error> {
+    peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == uuid)
+        .ok_or(Error::CharacteristicNotFound(name))
+}
+
+/// Wake up a SPRK+/Ollie/BB-8
+///
+/// Unlocks the anti-DoS characteristic, sets the TX power and writes the
+/// wake-up byte, pausing between each write so they are processed in order.
+/// Services must already be discovered on the peripheral.
+pub async fn wake(peripheral: &impl BlePeripheral) -> Result<(), Error> {
+    let anti_dos = find_characteristic(peripheral, uuids::ANTI_DOS, "Anti DOS")?;
+    let tx_power = find_characteristic(peripheral, uuids::TX_POWER, "TX power")?;
+    let wakeup = find_characteristic(peripheral, uuids::WAKEUP, "Wakeup")?;
+
+    peripheral
+        .write_without_response(&anti_dos, ANTI_DOS_UNLOCK)
+        .await?;
+    sleep(WAKE_WRITE_DELAY).await;
+
+    peripheral
+        .write_without_response(&tx_power, &[TX_POWER_LEVEL])
+        .await?;
+    sleep(WAKE_WRITE_DELAY).await;
+
+    peripheral.write_without_response(&wakeup, &[0x01]).await?;
+    sleep(WAKE_WRITE_DELAY).await;
+
+    Ok(())
+}
+
+/// Sphero BLE Transport
+/// Commands are written to the command characteristic; responses and async
+/// messages arrive as notifications on the response characteristic.
+pub struct BleTransport<P: Peripheral> {
+    peripheral: P,
+    command: Characteristic,
+    response: Characteristic,
+    connect_timeout: Duration,
+}
+
+impl<P: Peripheral> BleTransport<P> {
+    /// Connect to `peripheral`, wake it up and subscribe to its responses
+    /// Gives up with `Error::ConnectionTimeout` after `DEFAULT_CONNECT_TIMEOUT`.
+    pub async fn connect(peripheral: P) -> Result<Self, Error> {
+        Self::connect_with_timeout(peripheral, DEFAULT_CONNECT_TIMEOUT).await
+    }
+
+    /// Same as `connect`, giving up after `timeout`
+    /// Reconnecting later is bounded by the same timeout.
+    pub async fn connect_with_timeout(peripheral: P, timeout: Duration) -> Result<Self, Error> {
+        let connecting = async {
+            if !peripheral.is_connected().await? {
+                peripheral.connect().await?;
+            }
+            peripheral.discover_services().await?;
+            wake(&peripheral).await?;
+
+            let command = find_characteristic(&peripheral, uuids::COMMAND, "Command")?;
+            let response = find_characteristic(&peripheral, uuids::RESPONSE, "Response")?;
+            peripheral.subscribe(&response).await?;
+            Ok::<_, Error>((command, response))
+        };
+        let (command, response) = runtime::timeout(timeout, connecting)
+            .await
+            .map_err(|_| Error::ConnectionTimeout)??;
+        Ok(Self {
+            peripheral,
+            command,
+            response,
+            connect_timeout: timeout,
+        })
+    }
+
+    /// Underlying peripheral
+    pub fn peripheral(&self) -> &P {
+        &self.peripheral
+    }
+}
+
+impl<P: Peripheral> Transport for BleTransport<P> {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        self.peripheral
+            .write(&self.command, data, WriteType::WithoutResponse)
+            .await
+            .map_err(Error::from)
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let uuid = self.response.uuid;
+        let notifications = self.peripheral.notifications().await?;
+        Ok(notifications
+            .filter_map(move |n| ready((n.uuid == uuid).then_some(n.value)))
+            .boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.peripheral.disconnect().await.map_err(Error::from)
+    }
+
+    async fn reconnect(&self) -> Result<(), Error> {
+        let connecting = async {
+            if !self.peripheral.is_connected().await? {
+                self.peripheral.connect().await?;
+            }
+            self.peripheral.discover_services().await?;
+            wake(&self.peripheral).await?;
+            self.peripheral.subscribe(&self.response).await?;
+            Ok::<_, Error>(())
+        };
+        runtime::timeout(self.connect_timeout, connecting)
+            .await
+            .map_err(|_| Error::ConnectionTimeout)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use btleplug::api::CharPropFlags;
+    use futures::executor::block_on;
+    use std::sync::Mutex;
+
+    /// Peripheral exposing `uuids`, recording every write
+    struct FakePeripheral {
+        uuids: Vec<Uuid>,
+        writes: Mutex<Vec<(Uuid, Vec<u8>)>>,
+    }
+
+    impl FakePeripheral {
+        fn with(uuids: &[Uuid]) -> Self {
+            Self {
+                uuids: uuids.to_vec(),
+                writes: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl BlePeripheral for FakePeripheral {
+        fn characteristics(&self) -> BTreeSet<Characteristic> {
+            self.uuids
+                .iter()
+                .map(|&uuid| Characteristic {
+                    uuid,
+                    service_uuid: Uuid::nil(),
+                    properties: CharPropFlags::WRITE_WITHOUT_RESPONSE,
+                    descriptors: BTreeSet::new(),
+                })
+                .collect()
+        }
+
+        async fn write_without_response(
+            &self,
+            characteristic: &Characteristic,
+            data: &[u8],
+        ) -> Result<(), Error> {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((characteristic.uuid, data.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wake_without_wakeup_characteristic_names_it_and_writes_nothing() {
+        let peripheral = FakePeripheral::with(&[uuids::ANTI_DOS, uuids::TX_POWER]);
+        let woken = block_on(wake(&peripheral));
+        assert!(matches!(
+            woken,
+            Err(Error::CharacteristicNotFound("Wakeup"))
+        ));
+        assert!(peripheral.writes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wake_writes_the_sequence_in_order() {
+        let peripheral = FakePeripheral::with(&[uuids::ANTI_DOS, uuids::TX_POWER, uuids::WAKEUP]);
+        block_on(wake(&peripheral)).unwrap();
+        assert_eq!(
+            *peripheral.writes.lock().unwrap(),
+            vec![
+                (uuids::ANTI_DOS, ANTI_DOS_UNLOCK.to_vec()),
+                (uuids::TX_POWER, vec![TX_POWER_LEVEL]),
+                (uuids::WAKEUP, vec![0x01]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+/*!
+ * Sphero Mock Transport
+ *
+ * An in-memory transport that records every write and replies through a
+ * user supplied responder. Useful for exercising clients without hardware.
+ */
+use crate::error::Error;
+use crate::packet::{MRSPField, SOP2Field, SpheroCommandPacketV1, SpheroResponsePacketV1};
+use crate::transport::Transport;
+use deku::DekuContainerWrite;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::{Arc, Mutex};
+
+/// Produces the inbound chunks sent in reply to a write
+pub type Responder = Box<dyn FnMut(&[u8]) -> Vec<Vec<u8>> + Send>;
+
+/// Sphero Mock Transport
+/// Clones share the same state, so a test can keep a handle while a client owns the other.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    written: Vec<Vec<u8>>,
+    subscribers: Vec<UnboundedSender<Vec<u8>>>,
+    responder: Option<Responder>,
+    closed: bool,
+    disconnected: bool,
+}
+
+impl MockTransport {
+    /// Create a mock that never replies
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a mock that acknowledges every command requesting a response
+    pub fn acknowledging() -> Self {
+        Self::with_responder(|bytes| match SpheroCommandPacketV1::parse(bytes) {
+            Ok(packet) if packet.sop2() == SOP2Field::Response => vec![ack(&packet)],
+            _ => vec![],
+        })
+    }
+
+    /// Create a mock that replies to each write with the chunks returned by `responder`
+    pub fn with_responder<F>(responder: F) -> Self
+    where
+        F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        let mock = Self::default();
+        mock.set_responder(responder);
+        mock
+    }
+
+    /// Replace the responder
+    pub fn set_responder<F>(&self, responder: F)
+    where
+        F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        self.state.lock().unwrap().responder = Some(Box::new(responder));
+    }
+
+    /// Deliver a chunk of bytes to every subscriber, as if sent by the robot
+    pub fn inject(&self, bytes: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .subscribers
+            .retain(|tx| tx.unbounded_send(bytes.clone()).is_ok());
+    }
+
+    /// Every chunk written so far
+    pub fn written(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().written.clone()
+    }
+
+    /// Every chunk written so far, parsed as command packets
+    pub fn written_packets(&self) -> Vec<SpheroCommandPacketV1> {
+        self.written()
+            .iter()
+            .filter_map(|bytes| SpheroCommandPacketV1::parse(bytes).ok())
+            .collect()
+    }
+
+    /// Forget everything written so far
+    pub fn clear_written(&self) {
+        self.state.lock().unwrap().written.clear();
+    }
+
+    /// Whether the transport has been closed
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Drop the link as if the robot went out of range
+    /// Inbound streams end and writes fail until `reconnect` is called.
+    pub fn disconnect(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.disconnected = true;
+        state.subscribers.clear();
+    }
+}
+
+impl Transport for MockTransport {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let replies = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Err(Error::Transport("mock transport closed".to_string()));
+            }
+            if state.disconnected {
+                return Err(Error::Transport("mock transport disconnected".to_string()));
+            }
+            state.written.push(data.to_vec());
+            match state.responder.as_mut() {
+                Some(responder) => responder(data),
+                None => vec![],
+            }
+        };
+        for reply in replies {
+            self.inject(reply);
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().subscribers.push(tx);
+        Ok(rx.boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        // Ends every inbound stream
+        state.subscribers.clear();
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(Error::Transport("mock transport closed".to_string()));
+        }
+        state.disconnected = false;
+        Ok(())
+    }
+}
+
+/// Serialized OK response to `packet` with no data
+pub fn ack(packet: &SpheroCommandPacketV1) -> Vec<u8> {
+    respond(packet, MRSPField::Ok, vec![])
+}
+
+/// Serialized response to `packet` with the given response code and data
+pub fn respond(packet: &SpheroCommandPacketV1, mrsp: MRSPField, data: Vec<u8>) -> Vec<u8> {
+    SpheroResponsePacketV1::new(mrsp, packet.seq(), data)
+        .to_bytes()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Ping, ToCommandPacket};
+    use futures::executor::block_on;
+    use futures::FutureExt;
+
+    fn ping(seq: u8) -> Vec<u8> {
+        Ping {}.to_packet(seq).to_bytes().unwrap()
+    }
+
+    #[test]
+    fn new_records_writes_and_never_replies() {
+        let mock = MockTransport::new();
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        block_on(mock.write(&ping(1))).unwrap();
+        assert_eq!(mock.written(), [ping(1)]);
+        assert!(inbound.next().now_or_never().is_none());
+    }
+
+    #[test]
+    fn acknowledging_answers_only_commands_wanting_an_answer() {
+        let mock = MockTransport::acknowledging();
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        let quiet = Ping {}.to_packet(2).with_response_required(false);
+        block_on(mock.write(&quiet.to_bytes().unwrap())).unwrap();
+        block_on(mock.write(&ping(3))).unwrap();
+
+        let expected = SpheroResponsePacketV1::new(MRSPField::Ok, 3, vec![]);
+        assert_eq!(block_on(inbound.next()), Some(expected.to_bytes().unwrap()));
+        assert!(inbound.next().now_or_never().is_none());
+    }
+
+    #[test]
+    fn responder_replies_go_to_every_subscriber() {
+        let mock = MockTransport::with_responder(|bytes| vec![bytes.to_vec(), vec![0xaa]]);
+        let mut first = block_on(mock.subscribe()).unwrap();
+        let mut second = block_on(mock.subscribe()).unwrap();
+        block_on(mock.write(&[1, 2])).unwrap();
+        for inbound in [&mut first, &mut second] {
+            assert_eq!(block_on(inbound.next()), Some(vec![1, 2]));
+            assert_eq!(block_on(inbound.next()), Some(vec![0xaa]));
+        }
+    }
+
+    #[test]
+    fn set_responder_replaces_the_responder() {
+        let mock = MockTransport::with_responder(|_| vec![vec![1]]);
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        mock.set_responder(|_| vec![vec![2]]);
+        block_on(mock.write(&[0])).unwrap();
+        assert_eq!(block_on(inbound.next()), Some(vec![2]));
+    }
+
+    #[test]
+    fn written_packets_skips_what_isnt_a_command() {
+        let mock = MockTransport::new();
+        block_on(mock.write(&[0x01, 0x02])).unwrap();
+        block_on(mock.write(&ping(4))).unwrap();
+        let packets = mock.written_packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].seq(), 4);
+
+        mock.clear_written();
+        assert!(mock.written().is_empty());
+    }
+
+    #[test]
+    fn close_ends_inbound_streams_and_fails_writes() {
+        let mock = MockTransport::acknowledging();
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        block_on(mock.close()).unwrap();
+        assert!(mock.is_closed());
+        assert_eq!(block_on(inbound.next()), None);
+        assert!(matches!(
+            block_on(mock.write(&ping(1))),
+            Err(Error::Transport(_))
+        ));
+        assert!(block_on(mock.reconnect()).is_err());
+        assert!(mock.written().is_empty());
+    }
+
+    #[test]
+    fn disconnect_fails_writes_until_reconnected() {
+        let mock = MockTransport::acknowledging();
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        mock.disconnect();
+        assert_eq!(block_on(inbound.next()), None);
+        assert!(matches!(
+            block_on(mock.write(&ping(1))),
+            Err(Error::Transport(_))
+        ));
+
+        block_on(mock.reconnect()).unwrap();
+        let mut inbound = block_on(mock.subscribe()).unwrap();
+        block_on(mock.write(&ping(1))).unwrap();
+        assert!(block_on(inbound.next()).is_some());
+        assert!(!mock.is_closed());
+    }
+}
@@ -0,0 +1,42 @@
+/*!
+ * Sphero Transport
+ *
+ * Links used to carry packets between the host and a robot
+ */
+use crate::error::Error;
+use futures::stream::BoxStream;
+use std::future::Future;
+
+#[cfg(feature = "ble")]
+pub mod ble;
+pub mod mock;
+pub mod record;
+#[cfg(feature = "serial")]
+pub mod serial;
+#[cfg(feature = "async")]
+pub mod sim;
+
+pub use self::record::{Direction, Record, RecordedChunk, Recording, Replay, WriteCheck};
+
+/// Sphero Transport
+/// A bidirectional byte link to a robot. Writes carry whole command packets,
+/// while the inbound side yields chunks of bytes as they arrive from the link.
+pub trait Transport: Send + Sync {
+    /// Write raw packet bytes to the robot
+    fn write(&self, data: &[u8]) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Subscribe to the raw bytes received from the robot
+    fn subscribe(&self) -> impl Future<Output = Result<BoxStream<'static, Vec<u8>>, Error>> + Send;
+
+    /// Close the link
+    /// The default does nothing, for links that close when dropped.
+    fn close(&self) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Re-establish a dropped link, after which `subscribe` is called again
+    /// The default fails with `Error::Disconnected`, for links that can't come back.
+    fn reconnect(&self) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Err(Error::Disconnected) }
+    }
+}
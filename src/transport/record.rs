@@ -0,0 +1,512 @@
+/*!
+ * Sphero Recording Transport
+ *
+ * `Record` wraps a transport and logs every chunk written and received;
+ * `Replay` plays the received side of such a log back, turning a session
+ * with a real robot into a repeatable test.
+ *
+ * A recording is text, one chunk per line: the time since recording began
+ * in seconds, `>` for a write or `<` for a received chunk, then the bytes in
+ * hex. Blank lines and lines starting with `#` are ignored.
+ *
+ * ```text
+ * # sphero-rs recording v1
+ * 0.000112 > ff ff 00 01 00 01 fd
+ * 0.031870 < ff ff 00 00 01 ff
+ * ```
+ */
+use crate::error::Error;
+use crate::trace::hex;
+use crate::transport::Transport;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// First line of every recording
+const HEADER: &str = "# sphero-rs recording v1";
+
+/// Which way a chunk went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Written to the robot, `>`
+    Outbound,
+    /// Received from the robot, `<`
+    Inbound,
+}
+
+/// One chunk of a recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedChunk {
+    /// Time since recording began
+    pub at: Duration,
+    /// Which way it went
+    pub direction: Direction,
+    /// Bytes as written or received
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for RecordedChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            Direction::Outbound => '>',
+            Direction::Inbound => '<',
+        };
+        write!(
+            f,
+            "{}.{:06} {} {}",
+            self.at.as_secs(),
+            self.at.subsec_micros(),
+            arrow,
+            hex(&self.bytes)
+        )
+    }
+}
+
+/// Sphero Transport Recording
+///
+/// ```
+/// use sphero_rs::transport::{Direction, Recording};
+///
+/// let text = "# sphero-rs recording v1\n0.000112 > ff ff 00 01 00 01 fd\n0.031870 < ff ff 00 00 01 ff\n";
+/// let recording: Recording = text.parse()?;
+/// assert_eq!(recording.chunks.len(), 2);
+/// assert_eq!(recording.chunks[1].direction, Direction::Inbound);
+/// assert_eq!(recording.chunks[1].bytes, vec![0xff, 0xff, 0x00, 0x00, 0x01, 0xff]);
+/// assert_eq!(recording.to_string(), text);
+///
+/// assert!("0.1 ? ff".parse::<Recording>().is_err());
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Recording {
+    /// Chunks, in the order they were seen
+    pub chunks: Vec<RecordedChunk>,
+}
+
+impl FromStr for Recording {
+    type Err = Error;
+
+    /// Fails with `Error::Transport` naming the first malformed line
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let chunks = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                parse_line(line)
+                    .ok_or_else(|| Error::Transport(format!("bad recording line {}", i + 1)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { chunks })
+    }
+}
+
+fn parse_line(line: &str) -> Option<RecordedChunk> {
+    let mut words = line.split_whitespace();
+    let at = parse_time(words.next()?)?;
+    let direction = match words.next()? {
+        ">" => Direction::Outbound,
+        "<" => Direction::Inbound,
+        _ => return None,
+    };
+    let bytes = words
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect::<Option<_>>()?;
+    Some(RecordedChunk {
+        at,
+        direction,
+        bytes,
+    })
+}
+
+/// Seconds with up to nine decimals, read exactly
+fn parse_time(word: &str) -> Option<Duration> {
+    let (secs, fraction) = word.split_once('.').unwrap_or((word, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{fraction:0<9}").parse().ok()?;
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for chunk in &self.chunks {
+            writeln!(f, "{chunk}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sphero Recording Transport
+///
+/// Passes everything through to the wrapped transport, logging each chunk
+/// to `log` as it goes. A failure to log fails the next write or close with
+/// `Error::Transport`. Wrap files in a `BufWriter`; the log is flushed on close.
+pub struct Record<T, W: Write + Send + 'static> {
+    inner: T,
+    log: Arc<Mutex<RecordLog<W>>>,
+}
+
+struct RecordLog<W> {
+    writer: W,
+    start: Instant,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> RecordLog<W> {
+    fn append(&mut self, direction: Direction, bytes: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        let chunk = RecordedChunk {
+            at: self.start.elapsed(),
+            direction,
+            bytes: bytes.to_vec(),
+        };
+        if let Err(e) = writeln!(self.writer, "{chunk}") {
+            self.error = Some(e);
+        }
+    }
+
+    fn check(&mut self) -> Result<(), Error> {
+        match self.error.take() {
+            Some(e) => Err(Error::Transport(format!("recording failed: {e}"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Transport, W: Write + Send + 'static> Record<T, W> {
+    /// Record traffic over `inner` to `log`, starting the clock now
+    pub fn new(inner: T, mut log: W) -> io::Result<Self> {
+        writeln!(log, "{HEADER}")?;
+        Ok(Self {
+            inner,
+            log: Arc::new(Mutex::new(RecordLog {
+                writer: log,
+                start: Instant::now(),
+                error: None,
+            })),
+        })
+    }
+
+    /// Wrapped transport
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Flush the log now
+    pub fn flush(&self) -> io::Result<()> {
+        self.log.lock().unwrap().writer.flush()
+    }
+}
+
+impl<T: Transport, W: Write + Send + 'static> Transport for Record<T, W> {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        self.log.lock().unwrap().append(Direction::Outbound, data);
+        self.inner.write(data).await?;
+        self.log.lock().unwrap().check()
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let log = self.log.clone();
+        let inbound = self.inner.subscribe().await?;
+        Ok(inbound
+            .map(move |chunk| {
+                log.lock().unwrap().append(Direction::Inbound, &chunk);
+                chunk
+            })
+            .boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let logged = {
+            let mut log = self.log.lock().unwrap();
+            let flushed = log.writer.flush();
+            log.check()
+                .and(flushed.map_err(|e| Error::Transport(e.to_string())))
+        };
+        self.inner.close().await?;
+        logged
+    }
+
+    async fn reconnect(&self) -> Result<(), Error> {
+        self.inner.reconnect().await
+    }
+}
+
+/// What `Replay` does with writes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WriteCheck {
+    /// Fail a write that differs from the recorded one with `Error::Transport`
+    #[default]
+    Strict,
+    /// Accept any write
+    Ignore,
+}
+
+/// Sphero Replay Transport
+///
+/// Plays back the received side of a recording. Chunks received before the
+/// first write are delivered on subscribe; those received after the n-th
+/// recorded write are delivered when the n-th write is made, so replay
+/// follows the conversation rather than the recorded timing. Clones share
+/// the same state, so a test can keep a handle while a client owns the other.
+#[derive(Clone)]
+pub struct Replay {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+struct ReplayState {
+    chunks: VecDeque<RecordedChunk>,
+    subscribers: Vec<UnboundedSender<Vec<u8>>>,
+    check: WriteCheck,
+    writes: usize,
+    closed: bool,
+}
+
+impl ReplayState {
+    /// Deliver received chunks up to the next recorded write
+    fn release(&mut self) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        while self
+            .chunks
+            .front()
+            .is_some_and(|chunk| chunk.direction == Direction::Inbound)
+        {
+            let chunk = self.chunks.pop_front().unwrap();
+            self.subscribers
+                .retain(|tx| tx.unbounded_send(chunk.bytes.clone()).is_ok());
+        }
+    }
+}
+
+impl Replay {
+    /// Replay `recording`, checking writes as `check` says
+    pub fn new(recording: Recording, check: WriteCheck) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReplayState {
+                chunks: recording.chunks.into(),
+                subscribers: vec![],
+                check,
+                writes: 0,
+                closed: false,
+            })),
+        }
+    }
+
+    /// Chunks not yet replayed, written and received alike
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().chunks.len()
+    }
+
+    /// Whether the whole recording has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+impl Transport for Replay {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(Error::Transport("replay transport closed".to_string()));
+        }
+        state.writes += 1;
+        state.release();
+        let expected = match state.chunks.front() {
+            Some(chunk) if chunk.direction == Direction::Outbound => state.chunks.pop_front(),
+            _ => None,
+        };
+        state.release();
+
+        if state.check == WriteCheck::Ignore {
+            return Ok(());
+        }
+        match expected {
+            Some(chunk) if chunk.bytes == data => Ok(()),
+            Some(chunk) => Err(Error::Transport(format!(
+                "replay write {}: expected {}, got {}",
+                state.writes,
+                hex(&chunk.bytes),
+                hex(data)
+            ))),
+            None => Err(Error::Transport(format!(
+                "replay write {}: not in the recording, got {}",
+                state.writes,
+                hex(data)
+            ))),
+        }
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let (tx, rx) = unbounded();
+        let mut state = self.state.lock().unwrap();
+        state.subscribers.push(tx);
+        state.release();
+        Ok(rx.boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        // Ends every inbound stream
+        state.subscribers.clear();
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> Result<(), Error> {
+        if self.state.lock().unwrap().closed {
+            return Err(Error::Transport("replay transport closed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    const PING: [u8; 7] = [0xff, 0xff, 0x00, 0x01, 0x00, 0x01, 0xfd];
+    const PONG: [u8; 6] = [0xff, 0xff, 0x00, 0x00, 0x01, 0xff];
+
+    fn ping_pong(check: WriteCheck) -> Replay {
+        let recording = Recording {
+            chunks: vec![
+                RecordedChunk {
+                    at: Duration::ZERO,
+                    direction: Direction::Outbound,
+                    bytes: PING.to_vec(),
+                },
+                RecordedChunk {
+                    at: Duration::from_millis(30),
+                    direction: Direction::Inbound,
+                    bytes: PONG.to_vec(),
+                },
+            ],
+        };
+        Replay::new(recording, check)
+    }
+
+    #[test]
+    fn mismatched_write_fails_strict_replay() {
+        let replay = ping_pong(WriteCheck::Strict);
+        let mut inbound = block_on(replay.subscribe()).unwrap();
+
+        let mut other = PING;
+        other[3] = 0x02;
+        match block_on(replay.write(&other)) {
+            Err(Error::Transport(msg)) => {
+                assert!(msg.starts_with("replay write 1: expected ff ff 00 01"))
+            }
+            result => panic!("{result:?}"),
+        }
+        // The recorded answer still follows the write, so the session can be diagnosed
+        assert_eq!(block_on(inbound.next()), Some(PONG.to_vec()));
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn mismatched_write_passes_lenient_replay() {
+        let replay = ping_pong(WriteCheck::Ignore);
+        let mut inbound = block_on(replay.subscribe()).unwrap();
+        assert!(block_on(replay.write(&[0xff, 0xff, 0x00, 0x10, 0x00, 0x01, 0xee])).is_ok());
+        assert_eq!(block_on(inbound.next()), Some(PONG.to_vec()));
+    }
+
+    #[test]
+    fn write_past_the_end_fails() {
+        let replay = ping_pong(WriteCheck::Strict);
+        let _inbound = block_on(replay.subscribe()).unwrap();
+        assert!(block_on(replay.write(&PING)).is_ok());
+        match block_on(replay.write(&PING)) {
+            Err(Error::Transport(msg)) => assert!(msg.contains("not in the recording")),
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    const SESSION: &str = include_str!("../../examples/fixtures/sensor_session.rec");
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn recorded_session_replays_through_the_device() {
+        use crate::color::RgbColor;
+        use crate::command::{Ping, SetRGBLEDOutput};
+        use crate::device::SpheroDevice;
+        use crate::sensor::{Sensor, SensorFrame, StreamingConfig};
+
+        let accel = |x, y, z| SensorFrame {
+            values: vec![
+                (Sensor::AccelX, x),
+                (Sensor::AccelY, y),
+                (Sensor::AccelZ, z),
+            ],
+        };
+        let replay = Replay::new(SESSION.parse().unwrap(), WriteCheck::Strict);
+        let device = SpheroDevice::new(replay.clone()).await.unwrap();
+        // Let the firmware probe go out first, as it did
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(device.capabilities().await.unwrap().firmware(), (3, 18));
+
+        assert!(device.send(&Ping {}).await.is_ok());
+        let green = SetRGBLEDOutput::from_color(RgbColor::GREEN, false);
+        assert!(device.send(&green).await.is_ok());
+
+        let config =
+            StreamingConfig::new().with_sensors(&[Sensor::AccelX, Sensor::AccelY, Sensor::AccelZ]);
+        let mut stream = device.start_streaming(config).await.unwrap();
+        let mut frames = vec![];
+        for _ in 0..4 {
+            frames.push(stream.next().await.unwrap().unwrap());
+        }
+        assert_eq!(
+            frames,
+            [
+                accel(100, -200, 4096),
+                accel(120, -180, 4000),
+                accel(-50, 10, 4090),
+                accel(0, 0, 4096),
+            ]
+        );
+        stream.stop().await.unwrap();
+        assert!(replay.is_finished(), "{} chunks left", replay.remaining());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn truncated_recording_times_out_then_fails_writes() {
+        use crate::command::Ping;
+        use crate::device::{SendOptions, SpheroDevice};
+
+        // Cut off right after the ping went out, before its answer
+        let cut = SESSION.find("0.021304").unwrap();
+        let replay = Replay::new(SESSION[..cut].parse().unwrap(), WriteCheck::Strict);
+        let device = SpheroDevice::new(replay.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let options = SendOptions {
+            timeout: Duration::from_millis(50),
+            ..SendOptions::default()
+        };
+        let ping = device.send_with(&Ping {}, options).await;
+        assert!(matches!(ping, Err(Error::Timeout)), "{ping:?}");
+        assert!(replay.is_finished());
+
+        let ping = device.send_with(&Ping {}, options).await;
+        assert!(
+            matches!(&ping, Err(Error::Transport(msg)) if msg.contains("not in the recording")),
+            "{ping:?}"
+        );
+    }
+}
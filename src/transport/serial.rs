@@ -0,0 +1,178 @@
+/*!
+ * Sphero Serial Transport
+ *
+ * The original Sphero 1.0/2.0 speak the v1.20 API over Bluetooth Classic
+ * (RFCOMM), which the OS exposes as a serial port once the robot is bound.
+ * No wake-up sequence is needed. Reads return arbitrary slices of the byte
+ * stream, which the client's packet reader frames.
+ */
+use crate::error::Error;
+use crate::transport::Transport;
+use futures::lock::Mutex as AsyncMutex;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+/// Baud rate of the Sphero serial port profile
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+/// Size of each read from the port
+const READ_BUFFER_SIZE: usize = 256;
+
+impl From<tokio_serial::Error> for Error {
+    fn from(e: tokio_serial::Error) -> Self {
+        Error::Transport(e.to_string())
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::Transport(e.to_string())
+}
+
+/// Sphero Serial Transport
+/// The inbound side can only be subscribed to once.
+pub struct SerialTransport {
+    reader: Mutex<Option<ReadHalf<SerialStream>>>,
+    writer: AsyncMutex<Option<WriteHalf<SerialStream>>>,
+}
+
+impl SerialTransport {
+    /// Open the serial port at `path` (e.g. `/dev/rfcomm0` or `COM5`)
+    /// Must be called from within a tokio runtime.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        Self::open_with_baud_rate(path, DEFAULT_BAUD_RATE)
+    }
+
+    /// Open the serial port at `path` with a non-default baud rate
+    pub fn open_with_baud_rate(path: &str, baud_rate: u32) -> Result<Self, Error> {
+        let stream = tokio_serial::new(path, baud_rate).open_native_async()?;
+        Ok(Self::from_stream(stream))
+    }
+
+    /// Wrap an already open port
+    pub fn from_stream(stream: SerialStream) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: Mutex::new(Some(reader)),
+            writer: AsyncMutex::new(Some(writer)),
+        }
+    }
+}
+
+impl Transport for SerialTransport {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let mut writer = self.writer.lock().await;
+        let writer = writer
+            .as_mut()
+            .ok_or_else(|| Error::Transport("serial port closed".to_string()))?;
+        writer.write_all(data).await.map_err(io_error)?;
+        writer.flush().await.map_err(io_error)
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let reader = self
+            .reader
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| Error::Transport("serial port already subscribed".to_string()))?;
+        // Ends on EOF or on the first read error
+        Ok(stream::unfold(reader, |mut reader| async move {
+            let mut buffer = vec![0; READ_BUFFER_SIZE];
+            match reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => None,
+                Ok(n) => {
+                    buffer.truncate(n);
+                    Some((buffer, reader))
+                }
+            }
+        })
+        .boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        match self.writer.lock().await.take() {
+            Some(mut writer) => writer.shutdown().await.map_err(io_error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::client::SpheroClient;
+    use crate::command::Ping;
+    use crate::event::SpheroEvent;
+    use crate::packet::{MRSPField, SpheroAsynchronousPacketV1, SpheroCommandPacketV1};
+    use crate::transport::mock::respond;
+    use deku::DekuContainerWrite;
+    use std::time::Duration;
+
+    /// Transport on one end of a pseudo terminal, and the robot's end
+    fn loopback() -> (SerialTransport, SerialStream) {
+        let (robot, host) = SerialStream::pair().unwrap();
+        (SerialTransport::from_stream(host), robot)
+    }
+
+    #[tokio::test]
+    async fn response_split_across_reads_is_framed() {
+        let (transport, mut robot) = loopback();
+        let mut client = SpheroClient::new(transport);
+
+        let robot = async move {
+            // Ping is a 7 byte command
+            let mut command = [0; 7];
+            let _ = robot.read_exact(&mut command).await.unwrap();
+            let packet = SpheroCommandPacketV1::parse(&command).unwrap();
+
+            // A power notification, then the answer, cut mid-packet
+            let mut reply = SpheroAsynchronousPacketV1::new(0x01, vec![0x02])
+                .to_bytes()
+                .unwrap();
+            reply.extend(respond(&packet, MRSPField::Ok, vec![0xaa, 0xbb]));
+            for piece in [&reply[..4], &reply[4..9], &reply[9..]] {
+                robot.write_all(piece).await.unwrap();
+                robot.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            robot
+        };
+        let (response, _robot) = tokio::join!(client.send(&Ping {}), robot);
+        assert_eq!(response.unwrap().data(), [0xaa, 0xbb]);
+        let events = client.drain_events();
+        assert!(matches!(&events[..], [SpheroEvent::Async(packet)] if packet.idcode() == 0x01));
+    }
+
+    #[tokio::test]
+    async fn writes_reach_the_port_unchanged() {
+        let (transport, mut robot) = loopback();
+        let bytes: Vec<u8> = (0..=255).collect();
+        transport.write(&bytes).await.unwrap();
+        let mut received = vec![0; bytes.len()];
+        let _ = robot.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, bytes);
+    }
+
+    #[tokio::test]
+    async fn inbound_side_can_only_be_subscribed_once() {
+        let (transport, _robot) = loopback();
+        assert!(transport.subscribe().await.is_ok());
+        assert!(matches!(
+            transport.subscribe().await,
+            Err(Error::Transport(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn writes_fail_once_closed() {
+        let (transport, _robot) = loopback();
+        transport.close().await.unwrap();
+        assert!(matches!(
+            transport.write(&[0xff]).await,
+            Err(Error::Transport(_))
+        ));
+        // Closing twice is fine
+        assert!(transport.close().await.is_ok());
+    }
+}
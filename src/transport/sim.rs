@@ -0,0 +1,596 @@
+/*!
+ * Sphero Simulated Transport
+ *
+ * A robot in memory, for demos and tests without hardware. It answers the
+ * common commands the way the firmware does, moves in a straight line at
+ * the commanded speed and heading, streams sensor data from that motion and
+ * reports a collision when it runs into one of the configured walls.
+ *
+ * The model is deliberately simple: speed changes instantly, the robot
+ * never slips, and only the locator, IMU yaw, velocity and motor sources
+ * reflect its motion. It is self-consistent rather than accurate.
+ */
+use crate::color::RgbColor;
+use crate::error::Error;
+use crate::packet::{
+    CoreCommandID, DeviceID, MRSPField, SOP2Field, SpheroAsynchronousPacketV1, SpheroCommandID,
+    SpheroCommandPacketV1, SpheroResponsePacketV1,
+};
+use crate::power::{PowerState, Voltage};
+use crate::runtime;
+use crate::sensor::{Sensor, SensorMask};
+use crate::transport::Transport;
+use deku::DekuContainerWrite;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the model moves on while nothing is written
+const TICK: Duration = Duration::from_millis(10);
+/// Period of the firmware's 400 Hz sampling
+const SAMPLE_PERIOD: Duration = Duration::from_micros(2500);
+/// Longest stretch of sensor data sent late rather than skipped
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+/// Main application version reported by Get Versioning
+const FIRMWARE: (u8, u8) = (3, 18);
+
+/// Walls around the robot, in the locator's frame, in cm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Walls {
+    /// Left wall
+    pub min_x: f32,
+    /// Right wall
+    pub max_x: f32,
+    /// Back wall
+    pub min_y: f32,
+    /// Front wall
+    pub max_y: f32,
+}
+
+impl Walls {
+    /// A square room with the robot starting in the middle
+    pub fn square(half_width_cm: f32) -> Self {
+        Self {
+            min_x: -half_width_cm,
+            max_x: half_width_cm,
+            min_y: -half_width_cm,
+            max_y: half_width_cm,
+        }
+    }
+}
+
+/// Simulated Sphero Settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimConfig {
+    /// Ground speed per unit of Roll speed, in cm/s
+    pub cm_per_speed_unit: f32,
+    /// Walls that stop the robot, or an endless floor
+    pub walls: Option<Walls>,
+    /// Power state reported by Get Power State
+    pub power_state: PowerState,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            cm_per_speed_unit: 1.0,
+            walls: None,
+            power_state: PowerState::Ok,
+        }
+    }
+}
+
+/// Sphero Simulated Transport
+///
+/// The robot starts at the locator origin, facing heading 0 (+Y), and
+/// headings grow clockwise. Clones share the same robot, so a test can keep
+/// a handle while a client owns the other.
+///
+/// ```
+/// use sphero_rs::command::{ReadLocator, Roll};
+/// use sphero_rs::device::SpheroDevice;
+/// use sphero_rs::heading::Heading;
+/// use sphero_rs::transport::sim::{SimConfig, SimulatedSphero};
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let sim = SimulatedSphero::new(SimConfig::default());
+/// let device = SpheroDevice::new(sim.clone()).await?;
+/// let east = Roll { speed: 100.into(), heading: Heading::new(90)?, state: true };
+/// drop(device.send(&east).await?);
+/// tokio::time::sleep(Duration::from_millis(500)).await;
+/// drop(device.send(&Roll::stop(90)?).await?);
+///
+/// // 100 cm/s for half a second
+/// let locator = device.query(&ReadLocator {}).await?;
+/// assert!((locator.x - 50).abs() <= 5, "x = {}", locator.x);
+/// assert_eq!(locator.y, 0);
+/// assert_eq!(sim.speed(), 0);
+/// # Ok::<(), sphero_rs::error::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SimulatedSphero {
+    state: Arc<Mutex<SimState>>,
+}
+
+struct SimState {
+    config: SimConfig,
+    x: f32,
+    y: f32,
+    speed: u8,
+    /// Heading last rolled at
+    heading: u16,
+    /// Heading 0 relative to the locator's +Y axis, moved by Set Heading
+    heading_offset: u16,
+    led: RgbColor,
+    /// Robot clock at `clock_set`, in ms
+    clock: u32,
+    clock_set: Instant,
+    streaming: Option<Streaming>,
+    /// Collision dead time, or `None` while detection is off
+    collision_dead_time: Option<Duration>,
+    last_collision: Option<Instant>,
+    moved_at: Instant,
+    subscribers: Vec<UnboundedSender<Vec<u8>>>,
+    closed: bool,
+}
+
+struct Streaming {
+    sensors: Vec<Sensor>,
+    period: Duration,
+    frames_per_packet: usize,
+    /// Packets left to send, `None` for unlimited
+    packets_left: Option<u8>,
+    next_frame: Instant,
+    frames: Vec<u8>,
+}
+
+impl SimulatedSphero {
+    /// A robot at rest at the origin
+    pub fn new(config: SimConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Arc::new(Mutex::new(SimState {
+                config,
+                x: 0.0,
+                y: 0.0,
+                speed: 0,
+                heading: 0,
+                heading_offset: 0,
+                led: RgbColor::BLACK,
+                clock: 0,
+                clock_set: now,
+                streaming: None,
+                collision_dead_time: None,
+                last_collision: None,
+                moved_at: now,
+                subscribers: vec![],
+                closed: false,
+            })),
+        }
+    }
+
+    /// Position in the locator's frame, in cm
+    pub fn position(&self) -> (f32, f32) {
+        let mut state = self.state.lock().unwrap();
+        state.advance(Instant::now());
+        (state.x, state.y)
+    }
+
+    /// Roll speed, 0 once stopped or stopped by a wall
+    pub fn speed(&self) -> u8 {
+        self.state.lock().unwrap().speed
+    }
+
+    /// Heading last rolled at
+    pub fn heading(&self) -> u16 {
+        self.state.lock().unwrap().heading
+    }
+
+    /// Color of the main LED
+    pub fn led_color(&self) -> RgbColor {
+        self.state.lock().unwrap().led
+    }
+
+    /// Change the power state reported by Get Power State
+    pub fn set_power_state(&self, state: PowerState) {
+        self.state.lock().unwrap().config.power_state = state;
+    }
+}
+
+impl Default for SimulatedSphero {
+    fn default() -> Self {
+        Self::new(SimConfig::default())
+    }
+}
+
+impl SimState {
+    fn robot_ms(&self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.clock_set).as_millis() as u32;
+        self.clock.wrapping_add(elapsed)
+    }
+
+    /// Velocity over the floor, in cm/s
+    fn velocity(&self) -> (f32, f32) {
+        let speed = self.speed as f32 * self.config.cm_per_speed_unit;
+        let heading = ((self.heading + self.heading_offset) % 360) as f32;
+        let heading = heading.to_radians();
+        (speed * heading.sin(), speed * heading.cos())
+    }
+
+    fn send(&mut self, bytes: Vec<u8>) {
+        self.subscribers
+            .retain(|tx| tx.unbounded_send(bytes.clone()).is_ok());
+    }
+
+    /// Move on to `now`, sending the sensor data and collisions due by then
+    fn advance(&mut self, now: Instant) {
+        if let Some(streaming) = &mut self.streaming {
+            // Don't try to catch up after a long pause
+            if now.saturating_duration_since(streaming.next_frame) > MAX_CATCH_UP {
+                streaming.next_frame = now;
+            }
+        }
+        while let Some(streaming) = &self.streaming {
+            if streaming.next_frame > now {
+                break;
+            }
+            let at = streaming.next_frame;
+            self.move_to(at);
+            self.sample(at);
+        }
+        self.move_to(now);
+    }
+
+    fn move_to(&mut self, now: Instant) {
+        let dt = now.saturating_duration_since(self.moved_at).as_secs_f32();
+        self.moved_at = self.moved_at.max(now);
+        if self.speed == 0 || dt == 0.0 {
+            return;
+        }
+        let (vx, vy) = self.velocity();
+        self.x += vx * dt;
+        self.y += vy * dt;
+        let Some(walls) = self.config.walls else {
+            return;
+        };
+        let hit_x = self.x < walls.min_x || self.x > walls.max_x;
+        let hit_y = self.y < walls.min_y || self.y > walls.max_y;
+        if !hit_x && !hit_y {
+            return;
+        }
+        self.x = self.x.clamp(walls.min_x, walls.max_x);
+        self.y = self.y.clamp(walls.min_y, walls.max_y);
+        let speed = self.speed;
+        self.speed = 0;
+        self.collide(now, hit_x, hit_y, vx, vy, speed);
+    }
+
+    fn collide(&mut self, now: Instant, hit_x: bool, hit_y: bool, vx: f32, vy: f32, speed: u8) {
+        let Some(dead_time) = self.collision_dead_time else {
+            return;
+        };
+        if self
+            .last_collision
+            .is_some_and(|last| now.saturating_duration_since(last) < dead_time)
+        {
+            return;
+        }
+        self.last_collision = Some(now);
+        // Impact in roughly mG, as if the robot stopped within 10 ms
+        let impact = |v: f32, hit: bool| if hit { (-v * 100.0) as i16 } else { 0 };
+        let (x, y) = (impact(vx, hit_x), impact(vy, hit_y));
+        let mut data = vec![];
+        data.extend(x.to_be_bytes());
+        data.extend(y.to_be_bytes());
+        data.extend(0i16.to_be_bytes());
+        data.push(hit_x as u8 | (hit_y as u8) << 1);
+        data.extend(x.unsigned_abs().min(i16::MAX as u16).to_be_bytes());
+        data.extend(y.unsigned_abs().min(i16::MAX as u16).to_be_bytes());
+        data.push(speed);
+        data.extend(self.robot_ms(now).to_be_bytes());
+        self.send_async(0x07, data);
+    }
+
+    fn send_async(&mut self, idcode: u8, data: Vec<u8>) {
+        if let Ok(bytes) = SpheroAsynchronousPacketV1::new(idcode, data).to_bytes() {
+            self.send(bytes);
+        }
+    }
+
+    /// Take the frame due at `at`, sending the packet once it is full
+    fn sample(&mut self, at: Instant) {
+        let (vx, vy) = self.velocity();
+        let heading = (self.heading + self.heading_offset) % 360;
+        let yaw = if heading > 180 {
+            heading as f32 - 360.0
+        } else {
+            heading as f32
+        };
+        let speed = self.speed as i16;
+        let Some(streaming) = &mut self.streaming else {
+            return;
+        };
+        for sensor in &streaming.sensors {
+            let value: i16 = match sensor {
+                Sensor::AccelZ => 4096,
+                Sensor::AccelOne => 1000,
+                Sensor::ImuYaw => yaw as i16,
+                Sensor::QuaternionQ0 => ((yaw / 2.0).to_radians().cos() * 10000.0) as i16,
+                Sensor::QuaternionQ3 => ((yaw / 2.0).to_radians().sin() * 10000.0) as i16,
+                Sensor::OdometerX => self.x.round() as i16,
+                Sensor::OdometerY => self.y.round() as i16,
+                Sensor::VelocityX => (vx * 10.0).round() as i16,
+                Sensor::VelocityY => (vy * 10.0).round() as i16,
+                Sensor::LeftMotorPwmRaw
+                | Sensor::RightMotorPwmRaw
+                | Sensor::LeftMotorEmfRaw
+                | Sensor::RightMotorEmfRaw
+                | Sensor::LeftMotorEmf
+                | Sensor::RightMotorEmf => speed,
+                _ => 0,
+            };
+            streaming.frames.extend(value.to_be_bytes());
+        }
+        streaming.next_frame = at + streaming.period;
+        if streaming.frames.len() < streaming.frames_per_packet * streaming.sensors.len() * 2 {
+            return;
+        }
+        let data = std::mem::take(&mut streaming.frames);
+        if let Some(left) = &mut streaming.packets_left {
+            *left -= 1;
+            if *left == 0 {
+                self.streaming = None;
+            }
+        }
+        self.send_async(0x03, data);
+    }
+
+    /// Carry out `packet`, returning the data of its response
+    fn apply(&mut self, packet: &SpheroCommandPacketV1, now: Instant) -> Vec<u8> {
+        let data = packet.data();
+        let word = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let long = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let cid = packet.cid();
+        match packet.did() {
+            DeviceID::Core if cid == CoreCommandID::GetVersioningInformation as u8 => {
+                vec![2, 1, 3, FIRMWARE.0, FIRMWARE.1, 0x33, 0x44, 0x55, 1, 20, 0]
+            }
+            DeviceID::Core if cid == CoreCommandID::GetPowerState as u8 => {
+                let volts = Voltage::from_hundredths(match self.config.power_state {
+                    PowerState::Charging => 840,
+                    PowerState::Low => 700,
+                    PowerState::Critical => 650,
+                    _ => 780,
+                });
+                let awake = now.saturating_duration_since(self.clock_set).as_secs() as u16;
+                let mut response = vec![1, self.config.power_state.into()];
+                response.extend(volts.hundredths().to_be_bytes());
+                response.extend(0u16.to_be_bytes());
+                response.extend(awake.to_be_bytes());
+                response
+            }
+            DeviceID::Core if cid == CoreCommandID::AssignTimeValue as u8 && data.len() >= 4 => {
+                self.clock = long(0);
+                self.clock_set = now;
+                vec![]
+            }
+            DeviceID::Core if cid == CoreCommandID::PollPacketTimes as u8 && data.len() >= 4 => {
+                let robot_ms = self.robot_ms(now);
+                let mut response = data[..4].to_vec();
+                response.extend(robot_ms.to_be_bytes());
+                response.extend(robot_ms.to_be_bytes());
+                response
+            }
+            DeviceID::Sphero if cid == SpheroCommandID::Roll as u8 && data.len() >= 4 => {
+                self.speed = if data[3] == 0 { 0 } else { data[0] };
+                self.heading = word(1) % 360;
+                vec![]
+            }
+            DeviceID::Sphero if cid == SpheroCommandID::SetHeading as u8 && data.len() >= 2 => {
+                // Where the robot faces now becomes the new heading
+                let facing = self.heading + self.heading_offset;
+                self.heading_offset = (facing + 360 - word(0) % 360) % 360;
+                self.heading = word(0) % 360;
+                vec![]
+            }
+            DeviceID::Sphero
+                if cid == SpheroCommandID::SetRGBLEDOutput as u8 && data.len() >= 3 =>
+            {
+                self.led = RgbColor::new(data[0], data[1], data[2]);
+                vec![]
+            }
+            DeviceID::Sphero if cid == SpheroCommandID::GetRGBLEDOutput as u8 => {
+                vec![self.led.red, self.led.green, self.led.blue]
+            }
+            DeviceID::Sphero
+                if cid == SpheroCommandID::ConfigureLocator as u8 && data.len() >= 5 =>
+            {
+                self.x = word(1) as i16 as f32;
+                self.y = word(3) as i16 as f32;
+                vec![]
+            }
+            DeviceID::Sphero if cid == SpheroCommandID::ReadLocator as u8 => {
+                let (vx, vy) = self.velocity();
+                let words = [self.x, self.y, vx * 10.0, vy * 10.0];
+                let mut response: Vec<u8> = words
+                    .iter()
+                    .flat_map(|word| (word.round() as i16).to_be_bytes())
+                    .collect();
+                response.extend(((vx.hypot(vy) * 10.0).round() as u16).to_be_bytes());
+                response
+            }
+            DeviceID::Sphero
+                if cid == SpheroCommandID::ConfigureCollisionDetection as u8 && data.len() >= 6 =>
+            {
+                self.collision_dead_time =
+                    (data[0] != 0).then(|| Duration::from_millis(data[5] as u64 * 10));
+                vec![]
+            }
+            DeviceID::Sphero
+                if cid == SpheroCommandID::SetDataStreaming as u8 && data.len() >= 9 =>
+            {
+                let mask = SensorMask {
+                    mask1: long(4),
+                    mask2: if data.len() >= 13 { long(9) } else { 0 },
+                };
+                let sensors = mask.sensors();
+                self.streaming = (!sensors.is_empty() && word(0) > 0).then(|| {
+                    let period = SAMPLE_PERIOD * word(0) as u32;
+                    Streaming {
+                        sensors,
+                        period,
+                        frames_per_packet: word(2).max(1) as usize,
+                        packets_left: (data[8] != 0).then_some(data[8]),
+                        next_frame: now + period,
+                        frames: vec![],
+                    }
+                });
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl Transport for SimulatedSphero {
+    async fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(Error::Transport("simulated robot closed".to_string()));
+        }
+        // Like the firmware, ignore anything that isn't a command
+        let Ok(packet) = SpheroCommandPacketV1::parse(data) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        state.advance(now);
+        let response = state.apply(&packet, now);
+        if packet.sop2() == SOP2Field::Response {
+            let reply = SpheroResponsePacketV1::new(MRSPField::Ok, packet.seq(), response);
+            state.send(reply.to_bytes()?);
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, Vec<u8>>, Error> {
+        let (tx, rx) = unbounded();
+        self.state.lock().unwrap().subscribers.push(tx);
+        // Keeps the robot moving and streaming while nothing is written
+        let ticks = stream::unfold(self.state.clone(), |state| async move {
+            runtime::sleep(TICK).await;
+            {
+                let mut state = state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                state.advance(Instant::now());
+            }
+            Some(((), state))
+        })
+        .filter_map(|()| async { None::<Vec<u8>> });
+        Ok(stream::select(rx, ticks).boxed())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        // Ends every inbound stream
+        state.subscribers.clear();
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::collision::CollisionConfig;
+    use crate::command::{ConfigureCollisionDetection, ReadLocator, Roll};
+    use crate::device::SpheroDevice;
+    use crate::event::AsyncMessage;
+    use crate::heading::Heading;
+    use crate::sensor::StreamingConfig;
+
+    /// How far the position may stray from the prediction, in cm
+    const TOLERANCE: f32 = 6.0;
+
+    fn near(actual: (f32, f32), expected: (f32, f32)) -> bool {
+        (actual.0 - expected.0).hypot(actual.1 - expected.1) <= TOLERANCE
+    }
+
+    fn roll(speed: u8, heading: u16) -> Roll {
+        Roll {
+            speed: speed.into(),
+            heading: Heading::new(heading).unwrap(),
+            state: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn locator_and_streamed_odometry_follow_the_rolls() {
+        let sim = SimulatedSphero::default();
+        let device = SpheroDevice::new(sim.clone()).await.unwrap();
+        let streaming =
+            StreamingConfig::new().with_sensors(&[Sensor::OdometerX, Sensor::OdometerY]);
+        let mut stream = device.start_streaming(streaming).await.unwrap();
+        let odometer = Mutex::new((0.0, 0.0));
+        let watch = async {
+            while let Some(frame) = stream.next().await {
+                let frame = frame.unwrap();
+                let x = frame.get(Sensor::OdometerX).unwrap_or_default();
+                let y = frame.get(Sensor::OdometerY).unwrap_or_default();
+                *odometer.lock().unwrap() = (x as f32, y as f32);
+            }
+        };
+        let drive = async {
+            // 80 cm/s east for a second
+            drop(device.send(&roll(80, 90)).await.unwrap());
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(device.send(&Roll::stop(90).unwrap()).await.unwrap());
+            let locator = device.query(&ReadLocator {}).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            (locator.x as f32, locator.y as f32)
+        };
+        let position = tokio::select! {
+            position = drive => position,
+            () = watch => panic!("sensor stream ended"),
+        };
+
+        assert!(near(position, (80.0, 0.0)), "located at {position:?}");
+        let streamed = *odometer.lock().unwrap();
+        assert!(near(streamed, position), "streamed {streamed:?}");
+        assert!(stream.stop().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn running_into_a_wall_reports_a_collision_and_stops() {
+        let sim = SimulatedSphero::new(SimConfig {
+            walls: Some(Walls::square(50.0)),
+            ..SimConfig::default()
+        });
+        let device = SpheroDevice::new(sim.clone()).await.unwrap();
+        let collision = ConfigureCollisionDetection::from(CollisionConfig::method1());
+        drop(device.send(&collision).await.unwrap());
+        let mut events = device.events();
+
+        // North for a second would reach y = 100, but the wall is at 50
+        drop(device.send(&roll(100, 0)).await.unwrap());
+        let hit = tokio::time::timeout(Duration::from_secs(2), async {
+            while let Some(message) = events.next().await {
+                if let AsyncMessage::Collision(hit) = message {
+                    return Some(hit);
+                }
+            }
+            None
+        })
+        .await
+        .unwrap()
+        .expect("no collision reported");
+
+        assert_eq!((hit.axis, hit.speed), (0b10, 100));
+        assert_eq!(sim.speed(), 0);
+        assert!(near(sim.position(), (0.0, 50.0)), "at {:?}", sim.position());
+    }
+}